@@ -0,0 +1,329 @@
+// Provider API Key Vault
+//
+// Provider keys can be stored in the OS keychain (macOS Keychain, Windows Credential Manager,
+// Secret Service on Linux) instead of being handed to every client tool. When a backend has
+// stored keys, the proxy drops whatever credentials the client sent and sets one of the real keys
+// on the way out, so tools can run with a dummy key. A backend can hold several keys (e.g. team
+// members sharing org keys); requests are spread across them round-robin, or to the key with the
+// fewest requests in flight. The `api_keys` table only holds labels and usage counters; the keys
+// themselves never touch the database. Keys are cached in memory once read, and adding or
+// removing a key updates the cache, so a rotation applies to the next request.
+
+use crate::backends::Backend;
+use crate::dlp_vault::masked_preview;
+use crate::storage::{self, ApiKeyRecord, BackendStore};
+use axum::http::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Keychain service the keys are stored under (one entry per key)
+pub const KEYCHAIN_SERVICE: &str = "ai.quilr.llmwatcher";
+
+/// Credential headers dropped from client requests when a stored key is injected
+pub const CLIENT_CREDENTIAL_HEADERS: &[&str] = &["authorization", "x-api-key", "x-goog-api-key", "api-key"];
+
+/// How a provider expects its API key
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ApiKeyHeader {
+    /// `Authorization: Bearer <key>` (OpenAI and compatible APIs)
+    Bearer,
+    /// `x-api-key: <key>` (Anthropic)
+    XApiKey,
+    /// `x-goog-api-key: <key>` (Gemini)
+    GoogApiKey,
+}
+
+/// How requests are spread across a backend's keys
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyBalancing {
+    #[default]
+    RoundRobin,
+    /// The key with the fewest requests in flight (ties go to the least used overall)
+    LeastLoaded,
+}
+
+struct CachedKey {
+    id: i64,
+    key: String,
+    in_flight: Arc<AtomicUsize>,
+    /// Requests sent with the key, seeded from the database
+    request_count: Arc<AtomicI64>,
+}
+
+#[derive(Default)]
+struct BackendKeys {
+    keys: Vec<CachedKey>,
+    /// Round-robin position
+    next: AtomicUsize,
+}
+
+/// Keys read from the keychain, by backend name; None until first use
+static KEYS: LazyLock<RwLock<Option<HashMap<String, BackendKeys>>>> = LazyLock::new(|| RwLock::new(None));
+
+fn keychain_entry(key_id: i64) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("api-key:{}", key_id)).map_err(|e| e.to_string())
+}
+
+/// Read every stored key from the keychain into the cache, once
+fn ensure_loaded() {
+    if KEYS.read().unwrap().is_some() {
+        return;
+    }
+
+    let mut keys: HashMap<String, BackendKeys> = HashMap::new();
+    let rows = storage::shared()
+        .and_then(|store| store.api_keys())
+        .unwrap_or_default();
+
+    for ApiKeyRecord { id, backend, request_count, .. } in rows {
+        match keychain_entry(id).and_then(|e| e.get_password().map_err(|e| e.to_string())) {
+            Ok(key) => keys.entry(backend).or_default().keys.push(CachedKey {
+                id,
+                key,
+                in_flight: Arc::new(AtomicUsize::new(0)),
+                request_count: Arc::new(AtomicI64::new(request_count)),
+            }),
+            Err(e) => eprintln!("[API_KEYS] Failed to read key {} for backend '{}': {}", id, backend, e),
+        }
+    }
+
+    let mut cache = KEYS.write().unwrap();
+    if cache.is_none() {
+        *cache = Some(keys);
+    }
+}
+
+/// Store a new key for a backend; it is used from the next request on
+pub fn add_api_key(backend: &str, key: &str, label: Option<&str>) -> Result<i64, String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API key cannot be empty".to_string());
+    }
+    if HeaderValue::from_str(key).is_err() {
+        return Err("API key contains characters that can't be sent in a header".to_string());
+    }
+    let label = label.map(str::trim).filter(|l| !l.is_empty());
+
+    ensure_loaded();
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let id = store.add_api_key(backend, label).map_err(|e| e.to_string())?;
+
+    if let Err(e) = keychain_entry(id).and_then(|entry| entry.set_password(key).map_err(|e| e.to_string())) {
+        let _ = store.delete_api_key(id);
+        return Err(format!("Failed to store key in the keychain: {}", e));
+    }
+
+    if let Some(keys) = KEYS.write().unwrap().as_mut() {
+        keys.entry(backend.to_string()).or_default().keys.push(CachedKey {
+            id,
+            key: key.to_string(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            request_count: Arc::new(AtomicI64::new(0)),
+        });
+    }
+    Ok(id)
+}
+
+/// Remove a stored key; once a backend has none, clients' own credentials are forwarded again
+/// The row is only deleted once the keychain entry is gone, so no key is left in the keychain
+/// without a row to list (and remove) it by
+pub fn delete_api_key(key_id: i64) -> Result<(), String> {
+    ensure_loaded();
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    match keychain_entry(key_id)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(format!("Failed to remove key from the keychain: {}", e)),
+    }
+    store.delete_api_key(key_id).map_err(|e| e.to_string())?;
+
+    if let Some(keys) = KEYS.write().unwrap().as_mut() {
+        for backend_keys in keys.values_mut() {
+            backend_keys.keys.retain(|k| k.id != key_id);
+        }
+    }
+    Ok(())
+}
+
+/// Remove every key of a backend (e.g. when the backend is deleted)
+pub fn delete_backend_keys(backend: &str) -> Result<(), String> {
+    let keys = storage::shared()
+        .and_then(|store| store.api_keys())
+        .map_err(|e| e.to_string())?;
+
+    for key in keys.into_iter().filter(|k| k.backend == backend) {
+        delete_api_key(key.id)?;
+    }
+    Ok(())
+}
+
+/// Keep a renamed backend's cached keys; its `api_keys` rows are moved by `Database::update_backend`
+pub fn rename_backend_keys(old_name: &str, new_name: &str) {
+    if old_name == new_name {
+        return;
+    }
+    if let Some(keys) = KEYS.write().unwrap().as_mut() {
+        if let Some(backend_keys) = keys.remove(old_name) {
+            keys.insert(new_name.to_string(), backend_keys);
+        }
+    }
+}
+
+/// A stored key with its usage, as shown in the UI
+#[derive(Serialize)]
+pub struct StoredApiKey {
+    pub id: i64,
+    pub backend: String,
+    pub label: Option<String>,
+    pub preview: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub request_count: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub in_flight: usize,
+}
+
+/// Every stored key with a masked preview and its usage counters
+pub fn list_api_keys() -> Result<Vec<StoredApiKey>, String> {
+    ensure_loaded();
+    let rows = storage::shared()
+        .and_then(|store| store.api_keys())
+        .map_err(|e| e.to_string())?;
+
+    let cache = KEYS.read().unwrap();
+    let cached = |backend: &str, id: i64| {
+        cache
+            .as_ref()
+            .and_then(|keys| keys.get(backend))
+            .and_then(|b| b.keys.iter().find(|k| k.id == id))
+    };
+
+    let keys = rows
+        .into_iter()
+        .map(|row| {
+            let key = cached(&row.backend, row.id);
+            StoredApiKey {
+                id: row.id,
+                label: row.label,
+                // Unreadable keys (e.g. removed from the keychain by hand) are listed but never used
+                preview: key.map(|k| masked_preview(&k.key)).unwrap_or_else(|| "unavailable".to_string()),
+                in_flight: key.map(|k| k.in_flight.load(Ordering::Relaxed)).unwrap_or(0),
+                backend: row.backend,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+                request_count: row.request_count,
+                input_tokens: row.input_tokens,
+                output_tokens: row.output_tokens,
+            }
+        })
+        .collect();
+
+    Ok(keys)
+}
+
+/// A key checked out for one request; release it once the response is done to record its usage
+pub struct KeyLease {
+    key_id: i64,
+    in_flight: Arc<AtomicUsize>,
+    input_tokens: i64,
+    output_tokens: i64,
+    released: bool,
+}
+
+impl KeyLease {
+    /// Token usage of the response, added to the key's counters
+    pub fn record_tokens(&mut self, input_tokens: i32, output_tokens: i32) {
+        self.input_tokens = input_tokens.max(0) as i64;
+        self.output_tokens = output_tokens.max(0) as i64;
+    }
+
+    /// Free the key and write its usage to `api_keys` on the blocking pool
+    pub fn release(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        if std::mem::replace(&mut self.released, true) {
+            return;
+        }
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            eprintln!("[API_KEYS] No runtime to record usage of key {}", self.key_id);
+            return;
+        };
+        let (key_id, input_tokens, output_tokens) = (self.key_id, self.input_tokens, self.output_tokens);
+        runtime.spawn_blocking(move || {
+            let result = storage::shared().and_then(|store| store.record_api_key_usage(key_id, input_tokens, output_tokens));
+            if let Err(e) = result {
+                eprintln!("[API_KEYS] Failed to record usage of key {}: {}", key_id, e);
+            }
+        });
+    }
+}
+
+/// Leases dropped without `release` (e.g. on an error response) are released here, never blocking
+impl Drop for KeyLease {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Pick one of a backend's keys
+fn checkout(backend: &str, balancing: KeyBalancing) -> Option<(String, KeyLease)> {
+    ensure_loaded();
+    let cache = KEYS.read().unwrap();
+    let backend_keys = cache.as_ref()?.get(backend)?;
+    let keys = &backend_keys.keys;
+    if keys.is_empty() {
+        return None;
+    }
+
+    let chosen = match balancing {
+        KeyBalancing::RoundRobin => &keys[backend_keys.next.fetch_add(1, Ordering::Relaxed) % keys.len()],
+        KeyBalancing::LeastLoaded => keys
+            .iter()
+            .min_by_key(|k| (k.in_flight.load(Ordering::Relaxed), k.request_count.load(Ordering::Relaxed)))?,
+    };
+    chosen.in_flight.fetch_add(1, Ordering::Relaxed);
+    chosen.request_count.fetch_add(1, Ordering::Relaxed);
+
+    Some((
+        chosen.key.clone(),
+        KeyLease {
+            key_id: chosen.id,
+            in_flight: chosen.in_flight.clone(),
+            input_tokens: 0,
+            output_tokens: 0,
+            released: false,
+        },
+    ))
+}
+
+/// Drop every credential the client sent
+pub fn strip_client_credentials(headers: &mut HeaderMap) {
+    for header in CLIENT_CREDENTIAL_HEADERS {
+        headers.remove(*header);
+    }
+}
+
+/// Replace the client's credentials with one of the backend's stored keys, if it has any
+/// The returned lease should be kept until the response is done, so load and usage are tracked
+pub fn inject_api_key(backend: &dyn Backend, headers: &mut HeaderMap) -> Option<KeyLease> {
+    let (key, lease) = checkout(backend.name(), backend.api_key_balancing())?;
+
+    let (name, value) = match backend.api_key_header() {
+        ApiKeyHeader::Bearer => ("authorization", format!("Bearer {}", key)),
+        ApiKeyHeader::XApiKey => ("x-api-key", key),
+        ApiKeyHeader::GoogApiKey => ("x-goog-api-key", key),
+    };
+    let mut value = HeaderValue::from_str(&value).ok()?;
+    value.set_sensitive(true);
+
+    strip_client_credentials(headers);
+    headers.insert(name, value);
+    Some(lease)
+}
@@ -0,0 +1,1088 @@
+// API Translation
+//
+// A backend can take requests in the other API shape than its upstream speaks. With
+// `anthropic_to_openai`, clients send Anthropic Messages requests (e.g. Claude Code) to an
+// OpenAI Chat Completions compatible upstream; `openai_to_anthropic` is the reverse. A request is
+// translated as soon as it arrives, so DLP, limits, the cache, failover and the log all see it as
+// the upstream does, and the response (streamed events and errors included) is translated back
+// for the client. Messages, system prompts, images, tool definitions, tool calls and tool results
+// are converted; what has no counterpart (thinking blocks, documents, logprobs) is dropped.
+// Requests to other endpoints, such as model lists or token counting, are forwarded unchanged.
+
+use crate::backends::ClientApi;
+use axum::http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Anthropic API version sent for clients speaking OpenAI, which don't send one
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Output limit for OpenAI requests without one; Anthropic requires it
+const DEFAULT_MAX_TOKENS: u64 = 4096;
+
+/// Which way requests are translated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationMode {
+    #[default]
+    Off,
+    /// Clients speak Anthropic Messages, the upstream OpenAI Chat Completions
+    AnthropicToOpenai,
+    /// Clients speak OpenAI Chat Completions, the upstream Anthropic Messages
+    OpenaiToAnthropic,
+}
+
+/// API translation settings of a backend (`translation` in its settings JSON)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiTranslation {
+    pub mode: TranslationMode,
+    /// Model to ask the upstream for (empty = the model the client asked for)
+    pub model: String,
+}
+
+impl ApiTranslation {
+    /// API the client speaks, as recorded in the log
+    pub fn client_api(&self) -> &'static str {
+        match self.mode {
+            TranslationMode::Off => "",
+            TranslationMode::AnthropicToOpenai => "anthropic",
+            TranslationMode::OpenaiToAnthropic => "openai",
+        }
+    }
+
+    /// API the client speaks, when requests are translated
+    pub fn client(&self) -> Option<ClientApi> {
+        match self.mode {
+            TranslationMode::Off => None,
+            TranslationMode::AnthropicToOpenai => Some(ClientApi::Anthropic),
+            TranslationMode::OpenaiToAnthropic => Some(ClientApi::OpenAI),
+        }
+    }
+
+    /// Upstream path and body of a client request, or None if it isn't translated
+    pub fn translate_request(&self, path: &str, body: &str) -> Option<(String, String)> {
+        let (client_endpoint, upstream_endpoint) = match self.mode {
+            TranslationMode::Off => return None,
+            TranslationMode::AnthropicToOpenai => ("/messages", "/chat/completions"),
+            TranslationMode::OpenaiToAnthropic => ("/chat/completions", "/messages"),
+        };
+        let upstream_path = format!("{}{}", path.strip_suffix(client_endpoint)?, upstream_endpoint);
+
+        let request = serde_json::from_str::<Value>(body).ok()?;
+        let mut translated = if self.mode == TranslationMode::AnthropicToOpenai {
+            anthropic_request_to_openai(request.as_object()?)
+        } else {
+            openai_request_to_anthropic(request.as_object()?)
+        };
+        if !self.model.is_empty() {
+            translated.insert("model".to_string(), self.model.clone().into());
+        }
+        Some((upstream_path, Value::Object(translated).to_string()))
+    }
+
+    /// Move the client's credentials to the header the upstream API reads, and drop the client
+    /// API's version headers
+    pub fn translate_headers(&self, headers: &mut HeaderMap) {
+        match self.mode {
+            TranslationMode::Off => {}
+            TranslationMode::AnthropicToOpenai => {
+                let key = headers.remove("x-api-key");
+                let bearer = key
+                    .as_ref()
+                    .and_then(|k| k.to_str().ok())
+                    .and_then(|k| HeaderValue::from_str(&format!("Bearer {}", k)).ok());
+                if let Some(mut value) = bearer {
+                    value.set_sensitive(true);
+                    headers.insert(AUTHORIZATION, value);
+                }
+                headers.remove("anthropic-version");
+                headers.remove("anthropic-beta");
+            }
+            TranslationMode::OpenaiToAnthropic => {
+                let key = headers
+                    .get(AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+                    .and_then(|k| HeaderValue::from_str(k.trim()).ok());
+                if let Some(mut value) = key {
+                    value.set_sensitive(true);
+                    headers.remove(AUTHORIZATION);
+                    headers.insert("x-api-key", value);
+                }
+                headers
+                    .entry("anthropic-version")
+                    .or_insert(HeaderValue::from_static(ANTHROPIC_VERSION));
+                headers.remove("openai-organization");
+                headers.remove("openai-project");
+            }
+        }
+    }
+
+    /// A buffered upstream response in the client's API; bodies that aren't JSON are left as is
+    pub fn translate_response(&self, body: &str) -> String {
+        let Some(response) = serde_json::from_str::<Value>(body).ok().filter(Value::is_object) else {
+            return body.to_string();
+        };
+        match self.mode {
+            TranslationMode::Off => body.to_string(),
+            TranslationMode::AnthropicToOpenai => openai_response_to_anthropic(&response).to_string(),
+            TranslationMode::OpenaiToAnthropic => anthropic_response_to_openai(&response).to_string(),
+        }
+    }
+}
+
+/// Text of a string or an array of content blocks
+fn text_of(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn copy_fields(from: &Map<String, Value>, to: &mut Map<String, Value>, fields: &[(&str, &str)]) {
+    for (from_name, to_name) in fields {
+        if let Some(value) = from.get(*from_name).filter(|v| !v.is_null()) {
+            to.insert(to_name.to_string(), value.clone());
+        }
+    }
+}
+
+// Anthropic -> OpenAI
+
+fn anthropic_image_to_openai(block: &Value) -> Option<Value> {
+    let source = block.get("source")?;
+    let url = match source.get("type").and_then(Value::as_str)? {
+        "base64" => format!(
+            "data:{};base64,{}",
+            source.get("media_type")?.as_str()?,
+            source.get("data")?.as_str()?
+        ),
+        "url" => source.get("url")?.as_str()?.to_string(),
+        _ => return None,
+    };
+    Some(json!({"type": "image_url", "image_url": {"url": url}}))
+}
+
+/// A user turn: tool results become `tool` messages, the rest one user message
+fn anthropic_user_blocks_to_openai(blocks: &[Value], messages: &mut Vec<Value>) {
+    let mut parts = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("tool_result") => messages.push(json!({
+                "role": "tool",
+                "tool_call_id": block.get("tool_use_id").cloned().unwrap_or_default(),
+                "content": block.get("content").map(text_of).unwrap_or_default(),
+            })),
+            Some("text") => parts.push(json!({"type": "text", "text": block.get("text").cloned().unwrap_or_default()})),
+            Some("image") => parts.extend(anthropic_image_to_openai(block)),
+            _ => {}
+        }
+    }
+    if !parts.is_empty() {
+        messages.push(json!({"role": "user", "content": parts}));
+    }
+}
+
+fn anthropic_assistant_blocks_to_openai(blocks: &[Value]) -> Value {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") => text.push_str(block.get("text").and_then(Value::as_str).unwrap_or_default()),
+            Some("tool_use") => tool_calls.push(json!({
+                "id": block.get("id").cloned().unwrap_or_default(),
+                "type": "function",
+                "function": {
+                    "name": block.get("name").cloned().unwrap_or_default(),
+                    "arguments": block.get("input").map(Value::to_string).unwrap_or_else(|| "{}".to_string()),
+                },
+            })),
+            _ => {}
+        }
+    }
+
+    let mut message = json!({"role": "assistant", "content": if text.is_empty() { Value::Null } else { text.into() }});
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = tool_calls.into();
+    }
+    message
+}
+
+fn anthropic_request_to_openai(request: &Map<String, Value>) -> Map<String, Value> {
+    let mut messages = Vec::new();
+    let system = request.get("system").map(text_of).unwrap_or_default();
+    if !system.is_empty() {
+        messages.push(json!({"role": "system", "content": system}));
+    }
+    for message in request.get("messages").and_then(Value::as_array).into_iter().flatten() {
+        let role = message.get("role").and_then(Value::as_str).unwrap_or("user");
+        match message.get("content") {
+            Some(Value::Array(blocks)) if role == "assistant" => messages.push(anthropic_assistant_blocks_to_openai(blocks)),
+            Some(Value::Array(blocks)) => anthropic_user_blocks_to_openai(blocks, &mut messages),
+            Some(content) => messages.push(json!({"role": role, "content": content})),
+            None => {}
+        }
+    }
+
+    let mut out = Map::new();
+    copy_fields(
+        request,
+        &mut out,
+        &[("model", "model"), ("max_tokens", "max_tokens"), ("temperature", "temperature"), ("top_p", "top_p"), ("stop_sequences", "stop"), ("stream", "stream")],
+    );
+    out.insert("messages".to_string(), messages.into());
+    if out.get("stream").and_then(Value::as_bool) == Some(true) {
+        // Usage only comes in the last chunk when asked for
+        out.insert("stream_options".to_string(), json!({"include_usage": true}));
+    }
+
+    // Server tools (web search and the like) have no input schema and no OpenAI counterpart
+    let tools: Vec<Value> = request
+        .get("tools")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|tool| tool.get("input_schema").is_some())
+        .map(|tool| {
+            let mut function = Map::new();
+            copy_fields(
+                tool.as_object().unwrap_or(&Map::new()),
+                &mut function,
+                &[("name", "name"), ("description", "description"), ("input_schema", "parameters")],
+            );
+            json!({"type": "function", "function": function})
+        })
+        .collect();
+    if !tools.is_empty() {
+        out.insert("tools".to_string(), tools.into());
+    }
+
+    if let Some(choice) = request.get("tool_choice") {
+        let translated = match choice.get("type").and_then(Value::as_str) {
+            Some("any") => Some(json!("required")),
+            Some("none") => Some(json!("none")),
+            Some("tool") => Some(json!({"type": "function", "function": {"name": choice.get("name").cloned().unwrap_or_default()}})),
+            Some("auto") => Some(json!("auto")),
+            _ => None,
+        };
+        if let Some(translated) = translated {
+            out.insert("tool_choice".to_string(), translated);
+        }
+        if choice.get("disable_parallel_tool_use").and_then(Value::as_bool) == Some(true) {
+            out.insert("parallel_tool_calls".to_string(), false.into());
+        }
+    }
+    if let Some(user) = request.get("metadata").and_then(|m| m.get("user_id")) {
+        out.insert("user".to_string(), user.clone());
+    }
+    out
+}
+
+fn anthropic_stop_reason_to_openai(reason: &str) -> &'static str {
+    match reason {
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        "refusal" => "content_filter",
+        _ => "stop",
+    }
+}
+
+/// Prompt tokens as OpenAI counts them: cached input included
+fn anthropic_prompt_tokens(usage: &Value) -> u64 {
+    ["input_tokens", "cache_read_input_tokens", "cache_creation_input_tokens"]
+        .iter()
+        .filter_map(|field| usage.get(*field).and_then(Value::as_u64))
+        .sum()
+}
+
+fn anthropic_error_to_openai(error: &Value) -> Value {
+    json!({"error": {
+        "message": error.get("message").cloned().unwrap_or_default(),
+        "type": error.get("type").cloned().unwrap_or_else(|| "api_error".into()),
+        "code": Value::Null,
+    }})
+}
+
+fn anthropic_response_to_openai(response: &Value) -> Value {
+    if response.get("type").and_then(Value::as_str) == Some("error") {
+        return anthropic_error_to_openai(response.get("error").unwrap_or(&Value::Null));
+    }
+
+    let blocks = response.get("content").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    let message = anthropic_assistant_blocks_to_openai(blocks);
+    let usage = response.get("usage").cloned().unwrap_or_default();
+    let prompt_tokens = anthropic_prompt_tokens(&usage);
+    let completion_tokens = usage.get("output_tokens").and_then(Value::as_u64).unwrap_or(0);
+    let finish_reason = response
+        .get("stop_reason")
+        .and_then(Value::as_str)
+        .map(anthropic_stop_reason_to_openai);
+
+    json!({
+        "id": response.get("id").cloned().unwrap_or_default(),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": response.get("model").cloned().unwrap_or_default(),
+        "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    })
+}
+
+// OpenAI -> Anthropic
+
+fn openai_image_to_anthropic(part: &Value) -> Option<Value> {
+    let url = part.get("image_url")?.get("url")?.as_str()?;
+    let source = match url.strip_prefix("data:").and_then(|rest| rest.split_once(";base64,")) {
+        Some((media_type, data)) => json!({"type": "base64", "media_type": media_type, "data": data}),
+        None => json!({"type": "url", "url": url}),
+    };
+    Some(json!({"type": "image", "source": source}))
+}
+
+fn openai_user_content_to_anthropic(content: &Value) -> Vec<Value> {
+    match content {
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(Value::as_str) {
+                Some("text") => Some(json!({"type": "text", "text": part.get("text").cloned().unwrap_or_default()})),
+                Some("image_url") => openai_image_to_anthropic(part),
+                _ => None,
+            })
+            .collect(),
+        content => vec![json!({"type": "text", "text": text_of(content)})],
+    }
+}
+
+/// Append blocks to the conversation; Anthropic wants roles to alternate, so consecutive turns of
+/// one role (e.g. several tool results) are merged. Empty text blocks are rejected, so dropped.
+fn push_anthropic_turn(messages: &mut Vec<Value>, role: &str, blocks: Vec<Value>) {
+    let blocks: Vec<Value> = blocks
+        .into_iter()
+        .filter(|b| b.get("type").and_then(Value::as_str) != Some("text") || b.get("text").and_then(Value::as_str).is_some_and(|t| !t.is_empty()))
+        .collect();
+    if blocks.is_empty() {
+        return;
+    }
+    if let Some(last) = messages.last_mut().filter(|m| m.get("role").and_then(Value::as_str) == Some(role)) {
+        if let Some(content) = last.get_mut("content").and_then(Value::as_array_mut) {
+            content.extend(blocks);
+            return;
+        }
+    }
+    messages.push(json!({"role": role, "content": blocks}));
+}
+
+fn openai_tool_calls_to_anthropic(message: &Value) -> Vec<Value> {
+    message
+        .get("tool_calls")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|call| {
+            let function = call.get("function").cloned().unwrap_or_default();
+            let input = function
+                .get("arguments")
+                .and_then(Value::as_str)
+                .and_then(|args| serde_json::from_str::<Value>(args).ok())
+                .filter(Value::is_object)
+                .unwrap_or_else(|| json!({}));
+            json!({
+                "type": "tool_use",
+                "id": call.get("id").cloned().unwrap_or_default(),
+                "name": function.get("name").cloned().unwrap_or_default(),
+                "input": input,
+            })
+        })
+        .collect()
+}
+
+fn openai_request_to_anthropic(request: &Map<String, Value>) -> Map<String, Value> {
+    let mut system = Vec::new();
+    let mut messages = Vec::new();
+    for message in request.get("messages").and_then(Value::as_array).into_iter().flatten() {
+        let content = message.get("content").unwrap_or(&Value::Null);
+        match message.get("role").and_then(Value::as_str).unwrap_or("user") {
+            "system" | "developer" => system.push(text_of(content)),
+            "assistant" => {
+                let mut blocks = vec![json!({"type": "text", "text": text_of(content)})];
+                blocks.extend(openai_tool_calls_to_anthropic(message));
+                push_anthropic_turn(&mut messages, "assistant", blocks);
+            }
+            "tool" => {
+                let result = json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.get("tool_call_id").cloned().unwrap_or_default(),
+                    "content": text_of(content),
+                });
+                push_anthropic_turn(&mut messages, "user", vec![result]);
+            }
+            _ => push_anthropic_turn(&mut messages, "user", openai_user_content_to_anthropic(content)),
+        }
+    }
+
+    let mut out = Map::new();
+    copy_fields(request, &mut out, &[("model", "model"), ("top_p", "top_p"), ("stream", "stream")]);
+    let max_tokens = ["max_completion_tokens", "max_tokens"]
+        .iter()
+        .find_map(|field| request.get(*field).and_then(Value::as_u64))
+        .unwrap_or(DEFAULT_MAX_TOKENS);
+    out.insert("max_tokens".to_string(), max_tokens.into());
+    let system: Vec<String> = system.into_iter().filter(|s| !s.is_empty()).collect();
+    if !system.is_empty() {
+        out.insert("system".to_string(), system.join("\n\n").into());
+    }
+    out.insert("messages".to_string(), messages.into());
+    // OpenAI's range is 0-2, Anthropic's 0-1
+    if let Some(temperature) = request.get("temperature").and_then(Value::as_f64) {
+        out.insert("temperature".to_string(), temperature.min(1.0).into());
+    }
+    match request.get("stop") {
+        Some(Value::String(stop)) => {
+            out.insert("stop_sequences".to_string(), json!([stop]));
+        }
+        Some(stop @ Value::Array(_)) => {
+            out.insert("stop_sequences".to_string(), stop.clone());
+        }
+        _ => {}
+    }
+
+    let tools: Vec<Value> = request
+        .get("tools")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|tool| tool.get("function"))
+        .map(|function| {
+            let mut tool = Map::new();
+            copy_fields(
+                function.as_object().unwrap_or(&Map::new()),
+                &mut tool,
+                &[("name", "name"), ("description", "description"), ("parameters", "input_schema")],
+            );
+            tool.entry("input_schema")
+                .or_insert_with(|| json!({"type": "object", "properties": {}}));
+            Value::Object(tool)
+        })
+        .collect();
+    if !tools.is_empty() {
+        out.insert("tools".to_string(), tools.into());
+    }
+
+    let mut tool_choice = match request.get("tool_choice") {
+        Some(Value::String(choice)) if choice == "required" => Some(json!({"type": "any"})),
+        Some(Value::String(choice)) if choice == "none" => Some(json!({"type": "none"})),
+        Some(Value::String(_)) => Some(json!({"type": "auto"})),
+        Some(choice @ Value::Object(_)) => choice
+            .get("function")
+            .and_then(|f| f.get("name"))
+            .map(|name| json!({"type": "tool", "name": name})),
+        _ => None,
+    };
+    if request.get("parallel_tool_calls").and_then(Value::as_bool) == Some(false) {
+        tool_choice.get_or_insert_with(|| json!({"type": "auto"}))["disable_parallel_tool_use"] = true.into();
+    }
+    if let Some(tool_choice) = tool_choice {
+        out.insert("tool_choice".to_string(), tool_choice);
+    }
+    if let Some(user) = request.get("user") {
+        out.insert("metadata".to_string(), json!({"user_id": user}));
+    }
+    out
+}
+
+fn openai_finish_reason_to_anthropic(reason: &str) -> &'static str {
+    match reason {
+        "length" => "max_tokens",
+        "tool_calls" | "function_call" => "tool_use",
+        "content_filter" => "refusal",
+        _ => "end_turn",
+    }
+}
+
+fn openai_error_to_anthropic(error: &Value) -> Value {
+    json!({"type": "error", "error": {
+        "type": error.get("type").filter(|t| t.is_string()).cloned().unwrap_or_else(|| "api_error".into()),
+        "message": error.get("message").cloned().unwrap_or_default(),
+    }})
+}
+
+fn openai_response_to_anthropic(response: &Value) -> Value {
+    if let Some(error) = response.get("error") {
+        return openai_error_to_anthropic(error);
+    }
+
+    let choice = response.get("choices").and_then(|c| c.get(0)).cloned().unwrap_or_default();
+    let message = choice.get("message").cloned().unwrap_or_default();
+    let mut content = Vec::new();
+    if let Some(text) = message.get("content").and_then(Value::as_str).filter(|t| !t.is_empty()) {
+        content.push(json!({"type": "text", "text": text}));
+    }
+    content.extend(openai_tool_calls_to_anthropic(&message));
+    let usage = response.get("usage").cloned().unwrap_or_default();
+
+    json!({
+        "id": response.get("id").cloned().unwrap_or_default(),
+        "type": "message",
+        "role": "assistant",
+        "model": response.get("model").cloned().unwrap_or_default(),
+        "content": content,
+        "stop_reason": choice.get("finish_reason").and_then(Value::as_str).map(openai_finish_reason_to_anthropic),
+        "stop_sequence": Value::Null,
+        "usage": {
+            "input_tokens": usage.get("prompt_tokens").and_then(Value::as_u64).unwrap_or(0),
+            "output_tokens": usage.get("completion_tokens").and_then(Value::as_u64).unwrap_or(0),
+        },
+    })
+}
+
+// Streaming
+
+fn anthropic_event(data: Value) -> String {
+    format!("event: {}\ndata: {}\n\n", data["type"].as_str().unwrap_or_default(), data)
+}
+
+/// OpenAI chunks -> Anthropic events
+#[derive(Default)]
+struct ToAnthropicStream {
+    started: bool,
+    finished: bool,
+    /// Open content block: its index, and the OpenAI index of its tool call (None = text)
+    open_block: Option<(usize, Option<u64>)>,
+    next_block: usize,
+    stop_reason: Option<&'static str>,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl ToAnthropicStream {
+    fn start(&mut self, out: &mut String, chunk: &Value) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        out.push_str(&anthropic_event(json!({
+            "type": "message_start",
+            "message": {
+                "id": chunk.get("id").cloned().unwrap_or_default(),
+                "type": "message",
+                "role": "assistant",
+                "model": chunk.get("model").cloned().unwrap_or_default(),
+                "content": [],
+                "stop_reason": Value::Null,
+                "stop_sequence": Value::Null,
+                "usage": {"input_tokens": 0, "output_tokens": 0},
+            },
+        })));
+    }
+
+    fn close_block(&mut self, out: &mut String) {
+        if let Some((index, _)) = self.open_block.take() {
+            out.push_str(&anthropic_event(json!({"type": "content_block_stop", "index": index})));
+        }
+    }
+
+    fn open_block(&mut self, out: &mut String, tool_call: Option<u64>, content_block: Value) -> usize {
+        self.close_block(out);
+        let index = self.next_block;
+        self.next_block += 1;
+        self.open_block = Some((index, tool_call));
+        out.push_str(&anthropic_event(json!({"type": "content_block_start", "index": index, "content_block": content_block})));
+        index
+    }
+
+    fn chunk(&mut self, out: &mut String, chunk: &Value) {
+        if let Some(error) = chunk.get("error") {
+            out.push_str(&anthropic_event(openai_error_to_anthropic(error)));
+            self.finished = true;
+            return;
+        }
+        self.start(out, chunk);
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.input_tokens = usage.get("prompt_tokens").and_then(Value::as_u64).unwrap_or(self.input_tokens);
+            self.output_tokens = usage.get("completion_tokens").and_then(Value::as_u64).unwrap_or(self.output_tokens);
+        }
+        let Some(choice) = chunk.get("choices").and_then(|c| c.get(0)) else {
+            return;
+        };
+        let delta = choice.get("delta").cloned().unwrap_or_default();
+
+        if let Some(text) = delta.get("content").and_then(Value::as_str).filter(|t| !t.is_empty()) {
+            let index = match self.open_block {
+                Some((index, None)) => index,
+                _ => self.open_block(out, None, json!({"type": "text", "text": ""})),
+            };
+            out.push_str(&anthropic_event(json!({
+                "type": "content_block_delta",
+                "index": index,
+                "delta": {"type": "text_delta", "text": text},
+            })));
+        }
+
+        for call in delta.get("tool_calls").and_then(Value::as_array).into_iter().flatten() {
+            let call_index = call.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let function = call.get("function").cloned().unwrap_or_default();
+            let index = match self.open_block {
+                Some((index, Some(open))) if open == call_index => index,
+                _ => self.open_block(
+                    out,
+                    Some(call_index),
+                    json!({
+                        "type": "tool_use",
+                        "id": call.get("id").cloned().unwrap_or_default(),
+                        "name": function.get("name").cloned().unwrap_or_default(),
+                        "input": {},
+                    }),
+                ),
+            };
+            if let Some(arguments) = function.get("arguments").and_then(Value::as_str).filter(|a| !a.is_empty()) {
+                out.push_str(&anthropic_event(json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {"type": "input_json_delta", "partial_json": arguments},
+                })));
+            }
+        }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.stop_reason = Some(openai_finish_reason_to_anthropic(reason));
+        }
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.start(out, &Value::Null);
+        self.close_block(out);
+        out.push_str(&anthropic_event(json!({
+            "type": "message_delta",
+            "delta": {"stop_reason": self.stop_reason.unwrap_or("end_turn"), "stop_sequence": Value::Null},
+            "usage": {"input_tokens": self.input_tokens, "output_tokens": self.output_tokens},
+        })));
+        out.push_str(&anthropic_event(json!({"type": "message_stop"})));
+    }
+}
+
+/// Anthropic events -> OpenAI chunks
+#[derive(Default)]
+struct ToOpenaiStream {
+    started: bool,
+    id: Value,
+    model: Value,
+    created: i64,
+    /// Content block index -> OpenAI tool call index
+    tool_calls: HashMap<u64, usize>,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    done: bool,
+}
+
+impl ToOpenaiStream {
+    fn chunk(&self, out: &mut String, delta: Value, finish_reason: Option<&str>) {
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        });
+        out.push_str(&format!("data: {}\n\n", chunk));
+    }
+
+    fn event(&mut self, out: &mut String, event: &Value) {
+        match event.get("type").and_then(Value::as_str).unwrap_or_default() {
+            "message_start" => {
+                let message = event.get("message").cloned().unwrap_or_default();
+                self.started = true;
+                self.id = message.get("id").cloned().unwrap_or_default();
+                self.model = message.get("model").cloned().unwrap_or_default();
+                self.created = chrono::Utc::now().timestamp();
+                self.prompt_tokens = message.get("usage").map(anthropic_prompt_tokens).unwrap_or(0);
+                self.chunk(out, json!({"role": "assistant", "content": ""}), None);
+            }
+            "content_block_start" => {
+                let block = event.get("content_block").cloned().unwrap_or_default();
+                if block.get("type").and_then(Value::as_str) == Some("tool_use") {
+                    let call_index = self.tool_calls.len();
+                    self.tool_calls
+                        .insert(event.get("index").and_then(Value::as_u64).unwrap_or(0), call_index);
+                    let call = json!({
+                        "index": call_index,
+                        "id": block.get("id").cloned().unwrap_or_default(),
+                        "type": "function",
+                        "function": {"name": block.get("name").cloned().unwrap_or_default(), "arguments": ""},
+                    });
+                    self.chunk(out, json!({"tool_calls": [call]}), None);
+                }
+            }
+            "content_block_delta" => {
+                let delta = event.get("delta").cloned().unwrap_or_default();
+                match delta.get("type").and_then(Value::as_str) {
+                    Some("text_delta") => {
+                        self.chunk(out, json!({"content": delta.get("text").cloned().unwrap_or_default()}), None)
+                    }
+                    Some("input_json_delta") => {
+                        let block = event.get("index").and_then(Value::as_u64).unwrap_or(0);
+                        if let Some(&call_index) = self.tool_calls.get(&block) {
+                            let call = json!({
+                                "index": call_index,
+                                "function": {"arguments": delta.get("partial_json").cloned().unwrap_or_default()},
+                            });
+                            self.chunk(out, json!({"tool_calls": [call]}), None);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(tokens) = event.get("usage").and_then(|u| u.get("output_tokens")).and_then(Value::as_u64) {
+                    self.completion_tokens = tokens;
+                }
+                if let Some(reason) = event.get("delta").and_then(|d| d.get("stop_reason")).and_then(Value::as_str) {
+                    self.chunk(out, json!({}), Some(anthropic_stop_reason_to_openai(reason)));
+                }
+            }
+            "message_stop" => self.finish(out),
+            "error" => {
+                let error = anthropic_error_to_openai(event.get("error").unwrap_or(&Value::Null));
+                out.push_str(&format!("data: {}\n\n", error));
+                self.finish(out);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+        // The usage chunk OpenAI sends with `include_usage`
+        let usage = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [],
+            "usage": {
+                "prompt_tokens": self.prompt_tokens,
+                "completion_tokens": self.completion_tokens,
+                "total_tokens": self.prompt_tokens + self.completion_tokens,
+            },
+        });
+        out.push_str(&format!("data: {}\n\n", usage));
+        out.push_str("data: [DONE]\n\n");
+    }
+}
+
+enum StreamState {
+    ToAnthropic(ToAnthropicStream),
+    ToOpenai(ToOpenaiStream),
+}
+
+/// Translates a streamed upstream response for the client, event by event
+pub struct StreamTranslator {
+    translation: ApiTranslation,
+    state: Option<StreamState>,
+    /// Bytes of an incomplete event, or of a whole body that isn't an event stream (e.g. a JSON
+    /// error), translated once it ends
+    buffer: Vec<u8>,
+}
+
+impl StreamTranslator {
+    pub fn new(translation: &ApiTranslation, is_event_stream: bool) -> Self {
+        let state = match translation.mode {
+            _ if !is_event_stream => None,
+            TranslationMode::Off => None,
+            TranslationMode::AnthropicToOpenai => Some(StreamState::ToAnthropic(ToAnthropicStream::default())),
+            TranslationMode::OpenaiToAnthropic => Some(StreamState::ToOpenai(ToOpenaiStream::default())),
+        };
+        StreamTranslator {
+            translation: translation.clone(),
+            state,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Translate the complete events in a chunk; the rest waits for the next chunk
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<u8> {
+        if self.translation.mode == TranslationMode::Off {
+            return bytes.to_vec();
+        }
+        // Event boundaries are blank lines; JSON data never holds a raw CR
+        self.buffer.extend(bytes.iter().filter(|&&b| b != b'\r'));
+        if self.state.is_none() {
+            return Vec::new();
+        }
+
+        let mut out = String::new();
+        while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            self.event(&mut out, &String::from_utf8_lossy(&event));
+        }
+        out.into_bytes()
+    }
+
+    /// Translate what is left, closing the message if the upstream didn't
+    pub fn finish(&mut self) -> Vec<u8> {
+        if self.translation.mode == TranslationMode::Off {
+            return Vec::new();
+        }
+        let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+        if self.state.is_none() {
+            return self.translation.translate_response(&rest).into_bytes();
+        }
+
+        let mut out = String::new();
+        self.event(&mut out, &rest);
+        match self.state.as_mut() {
+            Some(StreamState::ToAnthropic(stream)) => stream.finish(&mut out),
+            Some(StreamState::ToOpenai(stream)) if stream.started => stream.finish(&mut out),
+            _ => {}
+        }
+        out.into_bytes()
+    }
+
+    fn event(&mut self, out: &mut String, event: &str) {
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim_start)
+            .collect();
+        if data.is_empty() {
+            return;
+        }
+        let data = data.join("\n");
+
+        match self.state.as_mut() {
+            Some(StreamState::ToAnthropic(stream)) if data == "[DONE]" => stream.finish(out),
+            Some(StreamState::ToAnthropic(stream)) => {
+                if let Ok(chunk) = serde_json::from_str::<Value>(&data) {
+                    stream.chunk(out, &chunk);
+                }
+            }
+            Some(StreamState::ToOpenai(stream)) => {
+                if let Ok(event) = serde_json::from_str::<Value>(&data) {
+                    stream.event(out, &event);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(mode: TranslationMode) -> ApiTranslation {
+        ApiTranslation { mode, model: String::new() }
+    }
+
+    #[test]
+    fn test_anthropic_request_to_openai() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 256,
+            "system": [{"type": "text", "text": "Be brief."}],
+            "stream": true,
+            "tools": [{"name": "read_file", "description": "Read a file", "input_schema": {"type": "object"}}],
+            "tool_choice": {"type": "any"},
+            "messages": [
+                {"role": "user", "content": "Open main.rs"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Reading it."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": {"path": "main.rs"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "fn main() {}"}]},
+                    {"type": "text", "text": "Explain it"}
+                ]}
+            ]
+        });
+        let (path, translated) = translation(TranslationMode::AnthropicToOpenai)
+            .translate_request("/v1/messages", &body.to_string())
+            .unwrap();
+        assert_eq!(path, "/v1/chat/completions");
+
+        let translated: Value = serde_json::from_str(&translated).unwrap();
+        let messages = translated["messages"].as_array().unwrap();
+        assert_eq!(messages[0], json!({"role": "system", "content": "Be brief."}));
+        assert_eq!(messages[1], json!({"role": "user", "content": "Open main.rs"}));
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["arguments"], r#"{"path":"main.rs"}"#);
+        assert_eq!(messages[3], json!({"role": "tool", "tool_call_id": "toolu_1", "content": "fn main() {}"}));
+        assert_eq!(messages[4]["content"][0]["text"], "Explain it");
+        assert_eq!(translated["tools"][0]["function"]["parameters"], json!({"type": "object"}));
+        assert_eq!(translated["tool_choice"], "required");
+        assert_eq!(translated["stream_options"]["include_usage"], true);
+
+        // Other endpoints pass through
+        assert!(translation(TranslationMode::AnthropicToOpenai)
+            .translate_request("/v1/messages/count_tokens", &body.to_string())
+            .is_none());
+    }
+
+    #[test]
+    fn test_openai_request_to_anthropic() {
+        let body = json!({
+            "model": "gpt-4o",
+            "temperature": 1.5,
+            "stop": "END",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Weather in Paris and Rome?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}},
+                    {"id": "call_2", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Rome\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "Sunny"},
+                {"role": "tool", "tool_call_id": "call_2", "content": "Rain"}
+            ]
+        });
+        let with_model = ApiTranslation {
+            mode: TranslationMode::OpenaiToAnthropic,
+            model: "claude-haiku-4".to_string(),
+        };
+        let (path, translated) = with_model.translate_request("/v1/chat/completions", &body.to_string()).unwrap();
+        assert_eq!(path, "/v1/messages");
+
+        let translated: Value = serde_json::from_str(&translated).unwrap();
+        assert_eq!(translated["model"], "claude-haiku-4");
+        assert_eq!(translated["system"], "Be brief.");
+        assert_eq!(translated["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(translated["temperature"], 1.0);
+        assert_eq!(translated["stop_sequences"], json!(["END"]));
+        let messages = translated["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        // The null content becomes no text block, only the tool calls
+        assert_eq!(messages[1]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(messages[1]["content"][0]["input"], json!({"city": "Paris"}));
+        // Both tool results go in one user turn
+        assert_eq!(messages[2]["content"][1]["tool_use_id"], "call_2");
+    }
+
+    #[test]
+    fn test_translate_responses() {
+        let openai = json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{"index": 0, "finish_reason": "tool_calls", "message": {"role": "assistant", "content": null,
+                "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}}]}}],
+            "usage": {"prompt_tokens": 20, "completion_tokens": 5}
+        });
+        let anthropic: Value = serde_json::from_str(
+            &translation(TranslationMode::AnthropicToOpenai).translate_response(&openai.to_string()),
+        )
+        .unwrap();
+        assert_eq!(anthropic["stop_reason"], "tool_use");
+        assert_eq!(anthropic["content"][0]["input"]["city"], "Paris");
+        assert_eq!(anthropic["usage"]["input_tokens"], 20);
+
+        let message = json!({
+            "id": "msg_1",
+            "type": "message",
+            "model": "claude-sonnet-4",
+            "content": [{"type": "text", "text": "Hello"}],
+            "stop_reason": "max_tokens",
+            "usage": {"input_tokens": 10, "cache_read_input_tokens": 90, "output_tokens": 3}
+        });
+        let openai: Value = serde_json::from_str(
+            &translation(TranslationMode::OpenaiToAnthropic).translate_response(&message.to_string()),
+        )
+        .unwrap();
+        assert_eq!(openai["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(openai["choices"][0]["finish_reason"], "length");
+        assert_eq!(openai["usage"]["prompt_tokens"], 100);
+
+        let error = json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}});
+        let openai: Value = serde_json::from_str(
+            &translation(TranslationMode::OpenaiToAnthropic).translate_response(&error.to_string()),
+        )
+        .unwrap();
+        assert_eq!(openai["error"]["type"], "overloaded_error");
+
+        assert_eq!(translation(TranslationMode::OpenaiToAnthropic).translate_response("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[test]
+    fn test_stream_openai_to_anthropic_events() {
+        let mut stream = StreamTranslator::new(&translation(TranslationMode::AnthropicToOpenai), true);
+        let chunks = concat!(
+            "data: {\"id\":\"c1\",\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"weather\",\"arguments\":\"{\\\"ci\"}}]}}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"ty\\\":1}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":4}}\n\n",
+            "data: [DONE]\n\n"
+        );
+        // Split mid-event: the first half waits for the rest
+        let (first, second) = chunks.split_at(40);
+        let mut out = stream.push(first.as_bytes());
+        assert!(out.is_empty());
+        out.extend(stream.push(second.as_bytes()));
+        out.extend(stream.finish());
+        let out = String::from_utf8(out).unwrap();
+
+        let events: Vec<Value> = out
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect();
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert_eq!(events[4]["content_block"]["name"], "weather");
+        assert_eq!(events[8]["delta"]["stop_reason"], "tool_use");
+        assert_eq!(events[8]["usage"]["output_tokens"], 4);
+    }
+
+    #[test]
+    fn test_stream_anthropic_to_openai_chunks() {
+        let mut stream = StreamTranslator::new(&translation(TranslationMode::OpenaiToAnthropic), true);
+        let events = concat!(
+            "event: message_start\r\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-sonnet-4\",\"usage\":{\"input_tokens\":12}}}\r\n\r\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":2}}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n"
+        );
+        let mut out = stream.push(events.as_bytes());
+        out.extend(stream.finish());
+        let out = String::from_utf8(out).unwrap();
+
+        let data: Vec<&str> = out.lines().filter_map(|l| l.strip_prefix("data: ")).collect();
+        assert_eq!(data.last(), Some(&"[DONE]"));
+        let chunks: Vec<Value> = data[..data.len() - 1].iter().map(|d| serde_json::from_str(d).unwrap()).collect();
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "Hello");
+        assert_eq!(chunks[2]["choices"][0]["finish_reason"], "stop");
+        assert_eq!(chunks[3]["usage"]["prompt_tokens"], 12);
+        assert_eq!(chunks[3]["usage"]["completion_tokens"], 2);
+        // [DONE] only once
+        assert_eq!(data.iter().filter(|d| **d == "[DONE]").count(), 1);
+    }
+}
@@ -1,8 +1,18 @@
 // Claude (Anthropic) Backend Implementation
 
+use axum::http::HeaderMap;
+use serde_json::json;
+
+use crate::api_keys::{ApiKeyHeader, KeyBalancing};
+use crate::api_translation::ApiTranslation;
 use crate::backends::custom::CustomBackendSettings;
-use crate::backends::Backend;
+use crate::backends::{Backend, ClientApi};
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
 use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+use crate::upstream::UpstreamSettings;
 use std::collections::HashMap;
 
 pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
@@ -205,7 +215,12 @@ impl Backend for ClaudeBackend {
         meta
     }
 
-    fn should_log(&self, body: &str) -> bool {
+    fn should_log(&self, path: &str, body: &str) -> bool {
+        // count_tokens takes the same body as a Messages API call
+        if self.is_token_count_request(path) {
+            return false;
+        }
+
         // Check if request body looks like a Messages API call
         if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
             // Must have "messages" array and "model" field
@@ -217,10 +232,40 @@ impl Backend for ClaudeBackend {
         }
     }
 
+    fn is_token_count_request(&self, path: &str) -> bool {
+        path.ends_with("/messages/count_tokens")
+    }
+
+    fn extract_extra_metadata(
+        &self,
+        request_body: &str,
+        _response_body: &str,
+        _headers: &HeaderMap,
+    ) -> Option<String> {
+        let json = serde_json::from_str::<serde_json::Value>(request_body).ok()?;
+        let user_id = json.get("metadata")?.get("user_id")?.as_str()?;
+
+        // Claude Code sends "user_<hash>_account_<uuid>_session_<uuid>", or the same fields as JSON
+        let session_id = match serde_json::from_str::<serde_json::Value>(user_id) {
+            Ok(fields) => fields.get("session_id")?.as_str()?.to_string(),
+            Err(_) => user_id.split_once("_session_")?.1.to_string(),
+        };
+
+        Some(json!({ "session_id": session_id }).to_string())
+    }
+
+    fn log_token_counts(&self) -> bool {
+        self.settings.log_token_counts
+    }
+
     fn is_dlp_enabled(&self) -> bool {
         self.settings.dlp_enabled
     }
 
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
     fn get_rate_limit(&self) -> (u32, u32) {
         (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
     }
@@ -228,4 +273,36 @@ impl Backend for ClaudeBackend {
     fn get_max_tokens_limit(&self) -> (u32, String) {
         (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
     }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+
+    fn client_api(&self, _request_body: &str) -> ClientApi {
+        ClientApi::Anthropic
+    }
+
+    fn api_key_header(&self) -> ApiKeyHeader {
+        ApiKeyHeader::XApiKey
+    }
 }
@@ -3,9 +3,16 @@
 use axum::http::HeaderMap;
 use serde_json::json;
 
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
 use crate::backends::custom::CustomBackendSettings;
 use crate::backends::Backend;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
 use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+use crate::upstream::UpstreamSettings;
 use std::collections::HashMap;
 
 pub const CODEX_BASE_URL: &str = "https://chatgpt.com/backend-api/codex";
@@ -231,7 +238,7 @@ impl Backend for CodexBackend {
         meta
     }
 
-    fn should_log(&self, body: &str) -> bool {
+    fn should_log(&self, _path: &str, body: &str) -> bool {
         // Log if request has "model" and "input" fields (completion request)
         if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
             let has_input = json.get("input").is_some();
@@ -310,6 +317,10 @@ impl Backend for CodexBackend {
         self.settings.dlp_enabled
     }
 
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
     fn get_rate_limit(&self) -> (u32, u32) {
         (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
     }
@@ -317,4 +328,28 @@ impl Backend for CodexBackend {
     fn get_max_tokens_limit(&self) -> (u32, String) {
         (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
     }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
 }
@@ -3,8 +3,16 @@
 use axum::http::HeaderMap;
 use serde::{Deserialize, Serialize};
 
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
 use crate::backends::Backend;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
 use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
 
 /// Settings for a custom backend
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,6 +32,53 @@ pub struct CustomBackendSettings {
     /// Action to take when max tokens is exceeded: "block" or "notify" (default: "block")
     #[serde(default = "default_block")]
     pub action_for_max_tokens_in_a_request: String,
+    /// Log token-counting calls to the token_counts table (default: false)
+    #[serde(default)]
+    pub log_token_counts: bool,
+    /// Dedicated listen port for this backend, in addition to its path prefix (0 = none)
+    #[serde(default)]
+    pub listen_port: u16,
+    /// Also scan system prompts / instructions with DLP (default: false)
+    #[serde(default)]
+    pub dlp_scan_system_prompt: bool,
+    /// Also scan tool definitions with DLP (default: false)
+    #[serde(default)]
+    pub dlp_scan_tool_definitions: bool,
+    /// Also scan assistant turns (text and tool call inputs) with DLP (default: false)
+    #[serde(default)]
+    pub dlp_scan_assistant_turns: bool,
+    /// Read text in attached images (OCR builds only) and block images containing detections (default: false)
+    #[serde(default)]
+    pub dlp_block_image_secrets: bool,
+    /// Upstream timeouts, retries and circuit breaker
+    #[serde(default)]
+    pub upstream: UpstreamSettings,
+    /// How requests are spread across the backend's stored API keys (default: round robin)
+    #[serde(default)]
+    pub api_key_balancing: KeyBalancing,
+    /// Per-header log actions (lowercase header name -> keep/hash/remove), on top of the defaults
+    #[serde(default)]
+    pub log_header_overrides: HashMap<String, HeaderLogAction>,
+    /// Secondary endpoint sampled requests are copied to (shadow traffic)
+    #[serde(default)]
+    pub mirror: MirrorSettings,
+    /// Backends tried in order when the upstream is overloaded
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackTarget>,
+    /// Translate between Anthropic Messages and OpenAI Chat Completions requests
+    #[serde(default)]
+    pub translation: ApiTranslation,
+}
+
+impl CustomBackendSettings {
+    pub fn dlp_scan_scope(&self) -> DlpScanScope {
+        DlpScanScope {
+            system_prompt: self.dlp_scan_system_prompt,
+            tool_definitions: self.dlp_scan_tool_definitions,
+            assistant_turns: self.dlp_scan_assistant_turns,
+            block_image_secrets: self.dlp_block_image_secrets,
+        }
+    }
 }
 
 fn default_true() -> bool {
@@ -38,6 +93,54 @@ fn default_block() -> String {
     "block".to_string()
 }
 
+/// Is this an Assistants API thread endpoint?
+/// Covers /threads, /threads/runs, /threads/{id}/messages and /threads/{id}/runs
+fn is_threads_path(path: &str) -> bool {
+    path.split('?')
+        .next()
+        .unwrap_or("")
+        .split('/')
+        .any(|segment| segment == "threads")
+}
+
+/// Count user/assistant messages in an OpenAI-style messages array
+fn count_messages(messages: &[serde_json::Value], meta: &mut RequestMetadata) {
+    for msg in messages {
+        if let Some(role) = msg.get("role").and_then(|v| v.as_str()) {
+            match role {
+                "user" => meta.user_message_count += 1,
+                "assistant" => meta.assistant_message_count += 1,
+                "system" => meta.has_system_prompt = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Capture Assistants API identifiers (thread, run, assistant) from a response object
+fn extract_thread_ids(json: &serde_json::Value, extra: &mut serde_json::Map<String, serde_json::Value>) {
+    let object = json.get("object").and_then(|v| v.as_str()).unwrap_or("");
+    let id = json.get("id").and_then(|v| v.as_str());
+
+    // Creating a thread returns the thread itself
+    if object == "thread" {
+        if let Some(id) = id {
+            extra.insert("thread_id".to_string(), serde_json::json!(id));
+        }
+    }
+    if object == "thread.run" {
+        if let Some(id) = id {
+            extra.insert("run_id".to_string(), serde_json::json!(id));
+        }
+    }
+    if let Some(thread_id) = json.get("thread_id").and_then(|v| v.as_str()) {
+        extra.insert("thread_id".to_string(), serde_json::json!(thread_id));
+    }
+    if let Some(assistant_id) = json.get("assistant_id").and_then(|v| v.as_str()) {
+        extra.insert("assistant_id".to_string(), serde_json::json!(assistant_id));
+    }
+}
+
 /// A custom backend that proxies to user-defined OpenAI-compatible endpoints
 pub struct CustomBackend {
     name: String,
@@ -87,16 +190,26 @@ impl Backend for CustomBackend {
 
             // Count messages in OpenAI format: {"messages": [{"role": "user", "content": "..."}]}
             if let Some(messages) = json.get("messages").and_then(|v| v.as_array()) {
-                for msg in messages {
-                    if let Some(role) = msg.get("role").and_then(|v| v.as_str()) {
-                        match role {
-                            "user" => meta.user_message_count += 1,
-                            "assistant" => meta.assistant_message_count += 1,
-                            "system" => meta.has_system_prompt = true,
-                            _ => {}
-                        }
-                    }
-                }
+                count_messages(messages, &mut meta);
+            }
+
+            // Assistants API: create message ({"role": "user", "content": ...}),
+            // create run (additional_messages) and create thread and run (thread.messages)
+            if json.get("role").is_some() && json.get("content").is_some() {
+                count_messages(std::slice::from_ref(&json), &mut meta);
+            }
+            if let Some(messages) = json.get("additional_messages").and_then(|v| v.as_array()) {
+                count_messages(messages, &mut meta);
+            }
+            if let Some(messages) = json
+                .get("thread")
+                .and_then(|t| t.get("messages"))
+                .and_then(|v| v.as_array())
+            {
+                count_messages(messages, &mut meta);
+            }
+            if json.get("instructions").is_some() || json.get("additional_instructions").is_some() {
+                meta.has_system_prompt = true;
             }
         }
 
@@ -173,9 +286,15 @@ impl Backend for CustomBackend {
         meta
     }
 
-    fn should_log(&self, body: &str) -> bool {
-        // Log if request has "model" and "messages" fields (chat completion request)
+    fn should_log(&self, path: &str, body: &str) -> bool {
         if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            // Assistants API thread operations with a JSON body (thread/message/run creation).
+            // Bodyless GETs (run polling, message listing) are not logged.
+            if is_threads_path(path) {
+                return json.is_object();
+            }
+
+            // Log if request has "model" and "messages" fields (chat completion request)
             let has_messages = json.get("messages").is_some();
             let has_model = json.get("model").and_then(|v| v.as_str()).is_some();
             has_messages && has_model
@@ -200,6 +319,16 @@ impl Backend for CustomBackend {
             if let Some(created) = json.get("created").and_then(|v| v.as_i64()) {
                 extra.insert("created".to_string(), serde_json::json!(created));
             }
+            extract_thread_ids(&json, &mut extra);
+        } else {
+            // Streaming runs send thread.* events; every event object carries the ids
+            for line in response_body.lines() {
+                if let Some(data) = line.strip_prefix("data: ") {
+                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
+                        extract_thread_ids(&json, &mut extra);
+                    }
+                }
+            }
         }
 
         if extra.is_empty() {
@@ -213,6 +342,10 @@ impl Backend for CustomBackend {
         self.settings.dlp_enabled
     }
 
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
     fn get_rate_limit(&self) -> (u32, u32) {
         (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
     }
@@ -220,4 +353,28 @@ impl Backend for CustomBackend {
     fn get_max_tokens_limit(&self) -> (u32, String) {
         (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
     }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
 }
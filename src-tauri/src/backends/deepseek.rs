@@ -0,0 +1,314 @@
+// DeepSeek Backend Implementation
+//
+// DeepSeek exposes an OpenAI-compatible chat completions API, with two extensions:
+// - reasoning models (deepseek-reasoner) stream `reasoning_content` alongside `content`
+// - usage reports context cache hits/misses as `prompt_cache_hit_tokens` / `prompt_cache_miss_tokens`
+
+use axum::http::HeaderMap;
+use serde_json::json;
+
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
+use crate::backends::custom::CustomBackendSettings;
+use crate::backends::Backend;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+pub const DEEPSEEK_BASE_URL: &str = "https://api.deepseek.com";
+
+pub struct DeepSeekBackend {
+    settings: CustomBackendSettings,
+}
+
+impl DeepSeekBackend {
+    pub fn new() -> Self {
+        Self {
+            settings: CustomBackendSettings::default(),
+        }
+    }
+
+    pub fn with_settings(settings_json: &str) -> Self {
+        let settings: CustomBackendSettings = serde_json::from_str(settings_json)
+            .unwrap_or_default();
+        Self { settings }
+    }
+}
+
+impl Default for DeepSeekBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply a DeepSeek usage object to response metadata
+/// prompt_tokens includes cache hits (OpenAI convention), hits are reported as cache reads
+fn apply_usage(meta: &mut ResponseMetadata, usage: &serde_json::Value) {
+    meta.input_tokens = usage
+        .get("prompt_tokens")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0) as i32;
+    meta.output_tokens = usage
+        .get("completion_tokens")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0) as i32;
+    meta.cache_read_tokens = usage
+        .get("prompt_cache_hit_tokens")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0) as i32;
+}
+
+/// Find the usage object in a response (last chunk carrying one when streaming)
+fn find_usage(body: &str, is_streaming: bool) -> Option<serde_json::Value> {
+    if is_streaming {
+        body.lines()
+            .rev()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .find_map(|json| json.get("usage").filter(|u| !u.is_null()).cloned())
+    } else {
+        serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| json.get("usage").cloned())
+    }
+}
+
+impl Backend for DeepSeekBackend {
+    fn name(&self) -> &'static str {
+        "deepseek"
+    }
+
+    fn base_url(&self) -> &'static str {
+        DEEPSEEK_BASE_URL
+    }
+
+    fn parse_request_metadata(&self, body: &str) -> RequestMetadata {
+        let mut meta = RequestMetadata::default();
+
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            if let Some(model) = json.get("model").and_then(|v| v.as_str()) {
+                meta.model = Some(model.to_string());
+            }
+            meta.has_tools = json.get("tools").is_some() || json.get("functions").is_some();
+
+            if let Some(messages) = json.get("messages").and_then(|v| v.as_array()) {
+                for msg in messages {
+                    if let Some(role) = msg.get("role").and_then(|v| v.as_str()) {
+                        match role {
+                            "user" => meta.user_message_count += 1,
+                            "assistant" => meta.assistant_message_count += 1,
+                            "system" => meta.has_system_prompt = true,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        meta
+    }
+
+    fn parse_response_metadata(&self, body: &str, is_streaming: bool) -> ResponseMetadata {
+        let mut meta = ResponseMetadata::default();
+
+        if is_streaming {
+            // Track tool calls by index: (id, name, accumulated_arguments)
+            let mut tool_calls_map: HashMap<i64, (String, String, String)> = HashMap::new();
+
+            for line in body.lines() {
+                let data = match line.strip_prefix("data: ") {
+                    Some(d) if d != "[DONE]" => d,
+                    _ => continue,
+                };
+                let json = match serde_json::from_str::<serde_json::Value>(data) {
+                    Ok(j) => j,
+                    Err(_) => continue,
+                };
+
+                if let Some(choices) = json.get("choices").and_then(|v| v.as_array()) {
+                    for choice in choices {
+                        if let Some(finish_reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+                            meta.stop_reason = Some(finish_reason.to_string());
+                        }
+
+                        let delta = match choice.get("delta") {
+                            Some(d) => d,
+                            None => continue,
+                        };
+
+                        // Reasoning models stream their chain of thought separately
+                        if delta
+                            .get("reasoning_content")
+                            .and_then(|v| v.as_str())
+                            .map(|s| !s.is_empty())
+                            .unwrap_or(false)
+                        {
+                            meta.has_thinking = true;
+                        }
+
+                        if let Some(tool_calls) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+                            for tc in tool_calls {
+                                let index = tc.get("index").and_then(|v| v.as_i64()).unwrap_or(0);
+                                let entry = tool_calls_map
+                                    .entry(index)
+                                    .or_insert_with(|| (String::new(), String::new(), String::new()));
+                                if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
+                                    entry.0 = id.to_string();
+                                }
+                                if let Some(function) = tc.get("function") {
+                                    if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                                        entry.1.push_str(name);
+                                    }
+                                    if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                                        entry.2.push_str(args);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // The final chunk carries usage
+                if let Some(usage) = json.get("usage").filter(|u| !u.is_null()) {
+                    apply_usage(&mut meta, usage);
+                }
+            }
+
+            let mut tool_calls: Vec<(i64, ToolCall)> = tool_calls_map
+                .into_iter()
+                .map(|(index, (id, name, args))| {
+                    let input = serde_json::from_str(&args).unwrap_or(serde_json::Value::Null);
+                    (index, ToolCall { id, name, input })
+                })
+                .collect();
+            tool_calls.sort_by_key(|(index, _)| *index);
+            meta.tool_calls = tool_calls.into_iter().map(|(_, tc)| tc).collect();
+        } else if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            if let Some(first_choice) = json
+                .get("choices")
+                .and_then(|v| v.as_array())
+                .and_then(|choices| choices.first())
+            {
+                if let Some(finish_reason) = first_choice.get("finish_reason").and_then(|v| v.as_str()) {
+                    meta.stop_reason = Some(finish_reason.to_string());
+                }
+
+                if let Some(message) = first_choice.get("message") {
+                    meta.has_thinking = message
+                        .get("reasoning_content")
+                        .and_then(|v| v.as_str())
+                        .map(|s| !s.is_empty())
+                        .unwrap_or(false);
+
+                    if let Some(tool_calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+                        for tc in tool_calls {
+                            let id = tc.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                            let function = tc.get("function");
+                            let name = function
+                                .and_then(|f| f.get("name"))
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("")
+                                .to_string();
+                            let args = function
+                                .and_then(|f| f.get("arguments"))
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("");
+                            let input = serde_json::from_str(args).unwrap_or(serde_json::Value::Null);
+                            meta.tool_calls.push(ToolCall { id, name, input });
+                        }
+                    }
+                }
+            }
+
+            if let Some(usage) = json.get("usage") {
+                apply_usage(&mut meta, usage);
+            }
+        }
+
+        meta
+    }
+
+    fn should_log(&self, _path: &str, body: &str) -> bool {
+        // Log chat completion requests (must have "model" and "messages")
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            let has_messages = json.get("messages").and_then(|v| v.as_array()).is_some();
+            let has_model = json.get("model").and_then(|v| v.as_str()).is_some();
+            has_messages && has_model
+        } else {
+            false
+        }
+    }
+
+    fn extract_extra_metadata(
+        &self,
+        _request_body: &str,
+        response_body: &str,
+        _headers: &HeaderMap,
+    ) -> Option<String> {
+        let is_streaming = response_body.trim_start().starts_with("data:");
+        let usage = find_usage(response_body, is_streaming)?;
+
+        let mut extra = serde_json::Map::new();
+        if let Some(miss) = usage.get("prompt_cache_miss_tokens").and_then(|v| v.as_i64()) {
+            extra.insert("prompt_cache_miss_tokens".to_string(), json!(miss));
+        }
+        if let Some(reasoning) = usage
+            .get("completion_tokens_details")
+            .and_then(|d| d.get("reasoning_tokens"))
+            .and_then(|v| v.as_i64())
+        {
+            extra.insert("reasoning_tokens".to_string(), json!(reasoning));
+        }
+
+        if extra.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&extra).unwrap_or_default())
+        }
+    }
+
+    fn is_dlp_enabled(&self) -> bool {
+        self.settings.dlp_enabled
+    }
+
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
+    fn get_rate_limit(&self) -> (u32, u32) {
+        (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
+    }
+
+    fn get_max_tokens_limit(&self) -> (u32, String) {
+        (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
+    }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+}
@@ -0,0 +1,254 @@
+// Gemini Backend Implementation
+//
+// Shared parsers for Google's generateContent / streamGenerateContent API format (also used by
+// Vertex AI), and the Gemini Developer API backend (generativelanguage.googleapis.com).
+// Streaming responses arrive either as SSE (`?alt=sse`) or as a JSON array of chunks.
+
+use crate::api_keys::{ApiKeyHeader, KeyBalancing};
+use crate::api_translation::ApiTranslation;
+use crate::backends::custom::CustomBackendSettings;
+use crate::backends::{Backend, ClientApi};
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Parse a generateContent request body
+pub fn parse_gemini_request(json: &serde_json::Value) -> RequestMetadata {
+    let mut meta = RequestMetadata::default();
+
+    if let Some(model) = json.get("model").and_then(|v| v.as_str()) {
+        meta.model = Some(model.trim_start_matches("models/").to_string());
+    }
+    meta.has_system_prompt =
+        json.get("systemInstruction").is_some() || json.get("system_instruction").is_some();
+    meta.has_tools = json.get("tools").is_some();
+
+    if let Some(contents) = json.get("contents").and_then(|v| v.as_array()) {
+        for content in contents {
+            // Role defaults to "user" when omitted
+            match content.get("role").and_then(|v| v.as_str()).unwrap_or("user") {
+                "user" => meta.user_message_count += 1,
+                "model" => meta.assistant_message_count += 1,
+                _ => {}
+            }
+        }
+    }
+
+    meta
+}
+
+/// Split a (possibly streamed) generateContent response into its JSON chunks
+pub fn gemini_response_chunks(body: &str) -> Vec<serde_json::Value> {
+    let trimmed = body.trim_start();
+
+    // Non-SSE streaming returns a JSON array of response chunks
+    if trimmed.starts_with('[') {
+        return serde_json::from_str::<Vec<serde_json::Value>>(trimmed).unwrap_or_default();
+    }
+
+    // SSE streaming (?alt=sse)
+    if trimmed.starts_with("data:") {
+        return body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data.trim()).ok())
+            .collect();
+    }
+
+    serde_json::from_str::<serde_json::Value>(trimmed)
+        .map(|v| vec![v])
+        .unwrap_or_default()
+}
+
+/// Parse a generateContent response (streaming or not)
+pub fn parse_gemini_response(body: &str) -> ResponseMetadata {
+    let mut meta = ResponseMetadata::default();
+
+    for chunk in gemini_response_chunks(body) {
+        if let Some(candidates) = chunk.get("candidates").and_then(|v| v.as_array()) {
+            for candidate in candidates {
+                if let Some(reason) = candidate.get("finishReason").and_then(|v| v.as_str()) {
+                    meta.stop_reason = Some(reason.to_string());
+                }
+
+                let parts = candidate
+                    .get("content")
+                    .and_then(|c| c.get("parts"))
+                    .and_then(|p| p.as_array());
+                for part in parts.into_iter().flatten() {
+                    if part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false) {
+                        meta.has_thinking = true;
+                    }
+                    if let Some(call) = part.get("functionCall") {
+                        meta.tool_calls.push(ToolCall {
+                            id: call.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                            name: call.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                            input: call.get("args").cloned().unwrap_or(serde_json::Value::Null),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Every chunk carries cumulative usage; the last one wins
+        if let Some(usage) = chunk.get("usageMetadata") {
+            let thoughts = usage
+                .get("thoughtsTokenCount")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0);
+            meta.input_tokens = usage
+                .get("promptTokenCount")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0) as i32;
+            meta.output_tokens = (usage
+                .get("candidatesTokenCount")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0)
+                + thoughts) as i32;
+            meta.cache_read_tokens = usage
+                .get("cachedContentTokenCount")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0) as i32;
+            if thoughts > 0 {
+                meta.has_thinking = true;
+            }
+        }
+    }
+
+    meta
+}
+
+pub struct GeminiBackend {
+    settings: CustomBackendSettings,
+}
+
+impl GeminiBackend {
+    pub fn new() -> Self {
+        Self {
+            settings: CustomBackendSettings::default(),
+        }
+    }
+
+    pub fn with_settings(settings_json: &str) -> Self {
+        let settings: CustomBackendSettings = serde_json::from_str(settings_json)
+            .unwrap_or_default();
+        Self { settings }
+    }
+}
+
+impl Default for GeminiBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for GeminiBackend {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn base_url(&self) -> &'static str {
+        GEMINI_BASE_URL
+    }
+
+    fn model_from_path(&self, path: &str) -> Option<String> {
+        // /v1beta/models/{model}:generateContent
+        let after = path.split("/models/").nth(1)?;
+        let model = after.split([':', '?', '/']).next()?;
+        if model.is_empty() {
+            None
+        } else {
+            Some(model.to_string())
+        }
+    }
+
+    fn is_streaming_request(&self, path: &str, _body: &[u8]) -> bool {
+        path.contains(":streamGenerateContent")
+    }
+
+    fn parse_request_metadata(&self, body: &str) -> RequestMetadata {
+        serde_json::from_str::<serde_json::Value>(body)
+            .map(|json| parse_gemini_request(&json))
+            .unwrap_or_default()
+    }
+
+    fn parse_response_metadata(&self, body: &str, _is_streaming: bool) -> ResponseMetadata {
+        parse_gemini_response(body)
+    }
+
+    fn should_log(&self, path: &str, body: &str) -> bool {
+        // countTokens takes the same body as generateContent, so filter on the method
+        if self.is_token_count_request(path) {
+            return false;
+        }
+
+        // The model is part of the URL, so only check for conversation content
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            json.get("contents").and_then(|v| v.as_array()).is_some()
+        } else {
+            false
+        }
+    }
+
+    fn is_token_count_request(&self, path: &str) -> bool {
+        path.contains(":countTokens")
+    }
+
+    fn log_token_counts(&self) -> bool {
+        self.settings.log_token_counts
+    }
+
+    fn is_dlp_enabled(&self) -> bool {
+        self.settings.dlp_enabled
+    }
+
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
+    fn get_rate_limit(&self) -> (u32, u32) {
+        (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
+    }
+
+    fn get_max_tokens_limit(&self) -> (u32, String) {
+        (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
+    }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+
+    fn client_api(&self, _request_body: &str) -> ClientApi {
+        ClientApi::Gemini
+    }
+
+    fn api_key_header(&self) -> ApiKeyHeader {
+        ApiKeyHeader::GoogApiKey
+    }
+}
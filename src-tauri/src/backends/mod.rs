@@ -3,9 +3,34 @@
 pub mod claude;
 pub mod codex;
 pub mod custom;
+pub mod deepseek;
+pub mod gemini;
+pub mod openai;
+pub mod perplexity;
+pub mod registry;
+pub mod vertex;
 
 use axum::http::HeaderMap;
+use crate::api_keys::{ApiKeyHeader, KeyBalancing};
+use crate::api_translation::ApiTranslation;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
 use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+/// API a backend's clients speak; the proxy's own errors (e.g. a DLP block) are sent in its shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientApi {
+    /// Anthropic Messages
+    Anthropic,
+    /// OpenAI Chat Completions / Responses and compatible APIs
+    OpenAI,
+    /// Gemini generateContent
+    Gemini,
+}
 
 /// Trait for API backend implementations
 /// Each backend (Claude, OpenAI, Gemini, etc.) implements this trait
@@ -16,6 +41,30 @@ pub trait Backend: Send + Sync {
     /// Returns the base URL for this backend's API
     fn base_url(&self) -> &str;
 
+    /// Build the upstream URL for a request path (including query string)
+    /// Default implementation appends the path to base_url
+    fn upstream_url(&self, full_path: &str) -> String {
+        format!("{}{}", self.base_url(), full_path)
+    }
+
+    /// Reject a request path the upstream URL can't safely be built from (answered with a 400)
+    fn validate_path(&self, _full_path: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Extract the model name from the request path, for APIs that put it in the URL
+    /// Only used when the request body doesn't name a model
+    fn model_from_path(&self, _path: &str) -> Option<String> {
+        None
+    }
+
+    /// Determine if the request expects a streaming response
+    /// Default implementation looks for `"stream": true` in the body
+    fn is_streaming_request(&self, _path: &str, body: &[u8]) -> bool {
+        body.windows(13)
+            .any(|w| w == b"\"stream\":true" || w == b"\"stream\": true")
+    }
+
     /// Parse request body to extract metadata
     fn parse_request_metadata(&self, body: &str) -> RequestMetadata;
 
@@ -24,7 +73,20 @@ pub trait Backend: Send + Sync {
 
     /// Determine if this request should be logged
     /// (e.g., only log Messages API calls, not token counting)
-    fn should_log(&self, body: &str) -> bool;
+    /// `path` is the request path with the backend prefix stripped
+    fn should_log(&self, path: &str, body: &str) -> bool;
+
+    /// Determine if this is a token-counting request (e.g. /v1/messages/count_tokens)
+    /// These are never logged as requests, but can be logged to the token_counts table
+    fn is_token_count_request(&self, _path: &str) -> bool {
+        false
+    }
+
+    /// Check if token-counting requests should be logged for this backend
+    /// Default implementation returns false
+    fn log_token_counts(&self) -> bool {
+        false
+    }
 
     /// Extract backend-specific metadata as JSON string
     /// This is stored in the extra_metadata column for flexible, backend-specific data
@@ -44,6 +106,12 @@ pub trait Backend: Send + Sync {
         true
     }
 
+    /// Request parts DLP scans besides user messages (system prompt, tool definitions)
+    /// Default implementation scans neither
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        DlpScanScope::default()
+    }
+
     /// Get rate limit settings (requests per window, window in minutes)
     /// Returns (0, 1) by default which means no rate limit
     fn get_rate_limit(&self) -> (u32, u32) {
@@ -56,9 +124,56 @@ pub trait Backend: Send + Sync {
     fn get_max_tokens_limit(&self) -> (u32, String) {
         (0, "block".to_string())
     }
+
+    /// Timeouts, retries and circuit breaker for requests to the upstream
+    fn upstream_settings(&self) -> UpstreamSettings {
+        UpstreamSettings::default()
+    }
+
+    /// Header a stored provider API key is sent in
+    /// Default implementation uses `Authorization: Bearer`, as OpenAI-compatible APIs do
+    fn api_key_header(&self) -> ApiKeyHeader {
+        ApiKeyHeader::Bearer
+    }
+
+    /// How requests are spread across the backend's stored API keys
+    fn api_key_balancing(&self) -> KeyBalancing {
+        KeyBalancing::default()
+    }
+
+    /// How headers are logged where they differ from the defaults (credentials hashed, rest kept)
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        HashMap::new()
+    }
+
+    /// Where a sample of the backend's requests is mirrored to (off by default)
+    fn mirror_settings(&self) -> MirrorSettings {
+        MirrorSettings::default()
+    }
+
+    /// Backends a request is sent to when this one is overloaded (none by default)
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        Vec::new()
+    }
+
+    /// API the client sending this request speaks, for errors the proxy answers with itself
+    /// Default implementation is OpenAI, as OpenAI-compatible APIs speak
+    fn client_api(&self, _request_body: &str) -> ClientApi {
+        ClientApi::OpenAI
+    }
+
+    /// How client requests are translated to the upstream's API (not at all by default)
+    fn api_translation(&self) -> ApiTranslation {
+        ApiTranslation::default()
+    }
 }
 
 // Re-export backends for convenience
 pub use claude::ClaudeBackend;
 pub use codex::CodexBackend;
 pub use custom::CustomBackend;
+pub use deepseek::DeepSeekBackend;
+pub use gemini::GeminiBackend;
+pub use openai::OpenAIBackend;
+pub use perplexity::PerplexityBackend;
+pub use vertex::VertexBackend;
@@ -0,0 +1,151 @@
+// OpenAI Platform Backend Implementation
+//
+// Handles api.openai.com with both API formats:
+// - Chat Completions (/v1/chat/completions) and Assistants, parsed like custom OpenAI-compatible backends
+// - Responses (/v1/responses), parsed like Codex (same `input` array and SSE event format)
+
+use axum::http::HeaderMap;
+
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
+use crate::backends::codex::CodexBackend;
+use crate::backends::custom::{CustomBackend, CustomBackendSettings};
+use crate::backends::Backend;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com";
+
+pub struct OpenAIBackend {
+    settings: CustomBackendSettings,
+    chat: CustomBackend,
+    responses: CodexBackend,
+}
+
+impl OpenAIBackend {
+    pub fn new() -> Self {
+        Self::with_settings("{}")
+    }
+
+    pub fn with_settings(settings_json: &str) -> Self {
+        let settings: CustomBackendSettings = serde_json::from_str(settings_json)
+            .unwrap_or_default();
+        Self {
+            settings,
+            chat: CustomBackend::new("openai".to_string(), OPENAI_BASE_URL.to_string(), settings_json),
+            responses: CodexBackend::new(),
+        }
+    }
+}
+
+impl Default for OpenAIBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Is this a Responses API request body (has an `input` field)?
+fn is_responses_request(body: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(body)
+        .map(|json| json.get("input").is_some())
+        .unwrap_or(false)
+}
+
+/// Is this a Responses API response body (response object or response.* stream events)?
+fn is_responses_response(body: &str) -> bool {
+    body.contains("\"object\":\"response\"")
+        || body.contains("\"object\": \"response\"")
+        || body.contains("\"type\":\"response.")
+}
+
+impl Backend for OpenAIBackend {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn base_url(&self) -> &'static str {
+        OPENAI_BASE_URL
+    }
+
+    fn parse_request_metadata(&self, body: &str) -> RequestMetadata {
+        if is_responses_request(body) {
+            self.responses.parse_request_metadata(body)
+        } else {
+            self.chat.parse_request_metadata(body)
+        }
+    }
+
+    fn parse_response_metadata(&self, body: &str, is_streaming: bool) -> ResponseMetadata {
+        if is_responses_response(body) {
+            self.responses.parse_response_metadata(body, is_streaming)
+        } else {
+            self.chat.parse_response_metadata(body, is_streaming)
+        }
+    }
+
+    fn should_log(&self, path: &str, body: &str) -> bool {
+        if path.ends_with("/responses") {
+            self.responses.should_log(path, body)
+        } else {
+            self.chat.should_log(path, body)
+        }
+    }
+
+    fn extract_extra_metadata(
+        &self,
+        request_body: &str,
+        response_body: &str,
+        headers: &HeaderMap,
+    ) -> Option<String> {
+        if is_responses_request(request_body) {
+            self.responses.extract_extra_metadata(request_body, response_body, headers)
+        } else {
+            self.chat.extract_extra_metadata(request_body, response_body, headers)
+        }
+    }
+
+    fn is_dlp_enabled(&self) -> bool {
+        self.settings.dlp_enabled
+    }
+
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
+    fn get_rate_limit(&self) -> (u32, u32) {
+        (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
+    }
+
+    fn get_max_tokens_limit(&self) -> (u32, String) {
+        (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
+    }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+}
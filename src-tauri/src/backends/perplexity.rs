@@ -0,0 +1,165 @@
+// Perplexity Backend Implementation
+//
+// Perplexity's Sonar API is OpenAI chat-completions compatible, so request/response
+// parsing is delegated to the custom (OpenAI-compatible) backend. Perplexity adds
+// `citations` and `search_results` to responses, which are kept in extra_metadata.
+
+use axum::http::HeaderMap;
+use serde_json::json;
+
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
+use crate::backends::custom::{CustomBackend, CustomBackendSettings};
+use crate::backends::Backend;
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+pub const PERPLEXITY_BASE_URL: &str = "https://api.perplexity.ai";
+
+pub struct PerplexityBackend {
+    settings: CustomBackendSettings,
+    inner: CustomBackend,
+}
+
+impl PerplexityBackend {
+    pub fn new() -> Self {
+        Self::with_settings("{}")
+    }
+
+    pub fn with_settings(settings_json: &str) -> Self {
+        let settings: CustomBackendSettings = serde_json::from_str(settings_json)
+            .unwrap_or_default();
+        Self {
+            settings,
+            inner: CustomBackend::new(
+                "perplexity".to_string(),
+                PERPLEXITY_BASE_URL.to_string(),
+                settings_json,
+            ),
+        }
+    }
+}
+
+impl Default for PerplexityBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the response objects from a body (every SSE chunk when streaming)
+fn response_objects(body: &str) -> Vec<serde_json::Value> {
+    if body.trim_start().starts_with("data:") {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.trim())
+            .filter(|data| *data != "[DONE]")
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .collect()
+    } else {
+        serde_json::from_str::<serde_json::Value>(body)
+            .map(|v| vec![v])
+            .unwrap_or_default()
+    }
+}
+
+impl Backend for PerplexityBackend {
+    fn name(&self) -> &'static str {
+        "perplexity"
+    }
+
+    fn base_url(&self) -> &'static str {
+        PERPLEXITY_BASE_URL
+    }
+
+    fn parse_request_metadata(&self, body: &str) -> RequestMetadata {
+        self.inner.parse_request_metadata(body)
+    }
+
+    fn parse_response_metadata(&self, body: &str, is_streaming: bool) -> ResponseMetadata {
+        self.inner.parse_response_metadata(body, is_streaming)
+    }
+
+    fn should_log(&self, path: &str, body: &str) -> bool {
+        self.inner.should_log(path, body)
+    }
+
+    fn extract_extra_metadata(
+        &self,
+        _request_body: &str,
+        response_body: &str,
+        _headers: &HeaderMap,
+    ) -> Option<String> {
+        let mut extra = serde_json::Map::new();
+
+        // Streaming chunks repeat the citations; the last chunk has the complete list
+        for obj in response_objects(response_body) {
+            if let Some(id) = obj.get("id").and_then(|v| v.as_str()) {
+                extra.insert("response_id".to_string(), json!(id));
+            }
+            if let Some(citations) = obj.get("citations").filter(|c| c.is_array()) {
+                extra.insert("citations".to_string(), citations.clone());
+            }
+            if let Some(results) = obj.get("search_results").filter(|r| r.is_array()) {
+                extra.insert("search_results".to_string(), results.clone());
+            }
+            if let Some(queries) = obj
+                .get("usage")
+                .and_then(|u| u.get("num_search_queries"))
+                .and_then(|v| v.as_i64())
+            {
+                extra.insert("num_search_queries".to_string(), json!(queries));
+            }
+        }
+
+        if extra.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&extra).unwrap_or_default())
+        }
+    }
+
+    fn is_dlp_enabled(&self) -> bool {
+        self.settings.dlp_enabled
+    }
+
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
+    fn get_rate_limit(&self) -> (u32, u32) {
+        (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
+    }
+
+    fn get_max_tokens_limit(&self) -> (u32, String) {
+        (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
+    }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+}
@@ -0,0 +1,332 @@
+// Runtime Backend Registry
+//
+// Backends are configured in the `backends` table. Each row names a backend kind (which Backend
+// implementation handles it), a listen-path prefix on the gateway and per-backend settings JSON.
+// The registry turns the enabled rows into Backend instances and resolves incoming requests to
+// them, either by path prefix (/claude/v1/messages) or by target host (api.anthropic.com).
+//
+// The live registry is held in BACKEND_REGISTRY and reloaded whenever backends change, so
+// requests are routed to added/removed/toggled backends without restarting the proxy.
+// Reloads are broadcast to subscribers (the per-backend listener manager).
+
+use std::net::IpAddr;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::watch;
+
+use crate::backends::claude::ANTHROPIC_BASE_URL;
+use crate::backends::codex::CODEX_BASE_URL;
+use crate::backends::custom::CustomBackendSettings;
+use crate::backends::deepseek::DEEPSEEK_BASE_URL;
+use crate::backends::gemini::GEMINI_BASE_URL;
+use crate::backends::openai::OPENAI_BASE_URL;
+use crate::backends::perplexity::PERPLEXITY_BASE_URL;
+use crate::backends::vertex::VERTEX_BASE_URL;
+use crate::backends::{
+    Backend, ClaudeBackend, CodexBackend, CustomBackend, DeepSeekBackend, GeminiBackend,
+    OpenAIBackend, PerplexityBackend, VertexBackend,
+};
+use crate::database::{get_proxy_bind_addresses_from_db, BackendRecord, Database};
+use crate::proxy::parse_bind_address;
+use crate::storage::BackendStore;
+use crate::BACKEND_REGISTRY;
+
+/// Backend kind for user-defined OpenAI-compatible endpoints
+pub const CUSTOM_KIND: &str = "custom";
+
+/// Backend kind for Cursor hooks (served by its own router, not proxied)
+pub const CURSOR_HOOKS_KIND: &str = "cursor-hooks";
+
+/// A backend that ships with the app and is always present in the `backends` table
+pub struct BuiltinBackend {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub base_url: &'static str,
+    pub path_prefix: &'static str,
+}
+
+/// Builtin backends, seeded into the `backends` table on startup
+pub const BUILTIN_BACKENDS: &[BuiltinBackend] = &[
+    BuiltinBackend { name: "claude", kind: "claude", base_url: ANTHROPIC_BASE_URL, path_prefix: "/claude" },
+    BuiltinBackend { name: "codex", kind: "codex", base_url: CODEX_BASE_URL, path_prefix: "/codex" },
+    BuiltinBackend { name: "deepseek", kind: "deepseek", base_url: DEEPSEEK_BASE_URL, path_prefix: "/deepseek" },
+    BuiltinBackend { name: "vertex", kind: "vertex", base_url: VERTEX_BASE_URL, path_prefix: "/vertex" },
+    BuiltinBackend { name: "perplexity", kind: "perplexity", base_url: PERPLEXITY_BASE_URL, path_prefix: "/perplexity" },
+    BuiltinBackend { name: "openai", kind: "openai", base_url: OPENAI_BASE_URL, path_prefix: "/openai" },
+    BuiltinBackend { name: "gemini", kind: "gemini", base_url: GEMINI_BASE_URL, path_prefix: "/gemini" },
+    BuiltinBackend { name: "cursor-hooks", kind: CURSOR_HOOKS_KIND, base_url: "N/A", path_prefix: "/cursor_hook" },
+];
+
+/// Create the Backend implementation for a kind
+/// Built-in kinds always talk to their own provider, so `name` and `base_url` only apply to custom
+/// backends (the only kind that can be added). Returns None for kinds that are not proxied
+/// (cursor-hooks) or unknown kinds
+pub fn create_backend(kind: &str, name: &str, base_url: &str, settings: &str) -> Option<Arc<dyn Backend>> {
+    let backend: Arc<dyn Backend> = match kind {
+        "claude" => Arc::new(ClaudeBackend::with_settings(settings)),
+        "codex" => Arc::new(CodexBackend::with_settings(settings)),
+        "deepseek" => Arc::new(DeepSeekBackend::with_settings(settings)),
+        "vertex" => Arc::new(VertexBackend::with_settings(settings)),
+        "perplexity" => Arc::new(PerplexityBackend::with_settings(settings)),
+        "openai" => Arc::new(OpenAIBackend::with_settings(settings)),
+        "gemini" => Arc::new(GeminiBackend::with_settings(settings)),
+        CUSTOM_KIND => Arc::new(CustomBackend::new(name.to_string(), base_url.to_string(), settings)),
+        _ => return None,
+    };
+    Some(backend)
+}
+
+/// Strip a path prefix on a segment boundary ("/claude" matches "/claude/v1" but not "/claudex")
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    match path.strip_prefix(prefix) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('?') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Split a base URL into its lowercase host and path (without trailing slash)
+fn host_and_path(base_url: &str) -> (Option<String>, String) {
+    match reqwest::Url::parse(base_url) {
+        Ok(url) => (
+            url.host_str().map(|h| h.to_lowercase()),
+            url.path().trim_end_matches('/').to_string(),
+        ),
+        Err(_) => (None, String::new()),
+    }
+}
+
+/// Whether a host is this machine: localhost, a loopback or unspecified IP, or a gateway bind address
+fn is_local_host(host: &str, local_addresses: &[IpAddr]) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    match parse_bind_address(host) {
+        Some(ip) => ip.is_loopback() || ip.is_unspecified() || local_addresses.contains(&ip),
+        None => false,
+    }
+}
+
+/// Whether a host prefix is a Google Cloud region, e.g. "us-east5" or "europe-west4"
+fn is_vertex_region(prefix: &str) -> bool {
+    match prefix.split_once('-') {
+        Some((area, zone)) => {
+            !area.is_empty()
+                && area.chars().all(|c| c.is_ascii_lowercase())
+                && zone.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                && zone.ends_with(|c: char| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// A backend resolved from a `backends` row
+#[derive(Clone)]
+pub struct RegisteredBackend {
+    pub name: String,
+    pub kind: String,
+    pub path_prefix: String,
+    /// Upstream host, used to route requests addressed to the provider directly
+    /// None for upstreams on this machine (loopback or the gateway's own address)
+    pub host: Option<String>,
+    /// Path component of the upstream base URL, e.g. "/v1"
+    pub upstream_path: String,
+    /// Dedicated listen port from settings (0 = path prefix routing only)
+    pub listen_port: u16,
+    pub backend: Arc<dyn Backend>,
+}
+
+impl RegisteredBackend {
+    /// Strip the upstream base path from a request addressed to the provider directly,
+    /// since the backend appends request paths to its base URL
+    pub fn strip_upstream_path<'a>(&self, path: &'a str) -> &'a str {
+        if self.upstream_path.is_empty() {
+            return path;
+        }
+        strip_path_prefix(path, &self.upstream_path).unwrap_or(path)
+    }
+
+    /// Strip this backend's listen-path prefix from a request path
+    pub fn strip_listen_prefix<'a>(&self, path: &'a str) -> &'a str {
+        strip_path_prefix(path, &self.path_prefix).unwrap_or(path)
+    }
+}
+
+/// Registry of enabled, proxied backends
+#[derive(Clone, Default)]
+pub struct Registry {
+    entries: Arc<Vec<RegisteredBackend>>,
+}
+
+impl Registry {
+    /// Build the registry from the enabled rows of the `backends` table
+    pub fn load(db: &Database) -> Self {
+        let records = db.get_enabled_backends().unwrap_or_else(|e| {
+            eprintln!("[REGISTRY] Failed to load backends: {}", e);
+            Vec::new()
+        });
+        let bind_addresses = get_proxy_bind_addresses_from_db();
+        let local_addresses: Vec<IpAddr> = [&bind_addresses.gateway, &bind_addresses.backend_listeners]
+            .into_iter()
+            .filter_map(|a| parse_bind_address(a))
+            .collect();
+        Self::from_records(records, &local_addresses)
+    }
+
+    fn from_records(records: Vec<BackendRecord>, local_addresses: &[IpAddr]) -> Self {
+        let mut entries = Vec::new();
+
+        for record in records {
+            let backend = match create_backend(&record.kind, &record.name, &record.base_url, &record.settings) {
+                Some(b) => b,
+                None => {
+                    if record.kind != CURSOR_HOOKS_KIND {
+                        eprintln!("[REGISTRY] Unknown backend kind '{}' for '{}'", record.kind, record.name);
+                    }
+                    continue;
+                }
+            };
+
+            let (host, upstream_path) = host_and_path(backend.base_url());
+            // Requests reach the gateway under its own address, so matching one of them by host
+            // would send traffic meant for the gateway to that backend (or back to the gateway)
+            let host = host.filter(|h| !is_local_host(h, local_addresses));
+            let listen_port = serde_json::from_str::<CustomBackendSettings>(&record.settings)
+                .map(|s| s.listen_port)
+                .unwrap_or(0);
+            entries.push(RegisteredBackend {
+                host,
+                upstream_path,
+                listen_port,
+                name: record.name,
+                kind: record.kind,
+                path_prefix: record.path_prefix,
+                backend,
+            });
+        }
+
+        Self {
+            entries: Arc::new(entries),
+        }
+    }
+
+    /// All registered backends
+    pub fn entries(&self) -> &[RegisteredBackend] {
+        &self.entries
+    }
+
+    /// Find a backend by name
+    pub fn by_name(&self, name: &str) -> Option<&RegisteredBackend> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Find the backend whose path prefix matches the request path (longest prefix wins)
+    pub fn by_prefix(&self, path: &str) -> Option<&RegisteredBackend> {
+        self.entries
+            .iter()
+            .filter(|e| strip_path_prefix(path, &e.path_prefix).is_some())
+            .max_by_key(|e| e.path_prefix.len())
+    }
+
+    /// Find the backend whose upstream host, or a subdomain of it, matches a request host
+    /// Vertex AI regional endpoints (e.g. us-east5-aiplatform.googleapis.com) match the Vertex backend
+    pub fn by_host(&self, host: &str) -> Option<&RegisteredBackend> {
+        let host = host.split(':').next().unwrap_or(host).to_lowercase();
+
+        self.entries.iter().find(|e| match &e.host {
+            Some(h) => {
+                host == *h
+                    || host.ends_with(&format!(".{}", h))
+                    || (e.kind == "vertex"
+                        && host.strip_suffix(&format!("-{}", h)).is_some_and(is_vertex_region))
+            }
+            None => false,
+        })
+    }
+}
+
+/// Registry version, bumped on every reload
+static REGISTRY_UPDATES: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::channel(0).0);
+
+/// Subscribe to registry reloads
+pub fn subscribe() -> watch::Receiver<u64> {
+    REGISTRY_UPDATES.subscribe()
+}
+
+/// Get the live registry
+pub fn current() -> Registry {
+    BACKEND_REGISTRY.read().unwrap().clone()
+}
+
+/// Reload the live registry from the `backends` table
+pub fn reload(db: &Database) {
+    let registry = Registry::load(db);
+    println!("[REGISTRY] Loaded {} backend(s)", registry.entries().len());
+    *BACKEND_REGISTRY.write().unwrap() = registry;
+    REGISTRY_UPDATES.send_modify(|version| *version += 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(name: &str, base_url: &str) -> BackendRecord {
+        record(name, CUSTOM_KIND, base_url)
+    }
+
+    fn record(name: &str, kind: &str, base_url: &str) -> BackendRecord {
+        BackendRecord {
+            id: 0,
+            name: name.to_string(),
+            kind: kind.to_string(),
+            base_url: base_url.to_string(),
+            path_prefix: format!("/{}", name),
+            settings: "{}".to_string(),
+            enabled: true,
+            is_builtin: false,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_by_host_skips_local_upstreams() {
+        let lan: IpAddr = "192.168.1.20".parse().unwrap();
+        let registry = Registry::from_records(
+            vec![
+                custom("ollama", "http://localhost:11434/v1"),
+                custom("loopback", "http://127.0.0.1:8000"),
+                custom("ipv6", "http://[::1]:8000"),
+                custom("gateway", "http://192.168.1.20:8008"),
+                custom("remote", "https://llm.example.com/v1"),
+            ],
+            &[lan],
+        );
+
+        for host in ["localhost:8008", "127.0.0.1", "[::1]:8008", "192.168.1.20:8008"] {
+            assert!(registry.by_host(host).is_none(), "{} matched a backend", host);
+        }
+        assert_eq!(registry.by_host("llm.example.com").map(|e| e.name.as_str()), Some("remote"));
+
+        // Still reachable by path prefix
+        assert_eq!(registry.by_prefix("/ollama/v1/chat/completions").map(|e| e.name.as_str()), Some("ollama"));
+    }
+
+    #[test]
+    fn test_by_host_regional_endpoints_only_for_vertex() {
+        let registry = Registry::from_records(
+            vec![
+                record("vertex", "vertex", VERTEX_BASE_URL),
+                record("openai", "openai", OPENAI_BASE_URL),
+            ],
+            &[],
+        );
+        let name = |host: &str| registry.by_host(host).map(|e| e.name.clone());
+
+        assert_eq!(name("aiplatform.googleapis.com"), Some("vertex".to_string()));
+        assert_eq!(name("us-east5-aiplatform.googleapis.com"), Some("vertex".to_string()));
+        assert_eq!(name("europe-west4-aiplatform.googleapis.com:443"), Some("vertex".to_string()));
+        assert_eq!(name("my-proxy-aiplatform.googleapis.com"), None);
+
+        assert_eq!(name("api.openai.com"), Some("openai".to_string()));
+        assert_eq!(name("eu.api.openai.com"), Some("openai".to_string()));
+        assert_eq!(name("foo-api.openai.com"), None);
+    }
+}
@@ -0,0 +1,257 @@
+// Vertex AI Backend Implementation
+//
+// Handles Google Cloud Vertex AI publisher model endpoints:
+// - publishers/anthropic/models/{model}:rawPredict / :streamRawPredict (Anthropic Messages format)
+// - publishers/google/models/{model}:generateContent / :streamGenerateContent (Gemini format)
+//
+// Clients point their Vertex base URL at /vertex/v1. The upstream host is derived from the
+// `locations/{region}` path segment, e.g. us-east5 -> https://us-east5-aiplatform.googleapis.com.
+// The region becomes part of the host name, so anything but lowercase letters, digits and dashes
+// is rejected before the request goes anywhere.
+
+use axum::http::HeaderMap;
+use serde_json::json;
+
+use crate::api_keys::KeyBalancing;
+use crate::api_translation::ApiTranslation;
+use crate::backends::claude::ClaudeBackend;
+use crate::backends::custom::CustomBackendSettings;
+use crate::backends::gemini::{parse_gemini_request, parse_gemini_response};
+use crate::backends::{Backend, ClientApi};
+use crate::dlp::DlpScanScope;
+use crate::failover::FallbackTarget;
+use crate::log_sanitizer::HeaderLogAction;
+use crate::mirror::MirrorSettings;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::upstream::UpstreamSettings;
+use std::collections::HashMap;
+
+pub const VERTEX_BASE_URL: &str = "https://aiplatform.googleapis.com";
+
+pub struct VertexBackend {
+    settings: CustomBackendSettings,
+    // Anthropic models on Vertex use the regular Messages API format
+    anthropic: ClaudeBackend,
+}
+
+impl VertexBackend {
+    pub fn new() -> Self {
+        Self {
+            settings: CustomBackendSettings::default(),
+            anthropic: ClaudeBackend::new(),
+        }
+    }
+
+    pub fn with_settings(settings_json: &str) -> Self {
+        let settings: CustomBackendSettings = serde_json::from_str(settings_json)
+            .unwrap_or_default();
+        Self {
+            settings,
+            anthropic: ClaudeBackend::new(),
+        }
+    }
+}
+
+impl Default for VertexBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extract the region from a `.../locations/{region}/...` path
+fn region_from_path(path: &str) -> Option<&str> {
+    let mut segments = path.split('/');
+    segments.find(|s| *s == "locations")?;
+    segments.next().filter(|s| !s.is_empty())
+}
+
+/// Whether a region can be put into a host name as is
+fn is_valid_region(region: &str) -> bool {
+    region.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Is this a Gemini-format body (request or response)?
+fn is_gemini_body(body: &str) -> bool {
+    body.contains("\"contents\"") || body.contains("\"candidates\"") || body.contains("\"usageMetadata\"")
+}
+
+impl Backend for VertexBackend {
+    fn name(&self) -> &'static str {
+        "vertex"
+    }
+
+    fn base_url(&self) -> &'static str {
+        VERTEX_BASE_URL
+    }
+
+    fn validate_path(&self, full_path: &str) -> Result<(), String> {
+        match region_from_path(full_path) {
+            Some(region) if !is_valid_region(region) => Err(format!("Invalid Vertex AI region '{}'", region)),
+            _ => Ok(()),
+        }
+    }
+
+    fn upstream_url(&self, full_path: &str) -> String {
+        match region_from_path(full_path) {
+            Some(region) if region != "global" && is_valid_region(region) => {
+                format!("https://{}-aiplatform.googleapis.com{}", region, full_path)
+            }
+            _ => format!("{}{}", VERTEX_BASE_URL, full_path),
+        }
+    }
+
+    fn model_from_path(&self, path: &str) -> Option<String> {
+        // .../publishers/{publisher}/models/{model}:{method}
+        let after = path.split("/models/").nth(1)?;
+        let model = after.split([':', '?', '/']).next()?;
+        if model.is_empty() {
+            None
+        } else {
+            Some(model.to_string())
+        }
+    }
+
+    fn is_streaming_request(&self, path: &str, body: &[u8]) -> bool {
+        path.contains(":streamRawPredict")
+            || path.contains(":streamGenerateContent")
+            || body
+                .windows(13)
+                .any(|w| w == b"\"stream\":true" || w == b"\"stream\": true")
+    }
+
+    fn parse_request_metadata(&self, body: &str) -> RequestMetadata {
+        match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(json) if json.get("contents").is_some() => parse_gemini_request(&json),
+            _ => self.anthropic.parse_request_metadata(body),
+        }
+    }
+
+    fn parse_response_metadata(&self, body: &str, is_streaming: bool) -> ResponseMetadata {
+        if is_gemini_body(body) {
+            parse_gemini_response(body)
+        } else {
+            self.anthropic.parse_response_metadata(body, is_streaming)
+        }
+    }
+
+    fn should_log(&self, path: &str, body: &str) -> bool {
+        // Token counting takes the same body as a generation call
+        if self.is_token_count_request(path) {
+            return false;
+        }
+
+        // The model is part of the URL on Vertex, so only check for conversation content
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            json.get("messages").and_then(|v| v.as_array()).is_some()
+                || json.get("contents").and_then(|v| v.as_array()).is_some()
+        } else {
+            false
+        }
+    }
+
+    fn extract_extra_metadata(
+        &self,
+        request_body: &str,
+        _response_body: &str,
+        _headers: &HeaderMap,
+    ) -> Option<String> {
+        let publisher = if is_gemini_body(request_body) { "google" } else { "anthropic" };
+        let mut extra = serde_json::Map::new();
+        extra.insert("publisher".to_string(), json!(publisher));
+
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(request_body) {
+            if let Some(version) = json.get("anthropic_version").and_then(|v| v.as_str()) {
+                extra.insert("anthropic_version".to_string(), json!(version));
+            }
+        }
+
+        Some(serde_json::to_string(&extra).unwrap_or_default())
+    }
+
+    fn is_token_count_request(&self, path: &str) -> bool {
+        // Anthropic: .../models/count-tokens:rawPredict, Gemini: ...:countTokens
+        path.contains("/count-tokens:") || path.contains(":countTokens")
+    }
+
+    fn log_token_counts(&self) -> bool {
+        self.settings.log_token_counts
+    }
+
+    fn is_dlp_enabled(&self) -> bool {
+        self.settings.dlp_enabled
+    }
+
+    fn dlp_scan_scope(&self) -> DlpScanScope {
+        self.settings.dlp_scan_scope()
+    }
+
+    fn get_rate_limit(&self) -> (u32, u32) {
+        (self.settings.rate_limit_requests, self.settings.rate_limit_minutes.max(1))
+    }
+
+    fn get_max_tokens_limit(&self) -> (u32, String) {
+        (self.settings.max_tokens_in_a_request, self.settings.action_for_max_tokens_in_a_request.clone())
+    }
+
+    fn upstream_settings(&self) -> UpstreamSettings {
+        self.settings.upstream
+    }
+
+    fn api_key_balancing(&self) -> KeyBalancing {
+        self.settings.api_key_balancing
+    }
+
+    fn log_header_overrides(&self) -> HashMap<String, HeaderLogAction> {
+        self.settings.log_header_overrides.clone()
+    }
+
+    fn mirror_settings(&self) -> MirrorSettings {
+        self.settings.mirror.clone()
+    }
+
+    fn fallbacks(&self) -> Vec<FallbackTarget> {
+        self.settings.fallbacks.clone()
+    }
+
+    fn api_translation(&self) -> ApiTranslation {
+        self.settings.translation.clone()
+    }
+
+    /// Gemini models take `contents`; Anthropic models on Vertex speak Messages
+    fn client_api(&self, request_body: &str) -> ClientApi {
+        match serde_json::from_str::<serde_json::Value>(request_body) {
+            Ok(json) if json.get("contents").is_some() => ClientApi::Gemini,
+            _ => ClientApi::Anthropic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_in_host() {
+        let backend = VertexBackend::new();
+        let path = "/v1/projects/acme/locations/us-east5/publishers/anthropic/models/claude-sonnet-4:rawPredict";
+        assert!(backend.validate_path(path).is_ok());
+        assert_eq!(backend.upstream_url(path), format!("https://us-east5-aiplatform.googleapis.com{}", path));
+        assert!(backend.upstream_url("/v1/projects/acme/locations/global/x").starts_with(VERTEX_BASE_URL));
+
+        // A region that would change the host is rejected, and never makes it into a URL
+        for region in ["evil.com#", "user@evil.com", "US-EAST5", "a:443"] {
+            let path = format!("/v1/projects/acme/locations/{}/publishers/google/models/gemini:generateContent", region);
+            assert!(backend.validate_path(&path).is_err(), "{}", region);
+            assert!(backend.upstream_url(&path).starts_with(&format!("{}/", VERTEX_BASE_URL)));
+        }
+    }
+
+    #[test]
+    fn test_client_api_follows_model_family() {
+        let backend = VertexBackend::new();
+        let gemini = r#"{"contents":[{"role":"user","parts":[{"text":"hi"}]}]}"#;
+        let anthropic = r#"{"anthropic_version":"vertex-2023-10-16","messages":[{"role":"user","content":"hi"}]}"#;
+        assert_eq!(backend.client_api(gemini), ClientApi::Gemini);
+        assert_eq!(backend.client_api(anthropic), ClientApi::Anthropic);
+    }
+}
@@ -0,0 +1,315 @@
+// Token Budgets
+//
+// Budgets cap the input + output tokens used over a day or a week (UTC), either in total, for
+// one backend, or for one Cursor user (the `user_email` Cursor hooks report). Usage is summed from
+// the `requests` table, leaving out requests that were blocked, rate limited or served from the
+// response cache since they never reached the model. Once a budget is used up, requests it covers
+// are refused with a 429 until the period resets; crossing the warning threshold raises one
+// notification per budget and period.
+
+use crate::storage::{self, SpendStore};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex, RwLock};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetScope {
+    /// Every logged request
+    Global,
+    /// Requests through one backend (`target` is its name)
+    Backend,
+    /// Cursor prompts from one user (`target` is their email)
+    User,
+}
+
+impl BudgetScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetScope::Global => "global",
+            BudgetScope::Backend => "backend",
+            BudgetScope::User => "user",
+        }
+    }
+
+    pub fn from_name(value: &str) -> BudgetScope {
+        match value {
+            "backend" => BudgetScope::Backend,
+            "user" => BudgetScope::User,
+            _ => BudgetScope::Global,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Daily,
+    Weekly,
+}
+
+impl BudgetPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Daily => "daily",
+            BudgetPeriod::Weekly => "weekly",
+        }
+    }
+
+    pub fn from_name(value: &str) -> BudgetPeriod {
+        if value == "weekly" {
+            BudgetPeriod::Weekly
+        } else {
+            BudgetPeriod::Daily
+        }
+    }
+
+    /// Start of the current period: midnight UTC, on Monday for weekly budgets
+    fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        match self {
+            BudgetPeriod::Daily => midnight,
+            BudgetPeriod::Weekly => midnight - Duration::days(now.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    fn length(&self) -> Duration {
+        match self {
+            BudgetPeriod::Daily => Duration::days(1),
+            BudgetPeriod::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+/// A token budget as stored in `token_budgets`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenBudget {
+    #[serde(default)]
+    pub id: i64,
+    pub scope: BudgetScope,
+    /// Backend name or user email; empty for global budgets
+    #[serde(default)]
+    pub target: String,
+    pub period: BudgetPeriod,
+    pub limit_tokens: i64,
+    /// Usage (in percent of the limit) that raises a warning (0 = no warning)
+    #[serde(default = "default_warn_percent")]
+    pub warn_percent: u8,
+}
+
+fn default_warn_percent() -> u8 {
+    80
+}
+
+impl TokenBudget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.limit_tokens <= 0 {
+            return Err("Token limit must be greater than 0".to_string());
+        }
+        if self.warn_percent > 100 {
+            return Err("Warning threshold must be between 0 and 100 percent".to_string());
+        }
+        match self.scope {
+            BudgetScope::Global if !self.target.is_empty() => {
+                Err("Global budgets don't take a backend or user".to_string())
+            }
+            BudgetScope::Backend if self.target.is_empty() => Err("Backend budgets need a backend name".to_string()),
+            BudgetScope::User if self.target.is_empty() => Err("User budgets need a user email".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the budget counts a request to `backend` from `user`
+    fn covers(&self, backend: &str, user: Option<&str>) -> bool {
+        match self.scope {
+            BudgetScope::Global => true,
+            BudgetScope::Backend => self.target == backend,
+            BudgetScope::User => user.is_some_and(|u| u.eq_ignore_ascii_case(&self.target)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let period = match self.period {
+            BudgetPeriod::Daily => "Daily",
+            BudgetPeriod::Weekly => "Weekly",
+        };
+        match self.scope {
+            BudgetScope::Global => format!("{} token budget", period),
+            BudgetScope::Backend => format!("{} token budget for backend '{}'", period, self.target),
+            BudgetScope::User => format!("{} token budget for {}", period, self.target),
+        }
+    }
+}
+
+/// A budget with its usage in the current period
+#[derive(Clone, Debug, Serialize)]
+pub struct BudgetStatus {
+    #[serde(flatten)]
+    pub budget: TokenBudget,
+    pub used_tokens: i64,
+    /// When the current period ends (RFC 3339)
+    pub resets_at: String,
+    /// Seconds until the period ends
+    pub resets_in_secs: i64,
+}
+
+impl BudgetStatus {
+    pub fn is_exhausted(&self) -> bool {
+        self.used_tokens >= self.budget.limit_tokens
+    }
+
+    fn is_past_warning(&self) -> bool {
+        let warn = self.budget.warn_percent as i64;
+        warn > 0 && self.used_tokens * 100 >= self.budget.limit_tokens * warn
+    }
+
+    /// Human-readable summary, e.g. for the 429 sent to the client
+    pub fn message(&self) -> String {
+        let state = if self.is_exhausted() { "exhausted" } else { "nearly used up" };
+        format!(
+            "{} {}: {} of {} tokens used. Resets at {} UTC.",
+            self.budget.describe(),
+            state,
+            self.used_tokens,
+            self.budget.limit_tokens,
+            self.resets_at.get(..16).unwrap_or(&self.resets_at).replace('T', " ")
+        )
+    }
+}
+
+/// Budgets read from the database, once; None until first use
+static BUDGETS: LazyLock<RwLock<Option<Vec<TokenBudget>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Budgets already warned about, by (budget id, period start)
+static WARNED: LazyLock<Mutex<HashSet<(i64, i64)>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+fn load_budgets() -> Vec<TokenBudget> {
+    if let Some(budgets) = BUDGETS.read().unwrap().as_ref() {
+        return budgets.clone();
+    }
+
+    let budgets = storage::shared()
+        .and_then(|store| store.token_budgets())
+        .unwrap_or_default();
+
+    *BUDGETS.write().unwrap() = Some(budgets.clone());
+    budgets
+}
+
+pub fn add_budget(budget: &TokenBudget) -> Result<i64, String> {
+    let mut budget = budget.clone();
+    budget.target = budget.target.trim().to_string();
+    budget.validate()?;
+
+    let id = storage::shared()
+        .and_then(|store| store.add_token_budget(&budget))
+        .map_err(|e| e.to_string())?;
+
+    *BUDGETS.write().unwrap() = None;
+    Ok(id)
+}
+
+pub fn delete_budget(id: i64) -> Result<(), String> {
+    storage::shared()
+        .and_then(|store| store.delete_token_budget(id))
+        .map_err(|e| e.to_string())?;
+    *BUDGETS.write().unwrap() = None;
+    Ok(())
+}
+
+/// Tokens the budget has counted so far in its current period
+fn status_of(store: &dyn SpendStore, budget: &TokenBudget, now: DateTime<Utc>) -> BudgetStatus {
+    let start = budget.period.start(now);
+    let resets_at = start + budget.period.length();
+    let used_tokens = store.tokens_used(budget, &start.to_rfc3339()).unwrap_or(0);
+
+    BudgetStatus {
+        budget: budget.clone(),
+        used_tokens,
+        resets_at: resets_at.to_rfc3339(),
+        resets_in_secs: (resets_at - now).num_seconds().max(1),
+    }
+}
+
+/// Every budget with its current usage, for the UI
+pub fn budget_statuses() -> Result<Vec<BudgetStatus>, String> {
+    let budgets = load_budgets();
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    Ok(budgets.iter().map(|b| status_of(&*store, b, now)).collect())
+}
+
+/// Check the budgets covering a request to `backend` (from `user`, for Cursor prompts)
+/// Err(the exhausted budget) if the request must be refused; Ok(budgets that just crossed their
+/// warning threshold) otherwise
+pub fn check_budgets(backend: &str, user: Option<&str>) -> Result<Vec<BudgetStatus>, BudgetStatus> {
+    let budgets: Vec<TokenBudget> = load_budgets().into_iter().filter(|b| b.covers(backend, user)).collect();
+    if budgets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Ok(store) = storage::shared() else {
+        return Ok(Vec::new());
+    };
+
+    let now = Utc::now();
+    let mut warnings = Vec::new();
+    for budget in &budgets {
+        let status = status_of(&*store, budget, now);
+        if status.is_exhausted() {
+            return Err(status);
+        }
+        if status.is_past_warning() {
+            let period_start = budget.period.start(now).timestamp();
+            if WARNED.lock().unwrap().insert((budget.id, period_start)) {
+                warnings.push(status);
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_period_start() {
+        // Thursday afternoon
+        let now = Utc.with_ymd_and_hms(2026, 10, 15, 15, 30, 0).unwrap();
+        assert_eq!(BudgetPeriod::Daily.start(now), Utc.with_ymd_and_hms(2026, 10, 15, 0, 0, 0).unwrap());
+        assert_eq!(BudgetPeriod::Weekly.start(now), Utc.with_ymd_and_hms(2026, 10, 12, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_validate_and_covers() {
+        let budget = TokenBudget {
+            id: 1,
+            scope: BudgetScope::User,
+            target: "dev@example.com".to_string(),
+            period: BudgetPeriod::Daily,
+            limit_tokens: 1000,
+            warn_percent: 80,
+        };
+        assert!(budget.validate().is_ok());
+        assert!(budget.covers("cursor-hooks", Some("Dev@Example.com")));
+        assert!(!budget.covers("cursor-hooks", None));
+
+        let no_target = TokenBudget {
+            scope: BudgetScope::Backend,
+            target: String::new(),
+            ..budget.clone()
+        };
+        assert!(no_target.validate().is_err());
+
+        let status = BudgetStatus {
+            budget,
+            used_tokens: 850,
+            resets_at: "2026-10-16T00:00:00+00:00".to_string(),
+            resets_in_secs: 60,
+        };
+        assert!(status.is_past_warning());
+        assert!(!status.is_exhausted());
+    }
+}
@@ -0,0 +1,122 @@
+// Canary Tokens (honeytokens)
+//
+// With canaries on, a request that had values redacted also carries a unique canary string,
+// placed right after the first placeholder so it sits among the data the model was not meant
+// to keep. The canary is stripped from that request's own response, and the user never sees
+// or sends it. If it ever shows up again (in another response, or in a later request), the
+// model or something behind it retained or leaked the prompt.
+//
+// Issued canaries are kept in the `dlp_canaries` table; hits are logged as "canary" detections.
+
+use crate::dlp::{DlpAction, DlpDetection, DlpDirection, Severity};
+use crate::storage::{self, DlpStore};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+pub const CANARY_PATTERN_NAME: &str = "Canary Token";
+
+/// Canaries are `lwc_` followed by 32 hex characters
+const CANARY_PREFIX: &str = "lwc_";
+
+fn canary_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\blwc_[0-9a-f]{32}\b").expect("canary regex compiles"))
+}
+
+/// A fresh canary string
+pub fn new_canary() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", CANARY_PREFIX, hex::encode(bytes))
+}
+
+/// Insert a canary after the earliest placeholder in a redacted body
+/// Returns None if no placeholder appears in the body as-is (e.g. only inside base64)
+pub fn inject_canary(body: &str, replacements: &HashMap<String, String>, canary: &str) -> Option<String> {
+    let end = replacements
+        .keys()
+        .filter_map(|placeholder| body.find(placeholder.as_str()).map(|at| at + placeholder.len()))
+        .min()?;
+
+    let mut injected = String::with_capacity(body.len() + canary.len() + 1);
+    injected.push_str(&body[..end]);
+    injected.push(' ');
+    injected.push_str(canary);
+    injected.push_str(&body[end..]);
+    Some(injected)
+}
+
+/// Detections for issued canaries found in text, marking each as triggered
+pub fn find_canaries(text: &str, direction: DlpDirection) -> Vec<DlpDetection> {
+    if !text.contains(CANARY_PREFIX) {
+        return Vec::new();
+    }
+
+    let Ok(store) = storage::shared() else {
+        return Vec::new();
+    };
+
+    let mut seen = HashSet::new();
+    let mut detections = Vec::new();
+    for found in canary_regex().find_iter(text) {
+        let canary = found.as_str();
+        if !seen.insert(canary) {
+            continue;
+        }
+        // Strings of the same shape that we never issued are not evidence of anything
+        let Some(issued) = store.find_canary(canary).ok().flatten() else {
+            continue;
+        };
+        eprintln!(
+            "[CANARY] Canary from request {:?} ({}) found in a {}",
+            issued.request_id,
+            issued.backend,
+            direction.name()
+        );
+        let _ = store.mark_canary_triggered(canary);
+
+        let metadata = serde_json::json!({
+            "canary_request_id": issued.request_id,
+            "canary_backend": issued.backend,
+            "canary_issued_at": issued.created_at,
+        });
+        detections.push(DlpDetection {
+            pattern_name: CANARY_PATTERN_NAME.to_string(),
+            pattern_type: "canary".to_string(),
+            original_value: canary.to_string(),
+            placeholder: String::new(),
+            message_index: None,
+            category: "canary".to_string(),
+            metadata: Some(metadata.to_string()),
+            action: DlpAction::Alert,
+            severity: Severity::Critical,
+            direction,
+            confidence: 1.0,
+        });
+    }
+    detections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_canary() {
+        let canary = new_canary();
+        assert!(canary_regex().is_match(&canary));
+
+        let replacements = HashMap::from([
+            ("Xq9rTzA".to_string(), "secret1".to_string()),
+            ("Bm2kLpW".to_string(), "secret2".to_string()),
+        ]);
+        let body = r#"{"messages":[{"content":"a Bm2kLpW b Xq9rTzA"}]}"#;
+        let injected = inject_canary(body, &replacements, &canary).unwrap();
+        assert_eq!(injected, body.replace("Bm2kLpW", &format!("Bm2kLpW {}", canary)));
+
+        assert!(inject_canary("{}", &replacements, &canary).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+// Proxy Client Authentication
+//
+// The proxy spends the user's provider keys (their own, or ones stored in the keychain), so a
+// port reachable from the network would let anyone on the LAN relay traffic through them. With
+// a client token required, requests must carry it in `X-Quilr-Token`; the app generates the token
+// and writes it into the shell env for Claude Code. Requests from this machine can be let through
+// without it (the default), so local tools that can't send custom headers keep working. The
+// header is stripped before a request is proxied, so it is never forwarded or logged.
+
+use crate::database::{get_proxy_client_auth_from_db, save_proxy_client_auth_to_db};
+use crate::proxy::proxy_error_body;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::{LazyLock, RwLock};
+
+/// Header clients send the token in
+pub const CLIENT_TOKEN_HEADER: &str = "X-Quilr-Token";
+
+/// Client token settings (`proxy_client_auth` in the settings table)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientAuthSettings {
+    pub require_token: bool,
+    /// Let requests from this machine through without a token
+    pub allow_loopback: bool,
+    pub token: String,
+}
+
+impl Default for ClientAuthSettings {
+    fn default() -> Self {
+        ClientAuthSettings {
+            require_token: false,
+            allow_loopback: true,
+            token: String::new(),
+        }
+    }
+}
+
+/// A fresh client token
+pub fn new_client_token() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    format!("lwt_{}", hex::encode(bytes))
+}
+
+/// Settings read from the database, once
+static SETTINGS: LazyLock<RwLock<Option<ClientAuthSettings>>> = LazyLock::new(|| RwLock::new(None));
+
+fn current() -> ClientAuthSettings {
+    if let Some(settings) = SETTINGS.read().unwrap().as_ref() {
+        return settings.clone();
+    }
+    let settings = get_proxy_client_auth_from_db();
+    *SETTINGS.write().unwrap() = Some(settings.clone());
+    settings
+}
+
+/// Save the settings and apply them to the next request, generating a token if there is none
+pub fn save_settings(mut settings: ClientAuthSettings) -> Result<ClientAuthSettings, String> {
+    if settings.token.is_empty() {
+        settings.token = new_client_token();
+    }
+    save_proxy_client_auth_to_db(&settings)?;
+    *SETTINGS.write().unwrap() = Some(settings.clone());
+    Ok(settings)
+}
+
+/// Compare without returning early, so response timing doesn't reveal how much of a guess matched
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Whether a request from `peer` may use the proxy
+fn is_allowed(settings: &ClientAuthSettings, peer: &SocketAddr, presented: Option<&str>) -> bool {
+    if !settings.require_token || (settings.allow_loopback && peer.ip().to_canonical().is_loopback()) {
+        return true;
+    }
+    !settings.token.is_empty() && presented.is_some_and(|token| tokens_match(token.trim(), &settings.token))
+}
+
+/// Middleware for the proxy listeners: reject requests without a valid client token
+pub async fn require_client_token(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let presented = req.headers_mut().remove(CLIENT_TOKEN_HEADER);
+    let presented = presented.as_ref().and_then(|v| v.to_str().ok());
+
+    if is_allowed(&current(), &peer, presented) {
+        return next.run(req).await;
+    }
+
+    eprintln!("[PROXY] Rejected request from {} without a valid client token", peer);
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::CONTENT_TYPE, "application/json")],
+        proxy_error_body(
+            format!("A valid {} header is required to use this proxy", CLIENT_TOKEN_HEADER),
+            "authentication_error",
+            "invalid_client_token",
+        ),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_required_for_remote_clients() {
+        let settings = ClientAuthSettings {
+            require_token: true,
+            allow_loopback: true,
+            token: "lwt_secret".to_string(),
+        };
+        let local: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let mapped: SocketAddr = "[::ffff:127.0.0.1]:50000".parse().unwrap();
+        let remote: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+
+        assert!(is_allowed(&settings, &local, None));
+        assert!(is_allowed(&settings, &mapped, None));
+        assert!(!is_allowed(&settings, &remote, None));
+        assert!(!is_allowed(&settings, &remote, Some("lwt_guess!")));
+        assert!(is_allowed(&settings, &remote, Some("lwt_secret")));
+
+        let strict = ClientAuthSettings {
+            allow_loopback: false,
+            ..settings.clone()
+        };
+        assert!(!is_allowed(&strict, &local, None));
+
+        let off = ClientAuthSettings {
+            require_token: false,
+            ..settings
+        };
+        assert!(is_allowed(&off, &remote, None));
+    }
+}
@@ -1,27 +1,36 @@
 // Backend Management Commands
 
-use crate::backends::claude::ANTHROPIC_BASE_URL;
-use crate::backends::codex::CODEX_BASE_URL;
-use crate::database::{CustomBackendRecord, Database};
-use crate::dlp_pattern_config::get_db_path;
+use crate::api_keys::{self, StoredApiKey};
+use crate::api_translation::ApiTranslation;
+use crate::backends::custom::CustomBackendSettings;
+use crate::backends::registry::{self, CUSTOM_KIND};
+use crate::database::{BackendRecord, Database};
+use crate::log_sanitizer::HeaderLogAction;
+use crate::proxy::restart_backend_listener;
+use crate::storage::BackendStore;
+use crate::upstream::{self, BreakerStatus, PoolStats, UpstreamSettings};
+use crate::PROXY_PORT;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CustomBackendResponse {
     pub id: i64,
     pub name: String,
     pub base_url: String,
+    pub path_prefix: String,
     pub settings: String,
     pub enabled: bool,
     pub created_at: String,
 }
 
-impl From<CustomBackendRecord> for CustomBackendResponse {
-    fn from(record: CustomBackendRecord) -> Self {
+impl From<BackendRecord> for CustomBackendResponse {
+    fn from(record: BackendRecord) -> Self {
         Self {
             id: record.id,
             name: record.name,
             base_url: record.base_url,
+            path_prefix: record.path_prefix,
             settings: record.settings,
             enabled: record.enabled,
             created_at: record.created_at,
@@ -29,10 +38,80 @@ impl From<CustomBackendRecord> for CustomBackendResponse {
     }
 }
 
+/// Validate a listen-path prefix, defaulting to "/{name}"
+fn normalize_path_prefix(path_prefix: Option<String>, name: &str) -> Result<String, String> {
+    let prefix = path_prefix
+        .map(|p| p.trim().trim_end_matches('/').to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| format!("/{}", name));
+    let prefix = if prefix.starts_with('/') { prefix } else { format!("/{}", prefix) };
+
+    // One or more segments, e.g. "/openai" or "/team-a/openai"
+    let valid = prefix[1..]
+        .split('/')
+        .all(|segment| !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_'));
+    if !valid {
+        return Err("Path prefix segments can only contain letters, numbers, hyphens, and underscores".to_string());
+    }
+
+    Ok(prefix)
+}
+
+/// Get the dedicated listen port from a settings JSON (0 = none)
+fn listen_port_of(settings: &str) -> u16 {
+    serde_json::from_str::<CustomBackendSettings>(settings)
+        .map(|s| s.listen_port)
+        .unwrap_or(0)
+}
+
+/// Validate the upstream policy, header log overrides, mirror, fallbacks and translation in a
+/// settings JSON
+fn validate_settings(settings: &str) -> Result<(), String> {
+    // An unknown action or mode would make the whole settings JSON unreadable, resetting every setting
+    let value = serde_json::from_str::<serde_json::Value>(settings).ok();
+    if let Some(overrides) = value.as_ref().and_then(|v| v.get("log_header_overrides")) {
+        serde_json::from_value::<HashMap<String, HeaderLogAction>>(overrides.clone())
+            .map_err(|_| "Header log actions must be keep, hash or remove".to_string())?;
+    }
+    if let Some(translation) = value.as_ref().and_then(|v| v.get("translation")) {
+        serde_json::from_value::<ApiTranslation>(translation.clone())
+            .map_err(|_| "Translation mode must be off, anthropic_to_openai or openai_to_anthropic".to_string())?;
+    }
+
+    serde_json::from_str::<CustomBackendSettings>(settings)
+        .map(|s| {
+            s.upstream.validate()?;
+            s.mirror.validate()?;
+            s.fallbacks.iter().try_for_each(|f| f.validate())
+        })
+        .unwrap_or(Ok(()))
+}
+
+/// Validate a backend's dedicated listen port: not the gateway port, not used by another backend
+fn validate_listen_port(db: &Database, name: &str, settings: &str) -> Result<(), String> {
+    let port = listen_port_of(settings);
+    if port == 0 {
+        return Ok(());
+    }
+    if port == *PROXY_PORT.lock().unwrap() {
+        return Err(format!("Port {} is already used by the gateway", port));
+    }
+
+    let backends = db.get_backends().map_err(|e| e.to_string())?;
+    if let Some(other) = backends
+        .iter()
+        .find(|b| b.name != name && listen_port_of(&b.settings) == port)
+    {
+        return Err(format!("Port {} is already used by backend '{}'", port, other.name));
+    }
+
+    Ok(())
+}
+
 /// Get all custom backends
 #[tauri::command]
 pub fn get_custom_backends() -> Result<Vec<CustomBackendResponse>, String> {
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
 
     db.get_custom_backends()
         .map(|backends| backends.into_iter().map(|b| b.into()).collect())
@@ -45,6 +124,7 @@ pub fn add_custom_backend(
     name: String,
     base_url: String,
     settings: String,
+    path_prefix: Option<String>,
 ) -> Result<i64, String> {
     // Validate name - must be alphanumeric with hyphens/underscores, no spaces
     let name = name.trim();
@@ -71,16 +151,21 @@ pub fn add_custom_backend(
             .map_err(|_| "Settings must be valid JSON".to_string())?;
     }
     let settings = if settings.is_empty() { "{}" } else { settings };
+    let path_prefix = normalize_path_prefix(path_prefix, name)?;
 
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
 
-    // Check if name already exists
-    if db.backend_name_exists(name).map_err(|e| e.to_string())? {
-        return Err(format!("Backend name '{}' already exists or is reserved", name));
+    // Check if name or path prefix already exists
+    if db.backend_name_exists(name, &path_prefix).map_err(|e| e.to_string())? {
+        return Err(format!("Backend name '{}' or path '{}' already exists or is reserved", name, path_prefix));
     }
+    validate_listen_port(&db, name, settings)?;
+    validate_settings(settings)?;
 
-    db.add_custom_backend(name, base_url, settings)
-        .map_err(|e| e.to_string())
+    let id = db.add_backend(name, CUSTOM_KIND, base_url, &path_prefix, settings)
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(id)
 }
 
 /// Update an existing custom backend
@@ -90,6 +175,7 @@ pub fn update_custom_backend(
     name: String,
     base_url: String,
     settings: String,
+    path_prefix: Option<String>,
 ) -> Result<(), String> {
     // Validate name
     let name = name.trim();
@@ -116,34 +202,62 @@ pub fn update_custom_backend(
             .map_err(|_| "Settings must be valid JSON".to_string())?;
     }
     let settings = if settings.is_empty() { "{}" } else { settings };
+    let path_prefix = normalize_path_prefix(path_prefix, name)?;
 
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
 
-    // Check if name already exists (excluding this backend)
-    if db.backend_name_exists_excluding(name, id).map_err(|e| e.to_string())? {
-        return Err(format!("Backend name '{}' already exists or is reserved", name));
+    // Check if name or path prefix already exists (excluding this backend)
+    if db.backend_name_exists_excluding(name, &path_prefix, id).map_err(|e| e.to_string())? {
+        return Err(format!("Backend name '{}' or path '{}' already exists or is reserved", name, path_prefix));
     }
-
-    db.update_custom_backend(id, name, base_url, settings)
-        .map_err(|e| e.to_string())
+    validate_listen_port(&db, name, settings)?;
+    validate_settings(settings)?;
+
+    let old_name = custom_backend_name(&db, id)?;
+    db.update_backend(id, name, base_url, &path_prefix, settings)
+        .map_err(|e| e.to_string())?;
+    if let Some(old_name) = old_name {
+        api_keys::rename_backend_keys(&old_name, name);
+    }
+    registry::reload(&db);
+    Ok(())
 }
 
 /// Toggle a custom backend enabled/disabled
 #[tauri::command]
 pub fn toggle_custom_backend(id: i64, enabled: bool) -> Result<(), String> {
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
 
-    db.toggle_custom_backend(id, enabled)
-        .map_err(|e| e.to_string())
+    db.set_backend_enabled(id, enabled)
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
+}
+
+/// Name of a custom backend, if it exists
+fn custom_backend_name(db: &Database, id: i64) -> Result<Option<String>, String> {
+    Ok(db
+        .get_custom_backends()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|b| b.id == id)
+        .map(|b| b.name))
 }
 
 /// Delete a custom backend
 #[tauri::command]
 pub fn delete_custom_backend(id: i64) -> Result<(), String> {
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
 
-    db.delete_custom_backend(id)
-        .map_err(|e| e.to_string())
+    // Remove its keys (rows and keychain entries) first, so none are left behind without a backend
+    if let Some(name) = custom_backend_name(&db, id)? {
+        api_keys::delete_backend_keys(&name)?;
+    }
+
+    db.delete_backend(id)
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
 }
 
 // ============================================================================
@@ -153,48 +267,52 @@ pub fn delete_custom_backend(id: i64) -> Result<(), String> {
 /// Predefined backend information with settings
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PredefinedBackendResponse {
+    pub id: i64,
     pub name: String,
     pub base_url: String,
+    pub path_prefix: String,
     pub settings: String,
+    pub enabled: bool,
 }
 
-/// List of predefined backends
-const PREDEFINED_BACKENDS: &[(&str, &str)] = &[
-    ("claude", ANTHROPIC_BASE_URL),
-    ("codex", CODEX_BASE_URL),
-    ("cursor-hooks", "N/A"),
-];
+/// Look up a builtin backend by name
+fn get_builtin_backend(db: &Database, name: &str) -> Result<BackendRecord, String> {
+    db.get_backend_by_name(name)
+        .map_err(|e| e.to_string())?
+        .filter(|b| b.is_builtin)
+        .ok_or_else(|| format!("Unknown predefined backend: {}", name))
+}
 
 /// Get all predefined backends with their settings
 #[tauri::command]
 pub fn get_predefined_backends() -> Result<Vec<PredefinedBackendResponse>, String> {
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
-
-    let mut backends = Vec::new();
-    for (name, base_url) in PREDEFINED_BACKENDS {
-        let settings = db
-            .get_predefined_backend_settings(name)
-            .map_err(|e| e.to_string())?;
-
-        backends.push(PredefinedBackendResponse {
-            name: name.to_string(),
-            base_url: base_url.to_string(),
-            settings,
-        });
-    }
-
-    Ok(backends)
+    let db = Database::shared().map_err(|e| e.to_string())?;
+
+    let mut backends: Vec<BackendRecord> = db
+        .get_backends()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|b| b.is_builtin)
+        .collect();
+    // Keep the seeding order (claude, codex, ...) rather than creation time
+    backends.sort_by_key(|b| b.id);
+
+    Ok(backends
+        .into_iter()
+        .map(|b| PredefinedBackendResponse {
+            id: b.id,
+            name: b.name,
+            base_url: b.base_url,
+            path_prefix: b.path_prefix,
+            settings: b.settings,
+            enabled: b.enabled,
+        })
+        .collect())
 }
 
 /// Update settings for a predefined backend
 #[tauri::command]
 pub fn update_predefined_backend(name: String, settings: String) -> Result<(), String> {
-    // Validate name is a known predefined backend
-    let valid_names: Vec<&str> = PREDEFINED_BACKENDS.iter().map(|(n, _)| *n).collect();
-    if !valid_names.contains(&name.as_str()) {
-        return Err(format!("Unknown predefined backend: {}", name));
-    }
-
     // Validate settings is valid JSON
     let settings = settings.trim();
     if !settings.is_empty() && settings != "{}" {
@@ -203,23 +321,138 @@ pub fn update_predefined_backend(name: String, settings: String) -> Result<(), S
     }
     let settings = if settings.is_empty() { "{}" } else { settings };
 
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    let db = Database::shared().map_err(|e| e.to_string())?;
+
+    // Validate name is a known predefined backend
+    get_builtin_backend(&db, &name)?;
+    validate_listen_port(&db, &name, settings)?;
+    validate_settings(settings)?;
 
     db.update_predefined_backend_settings(&name, settings)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
 }
 
 /// Reset predefined backend settings to defaults
 #[tauri::command]
 pub fn reset_predefined_backend(name: String) -> Result<(), String> {
+    let db = Database::shared().map_err(|e| e.to_string())?;
+
     // Validate name is a known predefined backend
-    let valid_names: Vec<&str> = PREDEFINED_BACKENDS.iter().map(|(n, _)| *n).collect();
-    if !valid_names.contains(&name.as_str()) {
-        return Err(format!("Unknown predefined backend: {}", name));
+    get_builtin_backend(&db, &name)?;
+
+    db.reset_predefined_backend_settings(&name)
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
+}
+
+/// Enable or disable a predefined backend
+#[tauri::command]
+pub fn toggle_predefined_backend(name: String, enabled: bool) -> Result<(), String> {
+    let db = Database::shared().map_err(|e| e.to_string())?;
+
+    let backend = get_builtin_backend(&db, &name)?;
+
+    db.set_backend_enabled(backend.id, enabled)
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
+}
+
+/// Tear down and re-create the dedicated listener of a backend (e.g. after the port was busy)
+#[tauri::command]
+pub fn restart_backend_port(name: String) -> Result<(), String> {
+    restart_backend_listener(&name)
+}
+
+// ============================================================================
+// Upstream Policy Commands
+// ============================================================================
+
+/// Timeouts, retries and circuit breaker settings of a backend
+#[tauri::command]
+pub fn get_backend_upstream_settings(name: String) -> Result<UpstreamSettings, String> {
+    let db = Database::shared().map_err(|e| e.to_string())?;
+    let backend = db
+        .get_backend_by_name(&name)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown backend: {}", name))?;
+
+    Ok(serde_json::from_str::<CustomBackendSettings>(&backend.settings)
+        .map(|s| s.upstream)
+        .unwrap_or_default())
+}
+
+/// Save a backend's upstream settings, keeping the rest of its settings JSON
+#[tauri::command]
+pub fn save_backend_upstream_settings(name: String, settings: UpstreamSettings) -> Result<(), String> {
+    settings.validate()?;
+
+    let db = Database::shared().map_err(|e| e.to_string())?;
+    let backend = db
+        .get_backend_by_name(&name)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown backend: {}", name))?;
+
+    let mut json: serde_json::Value = serde_json::from_str(&backend.settings)
+        .unwrap_or_else(|_| serde_json::json!({}));
+    let Some(fields) = json.as_object_mut() else {
+        return Err(format!("Settings of backend '{}' are not a JSON object", name));
+    };
+    fields.insert(
+        "upstream".to_string(),
+        serde_json::to_value(settings).map_err(|e| e.to_string())?,
+    );
+
+    db.update_backend_settings(&name, &json.to_string())
+        .map_err(|e| e.to_string())?;
+    registry::reload(&db);
+    Ok(())
+}
+
+/// Circuit breaker state of every backend that has been used since startup
+#[tauri::command]
+pub fn get_upstream_circuit_breakers() -> Vec<BreakerStatus> {
+    upstream::breaker_statuses()
+}
+
+/// Connection pool stats per upstream host (requests, HTTP/2 share, in flight) since startup
+#[tauri::command]
+pub fn get_upstream_pool_stats() -> Vec<PoolStats> {
+    upstream::pool_stats()
+}
+
+/// Close a backend's circuit breaker so requests are sent again right away
+#[tauri::command]
+pub fn reset_upstream_circuit_breaker(name: String) {
+    upstream::reset_breaker(&name);
+}
+
+// ============================================================================
+// Provider API Key Commands
+// ============================================================================
+
+/// Stored API keys of every backend, with masked previews and usage counters
+#[tauri::command]
+pub fn get_backend_api_keys() -> Result<Vec<StoredApiKey>, String> {
+    api_keys::list_api_keys()
+}
+
+/// Add an API key to a backend; used from the next request on, no restart needed
+#[tauri::command]
+pub fn add_backend_api_key(name: String, key: String, label: Option<String>) -> Result<i64, String> {
+    let db = Database::shared().map_err(|e| e.to_string())?;
+    if db.get_backend_by_name(&name).map_err(|e| e.to_string())?.is_none() {
+        return Err(format!("Unknown backend: {}", name));
     }
 
-    let db = Database::new(get_db_path()).map_err(|e| e.to_string())?;
+    api_keys::add_api_key(&name, &key, label.as_deref())
+}
 
-    db.reset_predefined_backend_settings(&name)
-        .map_err(|e| e.to_string())
+/// Remove a stored API key
+#[tauri::command]
+pub fn delete_backend_api_key(id: i64) -> Result<(), String> {
+    api_keys::delete_api_key(id)
 }
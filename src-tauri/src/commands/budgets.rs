@@ -0,0 +1,19 @@
+// Token Budget Tauri Commands
+
+use crate::budgets::{self, BudgetStatus, TokenBudget};
+
+/// Every budget with its usage in the current period
+#[tauri::command]
+pub fn get_token_budgets() -> Result<Vec<BudgetStatus>, String> {
+    budgets::budget_statuses()
+}
+
+#[tauri::command]
+pub fn add_token_budget(budget: TokenBudget) -> Result<i64, String> {
+    budgets::add_budget(&budget)
+}
+
+#[tauri::command]
+pub fn delete_token_budget(id: i64) -> Result<(), String> {
+    budgets::delete_budget(id)
+}
@@ -1,10 +1,74 @@
 // DLP Settings Tauri Commands
 
-use crate::database::{get_dlp_action_from_db, open_connection, save_dlp_action_to_db};
+use super::dlp_packs::PatternPackEntry;
+use crate::database::{
+    get_dlp_action_from_db, get_dlp_canary_enabled_from_db, get_dlp_detect_injection_from_db,
+    get_dlp_hash_values_from_db, save_dlp_detect_injection_to_db,
+    save_dlp_canary_enabled_to_db, save_dlp_hash_values_to_db, get_dlp_min_block_confidence_from_db, get_dlp_ner_settings_from_db,
+    get_dlp_scan_limits_from_db, get_dlp_scan_responses_from_db, get_dlp_severity_policy_from_db,
+    get_dlp_verify_secrets_from_db, save_dlp_action_to_db,
+    save_dlp_min_block_confidence_to_db, save_dlp_ner_settings_to_db, save_dlp_scan_limits_to_db,
+    save_dlp_scan_responses_to_db, save_dlp_severity_policy_to_db, save_dlp_verify_secrets_to_db,
+    get_dlp_webhook_settings_from_db, save_dlp_webhook_settings_to_db, get_dlp_incident_window_from_db,
+    save_dlp_incident_window_to_db,
+};
+use crate::dlp::{invalidate_dlp_pattern_cache, DlpAction, DlpScanLimits, PatternScope, Severity};
+use crate::dlp_webhooks::{self, WebhookSettings};
+use crate::ner::{self, NerSettings};
 use crate::pattern_utils::{
-    collect_matches_with_negative_context, compile_pattern_set, filter_by_min_occurrences,
+    collect_matches_with_negative_context, compile_pattern_set, explain_matches, filter_by_min_occurrences,
+    Allowlist, MatchFilters,
 };
+use crate::storage::{self, DlpPatternChanges, DlpStore, LogQueries};
+use crate::validators::parse_validator;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Detection categories, so PII leakage can be measured separately from secrets
+/// ("entity" is used by the NER patterns)
+const DLP_CATEGORIES: &[&str] = &["secret", "pii", "entity"];
+
+/// Validate an optional category, defaulting to "secret"
+pub(crate) fn parse_category(category: Option<&str>) -> Result<&'static str, String> {
+    match category.map(str::trim) {
+        None | Some("") => Ok("secret"),
+        Some(c) => DLP_CATEGORIES
+            .iter()
+            .find(|known| **known == c)
+            .copied()
+            .ok_or_else(|| format!("Unknown category '{}'", c)),
+    }
+}
+
+/// Validate an optional per-pattern action; empty means "follow the global DLP action"
+pub(crate) fn parse_action(action: Option<&str>) -> Result<Option<&'static str>, String> {
+    match action.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(a) => DlpAction::from_name(a)
+            .map(|a| Some(a.name()))
+            .ok_or_else(|| format!("Unknown action '{}'", a)),
+    }
+}
+
+/// Validate an optional scope, defaulting to "content"
+pub(crate) fn parse_scope(scope: Option<&str>) -> Result<&'static str, String> {
+    match scope.map(str::trim) {
+        None | Some("") => Ok("content"),
+        Some(s) => PatternScope::from_name(s)
+            .map(|s| s.name())
+            .ok_or_else(|| format!("Unknown scope '{}'", s)),
+    }
+}
+
+/// Validate an optional severity, defaulting to "medium"
+pub(crate) fn parse_severity(severity: Option<&str>) -> Result<&'static str, String> {
+    match severity.map(str::trim) {
+        None | Some("") => Ok("medium"),
+        Some(s) => Severity::from_name(s)
+            .map(|s| s.name())
+            .ok_or_else(|| format!("Unknown severity '{}'", s)),
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DlpPattern {
@@ -17,6 +81,15 @@ pub struct DlpPattern {
     pub enabled: bool,
     pub min_occurrences: i32,
     pub min_unique_chars: i32,
+    pub min_entropy: f64,
+    pub validator: Option<String>,
+    pub category: String,
+    pub action: Option<String>, // None follows the severity policy / global DLP action
+    pub severity: String,
+    pub allowlist: Vec<String>,
+    pub scope: String,
+    /// Match against Unicode-folded text
+    pub normalize: bool,
     pub is_builtin: bool,
 }
 
@@ -27,47 +100,16 @@ pub struct DlpSettings {
 
 #[tauri::command]
 pub fn get_dlp_settings() -> Result<DlpSettings, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
     // Get all patterns (builtin and custom)
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, name, pattern_type, patterns, negative_pattern_type, negative_patterns,
-                    enabled, min_occurrences, min_unique_chars, is_builtin
-             FROM dlp_patterns ORDER BY is_builtin DESC, id",
-        )
+    let patterns = storage::shared()
+        .and_then(|store| store.dlp_patterns())
         .map_err(|e| e.to_string())?;
 
-    let patterns: Vec<DlpPattern> = stmt
-        .query_map([], |row| {
-            let patterns_json: String = row.get(3)?;
-            let patterns: Vec<String> = serde_json::from_str(&patterns_json).unwrap_or_default();
-
-            let negative_patterns_json: Option<String> = row.get(5)?;
-            let negative_patterns: Option<Vec<String>> = negative_patterns_json
-                .and_then(|json| serde_json::from_str(&json).ok());
-
-            Ok(DlpPattern {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                pattern_type: row.get(2)?,
-                patterns,
-                negative_pattern_type: row.get(4)?,
-                negative_patterns,
-                enabled: row.get::<_, i32>(6)? == 1,
-                min_occurrences: row.get(7)?,
-                min_unique_chars: row.get(8)?,
-                is_builtin: row.get::<_, i32>(9)? == 1,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
-
     Ok(DlpSettings { patterns })
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn add_dlp_pattern(
     name: String,
     pattern_type: String,
@@ -76,6 +118,14 @@ pub fn add_dlp_pattern(
     negative_patterns: Option<Vec<String>>,
     min_occurrences: Option<i32>,
     min_unique_chars: Option<i32>,
+    min_entropy: Option<f64>,
+    validator: Option<String>,
+    category: Option<String>,
+    action: Option<String>,
+    severity: Option<String>,
+    allowlist: Option<Vec<String>>,
+    scope: Option<String>,
+    normalize: Option<bool>,
 ) -> Result<i64, String> {
     if name.trim().is_empty() {
         return Err("Name is required".to_string());
@@ -83,34 +133,41 @@ pub fn add_dlp_pattern(
     if patterns.is_empty() {
         return Err("At least one pattern is required".to_string());
     }
+    let allowlist = allowlist.unwrap_or_default();
+    if !allowlist.is_empty() {
+        Allowlist::compile(&allowlist)?;
+    }
 
-    let conn = open_connection().map_err(|e| e.to_string())?;
-    let patterns_json = serde_json::to_string(&patterns).map_err(|e| e.to_string())?;
-    let negative_patterns_json = negative_patterns
-        .as_ref()
-        .map(|np| serde_json::to_string(np).unwrap_or_else(|_| "[]".to_string()));
-    let created_at = chrono::Utc::now().to_rfc3339();
-
-    conn.execute(
-        "INSERT INTO dlp_patterns (name, pattern_type, patterns, negative_pattern_type, negative_patterns, enabled, min_occurrences, min_unique_chars, is_builtin, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, ?7, 0, ?8)",
-        rusqlite::params![
-            name.trim(),
-            pattern_type,
-            patterns_json,
-            negative_pattern_type,
-            negative_patterns_json,
-            min_occurrences.unwrap_or(1),
-            min_unique_chars.unwrap_or(0),
-            created_at
-        ],
-    )
-    .map_err(|e| e.to_string())?;
-
-    Ok(conn.last_insert_rowid())
+    let pattern = PatternPackEntry {
+        name: name.trim().to_string(),
+        pattern_type,
+        patterns,
+        negative_pattern_type,
+        negative_patterns,
+        enabled: true,
+        min_occurrences: min_occurrences.unwrap_or(1),
+        min_unique_chars: min_unique_chars.unwrap_or(0),
+        min_entropy: min_entropy.unwrap_or(0.0),
+        validator: parse_validator(validator.as_deref())?.map(|v| v.name().to_string()),
+        category: Some(parse_category(category.as_deref())?.to_string()),
+        action: parse_action(action.as_deref())?.map(str::to_string),
+        severity: Some(parse_severity(severity.as_deref())?.to_string()),
+        allowlist,
+        scope: Some(parse_scope(scope.as_deref())?.to_string()),
+        normalize: normalize.unwrap_or(false),
+    };
+
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let id = store
+        .add_dlp_pattern(&pattern.name, &pattern)
+        .map_err(|e| e.to_string())?;
+
+    invalidate_dlp_pattern_cache();
+    Ok(id)
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn update_dlp_pattern(
     id: i64,
     name: Option<String>,
@@ -121,176 +178,162 @@ pub fn update_dlp_pattern(
     enabled: Option<bool>,
     min_occurrences: Option<i32>,
     min_unique_chars: Option<i32>,
+    min_entropy: Option<f64>,
+    validator: Option<String>,
+    category: Option<String>,
+    action: Option<String>,
+    severity: Option<String>,
+    allowlist: Option<Vec<String>>,
+    scope: Option<String>,
+    normalize: Option<bool>,
 ) -> Result<(), String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    // Build dynamic update query based on provided fields
-    let mut updates: Vec<String> = Vec::new();
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-    if let Some(ref n) = name {
-        if n.trim().is_empty() {
-            return Err("Name cannot be empty".to_string());
-        }
-        updates.push("name = ?".to_string());
-        params.push(Box::new(n.trim().to_string()));
-    }
-
-    if let Some(ref pt) = pattern_type {
-        updates.push("pattern_type = ?".to_string());
-        params.push(Box::new(pt.clone()));
+    if name.as_ref().is_some_and(|n| n.trim().is_empty()) {
+        return Err("Name cannot be empty".to_string());
     }
-
-    if let Some(ref p) = patterns {
-        if p.is_empty() {
-            return Err("At least one pattern is required".to_string());
-        }
-        let patterns_json = serde_json::to_string(p).map_err(|e| e.to_string())?;
-        updates.push("patterns = ?".to_string());
-        params.push(Box::new(patterns_json));
-    }
-
-    // Handle negative_pattern_type - allow setting to null by passing empty string
-    if negative_pattern_type.is_some() {
-        let npt = negative_pattern_type.as_ref().unwrap();
-        if npt.is_empty() {
-            updates.push("negative_pattern_type = NULL".to_string());
-        } else {
-            updates.push("negative_pattern_type = ?".to_string());
-            params.push(Box::new(npt.clone()));
-        }
-    }
-
-    // Handle negative_patterns - allow setting to null by passing empty array
-    if negative_patterns.is_some() {
-        let np = negative_patterns.as_ref().unwrap();
-        if np.is_empty() {
-            updates.push("negative_patterns = NULL".to_string());
-        } else {
-            let np_json = serde_json::to_string(np).map_err(|e| e.to_string())?;
-            updates.push("negative_patterns = ?".to_string());
-            params.push(Box::new(np_json));
-        }
-    }
-
-    if let Some(e) = enabled {
-        updates.push("enabled = ?".to_string());
-        params.push(Box::new(e as i32));
-    }
-
-    if let Some(mo) = min_occurrences {
-        updates.push("min_occurrences = ?".to_string());
-        params.push(Box::new(mo));
+    if patterns.as_ref().is_some_and(|p| p.is_empty()) {
+        return Err("At least one pattern is required".to_string());
     }
 
-    if let Some(muc) = min_unique_chars {
-        updates.push("min_unique_chars = ?".to_string());
-        params.push(Box::new(muc));
-    }
+    let changes = DlpPatternChanges {
+        name: name.map(|n| n.trim().to_string()),
+        pattern_type,
+        patterns,
+        // An empty negative pattern type or list clears it
+        negative_pattern_type: negative_pattern_type.map(|npt| Some(npt).filter(|npt| !npt.is_empty())),
+        negative_patterns: negative_patterns.map(|np| Some(np).filter(|np| !np.is_empty())),
+        enabled,
+        min_occurrences,
+        min_unique_chars,
+        min_entropy,
+        // An empty validator clears it
+        validator: match validator {
+            Some(v) => Some(parse_validator(Some(&v))?.map(|v| v.name().to_string())),
+            None => None,
+        },
+        category: match category {
+            Some(c) => Some(parse_category(Some(&c))?.to_string()),
+            None => None,
+        },
+        // An empty action reverts to the global DLP action
+        action: match action {
+            Some(a) => Some(parse_action(Some(&a))?.map(str::to_string)),
+            None => None,
+        },
+        severity: match severity {
+            Some(s) => Some(parse_severity(Some(&s))?.to_string()),
+            None => None,
+        },
+        // An empty allowlist clears it
+        allowlist: match allowlist {
+            Some(entries) if entries.is_empty() => Some(None),
+            Some(entries) => {
+                Allowlist::compile(&entries)?;
+                Some(Some(entries))
+            }
+            None => None,
+        },
+        scope: match scope {
+            Some(s) => Some(parse_scope(Some(&s))?.to_string()),
+            None => None,
+        },
+        normalize,
+    };
 
-    if updates.is_empty() {
+    if changes.is_empty() {
         return Ok(()); // Nothing to update
     }
 
-    params.push(Box::new(id));
-
-    let sql = format!(
-        "UPDATE dlp_patterns SET {} WHERE id = ?",
-        updates.join(", ")
-    );
-
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-
-    conn.execute(&sql, params_refs.as_slice())
+    storage::shared()
+        .and_then(|store| store.update_dlp_pattern(id, &changes))
         .map_err(|e| e.to_string())?;
 
+    invalidate_dlp_pattern_cache();
     Ok(())
 }
 
 #[tauri::command]
 pub fn toggle_dlp_pattern(id: i64, enabled: bool) -> Result<(), String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "UPDATE dlp_patterns SET enabled = ?1 WHERE id = ?2",
-        rusqlite::params![enabled as i32, id],
-    )
-    .map_err(|e| e.to_string())?;
+    storage::shared()
+        .and_then(|store| store.set_dlp_pattern_enabled(id, enabled))
+        .map_err(|e| e.to_string())?;
 
+    invalidate_dlp_pattern_cache();
     Ok(())
 }
 
 #[tauri::command]
 pub fn delete_dlp_pattern(id: i64) -> Result<(), String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    // Prevent deleting builtin patterns
-    let is_builtin: bool = conn
-        .query_row(
-            "SELECT is_builtin FROM dlp_patterns WHERE id = ?1",
-            rusqlite::params![id],
-            |row| row.get::<_, i32>(0).map(|v| v == 1),
-        )
-        .unwrap_or(false);
-
-    if is_builtin {
+    let deleted = storage::shared()
+        .and_then(|store| store.delete_dlp_pattern(id))
+        .map_err(|e| e.to_string())?;
+
+    // Builtin patterns are kept
+    if !deleted {
         return Err("Cannot delete builtin patterns. You can disable them instead.".to_string());
     }
 
-    conn.execute(
-        "DELETE FROM dlp_patterns WHERE id = ?1",
-        rusqlite::params![id],
-    )
-    .map_err(|e| e.to_string())?;
-
+    invalidate_dlp_pattern_cache();
     Ok(())
 }
 
 #[derive(Serialize)]
 pub struct DlpDetectionRecord {
-    id: i64,
-    request_id: i64,
-    timestamp: String,
-    pattern_name: String,
-    pattern_type: String,
-    original_value: String,
-    placeholder: String,
-    message_index: Option<i32>,
+    pub id: i64,
+    pub request_id: i64,
+    pub timestamp: String,
+    pub pattern_name: String,
+    pub pattern_type: String,
+    pub original_value: String,
+    pub placeholder: String,
+    pub message_index: Option<i32>,
+    pub category: String,
+    pub metadata: Option<String>,
+    pub action: Option<String>,
+    pub severity: String,
+    pub direction: String,
+    pub confidence: Option<f64>,
+    /// Live verification of a detected key: "active", "inactive" or None (unchecked)
+    pub verified: Option<String>,
+    /// Keyed hash of the value when it is stored hashed (original_value is then a masked preview)
+    pub value_hash: Option<String>,
+    /// Incident grouping repeats of this value in the same conversation (None = not grouped)
+    pub incident_id: Option<i64>,
+    /// Detections in the incident so far (1 when not grouped)
+    pub occurrence_count: i64,
 }
 
+/// Per-pattern, category and severity counts are incidents: repeats of a value in one
+/// conversation within the grouping window count once
 #[derive(Serialize)]
 pub struct DlpStats {
-    total_detections: i64,
-    detections_by_pattern: Vec<PatternCount>,
-    recent_detections: Vec<DlpDetectionRecord>,
+    pub total_detections: i64,
+    pub total_incidents: i64,
+    pub detections_by_pattern: Vec<PatternCount>,
+    pub detections_by_category: Vec<CategoryCount>,
+    pub detections_by_severity: Vec<SeverityCount>,
+    pub recent_detections: Vec<DlpDetectionRecord>,
 }
 
 #[derive(Serialize)]
 pub struct PatternCount {
-    pattern_name: String,
-    count: i64,
+    pub pattern_name: String,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct CategoryCount {
+    pub category: String,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct SeverityCount {
+    pub severity: String,
+    pub count: i64,
 }
 
 #[tauri::command]
 pub fn get_dlp_detection_stats(time_range: String, backend: String) -> Result<DlpStats, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    // Ensure table exists
-    let _ = conn.execute(
-        "CREATE TABLE IF NOT EXISTS dlp_detections (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            request_id INTEGER,
-            timestamp TEXT NOT NULL,
-            pattern_name TEXT NOT NULL,
-            pattern_type TEXT NOT NULL,
-            original_value TEXT NOT NULL,
-            placeholder TEXT NOT NULL,
-            message_index INTEGER,
-            FOREIGN KEY (request_id) REFERENCES requests(id)
-        )",
-        [],
-    );
-
     let hours = match time_range.as_str() {
         "1h" => 1,
         "6h" => 6,
@@ -300,133 +343,251 @@ pub fn get_dlp_detection_stats(time_range: String, backend: String) -> Result<Dl
     };
     let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours);
     let cutoff_ts = cutoff.to_rfc3339();
+    let backend = (backend != "all").then_some(backend.as_str());
 
-    // Build backend filter
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND r.backend = '{}'", backend)
-    };
+    storage::shared()
+        .and_then(|store| store.dlp_detection_stats(&cutoff_ts, backend))
+        .map_err(|e| e.to_string())
+}
 
-    // Get total detections count (with backend filter)
-    let total_detections: i64 = conn
-        .query_row(
-            &format!(
-                "SELECT COUNT(*) FROM dlp_detections d
-                 JOIN requests r ON d.request_id = r.id
-                 WHERE d.timestamp >= ?1{}",
-                backend_filter
-            ),
-            [&cutoff_ts],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-
-    // Get detections by pattern (with backend filter)
-    let mut stmt = conn
-        .prepare(&format!(
-            "SELECT d.pattern_name, COUNT(*) as count FROM dlp_detections d
-             JOIN requests r ON d.request_id = r.id
-             WHERE d.timestamp >= ?1{} GROUP BY d.pattern_name ORDER BY count DESC",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn get_dlp_detections_for_request(request_id: i64) -> Result<Vec<DlpDetectionRecord>, String> {
+    storage::shared()
+        .and_then(|store| store.detections_for_request(request_id))
+        .map_err(|e| e.to_string())
+}
 
-    let detections_by_pattern: Vec<PatternCount> = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(PatternCount {
-                pattern_name: row.get(0)?,
-                count: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+#[tauri::command]
+pub fn get_dlp_action_setting() -> String {
+    get_dlp_action_from_db()
+}
 
-    // Get recent detections (with backend filter)
-    let mut stmt = conn
-        .prepare(&format!(
-            "SELECT d.id, d.request_id, d.timestamp, d.pattern_name, d.pattern_type, d.original_value, d.placeholder, d.message_index
-             FROM dlp_detections d
-             JOIN requests r ON d.request_id = r.id
-             WHERE d.timestamp >= ?1{} ORDER BY d.id DESC LIMIT 50",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn save_dlp_action_setting(action: String) -> Result<(), String> {
+    save_dlp_action_to_db(&action)?;
+    // Patterns without their own action resolve it from this setting
+    invalidate_dlp_pattern_cache();
+    Ok(())
+}
 
-    let recent_detections: Vec<DlpDetectionRecord> = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(DlpDetectionRecord {
-                id: row.get(0)?,
-                request_id: row.get(1)?,
-                timestamp: row.get(2)?,
-                pattern_name: row.get(3)?,
-                pattern_type: row.get(4)?,
-                original_value: row.get(5)?,
-                placeholder: row.get(6)?,
-                message_index: row.get(7)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+#[tauri::command]
+pub fn get_dlp_scan_responses_setting() -> bool {
+    get_dlp_scan_responses_from_db()
+}
 
-    Ok(DlpStats {
-        total_detections,
-        detections_by_pattern,
-        recent_detections,
-    })
+#[tauri::command]
+pub fn save_dlp_scan_responses_setting(enabled: bool) -> Result<(), String> {
+    save_dlp_scan_responses_to_db(enabled)
 }
 
 #[tauri::command]
-pub fn get_dlp_detections_for_request(request_id: i64) -> Result<Vec<DlpDetectionRecord>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
+pub fn get_dlp_verify_secrets_setting() -> bool {
+    get_dlp_verify_secrets_from_db()
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, request_id, timestamp, pattern_name, pattern_type, original_value, placeholder, message_index
-             FROM dlp_detections WHERE request_id = ?1 ORDER BY id ASC",
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn save_dlp_verify_secrets_setting(enabled: bool) -> Result<(), String> {
+    save_dlp_verify_secrets_to_db(enabled)
+}
 
-    let detections: Vec<DlpDetectionRecord> = stmt
-        .query_map([request_id], |row| {
-            Ok(DlpDetectionRecord {
-                id: row.get(0)?,
-                request_id: row.get(1)?,
-                timestamp: row.get(2)?,
-                pattern_name: row.get(3)?,
-                pattern_type: row.get(4)?,
-                original_value: row.get(5)?,
-                placeholder: row.get(6)?,
-                message_index: row.get(7)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
+#[tauri::command]
+pub fn get_dlp_hash_values_setting() -> bool {
+    get_dlp_hash_values_from_db()
+}
+
+/// Turning hashing on also converts the plaintext values already stored
+#[tauri::command]
+pub fn save_dlp_hash_values_setting(enabled: bool) -> Result<(), String> {
+    save_dlp_hash_values_to_db(enabled)?;
+    if enabled {
+        let converted = storage::shared()
+            .and_then(|store| store.hash_stored_detection_values())
+            .map_err(|e| e.to_string())?;
+        println!("[DLP] Hashed {} stored detection values", converted);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_dlp_detect_injection_setting() -> bool {
+    get_dlp_detect_injection_from_db()
+}
+
+#[tauri::command]
+pub fn save_dlp_detect_injection_setting(enabled: bool) -> Result<(), String> {
+    save_dlp_detect_injection_to_db(enabled)
+}
+
+#[tauri::command]
+pub fn get_dlp_webhook_settings() -> WebhookSettings {
+    get_dlp_webhook_settings_from_db()
+}
+
+/// Save the webhook URLs and severity threshold; blank URLs are dropped
+#[tauri::command]
+pub fn save_dlp_webhook_settings(mut settings: WebhookSettings) -> Result<(), String> {
+    let min_severity = Severity::from_name(&settings.min_severity)
+        .ok_or_else(|| format!("Unknown severity '{}'", settings.min_severity))?;
+    settings.min_severity = min_severity.name().to_string();
+
+    settings.urls = settings
+        .urls
+        .iter()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
         .collect();
+    for url in &settings.urls {
+        dlp_webhooks::validate_url(url)?;
+    }
+    if settings.enabled && settings.urls.is_empty() {
+        return Err("Add at least one webhook URL".to_string());
+    }
 
-    Ok(detections)
+    save_dlp_webhook_settings_to_db(&settings)
 }
 
+/// Send a sample event to a webhook URL (single attempt, no retries)
 #[tauri::command]
-pub fn get_dlp_action_setting() -> String {
-    get_dlp_action_from_db()
+pub async fn test_dlp_webhook(url: String) -> Result<(), String> {
+    let url = url.trim();
+    dlp_webhooks::validate_url(url)?;
+    dlp_webhooks::send_test_event(url).await
 }
 
 #[tauri::command]
-pub fn save_dlp_action_setting(action: String) -> Result<(), String> {
-    save_dlp_action_to_db(&action)
+pub fn get_dlp_canary_setting() -> bool {
+    get_dlp_canary_enabled_from_db()
+}
+
+#[tauri::command]
+pub fn save_dlp_canary_setting(enabled: bool) -> Result<(), String> {
+    save_dlp_canary_enabled_to_db(enabled)
+}
+
+#[tauri::command]
+pub fn get_dlp_scan_limits() -> DlpScanLimits {
+    get_dlp_scan_limits_from_db()
+}
+
+#[tauri::command]
+pub fn save_dlp_scan_limits(limits: DlpScanLimits) -> Result<(), String> {
+    save_dlp_scan_limits_to_db(&limits)
+}
+
+#[tauri::command]
+pub fn get_dlp_incident_window() -> i64 {
+    get_dlp_incident_window_from_db()
+}
+
+/// Minutes within which repeats of a value in one conversation extend the same incident (0 = off)
+#[tauri::command]
+pub fn save_dlp_incident_window(minutes: i64) -> Result<(), String> {
+    if !(0..=7 * 24 * 60).contains(&minutes) {
+        return Err("Grouping window must be between 0 and 10080 minutes (7 days)".to_string());
+    }
+    save_dlp_incident_window_to_db(minutes)
+}
+
+#[tauri::command]
+pub fn get_dlp_min_block_confidence() -> f64 {
+    get_dlp_min_block_confidence_from_db()
+}
+
+/// Save the confidence below which "block" detections are only alerted
+#[tauri::command]
+pub fn save_dlp_min_block_confidence(confidence: f64) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&confidence) {
+        return Err("Minimum confidence must be between 0 and 1".to_string());
+    }
+    save_dlp_min_block_confidence_to_db(confidence)?;
+    invalidate_dlp_pattern_cache();
+    Ok(())
+}
+
+/// NER settings plus whether this build can run the model
+#[derive(Serialize)]
+pub struct NerStatus {
+    #[serde(flatten)]
+    pub settings: NerSettings,
+    pub available: bool,
+    /// Where the model files are expected
+    pub model_dir: String,
+}
+
+#[tauri::command]
+pub fn get_dlp_ner_settings() -> NerStatus {
+    NerStatus {
+        settings: get_dlp_ner_settings_from_db(),
+        available: ner::is_available(),
+        model_dir: ner::model_dir().to_string_lossy().to_string(),
+    }
+}
+
+/// Turn "ner" patterns on or off and set the minimum model score
+#[tauri::command]
+pub fn save_dlp_ner_settings(settings: NerSettings) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&settings.min_score) {
+        return Err("Minimum score must be between 0 and 1".to_string());
+    }
+    save_dlp_ner_settings_to_db(&settings)?;
+    invalidate_dlp_pattern_cache();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_dlp_severity_policy() -> HashMap<String, String> {
+    get_dlp_severity_policy_from_db()
+}
+
+/// Save the severity -> action policy; severities mapped to "" are dropped and follow the global action
+#[tauri::command]
+pub fn save_dlp_severity_policy(policy: HashMap<String, String>) -> Result<(), String> {
+    let mut validated = HashMap::new();
+    for (severity, action) in policy {
+        let severity = Severity::from_name(&severity)
+            .ok_or_else(|| format!("Unknown severity '{}'", severity))?;
+        match parse_action(Some(&action))? {
+            Some("allow") => return Err("The severity policy cannot allow matches".to_string()),
+            Some(action) => {
+                validated.insert(severity.name().to_string(), action.to_string());
+            }
+            None => {}
+        }
+    }
+    save_dlp_severity_policy_to_db(&validated)?;
+    invalidate_dlp_pattern_cache();
+    Ok(())
 }
 
 #[derive(Serialize)]
 pub struct TestPatternResult {
     pub matches: Vec<String>,
     pub excluded: bool,
+    /// Every candidate match, kept or dropped, in text order
+    pub candidates: Vec<TestPatternCandidate>,
+    /// Matches were found but fewer distinct values than min_occurrences
+    pub below_min_occurrences: bool,
+}
+
+/// A candidate match in the test text and why it was kept or dropped
+#[derive(Serialize)]
+pub struct TestPatternCandidate {
+    pub value: String,
+    /// Character offsets in the test text
+    pub start: usize,
+    pub end: usize,
+    /// The pattern line that found it (None for NER entities)
+    pub pattern: Option<String>,
+    /// Text around the match that negative patterns are checked against
+    pub context: String,
+    pub accepted: bool,
+    /// The negative pattern or filter that dropped it
+    pub reason: Option<String>,
 }
 
 /// Test a pattern configuration against sample text without saving
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn test_dlp_pattern(
     pattern_type: String,
     patterns: Vec<String>,
@@ -434,6 +595,10 @@ pub fn test_dlp_pattern(
     negative_patterns: Option<Vec<String>>,
     min_occurrences: i32,
     min_unique_chars: i32,
+    min_entropy: Option<f64>,
+    validator: Option<String>,
+    allowlist: Option<Vec<String>>,
+    normalize: Option<bool>,
     test_text: String,
 ) -> Result<TestPatternResult, String> {
     // Compile patterns using shared utility
@@ -444,23 +609,55 @@ pub fn test_dlp_pattern(
         negative_pattern_type.as_deref(),
     )?;
 
+    let filters = MatchFilters {
+        min_unique_chars,
+        min_entropy: min_entropy.unwrap_or(0.0),
+        validator: parse_validator(validator.as_deref())?,
+        allowlist: Allowlist::compile(allowlist.as_deref().unwrap_or_default())?,
+        dictionaries: compiled.dictionaries.clone(),
+        entities: compiled.entities.clone(),
+        min_entity_score: get_dlp_ner_settings_from_db().min_score,
+        normalize: normalize.unwrap_or(false),
+    };
+
     // Collect matches with context-aware negative pattern filtering
-    // Each match is checked against negative patterns within its 30-char context window
+    // Each match is checked against negative patterns within its 30-char context window,
+    // then run through the allowlist and the unique-char, entropy and validator filters
     let match_result = collect_matches_with_negative_context(
         &test_text,
         &compiled.regexes,
         &compiled.negative_regexes,
-        min_unique_chars,
+        &filters,
     );
 
+    let found_any = !match_result.matches.is_empty();
+
     // Filter by min_occurrences threshold
     let matches = filter_by_min_occurrences(match_result, min_occurrences);
 
     // If all matches were excluded by negative patterns, indicate exclusion
     let excluded = matches.is_empty() && !compiled.negative_regexes.is_empty();
 
+    // Explain every candidate, naming the pattern line that found it
+    let pattern_lines: Vec<&String> = patterns.iter().filter(|p| !p.trim().is_empty()).collect();
+    let char_offset = |byte: usize| test_text[..byte].chars().count();
+    let candidates = explain_matches(&test_text, &compiled.regexes, &compiled.negative_regexes, &filters)
+        .into_iter()
+        .map(|e| TestPatternCandidate {
+            start: char_offset(e.start),
+            end: char_offset(e.end),
+            value: e.value,
+            pattern: e.regex_index.and_then(|i| pattern_lines.get(i)).map(|p| p.to_string()),
+            context: e.context,
+            accepted: e.rejection.is_none(),
+            reason: e.rejection.map(|r| r.describe()),
+        })
+        .collect();
+
     Ok(TestPatternResult {
+        below_min_occurrences: found_any && matches.is_empty(),
         matches,
         excluded,
+        candidates,
     })
 }
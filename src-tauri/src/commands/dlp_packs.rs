@@ -0,0 +1,363 @@
+// DLP Pattern Pack Import/Export Commands
+//
+// A pattern pack is a JSON or YAML file with the custom patterns of one installation, so a
+// security team can distribute a standard set of patterns to every laptop.
+// Gitleaks TOML configs are converted to pack entries and imported the same way.
+
+use super::dlp::{parse_action, parse_category, parse_scope, parse_severity};
+use crate::dlp::invalidate_dlp_pattern_cache;
+use crate::gitleaks::convert_gitleaks_config;
+use crate::pattern_utils::{compile_pattern_set, Allowlist};
+use crate::storage::{self, DlpStore};
+use crate::validators::parse_validator;
+use serde::{Deserialize, Serialize};
+
+/// Pack format version, bumped on incompatible changes
+const PATTERN_PACK_VERSION: u32 = 1;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_min_occurrences() -> i32 {
+    1
+}
+
+/// One pattern in a pack; everything but name, type and patterns is optional
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PatternPackEntry {
+    pub name: String,
+    pub pattern_type: String,
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub negative_pattern_type: Option<String>,
+    #[serde(default)]
+    pub negative_patterns: Option<Vec<String>>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_min_occurrences")]
+    pub min_occurrences: i32,
+    #[serde(default)]
+    pub min_unique_chars: i32,
+    #[serde(default)]
+    pub min_entropy: f64,
+    #[serde(default)]
+    pub validator: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub severity: Option<String>,
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PatternPack {
+    pub version: u32,
+    pub exported_at: String,
+    pub patterns: Vec<PatternPackEntry>,
+}
+
+#[derive(Serialize, Default)]
+pub struct PatternImportSummary {
+    pub added: usize,
+    pub replaced: usize,
+    pub skipped: usize,
+    /// One message per pattern that failed validation or couldn't be converted
+    pub errors: Vec<String>,
+}
+
+/// How to handle an imported pattern whose name is already taken
+#[derive(Clone, Copy, PartialEq)]
+enum ConflictMode {
+    Skip,
+    Replace,
+    Rename,
+}
+
+impl ConflictMode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(ConflictMode::Skip),
+            "replace" => Some(ConflictMode::Replace),
+            "rename" => Some(ConflictMode::Rename),
+            _ => None,
+        }
+    }
+}
+
+/// Export all custom patterns as a pattern pack: pretty-printed JSON, or YAML with `format` "yaml"
+#[tauri::command]
+pub fn export_dlp_patterns(format: Option<String>) -> Result<String, String> {
+    let patterns = storage::shared()
+        .and_then(|store| store.custom_dlp_patterns())
+        .map_err(|e| e.to_string())?;
+    let pack = PatternPack {
+        version: PATTERN_PACK_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        patterns,
+    };
+
+    match format.as_deref() {
+        None | Some("json") => serde_json::to_string_pretty(&pack).map_err(|e| e.to_string()),
+        Some("yaml") => serde_yaml::to_string(&pack).map_err(|e| e.to_string()),
+        Some(other) => Err(format!("Unknown pattern pack format '{}'", other)),
+    }
+}
+
+/// Read a pack as JSON when it is a JSON object, as YAML otherwise
+fn parse_pattern_pack(contents: &str) -> Result<PatternPack, String> {
+    let pack: PatternPack = if contents.trim_start().starts_with('{') {
+        serde_json::from_str(contents).map_err(|e| format!("Invalid pattern pack: {}", e))?
+    } else {
+        serde_yaml::from_str(contents).map_err(|e| format!("Invalid pattern pack: {}", e))?
+    };
+    if pack.version > PATTERN_PACK_VERSION {
+        return Err(format!(
+            "Pattern pack version {} is newer than supported version {}",
+            pack.version, PATTERN_PACK_VERSION
+        ));
+    }
+    Ok(pack)
+}
+
+/// Import a pattern pack (JSON or YAML), merging it into the custom patterns
+/// `on_conflict` decides what happens when a pattern with the same name exists:
+/// "skip" keeps the existing one, "replace" overwrites it, "rename" imports under a new name.
+/// Builtin patterns are never replaced.
+#[tauri::command]
+pub fn import_dlp_patterns(contents: String, on_conflict: String) -> Result<PatternImportSummary, String> {
+    let mode = ConflictMode::from_name(&on_conflict)
+        .ok_or_else(|| format!("Unknown conflict mode '{}'", on_conflict))?;
+
+    let pack = parse_pattern_pack(&contents)?;
+    import_pack_entries(pack.patterns, mode, Vec::new())
+}
+
+/// Import the rules of a gitleaks TOML config as custom patterns
+/// Rules that can't be converted are reported in `errors`; name conflicts follow `on_conflict`
+#[tauri::command]
+pub fn import_gitleaks_rules(contents: String, on_conflict: String) -> Result<PatternImportSummary, String> {
+    let mode = ConflictMode::from_name(&on_conflict)
+        .ok_or_else(|| format!("Unknown conflict mode '{}'", on_conflict))?;
+
+    let conversion = convert_gitleaks_config(&contents)?;
+    import_pack_entries(conversion.entries, mode, conversion.skipped)
+}
+
+/// Validate and store entries, resolving name conflicts per mode
+fn import_pack_entries(
+    entries: Vec<PatternPackEntry>,
+    mode: ConflictMode,
+    errors: Vec<String>,
+) -> Result<PatternImportSummary, String> {
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let summary = store_pack_entries(&*store, entries, mode, errors)?;
+    invalidate_dlp_pattern_cache();
+    Ok(summary)
+}
+
+fn store_pack_entries(
+    store: &dyn DlpStore,
+    entries: Vec<PatternPackEntry>,
+    mode: ConflictMode,
+    errors: Vec<String>,
+) -> Result<PatternImportSummary, String> {
+    let mut summary = PatternImportSummary {
+        errors,
+        ..Default::default()
+    };
+
+    for entry in entries {
+        let name = entry.name.trim().to_string();
+        let entry = match validate_pack_entry(&entry) {
+            Ok(entry) => entry,
+            Err(e) => {
+                summary.errors.push(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+
+        let existing = store.dlp_pattern_by_name(&name).map_err(|e| e.to_string())?;
+        let result = match (existing, mode) {
+            (None, _) => store.add_dlp_pattern(&name, &entry).map(|_| summary.added += 1),
+            (Some(_), ConflictMode::Skip) | (Some((_, true)), ConflictMode::Replace) => {
+                summary.skipped += 1;
+                Ok(())
+            }
+            (Some((id, false)), ConflictMode::Replace) => {
+                store.replace_dlp_pattern(id, &entry).map(|_| summary.replaced += 1)
+            }
+            (Some(_), ConflictMode::Rename) => {
+                let new_name = unused_name(store, &name)?;
+                store.add_dlp_pattern(&new_name, &entry).map(|_| summary.added += 1)
+            }
+        };
+
+        if let Err(e) = result {
+            summary.errors.push(format!("{}: {}", name, e));
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Apply the same checks as adding a pattern by hand; returns the entry with its validator,
+/// category, action, severity and scope names as stored
+fn validate_pack_entry(entry: &PatternPackEntry) -> Result<PatternPackEntry, String> {
+    if entry.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if entry.patterns.is_empty() {
+        return Err("At least one pattern is required".to_string());
+    }
+    compile_pattern_set(
+        &entry.patterns,
+        &entry.pattern_type,
+        entry.negative_patterns.as_ref(),
+        entry.negative_pattern_type.as_deref(),
+    )?;
+    Allowlist::compile(&entry.allowlist)?;
+    Ok(PatternPackEntry {
+        validator: parse_validator(entry.validator.as_deref())?.map(|v| v.name().to_string()),
+        category: Some(parse_category(entry.category.as_deref())?.to_string()),
+        action: parse_action(entry.action.as_deref())?.map(str::to_string),
+        severity: Some(parse_severity(entry.severity.as_deref())?.to_string()),
+        scope: Some(parse_scope(entry.scope.as_deref())?.to_string()),
+        ..entry.clone()
+    })
+}
+
+/// First free name of the form "Name (imported)", "Name (imported 2)", ...
+fn unused_name(store: &dyn DlpStore, name: &str) -> Result<String, String> {
+    let mut candidate = format!("{} (imported)", name);
+    let mut n = 2;
+    while store.dlp_pattern_by_name(&candidate).map_err(|e| e.to_string())?.is_some() {
+        candidate = format!("{} (imported {})", name, n);
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use rusqlite::Connection;
+
+    fn patterns_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE dlp_patterns (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, pattern_type TEXT NOT NULL,
+                patterns TEXT NOT NULL, negative_pattern_type TEXT, negative_patterns TEXT,
+                enabled INTEGER DEFAULT 1, min_occurrences INTEGER DEFAULT 1, min_unique_chars INTEGER DEFAULT 0,
+                is_builtin INTEGER DEFAULT 0, created_at TEXT NOT NULL, validator TEXT, min_entropy REAL DEFAULT 0,
+                category TEXT DEFAULT 'secret', action TEXT, severity TEXT, allowlist TEXT, scope TEXT,
+                normalize INTEGER DEFAULT 0
+            );
+            INSERT INTO dlp_patterns (name, pattern_type, patterns, is_builtin, created_at)
+            VALUES ('API Keys', 'regex', '[\"sk-[a-z]+\"]', 1, '');",
+        )
+        .unwrap();
+        Database::from_connection(conn)
+    }
+
+    fn entry(name: &str, pattern: &str) -> PatternPackEntry {
+        PatternPackEntry {
+            name: name.to_string(),
+            pattern_type: "regex".to_string(),
+            patterns: vec![pattern.to_string()],
+            negative_pattern_type: None,
+            negative_patterns: None,
+            enabled: true,
+            min_occurrences: 1,
+            min_unique_chars: 0,
+            min_entropy: 3.5,
+            validator: None,
+            category: None,
+            action: Some("block".to_string()),
+            severity: Some("critical".to_string()),
+            allowlist: vec!["itk_test".to_string()],
+            scope: None,
+            normalize: true,
+        }
+    }
+
+    fn pattern_of(db: &Database, name: &str) -> Option<Vec<String>> {
+        db.dlp_patterns().unwrap().into_iter().find(|p| p.name == name).map(|p| p.patterns)
+    }
+
+    #[test]
+    fn test_pack_round_trip_json_and_yaml() {
+        let source = patterns_db();
+        store_pack_entries(&source, vec![entry("Internal Tokens", "itk_[A-Za-z0-9]{32}")], ConflictMode::Skip, Vec::new())
+            .unwrap();
+        let pack = PatternPack {
+            version: PATTERN_PACK_VERSION,
+            exported_at: String::new(),
+            patterns: source.custom_dlp_patterns().unwrap(),
+        };
+        // Builtins aren't exported
+        assert_eq!(pack.patterns.len(), 1);
+
+        let json = serde_json::to_string_pretty(&pack).unwrap();
+        let yaml = serde_yaml::to_string(&pack).unwrap();
+        for contents in [json, yaml] {
+            let target = patterns_db();
+            let parsed = parse_pattern_pack(&contents).unwrap();
+            let summary = store_pack_entries(&target, parsed.patterns, ConflictMode::Skip, Vec::new()).unwrap();
+            assert_eq!((summary.added, summary.errors.len()), (1, 0));
+
+            let imported = &target.custom_dlp_patterns().unwrap()[0];
+            assert_eq!(imported.name, "Internal Tokens");
+            assert_eq!(imported.patterns, vec!["itk_[A-Za-z0-9]{32}"]);
+            assert_eq!(imported.action.as_deref(), Some("block"));
+            assert_eq!(imported.severity.as_deref(), Some("critical"));
+            assert_eq!(imported.allowlist, vec!["itk_test"]);
+            assert_eq!(imported.min_entropy, 3.5);
+            assert!(imported.normalize);
+        }
+
+        let newer = format!("version: {}\nexported_at: ''\npatterns: []\n", PATTERN_PACK_VERSION + 1);
+        assert!(parse_pattern_pack(&newer).is_err());
+    }
+
+    #[test]
+    fn test_import_conflict_modes() {
+        let conn = patterns_db();
+        store_pack_entries(&conn, vec![entry("Tokens", "old_[0-9]+")], ConflictMode::Skip, Vec::new()).unwrap();
+
+        let summary = store_pack_entries(&conn, vec![entry("Tokens", "new_[0-9]+")], ConflictMode::Skip, Vec::new()).unwrap();
+        assert_eq!((summary.added, summary.replaced, summary.skipped), (0, 0, 1));
+        assert_eq!(pattern_of(&conn, "Tokens").unwrap(), vec!["old_[0-9]+"]);
+
+        let summary = store_pack_entries(&conn, vec![entry("Tokens", "new_[0-9]+")], ConflictMode::Replace, Vec::new()).unwrap();
+        assert_eq!((summary.added, summary.replaced, summary.skipped), (0, 1, 0));
+        assert_eq!(pattern_of(&conn, "Tokens").unwrap(), vec!["new_[0-9]+"]);
+
+        for _ in 0..2 {
+            let summary = store_pack_entries(&conn, vec![entry("Tokens", "copy_[0-9]+")], ConflictMode::Rename, Vec::new()).unwrap();
+            assert_eq!(summary.added, 1);
+        }
+        assert!(pattern_of(&conn, "Tokens (imported)").is_some());
+        assert!(pattern_of(&conn, "Tokens (imported 2)").is_some());
+
+        // Builtins are never replaced; invalid entries are reported
+        let summary = store_pack_entries(
+            &conn,
+            vec![entry("API Keys", "x_[0-9]+"), entry("Broken", "[unclosed")],
+            ConflictMode::Replace,
+            Vec::new(),
+        )
+        .unwrap();
+        assert_eq!((summary.replaced, summary.skipped, summary.errors.len()), (0, 1, 1));
+        assert_eq!(pattern_of(&conn, "API Keys").unwrap(), vec!["sk-[a-z]+"]);
+    }
+}
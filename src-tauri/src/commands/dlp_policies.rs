@@ -0,0 +1,93 @@
+// DLP Policy Commands
+//
+// CRUD for per-backend / per-model overrides of a pattern's action (see dlp_policy.rs).
+
+use crate::dlp::{invalidate_dlp_pattern_cache, DlpAction};
+use crate::dlp_policy::{load_dlp_policies, DlpPolicy, ANY, OFF};
+use crate::storage::{self, DlpStore};
+
+/// Validate a policy action: any pattern action, or "off"
+fn parse_policy_action(action: &str) -> Result<&'static str, String> {
+    let action = action.trim();
+    if action == OFF {
+        return Ok(OFF);
+    }
+    DlpAction::from_name(action)
+        .map(|a| a.name())
+        .ok_or_else(|| format!("Unknown action '{}'", action))
+}
+
+/// Trimmed backend name or model glob; empty means "*"
+fn or_any(value: Option<&str>) -> String {
+    match value.map(str::trim) {
+        None | Some("") => ANY.to_string(),
+        Some(v) => v.to_string(),
+    }
+}
+
+#[tauri::command]
+pub fn get_dlp_policies() -> Result<Vec<DlpPolicy>, String> {
+    Ok(load_dlp_policies())
+}
+
+#[tauri::command]
+pub fn add_dlp_policy(
+    pattern_id: i64,
+    backend: Option<String>,
+    model_glob: Option<String>,
+    action: String,
+) -> Result<i64, String> {
+    let action = parse_policy_action(&action)?;
+    let store = storage::shared().map_err(|e| e.to_string())?;
+
+    if !store.dlp_pattern_exists(pattern_id).unwrap_or(false) {
+        return Err("Pattern not found".to_string());
+    }
+
+    let id = store
+        .add_dlp_policy(&DlpPolicy {
+            id: 0,
+            pattern_id,
+            backend: or_any(backend.as_deref()),
+            model_glob: or_any(model_glob.as_deref()),
+            action: action.to_string(),
+        })
+        .map_err(|e| e.to_string())?;
+
+    invalidate_dlp_pattern_cache();
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn update_dlp_policy(
+    id: i64,
+    backend: Option<String>,
+    model_glob: Option<String>,
+    action: String,
+) -> Result<(), String> {
+    let action = parse_policy_action(&action)?;
+    let policy = DlpPolicy {
+        id,
+        pattern_id: 0,
+        backend: or_any(backend.as_deref()),
+        model_glob: or_any(model_glob.as_deref()),
+        action: action.to_string(),
+    };
+
+    storage::shared()
+        .and_then(|store| store.update_dlp_policy(&policy))
+        .map_err(|e| e.to_string())?;
+
+    invalidate_dlp_pattern_cache();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_dlp_policy(id: i64) -> Result<(), String> {
+    storage::shared()
+        .and_then(|store| store.delete_dlp_policy(id))
+        .map_err(|e| e.to_string())?;
+
+    invalidate_dlp_pattern_cache();
+    Ok(())
+}
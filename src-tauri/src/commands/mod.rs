@@ -1,12 +1,20 @@
 // Tauri Commands Module
 
 pub mod backends;
+pub mod budgets;
 pub mod cursor;
 pub mod dlp;
+pub mod dlp_packs;
+pub mod dlp_policies;
+pub mod pricing;
 pub mod stats;
 
 // Re-export all commands for convenience
 pub use backends::*;
+pub use budgets::*;
 pub use cursor::*;
 pub use dlp::*;
+pub use dlp_packs::*;
+pub use dlp_policies::*;
+pub use pricing::*;
 pub use stats::*;
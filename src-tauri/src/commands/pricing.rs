@@ -0,0 +1,25 @@
+// Model Pricing Tauri Commands
+
+use crate::pricing::{self, ModelPrice};
+
+#[tauri::command]
+pub fn get_model_pricing() -> Vec<ModelPrice> {
+    pricing::load_prices()
+}
+
+/// Add a price, or update the one with the same id
+#[tauri::command]
+pub fn save_model_price(price: ModelPrice) -> Result<i64, String> {
+    pricing::save_price(&price)
+}
+
+#[tauri::command]
+pub fn delete_model_price(id: i64) -> Result<(), String> {
+    pricing::delete_price(id)
+}
+
+/// Put the default list prices back; prices added for other models are kept
+#[tauri::command]
+pub fn restore_default_model_pricing() -> Result<Vec<ModelPrice>, String> {
+    pricing::restore_defaults()
+}
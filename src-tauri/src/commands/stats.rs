@@ -1,8 +1,19 @@
 // Stats and Monitoring Tauri Commands
 
-use crate::database::{get_port_from_db, open_connection, save_port_to_db, DLP_ACTION_BLOCKED, DLP_ACTION_PASSED, DLP_ACTION_REDACTED, DLP_ACTION_RATELIMITED, DLP_ACTION_NOTIFY_RATELIMIT};
+use crate::client_auth::{self, new_client_token, ClientAuthSettings, CLIENT_TOKEN_HEADER};
+use crate::database::{get_port_from_db, get_proxy_bind_addresses_from_db, get_proxy_body_limits_from_db, get_proxy_client_auth_from_db, get_response_cache_from_db, get_retention_from_db, save_port_to_db, save_proxy_bind_addresses_to_db, save_proxy_body_limits_to_db, save_retention_to_db, DLP_ACTION_BLOCKED, DLP_ACTION_PASSED, DLP_ACTION_REDACTED, DLP_ACTION_RATELIMITED, DLP_ACTION_NOTIFY_RATELIMIT, DLP_ACTION_ALERTED, DLP_ACTION_SCAN_SKIPPED};
+use crate::db_encryption::{self, EncryptionStatus};
+use crate::log_export::{write_logs, ExportFormat};
+use crate::pricing::{self, find_price, ModelPrice, TokenUsage};
+use crate::proxy::{restart_all_backend_listeners, BindAddresses, BodyLimits};
+use crate::response_cache::{self, ResponseCacheSettings};
+use crate::retention::{self, RepairReport, RetentionReport, RetentionSettings};
+use crate::storage::{self, LogFilter, LogQueries, ModelUsage, SpendStore};
+use crate::upstream_proxy::{self, UpstreamProxySettings, UpstreamProxyStatus};
 use crate::{PROXY_PORT, PROXY_STATUS, RESTART_SENDER, ProxyStatus};
 use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::oneshot;
 
 // ========================================================================
 // Tray Menu Stats (Last 24h per backend)
@@ -42,79 +53,29 @@ pub struct TrayTokenTimeline {
 
 #[tauri::command]
 pub fn get_tray_stats() -> Result<TrayStats, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
     // Last 24 hours
     let cutoff_ts = get_cutoff_timestamp(24);
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT backend,
-                    COUNT(*) as request_count,
-                    COALESCE(SUM(input_tokens), 0) as input_tokens,
-                    COALESCE(SUM(output_tokens), 0) as output_tokens,
-                    COALESCE(SUM(cache_read_tokens), 0) + COALESCE(SUM(cache_creation_tokens), 0) as cache_tokens
-             FROM requests
-             WHERE timestamp >= ?1
-             GROUP BY backend
-             ORDER BY request_count DESC"
-        )
+    let backends = storage::shared()
+        .and_then(|store| store.backend_usage(&cutoff_ts))
         .map_err(|e| e.to_string())?;
 
-    let backends: Vec<BackendStats> = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(BackendStats {
-                backend: row.get(0)?,
-                request_count: row.get(1)?,
-                input_tokens: row.get(2)?,
-                output_tokens: row.get(3)?,
-                cache_tokens: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
-
     Ok(TrayStats { backends })
 }
 
 #[tauri::command]
 pub fn get_tray_token_timeline() -> Result<TrayTokenTimeline, String> {
-    use std::collections::HashMap;
-
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
     // Last 24 hours
     let cutoff_ts = get_cutoff_timestamp(24);
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT backend, timestamp, input_tokens
-             FROM requests
-             WHERE timestamp >= ?1 AND input_tokens > 0
-             ORDER BY timestamp ASC"
-        )
+    let points = storage::shared()
+        .and_then(|store| store.input_token_points(&cutoff_ts))
         .map_err(|e| e.to_string())?;
 
     // Group points by backend
     let mut backend_points: HashMap<String, Vec<TokenTimelinePoint>> = HashMap::new();
-
-    let rows = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, i64>(2)?,
-            ))
-        })
-        .map_err(|e| e.to_string())?;
-
-    for row in rows.filter_map(|r| r.ok()) {
-        let (backend, timestamp, input_tokens) = row;
-        backend_points
-            .entry(backend)
-            .or_default()
-            .push(TokenTimelinePoint { timestamp, input_tokens });
+    for (backend, point) in points {
+        backend_points.entry(backend).or_default().push(point);
     }
 
     // Convert to sorted vec
@@ -131,77 +92,117 @@ pub fn get_tray_token_timeline() -> Result<TrayTokenTimeline, String> {
 
 #[derive(Serialize)]
 pub struct ModelStats {
-    model: String,
-    count: i64,
+    pub model: String,
+    pub count: i64,
 }
 
 #[derive(Serialize)]
 pub struct FeatureStats {
-    with_system_prompt: i64,
-    with_tools: i64,
-    with_thinking: i64,
-    total_requests: i64,
+    pub with_system_prompt: i64,
+    pub with_tools: i64,
+    pub with_thinking: i64,
+    pub total_requests: i64,
 }
 
 #[derive(Serialize)]
 pub struct TokenTotals {
-    input: i64,
-    output: i64,
-    cache_read: i64,
-    cache_creation: i64,
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_creation: i64,
 }
 
 #[derive(Serialize)]
 pub struct RecentRequest {
-    id: i64,
-    timestamp: String,
-    model: String,
-    input_tokens: i64,
-    output_tokens: i64,
-    cache_read_tokens: i64,
-    cache_creation_tokens: i64,
-    latency_ms: i64,
-    stop_reason: String,
-    has_thinking: bool,
+    pub id: i64,
+    pub timestamp: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_creation_tokens: i64,
+    pub latency_ms: i64,
+    pub stop_reason: String,
+    pub has_thinking: bool,
+    /// Served from the response cache, so nothing was spent
+    #[serde(skip)]
+    pub cache_hit: bool,
+    /// Estimated cost in USD; None when the model has no price
+    pub estimated_cost_usd: Option<f64>,
 }
 
 #[derive(Serialize)]
 pub struct MessageLog {
-    id: i64,
-    timestamp: String,
-    backend: String,
-    model: String,
-    input_tokens: i64,
-    output_tokens: i64,
-    latency_ms: i64,
-    request_body: Option<String>,
-    response_body: Option<String>,
-    request_headers: Option<String>,
-    response_headers: Option<String>,
-    dlp_action: i64, // DLP_ACTION_PASSED=0, DLP_ACTION_REDACTED=1, DLP_ACTION_BLOCKED=2
+    pub id: i64,
+    pub timestamp: String,
+    pub backend: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub latency_ms: i64,
+    /// Time to first byte, and from there to the end of a streamed response (NULL for older rows)
+    pub ttfb_ms: Option<i64>,
+    pub stream_ms: Option<i64>,
+    pub request_headers: Option<String>,
+    pub response_headers: Option<String>,
+    pub dlp_action: i64, // DLP_ACTION_PASSED=0, DLP_ACTION_REDACTED=1, DLP_ACTION_BLOCKED=2, DLP_ACTION_ALERTED=5, DLP_ACTION_SCAN_SKIPPED=6
+    pub served_by: Option<String>,
+    pub translated_from: Option<String>,
+    /// Conversation the request belongs to (NULL when its client sends no conversation id)
+    pub conversation_id: Option<i64>,
 }
 
 #[derive(Serialize)]
 pub struct PaginatedLogs {
-    logs: Vec<MessageLog>,
-    total: i64,
+    pub logs: Vec<MessageLog>,
+    pub total: i64,
 }
 
 #[derive(Serialize)]
 pub struct LatencyPoint {
-    id: i64,
-    latency_ms: i64,
+    pub id: i64,
+    pub latency_ms: i64,
+    pub ttfb_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CostBreakdown {
+    name: String,
+    requests: i64,
+    cost_usd: f64,
+}
+
+#[derive(Serialize)]
+pub struct DailyCost {
+    date: String,
+    cost_usd: f64,
+}
+
+/// Estimated spend, from the logged token counts and the model prices
+#[derive(Serialize, Default)]
+pub struct CostSummary {
+    total_usd: f64,
+    by_model: Vec<CostBreakdown>,
+    by_backend: Vec<CostBreakdown>,
+    /// Per UTC day, oldest first
+    daily: Vec<DailyCost>,
+    /// Requests whose model has no price, left out of the estimate
+    unpriced_requests: i64,
 }
 
 #[derive(Serialize)]
 pub struct DashboardData {
-    models: Vec<ModelStats>,
-    features: FeatureStats,
-    token_totals: TokenTotals,
-    recent_requests: Vec<RecentRequest>,
-    latency_points: Vec<LatencyPoint>,
-    total_requests: i64,
-    avg_latency_ms: f64,
+    pub models: Vec<ModelStats>,
+    pub features: FeatureStats,
+    pub token_totals: TokenTotals,
+    pub recent_requests: Vec<RecentRequest>,
+    pub latency_points: Vec<LatencyPoint>,
+    pub total_requests: i64,
+    pub avg_latency_ms: f64,
+    pub avg_ttfb_ms: f64,
+    pub cache_hits: i64,
+    pub cache_misses: i64,
+    pub cost: CostSummary,
 }
 
 // Convert time range string to hours
@@ -221,225 +222,145 @@ fn get_cutoff_timestamp(hours: i64) -> String {
     cutoff.to_rfc3339()
 }
 
-#[tauri::command]
-pub fn get_dashboard_stats(time_range: String, backend: String) -> Result<DashboardData, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    let hours = time_range_to_hours(&time_range);
-    let cutoff_ts = get_cutoff_timestamp(hours);
+// "all" means every backend
+fn backend_option(backend: &str) -> Option<&str> {
+    (backend != "all").then_some(backend)
+}
 
-    // Build backend filter clause
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND backend = '{}'", backend.replace('\'', "''"))
+// The Logs view's filters; "all" (or an empty search) leaves a filter out
+fn log_filter(time_range: &str, backend: &str, model: &str, dlp_action: &str, search: &str) -> LogFilter {
+    let dlp_action = match dlp_action {
+        "passed" => Some(DLP_ACTION_PASSED),
+        "redacted" => Some(DLP_ACTION_REDACTED),
+        "blocked" => Some(DLP_ACTION_BLOCKED),
+        "ratelimited" => Some(DLP_ACTION_RATELIMITED),
+        "notify-ratelimit" => Some(DLP_ACTION_NOTIFY_RATELIMIT),
+        "alerted" => Some(DLP_ACTION_ALERTED),
+        "scan-skipped" => Some(DLP_ACTION_SCAN_SKIPPED),
+        _ => None,
     };
 
-    // Get model stats
-    let mut model_stmt = conn
-        .prepare(&format!(
-            "SELECT COALESCE(model, 'unknown') as model, COUNT(*) as count
-             FROM requests
-             WHERE model IS NOT NULL AND timestamp >= ?1{}
-             GROUP BY model
-             ORDER BY count DESC",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
-
-    let models: Vec<ModelStats> = model_stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(ModelStats {
-                model: row.get(0)?,
-                count: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
-
-    // Get feature stats
-    let features: FeatureStats = conn
-        .query_row(
-            &format!(
-                "SELECT
-                    COALESCE(SUM(has_system_prompt), 0),
-                    COALESCE(SUM(has_tools), 0),
-                    COALESCE(SUM(has_thinking), 0),
-                    COUNT(*)
-                 FROM requests
-                 WHERE timestamp >= ?1{}",
-                backend_filter
-            ),
-            [&cutoff_ts],
-            |row| {
-                Ok(FeatureStats {
-                    with_system_prompt: row.get(0)?,
-                    with_tools: row.get(1)?,
-                    with_thinking: row.get(2)?,
-                    total_requests: row.get(3)?,
-                })
-            },
-        )
-        .unwrap_or(FeatureStats {
-            with_system_prompt: 0,
-            with_tools: 0,
-            with_thinking: 0,
-            total_requests: 0,
-        });
-
-    // Get token totals
-    let token_totals: TokenTotals = conn
-        .query_row(
-            &format!(
-                "SELECT
-                    COALESCE(SUM(input_tokens), 0),
-                    COALESCE(SUM(output_tokens), 0),
-                    COALESCE(SUM(cache_read_tokens), 0),
-                    COALESCE(SUM(cache_creation_tokens), 0)
-                 FROM requests
-                 WHERE timestamp >= ?1{}",
-                backend_filter
-            ),
-            [&cutoff_ts],
-            |row| {
-                Ok(TokenTotals {
-                    input: row.get(0)?,
-                    output: row.get(1)?,
-                    cache_read: row.get(2)?,
-                    cache_creation: row.get(3)?,
-                })
-            },
-        )
-        .unwrap_or(TokenTotals {
-            input: 0,
-            output: 0,
-            cache_read: 0,
-            cache_creation: 0,
-        });
-
-    // Get recent requests for token chart
-    let mut recent_stmt = conn
-        .prepare(&format!(
-            "SELECT id, timestamp, COALESCE(model, 'unknown'), input_tokens, output_tokens,
-                    cache_read_tokens, cache_creation_tokens, latency_ms,
-                    COALESCE(stop_reason, 'unknown'), has_thinking
-             FROM requests
-             WHERE timestamp >= ?1{}
-             ORDER BY id DESC",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
+    LogFilter {
+        since: get_cutoff_timestamp(time_range_to_hours(time_range)),
+        backend: backend_option(backend).map(str::to_string),
+        model: (model != "all").then(|| model.to_string()),
+        dlp_action,
+        search: Some(search.to_string()).filter(|s| !s.trim().is_empty()),
+    }
+}
 
-    let recent_requests: Vec<RecentRequest> = recent_stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(RecentRequest {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                model: row.get(2)?,
-                input_tokens: row.get(3)?,
-                output_tokens: row.get(4)?,
-                cache_read_tokens: row.get(5)?,
-                cache_creation_tokens: row.get(6)?,
-                latency_ms: row.get(7)?,
-                stop_reason: row.get(8)?,
-                has_thinking: row.get::<_, i32>(9)? == 1,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
+/// Sorted by cost, highest first
+fn cost_breakdown(costs: HashMap<String, (i64, f64)>) -> Vec<CostBreakdown> {
+    let mut breakdown: Vec<CostBreakdown> = costs
+        .into_iter()
+        .map(|(name, (requests, cost_usd))| CostBreakdown { name, requests, cost_usd })
         .collect();
+    breakdown.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd).then_with(|| a.name.cmp(&b.name)));
+    breakdown
+}
 
-    // Get latency points for chart
-    let mut latency_stmt = conn
-        .prepare(&format!(
-            "SELECT id, latency_ms
-             FROM requests
-             WHERE latency_ms > 0 AND timestamp >= ?1{}
-             ORDER BY id DESC",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
+/// Estimated spend of the requests that used tokens at a model (not blocked, rate limited or served
+/// from the response cache), summed per day, backend and model before pricing
+fn cost_summary(usage: Vec<ModelUsage>, prices: &[ModelPrice]) -> CostSummary {
+    let mut summary = CostSummary::default();
+    let mut by_model: HashMap<String, (i64, f64)> = HashMap::new();
+    let mut by_backend: HashMap<String, (i64, f64)> = HashMap::new();
+    let mut daily: BTreeMap<String, f64> = BTreeMap::new();
+    for ModelUsage { date, backend, model, requests, usage } in usage {
+        let Some(price) = find_price(prices, &model) else {
+            summary.unpriced_requests += requests;
+            continue;
+        };
+        let cost = price.cost(&model, &usage);
+        summary.total_usd += cost;
+        for (costs, key) in [(&mut by_model, model), (&mut by_backend, backend)] {
+            let entry = costs.entry(key).or_default();
+            entry.0 += requests;
+            entry.1 += cost;
+        }
+        *daily.entry(date).or_default() += cost;
+    }
 
-    let latency_points: Vec<LatencyPoint> = latency_stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(LatencyPoint {
-                id: row.get(0)?,
-                latency_ms: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
+    summary.by_model = cost_breakdown(by_model);
+    summary.by_backend = cost_breakdown(by_backend);
+    summary.daily = daily
+        .into_iter()
+        .map(|(date, cost_usd)| DailyCost { date, cost_usd })
         .collect();
-
-    // Get totals
-    let total_requests: i64 = conn
-        .query_row(
-            &format!(
-                "SELECT COUNT(*) FROM requests WHERE timestamp >= ?1{}",
-                backend_filter
-            ),
-            [&cutoff_ts],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-
-    let avg_latency_ms: f64 = conn
-        .query_row(
-            &format!(
-                "SELECT COALESCE(AVG(latency_ms), 0)
-                 FROM requests
-                 WHERE latency_ms > 0 AND timestamp >= ?1{}",
-                backend_filter
-            ),
-            [&cutoff_ts],
-            |row| row.get(0),
-        )
-        .unwrap_or(0.0);
-
-    Ok(DashboardData {
-        models,
-        features,
-        token_totals,
-        recent_requests,
-        latency_points,
-        total_requests,
-        avg_latency_ms,
-    })
+    summary
 }
 
 #[tauri::command]
-pub fn get_backends() -> Result<Vec<String>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
+pub fn get_dashboard_stats(time_range: String, backend: String) -> Result<DashboardData, String> {
+    let hours = time_range_to_hours(&time_range);
+    let cutoff_ts = get_cutoff_timestamp(hours);
+    let backend = backend_option(&backend);
 
-    let mut stmt = conn
-        .prepare("SELECT DISTINCT backend FROM requests ORDER BY backend")
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let mut data = store
+        .dashboard_stats(&cutoff_ts, backend)
         .map_err(|e| e.to_string())?;
 
-    let backends: Vec<String> = stmt
-        .query_map([], |row| row.get(0))
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+    let prices = pricing::load_prices();
+    for request in &mut data.recent_requests {
+        let usage = TokenUsage {
+            input: request.input_tokens,
+            output: request.output_tokens,
+            cache_read: request.cache_read_tokens,
+            cache_creation: request.cache_creation_tokens,
+        };
+        // Served from the response cache, so nothing was spent
+        request.estimated_cost_usd = find_price(&prices, &request.model)
+            .map(|p| if request.cache_hit { 0.0 } else { p.cost(&request.model, &usage) });
+    }
+
+    let usage = store
+        .model_usage(&cutoff_ts, backend)
+        .map_err(|e| e.to_string())?;
+    data.cost = cost_summary(usage, &prices);
+
+    Ok(data)
+}
 
-    Ok(backends)
+#[derive(Serialize)]
+pub struct TokenCountModelStats {
+    pub model: String,
+    pub count: i64,
+    pub avg_input_tokens: f64,
+    pub max_input_tokens: i64,
 }
 
+#[derive(Serialize)]
+pub struct TokenCountStats {
+    pub total_calls: i64,
+    pub avg_input_tokens: f64,
+    pub max_input_tokens: i64,
+    pub models: Vec<TokenCountModelStats>,
+}
+
+/// Prompt sizes measured by token-counting calls (logged when enabled per backend)
 #[tauri::command]
-pub fn get_models() -> Result<Vec<String>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
+pub fn get_token_count_stats(time_range: String, backend: String) -> Result<TokenCountStats, String> {
+    let hours = time_range_to_hours(&time_range);
+    let cutoff_ts = get_cutoff_timestamp(hours);
 
-    let mut stmt = conn
-        .prepare("SELECT DISTINCT COALESCE(model, 'unknown') FROM requests ORDER BY model")
-        .map_err(|e| e.to_string())?;
+    storage::shared()
+        .and_then(|store| store.token_count_stats(&cutoff_ts, backend_option(&backend)))
+        .map_err(|e| e.to_string())
+}
 
-    let models: Vec<String> = stmt
-        .query_map([], |row| row.get(0))
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+#[tauri::command]
+pub fn get_backends() -> Result<Vec<String>, String> {
+    storage::shared()
+        .and_then(|store| store.logged_backends())
+        .map_err(|e| e.to_string())
+}
 
-    Ok(models)
+#[tauri::command]
+pub fn get_models() -> Result<Vec<String>, String> {
+    storage::shared()
+        .and_then(|store| store.logged_models())
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -451,110 +372,15 @@ pub fn get_message_logs(
     search: String,
     page: i64,
 ) -> Result<PaginatedLogs, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    let hours = time_range_to_hours(&time_range);
-    let cutoff_ts = get_cutoff_timestamp(hours);
-
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND backend = '{}'", backend.replace('\'', "''"))
-    };
-
-    let model_filter = if model == "all" {
-        String::new()
-    } else {
-        format!(" AND COALESCE(model, 'unknown') = '{}'", model.replace('\'', "''"))
-    };
-
-    let dlp_filter = match dlp_action.as_str() {
-        "passed" => format!(" AND COALESCE(dlp_action, 0) = {}", DLP_ACTION_PASSED),
-        "redacted" => format!(" AND dlp_action = {}", DLP_ACTION_REDACTED),
-        "blocked" => format!(" AND dlp_action = {}", DLP_ACTION_BLOCKED),
-        "ratelimited" => format!(" AND dlp_action = {}", DLP_ACTION_RATELIMITED),
-        "notify-ratelimit" => format!(" AND dlp_action = {}", DLP_ACTION_NOTIFY_RATELIMIT),
-        _ => String::new(),
-    };
-
-    // Search filter - case-insensitive LIKE on request_body and response_body
-    let search_filter = if search.trim().is_empty() {
-        String::new()
-    } else {
-        let escaped_search = search.replace('\'', "''").replace('%', "\\%").replace('_', "\\_");
-        format!(
-            " AND (LOWER(request_body) LIKE LOWER('%{}%') ESCAPE '\\' OR LOWER(response_body) LIKE LOWER('%{}%') ESCAPE '\\')",
-            escaped_search, escaped_search
-        )
-    };
-
-    let filters = format!("{}{}{}{}", backend_filter, model_filter, dlp_filter, search_filter);
-
-    // Get total count
-    let total: i64 = conn
-        .query_row(
-            &format!(
-                "SELECT COUNT(*) FROM requests WHERE timestamp >= ?1{}",
-                filters
-            ),
-            [&cutoff_ts],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-
-    let offset = page * 10;
-
-    let mut stmt = conn
-        .prepare(&format!(
-            "SELECT id, timestamp, backend, COALESCE(model, 'unknown'),
-                    input_tokens, output_tokens, latency_ms, request_body, response_body,
-                    request_headers, response_headers, COALESCE(dlp_action, 0)
-             FROM requests
-             WHERE timestamp >= ?1{}
-             ORDER BY id DESC
-             LIMIT 10 OFFSET ?2",
-            filters
-        ))
-        .map_err(|e| e.to_string())?;
-
-    let logs: Vec<MessageLog> = stmt
-        .query_map(rusqlite::params![&cutoff_ts, offset], |row| {
-            Ok(MessageLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                backend: row.get(2)?,
-                model: row.get(3)?,
-                input_tokens: row.get(4)?,
-                output_tokens: row.get(5)?,
-                latency_ms: row.get(6)?,
-                request_body: row.get(7)?,
-                response_body: row.get(8)?,
-                request_headers: row.get(9)?,
-                response_headers: row.get(10)?,
-                dlp_action: row.get(11)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+    let filter = log_filter(&time_range, &backend, &model, &dlp_action, &search);
 
-    Ok(PaginatedLogs { logs, total })
-}
-
-#[derive(Serialize)]
-pub struct ExportLog {
-    pub id: i64,
-    pub timestamp: String,
-    pub backend: String,
-    pub model: String,
-    pub input_tokens: i64,
-    pub output_tokens: i64,
-    pub latency_ms: i64,
-    pub request_body: Option<String>,
-    pub response_body: Option<String>,
-    pub dlp_action: i64,
+    storage::shared()
+        .and_then(|store| store.message_logs(&filter, page, 10))
+        .map_err(|e| e.to_string())
 }
 
+/// Write the logs matching the Logs view's filters, with their detections, to `path`
+/// `format` is "jsonl" or "csv"; returns the number of requests written
 #[tauri::command]
 pub fn export_message_logs(
     time_range: String,
@@ -562,77 +388,25 @@ pub fn export_message_logs(
     model: String,
     dlp_action: String,
     search: String,
-) -> Result<Vec<ExportLog>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
-    let hours = time_range_to_hours(&time_range);
-    let cutoff_ts = get_cutoff_timestamp(hours);
-
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND backend = '{}'", backend.replace('\'', "''"))
-    };
-
-    let model_filter = if model == "all" {
-        String::new()
-    } else {
-        format!(" AND COALESCE(model, 'unknown') = '{}'", model.replace('\'', "''"))
-    };
-
-    let dlp_filter = match dlp_action.as_str() {
-        "passed" => format!(" AND COALESCE(dlp_action, 0) = {}", DLP_ACTION_PASSED),
-        "redacted" => format!(" AND dlp_action = {}", DLP_ACTION_REDACTED),
-        "blocked" => format!(" AND dlp_action = {}", DLP_ACTION_BLOCKED),
-        "ratelimited" => format!(" AND dlp_action = {}", DLP_ACTION_RATELIMITED),
-        "notify-ratelimit" => format!(" AND dlp_action = {}", DLP_ACTION_NOTIFY_RATELIMIT),
-        _ => String::new(),
-    };
+    format: String,
+    path: String,
+) -> Result<usize, String> {
+    use std::io::Write;
 
-    let search_filter = if search.trim().is_empty() {
-        String::new()
-    } else {
-        let escaped_search = search.replace('\'', "''").replace('%', "\\%").replace('_', "\\_");
-        format!(
-            " AND (LOWER(request_body) LIKE LOWER('%{}%') ESCAPE '\\' OR LOWER(response_body) LIKE LOWER('%{}%') ESCAPE '\\')",
-            escaped_search, escaped_search
-        )
-    };
+    let format = ExportFormat::parse(&format)?;
+    let filter = log_filter(&time_range, &backend, &model, &dlp_action, &search);
 
-    let filters = format!("{}{}{}{}", backend_filter, model_filter, dlp_filter, search_filter);
-
-    let mut stmt = conn
-        .prepare(&format!(
-            "SELECT id, timestamp, backend, COALESCE(model, 'unknown'),
-                    input_tokens, output_tokens, latency_ms, request_body, response_body,
-                    COALESCE(dlp_action, 0)
-             FROM requests
-             WHERE timestamp >= ?1{}
-             ORDER BY id DESC",
-            filters
-        ))
+    let logs = storage::shared()
+        .and_then(|store| store.export_logs(&filter))
         .map_err(|e| e.to_string())?;
 
-    let logs: Vec<ExportLog> = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(ExportLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                backend: row.get(2)?,
-                model: row.get(3)?,
-                input_tokens: row.get(4)?,
-                output_tokens: row.get(5)?,
-                latency_ms: row.get(6)?,
-                request_body: row.get(7)?,
-                response_body: row.get(8)?,
-                dlp_action: row.get(9)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut out = std::io::BufWriter::new(file);
+    write_logs(&logs, format, &mut out)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
 
-    Ok(logs)
+    Ok(logs.len())
 }
 
 #[tauri::command]
@@ -692,19 +466,148 @@ pub fn save_port_setting(port: u16) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn restart_proxy() -> Result<String, String> {
-    let port = *PROXY_PORT.lock().unwrap();
+pub fn get_proxy_body_limits() -> BodyLimits {
+    get_proxy_body_limits_from_db()
+}
 
-    // Send restart signal
-    let sender_guard = RESTART_SENDER.lock().unwrap();
-    if let Some(sender) = sender_guard.as_ref() {
-        sender.send(true).map_err(|e| e.to_string())?;
-        Ok(format!("Proxy server restarting on port {}", port))
-    } else {
-        Err("Proxy server not initialized".to_string())
+#[tauri::command]
+pub fn save_proxy_body_limits(limits: BodyLimits) -> Result<(), String> {
+    save_proxy_body_limits_to_db(&limits)
+}
+
+#[tauri::command]
+pub fn get_proxy_bind_addresses() -> BindAddresses {
+    get_proxy_bind_addresses_from_db()
+}
+
+/// Save the bind addresses and re-bind the gateway and dedicated backend listeners
+#[tauri::command]
+pub async fn save_proxy_bind_addresses(addresses: BindAddresses) -> Result<(), String> {
+    let addresses = BindAddresses {
+        gateway: addresses.gateway.trim().to_string(),
+        backend_listeners: addresses.backend_listeners.trim().to_string(),
+    };
+    addresses.validate()?;
+    save_proxy_bind_addresses_to_db(&addresses)?;
+
+    restart_proxy().await?;
+    restart_all_backend_listeners()
+}
+
+#[tauri::command]
+pub fn get_proxy_client_auth() -> ClientAuthSettings {
+    get_proxy_client_auth_from_db()
+}
+
+/// Save whether a client token is required; the token itself is kept (or generated)
+#[tauri::command]
+pub fn save_proxy_client_auth(require_token: bool, allow_loopback: bool) -> Result<ClientAuthSettings, String> {
+    client_auth::save_settings(ClientAuthSettings {
+        require_token,
+        allow_loopback,
+        ..get_proxy_client_auth_from_db()
+    })
+}
+
+/// Replace the client token; clients using the old one are rejected from the next request
+#[tauri::command]
+pub fn regenerate_proxy_client_token() -> Result<ClientAuthSettings, String> {
+    client_auth::save_settings(ClientAuthSettings {
+        token: new_client_token(),
+        ..get_proxy_client_auth_from_db()
+    })
+}
+
+#[tauri::command]
+pub fn get_response_cache_settings() -> ResponseCacheSettings {
+    get_response_cache_from_db()
+}
+
+/// Save the response cache settings; turning the cache off also empties it
+#[tauri::command]
+pub fn save_response_cache_settings(settings: ResponseCacheSettings) -> Result<(), String> {
+    response_cache::save_settings(settings)
+}
+
+#[tauri::command]
+pub fn clear_response_cache() -> Result<(), String> {
+    response_cache::clear()
+}
+
+#[tauri::command]
+pub fn get_upstream_proxy_settings() -> UpstreamProxyStatus {
+    upstream_proxy::status()
+}
+
+#[tauri::command]
+pub fn get_retention_settings() -> RetentionSettings {
+    get_retention_from_db()
+}
+
+/// Save the retention policy and apply it right away (without compacting the database)
+#[tauri::command]
+pub async fn save_retention_settings(settings: RetentionSettings) -> Result<RetentionReport, String> {
+    settings.validate()?;
+    save_retention_to_db(&settings)?;
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || retention::enforce(&*store, &settings, false))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Apply the retention policy now and compact the database, reporting the space reclaimed
+#[tauri::command]
+pub async fn run_retention_cleanup() -> Result<RetentionReport, String> {
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || retention::enforce(&*store, &get_retention_from_db(), true))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_database_encryption_status() -> EncryptionStatus {
+    db_encryption::status()
+}
+
+/// Turn on database encryption; the database is encrypted the next time the app starts
+#[tauri::command]
+pub fn enable_database_encryption() -> Result<EncryptionStatus, String> {
+    db_encryption::enable()
+}
+
+/// Report rows left behind by deleted requests; with `repair`, delete them and reset inflated
+/// AUTOINCREMENT counters
+#[tauri::command]
+pub async fn check_database_orphans(repair: bool) -> Result<RepairReport, String> {
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    tokio::task::spawn_blocking(move || retention::check_orphans(&*store, repair))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Save the corporate proxy settings; a missing password keeps the stored one, an empty one removes it
+#[tauri::command]
+pub fn save_upstream_proxy_settings(settings: UpstreamProxySettings, password: Option<String>) -> Result<(), String> {
+    upstream_proxy::save_settings(settings, password)
+}
+
+/// Restart the gateway and wait until it listens again; a bind failure (e.g. port in use) is returned
+#[tauri::command]
+pub async fn restart_proxy() -> Result<String, String> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    {
+        let sender_guard = RESTART_SENDER.lock().unwrap();
+        let sender = sender_guard.as_ref().ok_or_else(|| "Proxy server not initialized".to_string())?;
+        sender.send(reply_tx).map_err(|e| e.to_string())?;
     }
+
+    let port = reply_rx.await.map_err(|_| "Proxy server stopped".to_string())??;
+    Ok(format!("Proxy server running on port {}", port))
 }
 
+/// Env var Claude Code reads extra request headers from ("Name: value" lines)
+const CLAUDE_CUSTOM_HEADERS_ENV: &str = "ANTHROPIC_CUSTOM_HEADERS";
+
 // Get env var name and route for a given tool
 fn get_tool_env_config(tool: &str) -> Result<(&'static str, &'static str), String> {
     match tool {
@@ -743,10 +646,23 @@ pub fn set_shell_env(shell: String, tool: String) -> Result<String, String> {
         return set_codex_function(shell, &base_url);
     }
 
-    match shell.as_str() {
+    let message = set_env_var(&shell, env_var, &base_url)?;
+
+    // With client tokens required, Claude Code sends the token as a custom header
+    let auth = get_proxy_client_auth_from_db();
+    if tool == "claude-code" && auth.require_token {
+        let header = format!("{}: {}", CLIENT_TOKEN_HEADER, auth.token);
+        set_env_var(&shell, CLAUDE_CUSTOM_HEADERS_ENV, &header)?;
+    }
+    Ok(message)
+}
+
+// Set an env var in the shell's config
+fn set_env_var(shell: &str, env_var: &str, value: &str) -> Result<String, String> {
+    match shell {
         "fish" => {
             // Fish: use set -Ux for universal export (persists automatically)
-            let manual_cmd = format!("set -Ux {} \"{}\"", env_var, base_url);
+            let manual_cmd = format!("set -Ux {} \"{}\"", env_var, value);
 
             let fish_path = match find_fish_binary() {
                 Some(path) => path,
@@ -777,7 +693,7 @@ pub fn set_shell_env(shell: String, tool: String) -> Result<String, String> {
             // Bash: append to ~/.bashrc
             let home = std::env::var("HOME").map_err(|_| "Could not get HOME directory")?;
             let bashrc_path = format!("{}/.bashrc", home);
-            let export_line = format!("export {}=\"{}\"", env_var, base_url);
+            let export_line = format!("export {}=\"{}\"", env_var, value);
 
             match update_shell_config(&bashrc_path, &export_line, env_var) {
                 Ok(_) => Ok(format!("{} added to ~/.bashrc. Run 'source ~/.bashrc' or restart your terminal.", env_var)),
@@ -791,7 +707,7 @@ pub fn set_shell_env(shell: String, tool: String) -> Result<String, String> {
             // Zsh: append to ~/.zshrc
             let home = std::env::var("HOME").map_err(|_| "Could not get HOME directory")?;
             let zshrc_path = format!("{}/.zshrc", home);
-            let export_line = format!("export {}=\"{}\"", env_var, base_url);
+            let export_line = format!("export {}=\"{}\"", env_var, value);
 
             match update_shell_config(&zshrc_path, &export_line, env_var) {
                 Ok(_) => Ok(format!("{} added to ~/.zshrc. Run 'source ~/.zshrc' or restart your terminal.", env_var)),
@@ -991,7 +907,18 @@ pub fn remove_shell_env(shell: String, tool: String) -> Result<String, String> {
         return remove_codex_function(shell);
     }
 
-    match shell.as_str() {
+    let message = remove_env_var(&shell, env_var)?;
+
+    // Drop the client token header set alongside the base URL
+    if tool == "claude-code" && get_proxy_client_auth_from_db().require_token {
+        remove_env_var(&shell, CLAUDE_CUSTOM_HEADERS_ENV)?;
+    }
+    Ok(message)
+}
+
+// Remove an env var from the shell's config
+fn remove_env_var(shell: &str, env_var: &str) -> Result<String, String> {
+    match shell {
         "fish" => {
             // Fish: erase universal variable
             let manual_cmd = format!("set -Ue {}", env_var);
@@ -1244,71 +1171,94 @@ pub struct ToolCallStats {
 
 #[tauri::command]
 pub fn get_tool_calls_for_request(request_id: i64) -> Result<Vec<ToolCallRecord>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
+    storage::shared()
+        .and_then(|store| store.tool_calls_for_request(request_id))
+        .map_err(|e| e.to_string())
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, request_id, tool_call_id, tool_name, tool_input
-             FROM tool_calls WHERE request_id = ?1 ORDER BY id ASC",
-        )
-        .map_err(|e| e.to_string())?;
+/// A logged request's bodies, loaded when the log is opened
+#[derive(Serialize)]
+pub struct RequestBodies {
+    pub request_body: Option<String>,
+    pub response_body: Option<String>,
+}
 
-    let tool_calls: Vec<ToolCallRecord> = stmt
-        .query_map([request_id], |row| {
-            Ok(ToolCallRecord {
-                id: row.get(0)?,
-                request_id: row.get(1)?,
-                tool_call_id: row.get(2)?,
-                tool_name: row.get(3)?,
-                tool_input: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+/// Bodies of a logged request; both None when they weren't stored
+#[tauri::command]
+pub fn get_request_bodies(request_id: i64) -> Result<RequestBodies, String> {
+    storage::shared()
+        .and_then(|store| store.request_bodies(request_id))
+        .map_err(|e| e.to_string())
+}
 
-    Ok(tool_calls)
+// ========================================================================
+// Conversation Commands
+// ========================================================================
+
+#[derive(Serialize)]
+pub struct ConversationSummary {
+    pub id: i64,
+    pub conversation_key: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub request_count: i64,
+    /// Backends (proxied APIs and hooks) the conversation went through
+    pub backends: Vec<String>,
 }
 
-#[tauri::command]
-pub fn get_tool_call_stats(time_range: String, backend: String) -> Result<Vec<ToolCallStats>, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
+/// A request in a conversation's timeline
+#[derive(Serialize)]
+pub struct ConversationEvent {
+    pub request_id: i64,
+    pub timestamp: String,
+    pub backend: String,
+    /// "proxy" or "hook"
+    pub source: String,
+    /// Hook event (e.g. beforeSubmitPrompt) for hook requests
+    pub hook_event: Option<String>,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub dlp_action: i64,
+    pub request_body: Option<String>,
+    pub response_body: Option<String>,
+    pub tool_calls: Vec<ToolCallRecord>,
+}
 
+#[derive(Serialize)]
+pub struct ConversationTimeline {
+    pub conversation: ConversationSummary,
+    pub events: Vec<ConversationEvent>,
+}
+
+/// Conversations active in the time range, most recent first
+#[tauri::command]
+pub fn get_conversations(time_range: String) -> Result<Vec<ConversationSummary>, String> {
     let hours = time_range_to_hours(&time_range);
     let cutoff_ts = get_cutoff_timestamp(hours);
 
-    // Build backend filter clause
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND r.backend = '{}'", backend.replace('\'', "''"))
-    };
-
-    let mut stmt = conn
-        .prepare(&format!(
-            "SELECT tc.tool_name, COUNT(*) as count
-             FROM tool_calls tc
-             JOIN requests r ON tc.request_id = r.id
-             WHERE r.timestamp >= ?1{}
-             GROUP BY tc.tool_name
-             ORDER BY count DESC
-             LIMIT 20",
-            backend_filter
-        ))
-        .map_err(|e| e.to_string())?;
+    storage::shared()
+        .and_then(|store| store.conversations(&cutoff_ts))
+        .map_err(|e| e.to_string())
+}
 
-    let stats: Vec<ToolCallStats> = stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok(ToolCallStats {
-                tool_name: row.get(0)?,
-                count: row.get(1)?,
-            })
-        })
+/// A conversation's requests in order, from the proxy and from hooks, with their tool calls
+#[tauri::command]
+pub fn get_conversation_timeline(conversation_id: i64) -> Result<ConversationTimeline, String> {
+    storage::shared()
+        .and_then(|store| store.conversation_timeline(conversation_id))
         .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
+        .ok_or_else(|| format!("Conversation {} not found", conversation_id))
+}
 
-    Ok(stats)
+#[tauri::command]
+pub fn get_tool_call_stats(time_range: String, backend: String) -> Result<Vec<ToolCallStats>, String> {
+    let hours = time_range_to_hours(&time_range);
+    let cutoff_ts = get_cutoff_timestamp(hours);
+
+    storage::shared()
+        .and_then(|store| store.tool_call_counts(&cutoff_ts, backend_option(&backend)))
+        .map_err(|e| e.to_string())
 }
 
 #[derive(Serialize)]
@@ -1362,66 +1312,15 @@ fn extract_target(tool_name: &str, tool_input: &str) -> Option<String> {
 
 #[tauri::command]
 pub fn get_tool_call_insights(time_range: String, backend: String) -> Result<ToolInsights, String> {
-    let conn = open_connection().map_err(|e| e.to_string())?;
-
     let hours = time_range_to_hours(&time_range);
     let cutoff_ts = get_cutoff_timestamp(hours);
 
-    let backend_filter = if backend == "all" {
-        String::new()
-    } else {
-        format!(" AND r.backend = '{}'", backend.replace('\'', "''"))
-    };
-
-    println!("[STATS] get_tool_call_insights: time_range={}, backend={}, cutoff_ts={}", time_range, backend, cutoff_ts);
-
-    // Debug: check tool_calls table
-    let tc_count: i64 = conn.query_row("SELECT COUNT(*) FROM tool_calls", [], |row| row.get(0)).unwrap_or(0);
-    println!("[STATS] Total tool_calls in DB: {}", tc_count);
-
-    // Debug: check recent requests with tool calls
-    if let Ok(mut debug_stmt) = conn.prepare(
-        "SELECT r.id, r.timestamp, r.backend, tc.tool_name
-         FROM tool_calls tc
-         JOIN requests r ON tc.request_id = r.id
-         ORDER BY r.id DESC LIMIT 5"
-    ) {
-        let debug_rows: Vec<(i64, String, String, String)> = debug_stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
-            .unwrap()
-            .filter_map(|r| r.ok())
-            .collect();
-        for (id, ts, backend, tool) in debug_rows {
-            println!("[STATS] Recent tool call: request_id={}, timestamp={}, backend={}, tool={}", id, ts, backend, tool);
-        }
-    }
-
     // Get raw tool calls
-    let query = format!(
-        "SELECT tc.tool_name, tc.tool_input
-         FROM tool_calls tc
-         JOIN requests r ON tc.request_id = r.id
-         WHERE r.timestamp >= ?1{}",
-        backend_filter
-    );
-    println!("[STATS] Query: {}", query);
-
-    let mut calls_stmt = conn
-        .prepare(&query)
+    let calls = storage::shared()
+        .and_then(|store| store.tool_call_inputs(&cutoff_ts, backend_option(&backend)))
         .map_err(|e| e.to_string())?;
 
-    let calls: Vec<(String, String)> = calls_stmt
-        .query_map([&cutoff_ts], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        })
-        .map_err(|e| e.to_string())?
-        .filter_map(|r| r.ok())
-        .collect();
-
-    println!("[STATS] Found {} tool calls", calls.len());
-
     // Count tools and targets
-    use std::collections::HashMap;
     let mut tool_counts: HashMap<String, i64> = HashMap::new();
     let mut target_counts: HashMap<String, HashMap<String, i64>> = HashMap::new();
 
@@ -0,0 +1,73 @@
+// Content Encodings
+//
+// Clients and upstreams may compress bodies with gzip, deflate, brotli or zstd. Bodies are decoded
+// before they are scanned, logged or rewritten, and go on uncompressed. Stacked encodings are undone
+// in reverse order ("gzip, br" is brotli over gzip). Decoding stops at the body size limit, so a
+// small compressed body can't expand without bound.
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// Decode a body sent with the given Content-Encoding, or None for an unknown encoding, corrupt
+/// data, or a body that decodes to more than `max_bytes`
+pub fn decode(content_encoding: &str, data: &[u8], max_bytes: usize) -> Option<Vec<u8>> {
+    let mut decoded = data.to_vec();
+    for encoding in content_encoding.split(',').rev() {
+        decoded = match encoding.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => decoded,
+            "gzip" | "x-gzip" => read_capped(GzDecoder::new(decoded.as_slice()), max_bytes)?,
+            "deflate" => read_capped(ZlibDecoder::new(decoded.as_slice()), max_bytes)?,
+            "br" => read_capped(brotli::Decompressor::new(decoded.as_slice(), 4096), max_bytes)?,
+            "zstd" => read_capped(zstd::stream::read::Decoder::new(decoded.as_slice()).ok()?, max_bytes)?,
+            _ => return None,
+        };
+    }
+    Some(decoded)
+}
+
+fn read_capped(reader: impl Read, max_bytes: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.take((max_bytes as u64).saturating_add(1)).read_to_end(&mut out).ok()?;
+    (out.len() <= max_bytes).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const BODY: &[u8] = br#"{"model":"claude-sonnet-4","messages":[{"role":"user","content":"hello"}]}"#;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        brotli::CompressorReader::new(data, 4096, 5, 22).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_decode_each_encoding() {
+        assert_eq!(decode("gzip", &gzip(BODY), 1024).unwrap(), BODY);
+        assert_eq!(decode("br", &brotli(BODY), 1024).unwrap(), BODY);
+        assert_eq!(decode("zstd", &zstd::encode_all(BODY, 3).unwrap(), 1024).unwrap(), BODY);
+        assert_eq!(decode("identity", BODY, 1024).unwrap(), BODY);
+        assert!(decode("compress", BODY, 1024).is_none());
+        assert!(decode("br", BODY, 1024).is_none());
+    }
+
+    #[test]
+    fn test_decode_stacked_and_capped() {
+        assert_eq!(decode("gzip, br", &brotli(&gzip(BODY)), 1024).unwrap(), BODY);
+
+        let large = vec![b'a'; 10_000];
+        assert!(decode("zstd", &zstd::encode_all(large.as_slice(), 3).unwrap(), 1024).is_none());
+        assert_eq!(decode("zstd", &zstd::encode_all(large.as_slice(), 3).unwrap(), usize::MAX).unwrap(), large);
+    }
+}
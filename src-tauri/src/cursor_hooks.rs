@@ -6,9 +6,20 @@
 //        afterAgentResponse, afterAgentThought, afterTabFileEdit
 
 use crate::backends::custom::CustomBackendSettings;
-use crate::database::{Database, DLP_ACTION_BLOCKED, DLP_ACTION_PASSED, DLP_ACTION_RATELIMITED};
-use crate::dlp::{check_dlp_patterns, DlpDetection};
+use crate::backends::registry::CURSOR_HOOKS_KIND;
+use crate::budgets::check_budgets;
+use crate::database::{
+    get_dlp_detect_injection_from_db, get_dlp_scan_limits_from_db, Database, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED,
+    DLP_ACTION_SCAN_SKIPPED,
+};
+use crate::dlp::{
+    check_dlp_path, check_dlp_patterns, dlp_action_for, should_block_without_redaction, DlpCheckResult, DlpDetection,
+    DlpTarget,
+};
+use crate::document_text::read_attachment_text;
+use crate::prompt_injection::find_prompt_injections;
 use crate::proxy::RateLimiter;
+use crate::storage::RequestLog;
 use axum::{
     extract::State,
     http::StatusCode,
@@ -292,6 +303,26 @@ fn estimate_tokens(text: &str) -> i32 {
     (count_words(text) as f32 * 1.5) as i32
 }
 
+/// DLP policy target for a hook request from `model`
+fn dlp_target(model: &str) -> DlpTarget<'_> {
+    DlpTarget {
+        backend: CURSOR_HOOKS_KIND,
+        model: Some(model),
+    }
+}
+
+/// Action logged for a hook. A text left (partly) unscanned by a scan limit is logged as such
+/// when nothing was found in what was scanned, as for proxied requests.
+fn hook_dlp_action(is_blocked: bool, dlp_result: &DlpCheckResult) -> i32 {
+    if is_blocked {
+        DLP_ACTION_BLOCKED
+    } else if dlp_result.scan_skipped.is_some() && dlp_result.detections.is_empty() {
+        DLP_ACTION_SCAN_SKIPPED
+    } else {
+        dlp_action_for(&dlp_result.detections)
+    }
+}
+
 /// Format detected entities for user message
 fn format_detection_message(detections: &[DlpDetection]) -> String {
     let mut message = String::from("Blocked: Sensitive data detected:\n");
@@ -383,16 +414,23 @@ async fn before_submit_prompt_handler(
         input.attachments.len()
     );
 
+    // Read attached files once; PDF and Office documents are extracted to text
+    let max_bytes = get_dlp_scan_limits_from_db().max_body_bytes;
+    let attachment_texts: Vec<(&str, Result<String, String>)> = input
+        .attachments
+        .iter()
+        .filter_map(|attachment| match (&attachment.file_path, &attachment.attachment_type) {
+            (Some(file_path), Some(att_type)) if att_type == "file" => {
+                Some((file_path.as_str(), read_attachment_text(file_path, max_bytes)))
+            }
+            _ => None,
+        })
+        .collect();
+
     // Calculate total token count first
     let mut total_token_count = estimate_tokens(&input.prompt);
-    for attachment in &input.attachments {
-        if let (Some(file_path), Some(att_type)) = (&attachment.file_path, &attachment.attachment_type) {
-            if att_type == "file" {
-                if let Ok(content) = std::fs::read_to_string(file_path) {
-                    total_token_count += estimate_tokens(&content);
-                }
-            }
-        }
+    for content in attachment_texts.iter().filter_map(|(_, content)| content.as_ref().ok()) {
+        total_token_count += estimate_tokens(content);
     }
 
     // Serialize full input for request_body (before moving fields)
@@ -475,44 +513,81 @@ async fn before_submit_prompt_handler(
         return (StatusCode::OK, Json(response));
     }
 
+    // Check token budgets (global, cursor-hooks and this user's)
+    match check_budgets("cursor-hooks", input.user_email.as_deref()) {
+        Ok(warnings) => {
+            for warning in warnings {
+                println!("[CURSOR_HOOK] {}", warning.message());
+            }
+        }
+        Err(budget) => {
+            println!(
+                "[CURSOR_HOOK] Budget exhausted for generation_id: {}: {}",
+                input.generation_id,
+                budget.message()
+            );
+            let response = BeforeSubmitPromptResponse {
+                should_continue: false,
+                user_message: Some(budget.message()),
+            };
+            let response_body_json = serde_json::to_string(&response).unwrap_or_default();
+
+            let _ = state.db.log_cursor_hook_request(
+                &input.generation_id,
+                "CursorChat",
+                &input.model,
+                total_token_count,
+                0,
+                &request_body_json,
+                &response_body_json,
+                429,
+                metadata_json.as_deref(),
+                None,
+                None,
+                DLP_ACTION_RATELIMITED,
+            );
+
+            return (StatusCode::OK, Json(response));
+        }
+    }
+
     // Check DLP patterns (only if DLP is enabled)
-    let mut all_detections: Vec<DlpDetection> = Vec::new();
+    let mut dlp_result = DlpCheckResult::default();
     if state.settings.dlp_enabled {
-        all_detections = check_dlp_patterns(&input.prompt);
-
-        // Also check attached files
-        for attachment in &input.attachments {
-            if let (Some(file_path), Some(att_type)) = (&attachment.file_path, &attachment.attachment_type) {
-                if att_type == "file" {
-                    match std::fs::read_to_string(file_path) {
-                        Ok(content) => {
-                            let file_detections = check_dlp_patterns(&content);
-                            if !file_detections.is_empty() {
-                                println!(
-                                    "[CURSOR_HOOK] DLP detected in attached file: {}",
-                                    file_path
-                                );
-                                all_detections.extend(file_detections);
-                            }
-                        }
-                        Err(e) => {
-                            println!(
-                                "[CURSOR_HOOK] Error reading attached file {}: {}",
-                                file_path, e
-                            );
-                        }
+        dlp_result = check_dlp_patterns(&input.prompt, dlp_target(&input.model));
+
+        // Also check attached files, by path and by content
+        for (file_path, content) in &attachment_texts {
+            dlp_result.detections.extend(check_dlp_path(file_path, dlp_target(&input.model)));
+            match content {
+                Ok(content) => {
+                    let file_result = check_dlp_patterns(content, dlp_target(&input.model));
+                    if !file_result.detections.is_empty() {
+                        println!(
+                            "[CURSOR_HOOK] DLP detected in attached file: {}",
+                            file_path
+                        );
                     }
+                    dlp_result.merge(file_result);
+                }
+                Err(e) => {
+                    println!(
+                        "[CURSOR_HOOK] Error reading attached file {}: {}",
+                        file_path, e
+                    );
                 }
             }
         }
     }
 
-    let is_blocked = !all_detections.is_empty();
+    // Hooks can only allow or deny, so "redact" patterns block here; "alert" patterns pass
+    let all_detections = &dlp_result.detections;
+    let is_blocked = should_block_without_redaction(all_detections);
 
     // Create or update request entry
     let response_status = if is_blocked { 403 } else { 200 };
     let user_message = if is_blocked {
-        Some(format_detection_message(&all_detections))
+        Some(format_detection_message(all_detections))
     } else {
         None
     };
@@ -525,7 +600,7 @@ async fn before_submit_prompt_handler(
     let response_body_json = serde_json::to_string(&response).unwrap_or_default();
 
     // Log to database
-    let dlp_action = if is_blocked { DLP_ACTION_BLOCKED } else { DLP_ACTION_PASSED };
+    let dlp_action = hook_dlp_action(is_blocked, &dlp_result);
     match state.db.log_cursor_hook_request(
         &input.generation_id,
         "CursorChat",
@@ -544,7 +619,7 @@ async fn before_submit_prompt_handler(
             println!("[CURSOR_HOOK] before_submit_prompt - logged request_id: {}", request_id);
             // Log DLP detections if any
             if !all_detections.is_empty() {
-                let _ = state.db.log_dlp_detections(request_id, &all_detections);
+                let _ = state.db.log_dlp_detections(request_id, all_detections);
             }
         }
         Err(e) => {
@@ -573,8 +648,8 @@ async fn before_read_file_handler(
     let content = match &input.content {
         Some(c) => c.clone(),
         None => {
-            // Read file from disk
-            match std::fs::read_to_string(&input.file_path) {
+            // Read file from disk (PDF and Office documents are extracted to text)
+            match read_attachment_text(&input.file_path, get_dlp_scan_limits_from_db().max_body_bytes) {
                 Ok(c) => c,
                 Err(e) => {
                     println!(
@@ -677,25 +752,37 @@ async fn before_read_file_handler(
     }
 
     // Check DLP patterns (only if DLP is enabled)
-    let mut all_detections: Vec<DlpDetection> = Vec::new();
+    let mut dlp_result = DlpCheckResult::default();
     if state.settings.dlp_enabled {
-        all_detections = check_dlp_patterns(&content);
+        // Path-scope patterns block credential files by name, whatever their content
+        dlp_result.detections = check_dlp_path(&input.file_path, dlp_target(&input.model));
+        dlp_result.merge(check_dlp_patterns(&content, dlp_target(&input.model)));
+
+        // File contents are third-party text; flag instructions planted in them
+        let detect_injection = get_dlp_detect_injection_from_db();
+        if detect_injection {
+            dlp_result.detections.extend(find_prompt_injections(&content));
+        }
 
         // Also check attached files if present
         if let Some(attachments) = &input.attachments {
             for attachment in attachments {
                 if let (Some(file_path), Some(att_type)) = (&attachment.file_path, &attachment.attachment_type) {
                     if att_type == "file" {
-                        match std::fs::read_to_string(file_path) {
+                        dlp_result.detections.extend(check_dlp_path(file_path, dlp_target(&input.model)));
+                        match read_attachment_text(file_path, get_dlp_scan_limits_from_db().max_body_bytes) {
                             Ok(att_content) => {
-                                let file_detections = check_dlp_patterns(&att_content);
-                                if !file_detections.is_empty() {
+                                let mut file_result = check_dlp_patterns(&att_content, dlp_target(&input.model));
+                                if detect_injection {
+                                    file_result.detections.extend(find_prompt_injections(&att_content));
+                                }
+                                if !file_result.detections.is_empty() {
                                     println!(
                                         "[CURSOR_HOOK] DLP detected in attached file: {}",
                                         file_path
                                     );
-                                    all_detections.extend(file_detections);
                                 }
+                                dlp_result.merge(file_result);
                             }
                             Err(e) => {
                                 println!(
@@ -710,10 +797,11 @@ async fn before_read_file_handler(
         }
     }
 
-    let is_blocked = !all_detections.is_empty();
+    let all_detections = &dlp_result.detections;
+    let is_blocked = should_block_without_redaction(all_detections);
 
     let (permission, user_message, agent_message) = if is_blocked {
-        let msg = format_detection_message(&all_detections);
+        let msg = format_detection_message(all_detections);
         (
             "deny".to_string(),
             Some(msg.clone()),
@@ -736,7 +824,7 @@ async fn before_read_file_handler(
 
     // Log to database
     let response_status = if is_blocked { 403 } else { 200 };
-    let dlp_action = if is_blocked { DLP_ACTION_BLOCKED } else { DLP_ACTION_PASSED };
+    let dlp_action = hook_dlp_action(is_blocked, &dlp_result);
 
     if let Ok(request_id) = state.db.log_cursor_hook_request(
         &input.generation_id,
@@ -753,7 +841,7 @@ async fn before_read_file_handler(
         dlp_action,
     ) {
         if !all_detections.is_empty() {
-            let _ = state.db.log_dlp_detections(request_id, &all_detections);
+            let _ = state.db.log_dlp_detections(request_id, all_detections);
         }
     }
 
@@ -800,12 +888,18 @@ async fn before_tab_file_read_handler(
 
     // Check DLP patterns (only if DLP is enabled)
     // NOTE: before_tab_file_read is NOT rate limited
-    let detections = if state.settings.dlp_enabled {
-        check_dlp_patterns(&content)
+    let dlp_result = if state.settings.dlp_enabled {
+        let mut dlp_result = DlpCheckResult {
+            detections: check_dlp_path(&input.file_path, dlp_target(&input.model)),
+            scan_skipped: None,
+        };
+        dlp_result.merge(check_dlp_patterns(&content, dlp_target(&input.model)));
+        dlp_result
     } else {
-        Vec::new()
+        DlpCheckResult::default()
     };
-    let is_blocked = !detections.is_empty();
+    let detections = &dlp_result.detections;
+    let is_blocked = should_block_without_redaction(detections);
 
     // Build extra metadata
     let metadata = CursorHookMetadata {
@@ -829,7 +923,7 @@ async fn before_tab_file_read_handler(
     // Log to database
     let token_count = estimate_tokens(&content);
     let response_status = if is_blocked { 403 } else { 200 };
-    let dlp_action = if is_blocked { DLP_ACTION_BLOCKED } else { DLP_ACTION_PASSED };
+    let dlp_action = hook_dlp_action(is_blocked, &dlp_result);
 
     if let Ok(request_id) = state.db.log_cursor_hook_request(
         &input.generation_id,
@@ -846,7 +940,7 @@ async fn before_tab_file_read_handler(
         dlp_action,
     ) {
         if !detections.is_empty() {
-            let _ = state.db.log_dlp_detections(request_id, &detections);
+            let _ = state.db.log_dlp_detections(request_id, detections);
         }
     }
 
@@ -1006,15 +1100,16 @@ async fn before_shell_execution_handler(
     let token_count = estimate_tokens(&input.command);
 
     // Check DLP patterns on command (only if DLP is enabled)
-    let detections = if state.settings.dlp_enabled {
-        check_dlp_patterns(&input.command)
+    let dlp_result = if state.settings.dlp_enabled {
+        check_dlp_patterns(&input.command, dlp_target(&input.model))
     } else {
-        Vec::new()
+        DlpCheckResult::default()
     };
-    let is_blocked = !detections.is_empty();
+    let detections = &dlp_result.detections;
+    let is_blocked = should_block_without_redaction(detections);
 
     let (permission, user_message, agent_message) = if is_blocked {
-        let msg = format_detection_message(&detections);
+        let msg = format_detection_message(detections);
         (
             "deny".to_string(),
             Some(msg.clone()),
@@ -1036,7 +1131,7 @@ async fn before_shell_execution_handler(
 
     // Log to database
     let response_status = if is_blocked { 403 } else { 200 };
-    let dlp_action = if is_blocked { DLP_ACTION_BLOCKED } else { DLP_ACTION_PASSED };
+    let dlp_action = hook_dlp_action(is_blocked, &dlp_result);
 
     if let Ok(request_id) = state.db.log_cursor_hook_request(
         &input.generation_id,
@@ -1056,7 +1151,7 @@ async fn before_shell_execution_handler(
 
         // Log DLP detections if any
         if !detections.is_empty() {
-            let _ = state.db.log_dlp_detections(request_id, &detections);
+            let _ = state.db.log_dlp_detections(request_id, detections);
             println!("[CURSOR_HOOK] before_shell_execution - logged {} DLP detections", detections.len());
         }
 
@@ -1120,15 +1215,16 @@ async fn before_mcp_execution_handler(
     let token_count = estimate_tokens(&args_str) + estimate_tokens(&input.tool_name);
 
     // Check DLP patterns on arguments (only if DLP is enabled)
-    let detections = if state.settings.dlp_enabled {
-        check_dlp_patterns(&args_str)
+    let dlp_result = if state.settings.dlp_enabled {
+        check_dlp_patterns(&args_str, dlp_target(&input.model))
     } else {
-        Vec::new()
+        DlpCheckResult::default()
     };
-    let is_blocked = !detections.is_empty();
+    let detections = &dlp_result.detections;
+    let is_blocked = should_block_without_redaction(detections);
 
     let (permission, user_message, agent_message) = if is_blocked {
-        let msg = format_detection_message(&detections);
+        let msg = format_detection_message(detections);
         (
             "deny".to_string(),
             Some(msg.clone()),
@@ -1151,7 +1247,7 @@ async fn before_mcp_execution_handler(
 
     // Log to database
     let response_status = if is_blocked { 403 } else { 200 };
-    let dlp_action = if is_blocked { DLP_ACTION_BLOCKED } else { DLP_ACTION_PASSED };
+    let dlp_action = hook_dlp_action(is_blocked, &dlp_result);
 
     if let Ok(request_id) = state.db.log_cursor_hook_request(
         &input.generation_id,
@@ -1171,7 +1267,7 @@ async fn before_mcp_execution_handler(
 
         // Log DLP detections if any
         if !detections.is_empty() {
-            let _ = state.db.log_dlp_detections(request_id, &detections);
+            let _ = state.db.log_dlp_detections(request_id, detections);
             println!("[CURSOR_HOOK] before_mcp_execution - logged {} DLP detections", detections.len());
         }
 
@@ -1217,3 +1313,19 @@ pub fn create_cursor_hooks_router(
         .route("/after_tab_file_edit", post(after_tab_file_edit_handler))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_dlp_action_logs_skipped_scans() {
+        let skipped = DlpCheckResult {
+            detections: Vec::new(),
+            scan_skipped: Some("text of 20 bytes exceeds the 10 byte limit".to_string()),
+        };
+        assert_eq!(hook_dlp_action(false, &skipped), DLP_ACTION_SCAN_SKIPPED);
+        assert_eq!(hook_dlp_action(true, &skipped), DLP_ACTION_BLOCKED);
+        assert_eq!(hook_dlp_action(false, &DlpCheckResult::default()), dlp_action_for(&[]));
+    }
+}
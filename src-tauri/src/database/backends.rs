@@ -0,0 +1,235 @@
+// Backends and the records of their stored API keys (the keys themselves are in the keychain)
+
+use super::{BackendRecord, Database};
+use crate::storage::{ApiKeyRecord, BackendStore, StorageResult};
+
+impl Database {
+    const BACKEND_COLUMNS: &'static str =
+        "id, name, kind, base_url, path_prefix, settings, enabled, is_builtin, created_at";
+
+    fn row_to_backend(row: &rusqlite::Row) -> Result<BackendRecord, rusqlite::Error> {
+        Ok(BackendRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            kind: row.get(2)?,
+            base_url: row.get(3)?,
+            path_prefix: row.get(4)?,
+            settings: row.get::<_, Option<String>>(5)?.unwrap_or_else(|| "{}".to_string()),
+            enabled: row.get::<_, i32>(6)? == 1,
+            is_builtin: row.get::<_, i32>(7)? == 1,
+            created_at: row.get(8)?,
+        })
+    }
+
+    fn query_backends(&self, where_clause: &str) -> Result<Vec<BackendRecord>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM backends {}",
+            Self::BACKEND_COLUMNS,
+            where_clause
+        ))?;
+
+        let backends = stmt
+            .query_map([], Self::row_to_backend)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(backends)
+    }
+}
+
+impl BackendStore for Database {
+    fn get_backends(&self) -> StorageResult<Vec<BackendRecord>> {
+        Ok(self.query_backends("ORDER BY is_builtin DESC, created_at DESC")?)
+    }
+
+    fn get_enabled_backends(&self) -> StorageResult<Vec<BackendRecord>> {
+        Ok(self.query_backends("WHERE enabled = 1 ORDER BY is_builtin DESC, created_at DESC")?)
+    }
+
+    fn get_backend_by_name(&self, name: &str) -> StorageResult<Option<BackendRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.query_row(
+            &format!("SELECT {} FROM backends WHERE name = ?1", Self::BACKEND_COLUMNS),
+            rusqlite::params![name],
+            Self::row_to_backend,
+        );
+
+        match result {
+            Ok(record) => Ok(Some(record)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_custom_backends(&self) -> StorageResult<Vec<BackendRecord>> {
+        Ok(self.query_backends("WHERE is_builtin = 0 ORDER BY created_at DESC")?)
+    }
+
+    fn add_backend(
+        &self,
+        name: &str,
+        kind: &str,
+        base_url: &str,
+        path_prefix: &str,
+        settings: &str,
+    ) -> StorageResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let created_at = chrono::Utc::now().to_rfc3339();
+
+        conn.execute(
+            "INSERT INTO backends (name, kind, base_url, path_prefix, settings, enabled, is_builtin, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, 0, ?6)",
+            rusqlite::params![name, kind, base_url, path_prefix, settings, created_at],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn update_backend(
+        &self,
+        id: i64,
+        name: &str,
+        base_url: &str,
+        path_prefix: &str,
+        settings: &str,
+    ) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+
+        // Stored API keys belong to the backend by name, so they follow a rename
+        tx.execute(
+            "UPDATE api_keys SET backend = ?1
+             WHERE backend = (SELECT name FROM backends WHERE id = ?2 AND is_builtin = 0)",
+            rusqlite::params![name, id],
+        )?;
+        tx.execute(
+            "UPDATE backends SET name = ?1, base_url = ?2, path_prefix = ?3, settings = ?4
+             WHERE id = ?5 AND is_builtin = 0",
+            rusqlite::params![name, base_url, path_prefix, settings, id],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn update_backend_settings(&self, name: &str, settings: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE backends SET settings = ?1 WHERE name = ?2",
+            rusqlite::params![settings, name],
+        )?;
+
+        Ok(())
+    }
+
+    fn set_backend_enabled(&self, id: i64, enabled: bool) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE backends SET enabled = ?1 WHERE id = ?2",
+            rusqlite::params![enabled as i32, id],
+        )?;
+
+        Ok(())
+    }
+
+    fn delete_backend(&self, id: i64) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "DELETE FROM backends WHERE id = ?1 AND is_builtin = 0",
+            rusqlite::params![id],
+        )?;
+
+        Ok(())
+    }
+
+    fn backend_name_exists_excluding(
+        &self,
+        name: &str,
+        path_prefix: &str,
+        exclude_id: i64,
+    ) -> StorageResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM backends
+             WHERE (LOWER(name) = LOWER(?1) OR LOWER(path_prefix) = LOWER(?2)) AND id != ?3",
+            rusqlite::params![name, path_prefix, exclude_id],
+            |row| row.get(0),
+        )?;
+
+        Ok(count > 0)
+    }
+
+    fn update_predefined_backend_settings(&self, name: &str, settings: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE backends SET settings = ?1 WHERE name = ?2 AND is_builtin = 1",
+            rusqlite::params![settings, name],
+        )?;
+
+        Ok(())
+    }
+
+    fn reset_predefined_backend_settings(&self, name: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE backends SET settings = '{}' WHERE name = ?1 AND is_builtin = 1",
+            rusqlite::params![name],
+        )?;
+
+        Ok(())
+    }
+
+    fn api_keys(&self) -> StorageResult<Vec<ApiKeyRecord>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, backend, label, created_at, last_used_at, request_count, input_tokens, output_tokens
+             FROM api_keys ORDER BY backend, id",
+        )?;
+        let keys = stmt
+            .query_map([], |row| {
+                Ok(ApiKeyRecord {
+                    id: row.get(0)?,
+                    backend: row.get(1)?,
+                    label: row.get(2)?,
+                    created_at: row.get(3)?,
+                    last_used_at: row.get(4)?,
+                    request_count: row.get(5)?,
+                    input_tokens: row.get(6)?,
+                    output_tokens: row.get(7)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(keys)
+    }
+
+    fn add_api_key(&self, backend: &str, label: Option<&str>) -> StorageResult<i64> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO api_keys (backend, label, created_at) VALUES (?1, ?2, ?3)",
+            rusqlite::params![backend, label, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn delete_api_key(&self, id: i64) -> StorageResult<()> {
+        self.connection()?
+            .execute("DELETE FROM api_keys WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
+
+    fn record_api_key_usage(&self, id: i64, input_tokens: i64, output_tokens: i64) -> StorageResult<()> {
+        self.connection()?.execute(
+            "UPDATE api_keys SET request_count = request_count + 1,
+                input_tokens = input_tokens + ?1, output_tokens = output_tokens + ?2, last_used_at = ?3
+             WHERE id = ?4",
+            rusqlite::params![input_tokens, output_tokens, chrono::Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,439 @@
+// DLP patterns, policies, canaries and the token vault
+
+use super::{mask_logged_bodies, Database};
+use crate::commands::dlp::DlpPattern;
+use crate::commands::dlp_packs::PatternPackEntry;
+use crate::dlp_policy::DlpPolicy;
+use crate::dlp_vault::{masked_preview, value_digest};
+use crate::storage::{DlpPatternChanges, DlpStore, IssuedCanary, StorageResult};
+use rusqlite::OptionalExtension;
+use std::collections::HashMap;
+
+/// Column values of a pack entry, in the order shared by insert and replace
+fn entry_params(entry: &PatternPackEntry) -> StorageResult<Vec<Box<dyn rusqlite::ToSql>>> {
+    let patterns_json = serde_json::to_string(&entry.patterns)?;
+    let negative_patterns_json = match &entry.negative_patterns {
+        Some(np) => Some(serde_json::to_string(np)?),
+        None => None,
+    };
+    let allowlist_json = if entry.allowlist.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&entry.allowlist)?)
+    };
+
+    Ok(vec![
+        Box::new(entry.pattern_type.clone()),
+        Box::new(patterns_json),
+        Box::new(entry.negative_pattern_type.clone()),
+        Box::new(negative_patterns_json),
+        Box::new(entry.enabled as i32),
+        Box::new(entry.min_occurrences),
+        Box::new(entry.min_unique_chars),
+        Box::new(entry.min_entropy),
+        Box::new(entry.validator.clone()),
+        Box::new(entry.category.clone()),
+        Box::new(entry.action.clone()),
+        Box::new(entry.severity.clone()),
+        Box::new(allowlist_json),
+        Box::new(entry.scope.clone()),
+        Box::new(entry.normalize as i32),
+    ])
+}
+
+impl DlpStore for Database {
+    fn dlp_patterns(&self) -> StorageResult<Vec<DlpPattern>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, pattern_type, patterns, negative_pattern_type, negative_patterns,
+                    enabled, min_occurrences, min_unique_chars, is_builtin, validator, category,
+                    min_entropy, action, severity, allowlist, scope, normalize
+             FROM dlp_patterns ORDER BY is_builtin DESC, id",
+        )?;
+
+        let patterns = stmt
+            .query_map([], |row| {
+                let patterns_json: String = row.get(3)?;
+                let patterns: Vec<String> = serde_json::from_str(&patterns_json).unwrap_or_default();
+
+                let negative_patterns_json: Option<String> = row.get(5)?;
+                let negative_patterns: Option<Vec<String>> = negative_patterns_json
+                    .and_then(|json| serde_json::from_str(&json).ok());
+
+                let allowlist_json: Option<String> = row.get(15)?;
+                let allowlist: Vec<String> = allowlist_json
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default();
+
+                Ok(DlpPattern {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    pattern_type: row.get(2)?,
+                    patterns,
+                    negative_pattern_type: row.get(4)?,
+                    negative_patterns,
+                    enabled: row.get::<_, Option<i32>>(6)?.unwrap_or(1) == 1,
+                    min_occurrences: row.get(7)?,
+                    min_unique_chars: row.get(8)?,
+                    min_entropy: row.get::<_, Option<f64>>(12)?.unwrap_or(0.0),
+                    validator: row.get(10)?,
+                    category: row
+                        .get::<_, Option<String>>(11)?
+                        .unwrap_or_else(|| "secret".to_string()),
+                    action: row.get(13)?,
+                    severity: row
+                        .get::<_, Option<String>>(14)?
+                        .unwrap_or_else(|| "medium".to_string()),
+                    allowlist,
+                    scope: row
+                        .get::<_, Option<String>>(16)?
+                        .unwrap_or_else(|| "content".to_string()),
+                    normalize: row.get::<_, Option<i32>>(17)?.unwrap_or(0) == 1,
+                    is_builtin: row.get::<_, i32>(9)? == 1,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(patterns)
+    }
+
+    fn custom_dlp_patterns(&self) -> StorageResult<Vec<PatternPackEntry>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT name, pattern_type, patterns, negative_pattern_type, negative_patterns, enabled,
+                    min_occurrences, min_unique_chars, min_entropy, validator, category, action,
+                    severity, allowlist, scope, normalize
+             FROM dlp_patterns WHERE is_builtin = 0 ORDER BY id",
+        )?;
+
+        let patterns = stmt
+            .query_map([], |row| {
+                let patterns_json: String = row.get(2)?;
+                let negative_patterns_json: Option<String> = row.get(4)?;
+                let allowlist_json: Option<String> = row.get(13)?;
+
+                Ok(PatternPackEntry {
+                    name: row.get(0)?,
+                    pattern_type: row.get(1)?,
+                    patterns: serde_json::from_str(&patterns_json).unwrap_or_default(),
+                    negative_pattern_type: row.get(3)?,
+                    negative_patterns: negative_patterns_json.and_then(|json| serde_json::from_str(&json).ok()),
+                    enabled: row.get::<_, i32>(5)? == 1,
+                    min_occurrences: row.get(6)?,
+                    min_unique_chars: row.get(7)?,
+                    min_entropy: row.get::<_, Option<f64>>(8)?.unwrap_or(0.0),
+                    validator: row.get(9)?,
+                    category: row.get(10)?,
+                    action: row.get(11)?,
+                    severity: row.get(12)?,
+                    allowlist: allowlist_json
+                        .and_then(|json| serde_json::from_str(&json).ok())
+                        .unwrap_or_default(),
+                    scope: row.get(14)?,
+                    normalize: row.get::<_, Option<i32>>(15)?.unwrap_or(0) == 1,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(patterns)
+    }
+
+    fn dlp_pattern_by_name(&self, name: &str) -> StorageResult<Option<(i64, bool)>> {
+        let pattern = self
+            .connection()?
+            .query_row(
+                "SELECT id, is_builtin FROM dlp_patterns WHERE name = ?1",
+                [name],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i32>(1)? == 1)),
+            )
+            .optional()?;
+        Ok(pattern)
+    }
+
+    fn dlp_pattern_exists(&self, id: i64) -> StorageResult<bool> {
+        let count: i64 = self.connection()?.query_row(
+            "SELECT COUNT(*) FROM dlp_patterns WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn add_dlp_pattern(&self, name: &str, pattern: &PatternPackEntry) -> StorageResult<i64> {
+        let mut params = entry_params(pattern)?;
+        params.push(Box::new(name.to_string()));
+        params.push(Box::new(chrono::Utc::now().to_rfc3339()));
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO dlp_patterns (pattern_type, patterns, negative_pattern_type, negative_patterns, enabled, min_occurrences, min_unique_chars, min_entropy, validator, category, action, severity, allowlist, scope, normalize, name, created_at, is_builtin)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, 0)",
+            params_refs.as_slice(),
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn replace_dlp_pattern(&self, id: i64, pattern: &PatternPackEntry) -> StorageResult<()> {
+        let mut params = entry_params(pattern)?;
+        params.push(Box::new(id));
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        self.connection()?.execute(
+            "UPDATE dlp_patterns SET pattern_type = ?1, patterns = ?2, negative_pattern_type = ?3, negative_patterns = ?4, enabled = ?5, min_occurrences = ?6, min_unique_chars = ?7, min_entropy = ?8, validator = ?9, category = ?10, action = ?11, severity = ?12, allowlist = ?13, scope = ?14, normalize = ?15
+             WHERE id = ?16",
+            params_refs.as_slice(),
+        )?;
+        Ok(())
+    }
+
+    fn update_dlp_pattern(&self, id: i64, changes: &DlpPatternChanges) -> StorageResult<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        // Build dynamic update query based on the changed fields
+        let mut updates: Vec<&str> = Vec::new();
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        let mut set = |column: &'static str, value: Box<dyn rusqlite::ToSql>| {
+            updates.push(column);
+            params.push(value);
+        };
+
+        if let Some(name) = &changes.name {
+            set("name", Box::new(name.clone()));
+        }
+        if let Some(pattern_type) = &changes.pattern_type {
+            set("pattern_type", Box::new(pattern_type.clone()));
+        }
+        if let Some(patterns) = &changes.patterns {
+            set("patterns", Box::new(serde_json::to_string(patterns)?));
+        }
+        if let Some(negative_pattern_type) = &changes.negative_pattern_type {
+            set("negative_pattern_type", Box::new(negative_pattern_type.clone()));
+        }
+        if let Some(negative_patterns) = &changes.negative_patterns {
+            let json = match negative_patterns {
+                Some(np) => Some(serde_json::to_string(np)?),
+                None => None,
+            };
+            set("negative_patterns", Box::new(json));
+        }
+        if let Some(enabled) = changes.enabled {
+            set("enabled", Box::new(enabled as i32));
+        }
+        if let Some(min_occurrences) = changes.min_occurrences {
+            set("min_occurrences", Box::new(min_occurrences));
+        }
+        if let Some(min_unique_chars) = changes.min_unique_chars {
+            set("min_unique_chars", Box::new(min_unique_chars));
+        }
+        if let Some(min_entropy) = changes.min_entropy {
+            set("min_entropy", Box::new(min_entropy));
+        }
+        if let Some(validator) = &changes.validator {
+            set("validator", Box::new(validator.clone()));
+        }
+        if let Some(category) = &changes.category {
+            set("category", Box::new(category.clone()));
+        }
+        if let Some(action) = &changes.action {
+            set("action", Box::new(action.clone()));
+        }
+        if let Some(severity) = &changes.severity {
+            set("severity", Box::new(severity.clone()));
+        }
+        if let Some(allowlist) = &changes.allowlist {
+            let json = match allowlist {
+                Some(entries) => Some(serde_json::to_string(entries)?),
+                None => None,
+            };
+            set("allowlist", Box::new(json));
+        }
+        if let Some(scope) = &changes.scope {
+            set("scope", Box::new(scope.clone()));
+        }
+        if let Some(normalize) = changes.normalize {
+            set("normalize", Box::new(normalize as i32));
+        }
+
+        params.push(Box::new(id));
+        let sql = format!(
+            "UPDATE dlp_patterns SET {} WHERE id = ?",
+            updates.iter().map(|column| format!("{} = ?", column)).collect::<Vec<_>>().join(", ")
+        );
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        self.connection()?.execute(&sql, params_refs.as_slice())?;
+        Ok(())
+    }
+
+    fn set_dlp_pattern_enabled(&self, id: i64, enabled: bool) -> StorageResult<()> {
+        self.connection()?.execute(
+            "UPDATE dlp_patterns SET enabled = ?1 WHERE id = ?2",
+            rusqlite::params![enabled as i32, id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_dlp_pattern(&self, id: i64) -> StorageResult<bool> {
+        let conn = self.connection()?;
+
+        let is_builtin: bool = conn
+            .query_row(
+                "SELECT is_builtin FROM dlp_patterns WHERE id = ?1",
+                rusqlite::params![id],
+                |row| row.get::<_, i32>(0).map(|v| v == 1),
+            )
+            .optional()?
+            .unwrap_or(false);
+        if is_builtin {
+            return Ok(false);
+        }
+
+        conn.execute("DELETE FROM dlp_patterns WHERE id = ?1", rusqlite::params![id])?;
+        // Policies of a deleted pattern would never match again
+        conn.execute("DELETE FROM dlp_policies WHERE pattern_id = ?1", rusqlite::params![id])?;
+        Ok(true)
+    }
+
+    fn dlp_policies(&self) -> StorageResult<Vec<DlpPolicy>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, pattern_id, backend, model_glob, action FROM dlp_policies ORDER BY id",
+        )?;
+
+        let policies = stmt
+            .query_map([], |row| {
+                Ok(DlpPolicy {
+                    id: row.get(0)?,
+                    pattern_id: row.get(1)?,
+                    backend: row.get(2)?,
+                    model_glob: row.get(3)?,
+                    action: row.get(4)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(policies)
+    }
+
+    fn add_dlp_policy(&self, policy: &DlpPolicy) -> StorageResult<i64> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO dlp_policies (pattern_id, backend, model_glob, action, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                policy.pattern_id,
+                policy.backend,
+                policy.model_glob,
+                policy.action,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn update_dlp_policy(&self, policy: &DlpPolicy) -> StorageResult<()> {
+        self.connection()?.execute(
+            "UPDATE dlp_policies SET backend = ?1, model_glob = ?2, action = ?3 WHERE id = ?4",
+            rusqlite::params![policy.backend, policy.model_glob, policy.action, policy.id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_dlp_policy(&self, id: i64) -> StorageResult<()> {
+        self.connection()?
+            .execute("DELETE FROM dlp_policies WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
+
+    fn find_canary(&self, token: &str) -> StorageResult<Option<IssuedCanary>> {
+        let canary = self
+            .connection()?
+            .query_row(
+                "SELECT request_id, backend, created_at FROM dlp_canaries WHERE token = ?1",
+                rusqlite::params![token],
+                |row| {
+                    Ok(IssuedCanary {
+                        request_id: row.get(0)?,
+                        backend: row.get(1)?,
+                        created_at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(canary)
+    }
+
+    fn mark_canary_triggered(&self, token: &str) -> StorageResult<()> {
+        self.connection()?.execute(
+            "UPDATE dlp_canaries SET triggered_at = ?1, trigger_count = trigger_count + 1 WHERE token = ?2",
+            rusqlite::params![chrono::Utc::now().to_rfc3339(), token],
+        )?;
+        Ok(())
+    }
+
+    fn hash_stored_detection_values(&self) -> StorageResult<usize> {
+        let conn = self.connection()?;
+
+        let rows: Vec<(i64, i64, String)> = {
+            let mut stmt =
+                conn.prepare("SELECT id, request_id, original_value FROM dlp_detections WHERE value_hash IS NULL")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<Result<_, _>>()?
+        };
+
+        let mut values_by_request: HashMap<i64, Vec<&str>> = HashMap::new();
+        for (id, request_id, value) in &rows {
+            conn.execute(
+                "UPDATE dlp_detections SET original_value = ?1, value_hash = ?2 WHERE id = ?3",
+                rusqlite::params![masked_preview(value), value_digest(value), id],
+            )?;
+            values_by_request.entry(*request_id).or_default().push(value);
+        }
+        for (request_id, values) in &values_by_request {
+            mask_logged_bodies(&conn, *request_id, values)?;
+        }
+
+        Ok(rows.len())
+    }
+
+    fn vault_surrogate(&self, value_hash: &str) -> StorageResult<Option<String>> {
+        let conn = self.connection()?;
+        let surrogate = conn
+            .query_row(
+                "SELECT surrogate FROM dlp_tokens WHERE value_hash = ?1",
+                [value_hash],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        if surrogate.is_some() {
+            conn.execute(
+                "UPDATE dlp_tokens SET last_used_at = ?1 WHERE value_hash = ?2",
+                rusqlite::params![chrono::Utc::now().to_rfc3339(), value_hash],
+            )?;
+        }
+        Ok(surrogate)
+    }
+
+    fn vault_surrogate_taken(&self, surrogate: &str) -> StorageResult<bool> {
+        let taken = self
+            .connection()?
+            .query_row("SELECT 1 FROM dlp_tokens WHERE surrogate = ?1", [surrogate], |_| Ok(()))
+            .optional()?;
+        Ok(taken.is_some())
+    }
+
+    fn store_vault_surrogate(&self, value_hash: &str, surrogate: &str, pattern_name: &str) -> StorageResult<()> {
+        self.connection()?.execute(
+            "INSERT OR IGNORE INTO dlp_tokens (value_hash, surrogate, pattern_name, created_at, last_used_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)",
+            rusqlite::params![value_hash, surrogate, pattern_name, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+}
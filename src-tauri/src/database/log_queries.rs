@@ -0,0 +1,810 @@
+// Queries behind the tray, dashboard, Logs, conversation and DLP views
+
+use super::{backend_filter, Database, DLP_ACTION_PASSED};
+use crate::commands::dlp::{CategoryCount, DlpDetectionRecord, DlpStats, PatternCount, SeverityCount};
+use crate::commands::stats::{
+    BackendStats, ConversationEvent, ConversationSummary, ConversationTimeline, CostSummary, DashboardData,
+    FeatureStats, LatencyPoint, MessageLog, ModelStats, PaginatedLogs, RecentRequest, RequestBodies,
+    TokenCountModelStats, TokenCountStats, TokenTimelinePoint, TokenTotals, ToolCallRecord, ToolCallStats,
+};
+use crate::log_export::{ExportDetection, ExportLog};
+use crate::storage::{LogFilter, LogQueries, StorageResult};
+use rusqlite::{Connection, OptionalExtension};
+use std::collections::HashMap;
+
+const CONVERSATION_SUMMARY_SELECT: &str = "
+    SELECT c.id, c.conversation_key, c.first_seen, c.last_seen, COUNT(rc.request_id),
+           (SELECT GROUP_CONCAT(DISTINCT r.backend) FROM request_conversations x JOIN requests r ON r.id = x.request_id WHERE x.conversation_id = c.id)
+    FROM conversations c
+    LEFT JOIN request_conversations rc ON rc.conversation_id = c.id";
+
+/// Columns of a `DlpDetectionRecord`, for `dlp_detections d LEFT JOIN dlp_incidents i`
+const DETECTION_RECORD_COLUMNS: &str =
+    "d.id, d.request_id, d.timestamp, d.pattern_name, d.pattern_type, d.original_value, d.placeholder, d.message_index,
+     COALESCE(d.category, 'secret'), d.metadata, d.action,
+     COALESCE(d.severity, 'medium'), COALESCE(d.direction, 'request'), d.confidence, d.verified, d.value_hash,
+     d.incident_id, COALESCE(i.occurrence_count, 1)";
+
+fn conversation_summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<ConversationSummary> {
+    let backends: Option<String> = row.get(5)?;
+    Ok(ConversationSummary {
+        id: row.get(0)?,
+        conversation_key: row.get(1)?,
+        first_seen: row.get(2)?,
+        last_seen: row.get(3)?,
+        request_count: row.get(4)?,
+        backends: backends
+            .map(|b| b.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+    })
+}
+
+fn detection_record_from_row(row: &rusqlite::Row) -> rusqlite::Result<DlpDetectionRecord> {
+    Ok(DlpDetectionRecord {
+        id: row.get(0)?,
+        request_id: row.get(1)?,
+        timestamp: row.get(2)?,
+        pattern_name: row.get(3)?,
+        pattern_type: row.get(4)?,
+        original_value: row.get(5)?,
+        placeholder: row.get(6)?,
+        message_index: row.get(7)?,
+        category: row.get(8)?,
+        metadata: row.get(9)?,
+        action: row.get(10)?,
+        severity: row.get(11)?,
+        direction: row.get(12)?,
+        confidence: row.get(13)?,
+        verified: row.get(14)?,
+        value_hash: row.get(15)?,
+        incident_id: row.get(16)?,
+        occurrence_count: row.get(17)?,
+    })
+}
+
+/// The Logs view's filters as SQL conditions on `requests`, after `timestamp >= ?1`
+fn log_filters(filter: &LogFilter) -> String {
+    let mut filters = backend_filter("backend", filter.backend.as_deref());
+
+    if let Some(model) = &filter.model {
+        filters.push_str(&format!(" AND COALESCE(model, 'unknown') = '{}'", model.replace('\'', "''")));
+    }
+
+    match filter.dlp_action {
+        Some(DLP_ACTION_PASSED) => filters.push_str(&format!(" AND COALESCE(dlp_action, 0) = {}", DLP_ACTION_PASSED)),
+        Some(action) => filters.push_str(&format!(" AND dlp_action = {}", action)),
+        None => {}
+    }
+
+    // Search filter - case-insensitive LIKE on the request and response bodies
+    if let Some(search) = &filter.search {
+        let escaped_search = search.replace('\'', "''").replace('%', "\\%").replace('_', "\\_");
+        filters.push_str(&format!(
+            " AND id IN (SELECT request_id FROM request_bodies WHERE LOWER(request_body) LIKE LOWER('%{}%') ESCAPE '\\' OR LOWER(response_body) LIKE LOWER('%{}%') ESCAPE '\\')",
+            escaped_search, escaped_search
+        ));
+    }
+
+    filters
+}
+
+fn tool_calls_of(conn: &Connection, request_id: i64) -> rusqlite::Result<Vec<ToolCallRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, request_id, tool_call_id, tool_name, tool_input
+         FROM tool_calls WHERE request_id = ?1 ORDER BY id ASC",
+    )?;
+
+    let tool_calls = stmt
+        .query_map([request_id], |row| {
+            Ok(ToolCallRecord {
+                id: row.get(0)?,
+                request_id: row.get(1)?,
+                tool_call_id: row.get(2)?,
+                tool_name: row.get(3)?,
+                tool_input: row.get(4)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(tool_calls)
+}
+
+impl LogQueries for Database {
+    fn backend_usage(&self, since: &str) -> StorageResult<Vec<BackendStats>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT backend,
+                    COUNT(*) as request_count,
+                    COALESCE(SUM(input_tokens), 0) as input_tokens,
+                    COALESCE(SUM(output_tokens), 0) as output_tokens,
+                    COALESCE(SUM(cache_read_tokens), 0) + COALESCE(SUM(cache_creation_tokens), 0) as cache_tokens
+             FROM requests
+             WHERE timestamp >= ?1
+             GROUP BY backend
+             ORDER BY request_count DESC"
+        )?;
+
+        let backends = stmt
+            .query_map([since], |row| {
+                Ok(BackendStats {
+                    backend: row.get(0)?,
+                    request_count: row.get(1)?,
+                    input_tokens: row.get(2)?,
+                    output_tokens: row.get(3)?,
+                    cache_tokens: row.get(4)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(backends)
+    }
+
+    fn input_token_points(&self, since: &str) -> StorageResult<Vec<(String, TokenTimelinePoint)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT backend, timestamp, input_tokens
+             FROM requests
+             WHERE timestamp >= ?1 AND input_tokens > 0
+             ORDER BY timestamp ASC"
+        )?;
+
+        let points = stmt
+            .query_map([since], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    TokenTimelinePoint {
+                        timestamp: row.get(1)?,
+                        input_tokens: row.get(2)?,
+                    },
+                ))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(points)
+    }
+
+    fn dashboard_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<DashboardData> {
+        let conn = self.connection()?;
+        let backend_filter = backend_filter("backend", backend);
+
+        // Get model stats
+        let mut model_stmt = conn.prepare(&format!(
+            "SELECT COALESCE(model, 'unknown') as model, COUNT(*) as count
+             FROM requests
+             WHERE model IS NOT NULL AND timestamp >= ?1{}
+             GROUP BY model
+             ORDER BY count DESC",
+            backend_filter
+        ))?;
+
+        let models: Vec<ModelStats> = model_stmt
+            .query_map([since], |row| {
+                Ok(ModelStats {
+                    model: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get feature stats
+        let features: FeatureStats = conn
+            .query_row(
+                &format!(
+                    "SELECT
+                        COALESCE(SUM(has_system_prompt), 0),
+                        COALESCE(SUM(has_tools), 0),
+                        COALESCE(SUM(has_thinking), 0),
+                        COUNT(*)
+                     FROM requests
+                     WHERE timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| {
+                    Ok(FeatureStats {
+                        with_system_prompt: row.get(0)?,
+                        with_tools: row.get(1)?,
+                        with_thinking: row.get(2)?,
+                        total_requests: row.get(3)?,
+                    })
+                },
+            )
+            .unwrap_or(FeatureStats {
+                with_system_prompt: 0,
+                with_tools: 0,
+                with_thinking: 0,
+                total_requests: 0,
+            });
+
+        // Get token totals
+        let token_totals: TokenTotals = conn
+            .query_row(
+                &format!(
+                    "SELECT
+                        COALESCE(SUM(input_tokens), 0),
+                        COALESCE(SUM(output_tokens), 0),
+                        COALESCE(SUM(cache_read_tokens), 0),
+                        COALESCE(SUM(cache_creation_tokens), 0)
+                     FROM requests
+                     WHERE timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| {
+                    Ok(TokenTotals {
+                        input: row.get(0)?,
+                        output: row.get(1)?,
+                        cache_read: row.get(2)?,
+                        cache_creation: row.get(3)?,
+                    })
+                },
+            )
+            .unwrap_or(TokenTotals {
+                input: 0,
+                output: 0,
+                cache_read: 0,
+                cache_creation: 0,
+            });
+
+        // Get recent requests for token chart
+        let mut recent_stmt = conn.prepare(&format!(
+            "SELECT id, timestamp, COALESCE(model, 'unknown'), input_tokens, output_tokens,
+                    cache_read_tokens, cache_creation_tokens, latency_ms,
+                    COALESCE(stop_reason, 'unknown'), has_thinking, COALESCE(response_cache, 0)
+             FROM requests
+             WHERE timestamp >= ?1{}
+             ORDER BY id DESC",
+            backend_filter
+        ))?;
+
+        let recent_requests: Vec<RecentRequest> = recent_stmt
+            .query_map([since], |row| {
+                Ok(RecentRequest {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    model: row.get(2)?,
+                    input_tokens: row.get(3)?,
+                    output_tokens: row.get(4)?,
+                    cache_read_tokens: row.get(5)?,
+                    cache_creation_tokens: row.get(6)?,
+                    latency_ms: row.get(7)?,
+                    stop_reason: row.get(8)?,
+                    has_thinking: row.get::<_, i32>(9)? == 1,
+                    cache_hit: row.get::<_, i32>(10)? == 1,
+                    estimated_cost_usd: None,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get latency points for chart
+        let mut latency_stmt = conn.prepare(&format!(
+            "SELECT id, latency_ms, ttfb_ms
+             FROM requests
+             WHERE latency_ms > 0 AND timestamp >= ?1{}
+             ORDER BY id DESC",
+            backend_filter
+        ))?;
+
+        let latency_points: Vec<LatencyPoint> = latency_stmt
+            .query_map([since], |row| {
+                Ok(LatencyPoint {
+                    id: row.get(0)?,
+                    latency_ms: row.get(1)?,
+                    ttfb_ms: row.get(2)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get totals
+        let total_requests: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM requests WHERE timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        let avg_latency_ms: f64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(AVG(latency_ms), 0)
+                     FROM requests
+                     WHERE latency_ms > 0 AND timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| row.get(0),
+            )
+            .unwrap_or(0.0);
+
+        let avg_ttfb_ms: f64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(AVG(ttfb_ms), 0)
+                     FROM requests
+                     WHERE ttfb_ms IS NOT NULL AND timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| row.get(0),
+            )
+            .unwrap_or(0.0);
+
+        // Response cache: hits and misses among the requests that could be cached
+        let (cache_hits, cache_misses): (i64, i64) = conn
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(SUM(response_cache = 1), 0), COALESCE(SUM(response_cache = 0), 0)
+                     FROM requests
+                     WHERE timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap_or((0, 0));
+
+        Ok(DashboardData {
+            models,
+            features,
+            token_totals,
+            recent_requests,
+            latency_points,
+            total_requests,
+            avg_latency_ms,
+            avg_ttfb_ms,
+            cache_hits,
+            cache_misses,
+            cost: CostSummary::default(),
+        })
+    }
+
+    fn token_count_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<TokenCountStats> {
+        let conn = self.connection()?;
+        let backend_filter = backend_filter("backend", backend);
+
+        let (total_calls, avg_input_tokens, max_input_tokens): (i64, f64, i64) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(AVG(input_tokens), 0), COALESCE(MAX(input_tokens), 0)
+                 FROM token_counts
+                 WHERE timestamp >= ?1{}",
+                backend_filter
+            ),
+            [since],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        let mut model_stmt = conn.prepare(&format!(
+            "SELECT COALESCE(model, 'unknown'), COUNT(*), AVG(input_tokens), MAX(input_tokens)
+             FROM token_counts
+             WHERE timestamp >= ?1{}
+             GROUP BY model
+             ORDER BY COUNT(*) DESC",
+            backend_filter
+        ))?;
+
+        let models: Vec<TokenCountModelStats> = model_stmt
+            .query_map([since], |row| {
+                Ok(TokenCountModelStats {
+                    model: row.get(0)?,
+                    count: row.get(1)?,
+                    avg_input_tokens: row.get(2)?,
+                    max_input_tokens: row.get(3)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(TokenCountStats {
+            total_calls,
+            avg_input_tokens,
+            max_input_tokens,
+            models,
+        })
+    }
+
+    fn logged_backends(&self) -> StorageResult<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT backend FROM requests ORDER BY backend")?;
+        let backends = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+        Ok(backends)
+    }
+
+    fn logged_models(&self) -> StorageResult<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT COALESCE(model, 'unknown') FROM requests ORDER BY model")?;
+        let models = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+        Ok(models)
+    }
+
+    fn message_logs(&self, filter: &LogFilter, page: i64, page_size: i64) -> StorageResult<PaginatedLogs> {
+        let conn = self.connection()?;
+        let filters = log_filters(filter);
+
+        // Get total count
+        let total: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM requests WHERE timestamp >= ?1{}",
+                    filters
+                ),
+                [&filter.since],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, timestamp, backend, COALESCE(model, 'unknown'),
+                    input_tokens, output_tokens, latency_ms,
+                    request_headers, response_headers, COALESCE(dlp_action, 0), served_by,
+                    translated_from, ttfb_ms, stream_ms,
+                    (SELECT conversation_id FROM request_conversations WHERE request_id = requests.id)
+             FROM requests
+             WHERE timestamp >= ?1{}
+             ORDER BY id DESC
+             LIMIT ?2 OFFSET ?3",
+            filters
+        ))?;
+
+        let logs: Vec<MessageLog> = stmt
+            .query_map(rusqlite::params![&filter.since, page_size, page * page_size], |row| {
+                Ok(MessageLog {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    backend: row.get(2)?,
+                    model: row.get(3)?,
+                    input_tokens: row.get(4)?,
+                    output_tokens: row.get(5)?,
+                    latency_ms: row.get(6)?,
+                    ttfb_ms: row.get(12)?,
+                    stream_ms: row.get(13)?,
+                    request_headers: row.get(7)?,
+                    response_headers: row.get(8)?,
+                    dlp_action: row.get(9)?,
+                    served_by: row.get(10)?,
+                    translated_from: row.get(11)?,
+                    conversation_id: row.get(14)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(PaginatedLogs { logs, total })
+    }
+
+    fn export_logs(&self, filter: &LogFilter) -> StorageResult<Vec<ExportLog>> {
+        let conn = self.connection()?;
+        let filters = log_filters(filter);
+
+        // Detections of the exported requests, by request
+        let mut detections: HashMap<i64, Vec<ExportDetection>> = HashMap::new();
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT request_id, pattern_name, COALESCE(category, 'secret'), COALESCE(severity, 'medium'),
+                        COALESCE(direction, 'request'), action, placeholder, value_hash
+                 FROM dlp_detections
+                 WHERE request_id IN (SELECT id FROM requests WHERE timestamp >= ?1{})
+                 ORDER BY id",
+                filters
+            ))?;
+            let rows = stmt.query_map([&filter.since], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    ExportDetection {
+                        pattern_name: row.get(1)?,
+                        category: row.get(2)?,
+                        severity: row.get(3)?,
+                        direction: row.get(4)?,
+                        action: row.get(5)?,
+                        replaced_with: row.get(6)?,
+                        value_hash: row.get(7)?,
+                    },
+                ))
+            })?;
+            for (request_id, detection) in rows.filter_map(|r| r.ok()) {
+                detections.entry(request_id).or_default().push(detection);
+            }
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, timestamp, backend, COALESCE(model, 'unknown'),
+                    input_tokens, output_tokens, latency_ms, b.request_body, b.response_body,
+                    COALESCE(dlp_action, 0), served_by
+             FROM requests
+             LEFT JOIN request_bodies b ON b.request_id = requests.id
+             WHERE timestamp >= ?1{}
+             ORDER BY id DESC",
+            filters
+        ))?;
+
+        let logs = stmt
+            .query_map([&filter.since], |row| {
+                let id: i64 = row.get(0)?;
+                Ok(ExportLog {
+                    id,
+                    timestamp: row.get(1)?,
+                    backend: row.get(2)?,
+                    model: row.get(3)?,
+                    input_tokens: row.get(4)?,
+                    output_tokens: row.get(5)?,
+                    latency_ms: row.get(6)?,
+                    request_body: row.get(7)?,
+                    response_body: row.get(8)?,
+                    dlp_action: row.get(9)?,
+                    served_by: row.get(10)?,
+                    detections: detections.remove(&id).unwrap_or_default(),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(logs)
+    }
+
+    fn tool_calls_for_request(&self, request_id: i64) -> StorageResult<Vec<ToolCallRecord>> {
+        let conn = self.connection()?;
+        Ok(tool_calls_of(&conn, request_id)?)
+    }
+
+    fn request_bodies(&self, request_id: i64) -> StorageResult<RequestBodies> {
+        let bodies = self
+            .connection()?
+            .query_row(
+                "SELECT request_body, response_body FROM request_bodies WHERE request_id = ?1",
+                [request_id],
+                |row| {
+                    Ok(RequestBodies {
+                        request_body: row.get(0)?,
+                        response_body: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(bodies.unwrap_or(RequestBodies {
+            request_body: None,
+            response_body: None,
+        }))
+    }
+
+    fn conversations(&self, since: &str) -> StorageResult<Vec<ConversationSummary>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE c.last_seen >= ?1 GROUP BY c.id ORDER BY c.last_seen DESC LIMIT 200",
+            CONVERSATION_SUMMARY_SELECT
+        ))?;
+
+        let conversations = stmt
+            .query_map([since], conversation_summary_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(conversations)
+    }
+
+    fn conversation_timeline(&self, conversation_id: i64) -> StorageResult<Option<ConversationTimeline>> {
+        let conn = self.connection()?;
+
+        let Some(conversation) = conn
+            .query_row(
+                &format!("{} WHERE c.id = ?1 GROUP BY c.id", CONVERSATION_SUMMARY_SELECT),
+                [conversation_id],
+                conversation_summary_from_row,
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.timestamp, r.backend,
+                    CASE WHEN json_valid(r.extra_metadata) THEN json_extract(r.extra_metadata, '$.hook_event_name') END,
+                    COALESCE(r.model, 'unknown'), r.input_tokens, r.output_tokens, COALESCE(r.dlp_action, 0),
+                    b.request_body, b.response_body
+             FROM request_conversations rc
+             JOIN requests r ON r.id = rc.request_id
+             LEFT JOIN request_bodies b ON b.request_id = r.id
+             WHERE rc.conversation_id = ?1
+             ORDER BY r.timestamp ASC, r.id ASC",
+        )?;
+
+        let mut events: Vec<ConversationEvent> = stmt
+            .query_map([conversation_id], |row| {
+                let backend: String = row.get(2)?;
+                Ok(ConversationEvent {
+                    request_id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    source: if backend == "cursor-hooks" { "hook" } else { "proxy" }.to_string(),
+                    backend,
+                    hook_event: row.get(3)?,
+                    model: row.get(4)?,
+                    input_tokens: row.get(5)?,
+                    output_tokens: row.get(6)?,
+                    dlp_action: row.get(7)?,
+                    request_body: row.get(8)?,
+                    response_body: row.get(9)?,
+                    tool_calls: Vec::new(),
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        for event in &mut events {
+            event.tool_calls = tool_calls_of(&conn, event.request_id)?;
+        }
+
+        Ok(Some(ConversationTimeline { conversation, events }))
+    }
+
+    fn tool_call_counts(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<ToolCallStats>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT tc.tool_name, COUNT(*) as count
+             FROM tool_calls tc
+             JOIN requests r ON tc.request_id = r.id
+             WHERE r.timestamp >= ?1{}
+             GROUP BY tc.tool_name
+             ORDER BY count DESC
+             LIMIT 20",
+            backend_filter("r.backend", backend)
+        ))?;
+
+        let stats = stmt
+            .query_map([since], |row| {
+                Ok(ToolCallStats {
+                    tool_name: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(stats)
+    }
+
+    fn tool_call_inputs(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<(String, String)>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT tc.tool_name, tc.tool_input
+             FROM tool_calls tc
+             JOIN requests r ON tc.request_id = r.id
+             WHERE r.timestamp >= ?1{}",
+            backend_filter("r.backend", backend)
+        ))?;
+
+        let calls = stmt
+            .query_map([since], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(calls)
+    }
+
+    fn dlp_detection_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<DlpStats> {
+        let conn = self.connection()?;
+        let backend_filter = backend_filter("r.backend", backend);
+
+        // Get total detection and incident counts (with backend filter)
+        // Detections not grouped into an incident count as their own incident
+        let (total_detections, total_incidents): (i64, i64) = conn
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*), COUNT(DISTINCT COALESCE(d.incident_id, -d.id)) FROM dlp_detections d
+                     JOIN requests r ON d.request_id = r.id
+                     WHERE d.timestamp >= ?1{}",
+                    backend_filter
+                ),
+                [since],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap_or((0, 0));
+
+        // Get incidents by pattern (with backend filter)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.pattern_name, COUNT(DISTINCT COALESCE(d.incident_id, -d.id)) as count FROM dlp_detections d
+             JOIN requests r ON d.request_id = r.id
+             WHERE d.timestamp >= ?1{} GROUP BY d.pattern_name ORDER BY count DESC",
+            backend_filter
+        ))?;
+
+        let detections_by_pattern: Vec<PatternCount> = stmt
+            .query_map([since], |row| {
+                Ok(PatternCount {
+                    pattern_name: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get incidents by category (with backend filter)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(d.category, 'secret') as category, COUNT(DISTINCT COALESCE(d.incident_id, -d.id)) as count FROM dlp_detections d
+             JOIN requests r ON d.request_id = r.id
+             WHERE d.timestamp >= ?1{} GROUP BY category ORDER BY count DESC",
+            backend_filter
+        ))?;
+
+        let detections_by_category: Vec<CategoryCount> = stmt
+            .query_map([since], |row| {
+                Ok(CategoryCount {
+                    category: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get incidents by severity, most severe first (with backend filter)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(d.severity, 'medium') as severity, COUNT(DISTINCT COALESCE(d.incident_id, -d.id)) as count FROM dlp_detections d
+             JOIN requests r ON d.request_id = r.id
+             WHERE d.timestamp >= ?1{} GROUP BY severity
+             ORDER BY CASE severity WHEN 'critical' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 ELSE 3 END",
+            backend_filter
+        ))?;
+
+        let detections_by_severity: Vec<SeverityCount> = stmt
+            .query_map([since], |row| {
+                Ok(SeverityCount {
+                    severity: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Get recent detections, latest one per incident (with backend filter)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM dlp_detections d
+             JOIN requests r ON d.request_id = r.id
+             LEFT JOIN dlp_incidents i ON d.incident_id = i.id
+             WHERE d.timestamp >= ?1{}
+               AND (d.incident_id IS NULL OR d.id = (SELECT MAX(id) FROM dlp_detections WHERE incident_id = d.incident_id))
+             ORDER BY d.id DESC LIMIT 50",
+            DETECTION_RECORD_COLUMNS, backend_filter
+        ))?;
+
+        let recent_detections: Vec<DlpDetectionRecord> = stmt
+            .query_map([since], detection_record_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(DlpStats {
+            total_detections,
+            total_incidents,
+            detections_by_pattern,
+            detections_by_category,
+            detections_by_severity,
+            recent_detections,
+        })
+    }
+
+    fn detections_for_request(&self, request_id: i64) -> StorageResult<Vec<DlpDetectionRecord>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM dlp_detections d
+             LEFT JOIN dlp_incidents i ON d.incident_id = i.id
+             WHERE d.request_id = ?1 ORDER BY d.id ASC",
+            DETECTION_RECORD_COLUMNS
+        ))?;
+
+        let detections = stmt
+            .query_map([request_id], detection_record_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(detections)
+    }
+}
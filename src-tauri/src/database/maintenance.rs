@@ -0,0 +1,147 @@
+// Retention: deleting old requests with everything that belongs to them
+
+use super::{delete_empty_conversations, Database, ORPHAN_CHECKS};
+use crate::storage::{LogMaintenance, StorageResult};
+
+impl LogMaintenance for Database {
+    fn cleanup_old_data(&self, cutoff_ts: &str) -> StorageResult<usize> {
+        let conn = self.conn.lock().unwrap();
+
+        // Delete DLP detections for requests that will be deleted (by relationship, not timestamp)
+        conn.execute(
+            "DELETE FROM dlp_detections WHERE request_id IN (SELECT id FROM requests WHERE timestamp < ?1)",
+            rusqlite::params![cutoff_ts],
+        )?;
+
+        // Delete incidents with no occurrence since the cutoff
+        conn.execute(
+            "DELETE FROM dlp_incidents WHERE last_seen < ?1",
+            rusqlite::params![cutoff_ts],
+        )?;
+
+        // Delete tool calls and bodies of requests that will be deleted
+        conn.execute(
+            "DELETE FROM tool_calls WHERE request_id IN (SELECT id FROM requests WHERE timestamp < ?1)",
+            rusqlite::params![cutoff_ts],
+        )?;
+        conn.execute(
+            "DELETE FROM request_bodies WHERE request_id IN (SELECT id FROM requests WHERE timestamp < ?1)",
+            rusqlite::params![cutoff_ts],
+        )?;
+
+        // Unlink requests that will be deleted from their conversations
+        conn.execute(
+            "DELETE FROM request_conversations WHERE request_id IN (SELECT id FROM requests WHERE timestamp < ?1)",
+            rusqlite::params![cutoff_ts],
+        )?;
+        delete_empty_conversations(&conn)?;
+
+        // Delete old token-counting calls
+        conn.execute(
+            "DELETE FROM token_counts WHERE timestamp < ?1",
+            rusqlite::params![cutoff_ts],
+        )?;
+
+        // Delete old requests
+        let deleted = conn.execute(
+            "DELETE FROM requests WHERE timestamp < ?1",
+            rusqlite::params![cutoff_ts],
+        )?;
+        Ok(deleted)
+    }
+
+    fn keep_newest_requests(&self, keep: u64) -> StorageResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let max_id: i64 = match conn.query_row(
+            "SELECT id FROM requests ORDER BY id DESC LIMIT 1 OFFSET ?1",
+            rusqlite::params![keep as i64],
+            |row| row.get(0),
+        ) {
+            Ok(id) => id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        conn.execute("DELETE FROM dlp_detections WHERE request_id <= ?1", rusqlite::params![max_id])?;
+        conn.execute("DELETE FROM tool_calls WHERE request_id <= ?1", rusqlite::params![max_id])?;
+        conn.execute("DELETE FROM request_bodies WHERE request_id <= ?1", rusqlite::params![max_id])?;
+        conn.execute(
+            "DELETE FROM dlp_incidents WHERE id NOT IN (SELECT incident_id FROM dlp_detections WHERE incident_id IS NOT NULL)",
+            [],
+        )?;
+        conn.execute("DELETE FROM request_conversations WHERE request_id <= ?1", rusqlite::params![max_id])?;
+        delete_empty_conversations(&conn)?;
+        let deleted = conn.execute("DELETE FROM requests WHERE id <= ?1", rusqlite::params![max_id])?;
+        Ok(deleted)
+    }
+
+    fn orphaned_rows(&self, delete: bool) -> StorageResult<Vec<(&'static str, usize)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut orphans = Vec::new();
+        for (table, orphaned) in ORPHAN_CHECKS {
+            let count = if delete {
+                conn.execute(&format!("DELETE FROM {} WHERE {}", table, orphaned), [])?
+            } else {
+                conn.query_row(&format!("SELECT COUNT(*) FROM {} WHERE {}", table, orphaned), [], |row| {
+                    row.get::<_, i64>(0)
+                })? as usize
+            };
+            orphans.push((*table, count));
+        }
+
+        let unlinked_canaries = "request_id IS NOT NULL AND request_id NOT IN (SELECT id FROM requests)";
+        let count = if delete {
+            conn.execute(&format!("UPDATE dlp_canaries SET request_id = NULL WHERE {}", unlinked_canaries), [])?
+        } else {
+            conn.query_row(&format!("SELECT COUNT(*) FROM dlp_canaries WHERE {}", unlinked_canaries), [], |row| {
+                row.get::<_, i64>(0)
+            })? as usize
+        };
+        orphans.push(("dlp_canaries", count));
+        Ok(orphans)
+    }
+
+    fn reset_sequences(&self) -> StorageResult<Vec<&'static str>> {
+        let conn = self.conn.lock().unwrap();
+        let mut reset = Vec::new();
+        for table in ["tool_calls", "dlp_incidents", "conversations"] {
+            let changed = conn.execute(
+                &format!(
+                    "UPDATE sqlite_sequence SET seq = (SELECT COALESCE(MAX(id), 0) FROM {table})
+                     WHERE name = '{table}' AND seq > (SELECT COALESCE(MAX(id), 0) FROM {table})",
+                    table = table
+                ),
+                [],
+            )?;
+            if changed > 0 {
+                reset.push(table);
+            }
+        }
+        Ok(reset)
+    }
+
+    fn request_count(&self) -> StorageResult<u64> {
+        let conn = self.conn.lock().unwrap();
+        let count = conn.query_row("SELECT COUNT(*) FROM requests", [], |row| row.get::<_, i64>(0))?;
+        Ok(count as u64)
+    }
+
+    fn database_size_bytes(&self) -> StorageResult<u64> {
+        let conn = self.conn.lock().unwrap();
+        let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let free_pages: i64 = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        Ok(((page_count - free_pages).max(0) * page_size) as u64)
+    }
+
+    fn vacuum(&self) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        Ok(())
+    }
+
+    fn check_connection(&self) -> StorageResult<()> {
+        self.connection()?.query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+}
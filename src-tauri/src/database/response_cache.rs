@@ -0,0 +1,46 @@
+// Response cache rows
+
+use super::Database;
+use crate::storage::{CacheStore, CachedResponse, StorageResult};
+use rusqlite::OptionalExtension;
+
+impl CacheStore for Database {
+    fn cached_response(&self, key: &str, oldest: i64) -> StorageResult<Option<CachedResponse>> {
+        let response = self
+            .connection()?
+            .query_row(
+                "SELECT status, headers, body FROM response_cache WHERE key = ?1 AND created_at >= ?2",
+                rusqlite::params![key, oldest],
+                |row| {
+                    Ok(CachedResponse {
+                        status: row.get(0)?,
+                        headers: row.get(1)?,
+                        body: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(response)
+    }
+
+    fn store_cached_response(&self, key: &str, backend: &str, response: &CachedResponse, created_at: i64) -> StorageResult<()> {
+        self.connection()?.execute(
+            "INSERT OR REPLACE INTO response_cache (key, backend, status, headers, body, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![key, backend, response.status, response.headers, response.body, created_at],
+        )?;
+        Ok(())
+    }
+
+    fn delete_cached_responses_before(&self, oldest: i64) -> StorageResult<usize> {
+        let deleted = self
+            .connection()?
+            .execute("DELETE FROM response_cache WHERE created_at < ?1", rusqlite::params![oldest])?;
+        Ok(deleted)
+    }
+
+    fn clear_cached_responses(&self) -> StorageResult<()> {
+        self.connection()?.execute("DELETE FROM response_cache", [])?;
+        Ok(())
+    }
+}
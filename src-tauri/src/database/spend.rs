@@ -0,0 +1,215 @@
+// Token budgets, model prices and the token usage they apply to
+
+use super::{backend_filter, Database, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED};
+use crate::budgets::{BudgetPeriod, BudgetScope, TokenBudget};
+use crate::pricing::{ModelPrice, TokenUsage};
+use crate::storage::{ModelUsage, SpendStore, StorageResult};
+use rusqlite::Connection;
+
+/// Requests whose tokens reached a model: not blocked, rate limited or served from the response cache
+const REACHED_MODEL: &str =
+    "COALESCE(dlp_action, 0) NOT IN (?2, ?3) AND COALESCE(response_cache, 0) = 0";
+
+/// Write prices, replacing the saved prices of those models; other models are kept
+pub(super) fn upsert_prices(conn: &Connection, prices: &[ModelPrice]) -> Result<(), rusqlite::Error> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = conn.prepare(
+        "INSERT INTO model_pricing (model, input_per_mtok, output_per_mtok, cache_read_per_mtok, cache_write_per_mtok, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(model) DO UPDATE SET
+            input_per_mtok = excluded.input_per_mtok,
+            output_per_mtok = excluded.output_per_mtok,
+            cache_read_per_mtok = excluded.cache_read_per_mtok,
+            cache_write_per_mtok = excluded.cache_write_per_mtok,
+            updated_at = excluded.updated_at",
+    )?;
+    for price in prices {
+        stmt.execute(rusqlite::params![
+            price.model,
+            price.input_per_mtok,
+            price.output_per_mtok,
+            price.cache_read_per_mtok,
+            price.cache_write_per_mtok,
+            now
+        ])?;
+    }
+    Ok(())
+}
+
+impl SpendStore for Database {
+    fn token_budgets(&self) -> StorageResult<Vec<TokenBudget>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, scope, target, period, limit_tokens, warn_percent FROM token_budgets ORDER BY id",
+        )?;
+        let budgets = stmt
+            .query_map([], |row| {
+                Ok(TokenBudget {
+                    id: row.get(0)?,
+                    scope: BudgetScope::from_name(&row.get::<_, String>(1)?),
+                    target: row.get(2)?,
+                    period: BudgetPeriod::from_name(&row.get::<_, String>(3)?),
+                    limit_tokens: row.get(4)?,
+                    warn_percent: row.get::<_, i64>(5)?.clamp(0, 100) as u8,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(budgets)
+    }
+
+    fn add_token_budget(&self, budget: &TokenBudget) -> StorageResult<i64> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO token_budgets (scope, target, period, limit_tokens, warn_percent, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                budget.scope.as_str(),
+                budget.target,
+                budget.period.as_str(),
+                budget.limit_tokens,
+                budget.warn_percent,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn delete_token_budget(&self, id: i64) -> StorageResult<()> {
+        self.connection()?
+            .execute("DELETE FROM token_budgets WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
+
+    fn tokens_used(&self, budget: &TokenBudget, since: &str) -> StorageResult<i64> {
+        let conn = self.connection()?;
+        let counted = format!(
+            "SELECT COALESCE(SUM(COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0)), 0)
+             FROM requests WHERE timestamp >= ?1 AND {}",
+            REACHED_MODEL
+        );
+        let used = match budget.scope {
+            BudgetScope::Global => conn.query_row(
+                &counted,
+                rusqlite::params![since, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED],
+                |row| row.get(0),
+            ),
+            BudgetScope::Backend => conn.query_row(
+                &format!("{} AND backend = ?4", counted),
+                rusqlite::params![since, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED, budget.target],
+                |row| row.get(0),
+            ),
+            BudgetScope::User => conn.query_row(
+                &format!(
+                    "{} AND json_valid(extra_metadata) AND lower(json_extract(extra_metadata, '$.user_email')) = lower(?4)",
+                    counted
+                ),
+                rusqlite::params![since, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED, budget.target],
+                |row| row.get(0),
+            ),
+        }?;
+        Ok(used)
+    }
+
+    fn model_usage(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<ModelUsage>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT substr(timestamp, 1, 10), backend, COALESCE(model, 'unknown'), COUNT(*),
+                    COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0),
+                    COALESCE(SUM(cache_read_tokens), 0), COALESCE(SUM(cache_creation_tokens), 0)
+             FROM requests
+             WHERE timestamp >= ?1 AND {}
+                   AND COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0) > 0{}
+             GROUP BY 1, 2, 3",
+            REACHED_MODEL,
+            backend_filter("backend", backend)
+        ))?;
+        let usage = stmt
+            .query_map(rusqlite::params![since, DLP_ACTION_BLOCKED, DLP_ACTION_RATELIMITED], |row| {
+                Ok(ModelUsage {
+                    date: row.get(0)?,
+                    backend: row.get(1)?,
+                    model: row.get(2)?,
+                    requests: row.get(3)?,
+                    usage: TokenUsage {
+                        input: row.get(4)?,
+                        output: row.get(5)?,
+                        cache_read: row.get(6)?,
+                        cache_creation: row.get(7)?,
+                    },
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(usage)
+    }
+
+    fn model_prices(&self) -> StorageResult<Vec<ModelPrice>> {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, model, input_per_mtok, output_per_mtok, cache_read_per_mtok, cache_write_per_mtok
+             FROM model_pricing ORDER BY model",
+        )?;
+        let prices = stmt
+            .query_map([], |row| {
+                Ok(ModelPrice {
+                    id: row.get(0)?,
+                    model: row.get(1)?,
+                    input_per_mtok: row.get(2)?,
+                    output_per_mtok: row.get(3)?,
+                    cache_read_per_mtok: row.get(4)?,
+                    cache_write_per_mtok: row.get(5)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(prices)
+    }
+
+    fn add_model_price(&self, price: &ModelPrice) -> StorageResult<i64> {
+        let conn = self.connection()?;
+        conn.execute(
+            "INSERT INTO model_pricing (model, input_per_mtok, output_per_mtok, cache_read_per_mtok, cache_write_per_mtok, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                price.model,
+                price.input_per_mtok,
+                price.output_per_mtok,
+                price.cache_read_per_mtok,
+                price.cache_write_per_mtok,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn update_model_price(&self, price: &ModelPrice) -> StorageResult<()> {
+        self.connection()?.execute(
+            "UPDATE model_pricing SET model = ?1, input_per_mtok = ?2, output_per_mtok = ?3,
+                cache_read_per_mtok = ?4, cache_write_per_mtok = ?5, updated_at = ?6
+             WHERE id = ?7",
+            rusqlite::params![
+                price.model,
+                price.input_per_mtok,
+                price.output_per_mtok,
+                price.cache_read_per_mtok,
+                price.cache_write_per_mtok,
+                chrono::Utc::now().to_rfc3339(),
+                price.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_model_price(&self, id: i64) -> StorageResult<()> {
+        self.connection()?
+            .execute("DELETE FROM model_pricing WHERE id = ?1", rusqlite::params![id])?;
+        Ok(())
+    }
+
+    fn upsert_model_prices(&self, prices: &[ModelPrice]) -> StorageResult<()> {
+        let conn = self.connection()?;
+        upsert_prices(&conn, prices)?;
+        Ok(())
+    }
+}
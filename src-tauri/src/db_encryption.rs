@@ -0,0 +1,206 @@
+// Database Encryption at Rest
+//
+// The request log holds full prompts and the secrets found in them, so the database can be
+// encrypted with SQLCipher (builds with the `sqlcipher` feature). The 256-bit key is generated on
+// this machine and kept in the OS keychain; every connection is keyed before it touches the file.
+//
+// An existing plaintext database is encrypted the next time the app starts, before anything opens
+// it: enabling stores the new key as pending, and at startup the database is exported into an
+// encrypted copy (sqlcipher_export) that replaces the original, and only then is the key made
+// active. A plaintext file is recognised by its SQLite header, so an interrupted migration is
+// finished rather than repeated.
+
+use crate::api_keys::KEYCHAIN_SERVICE;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Keychain entry holding the key of the encrypted database
+const KEYCHAIN_ACCOUNT: &str = "database-key";
+
+/// Keychain entry holding a key waiting for the database to be encrypted with it
+const PENDING_KEYCHAIN_ACCOUNT: &str = "database-key-pending";
+
+/// First bytes of every plaintext SQLite database; an encrypted one starts with random salt
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Read once, after any pending migration
+static ACTIVE_KEY: OnceLock<Option<String>> = OnceLock::new();
+
+fn keychain_entry(account: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, account).map_err(|e| e.to_string())
+}
+
+fn read_key(account: &str) -> Result<Option<String>, String> {
+    match keychain_entry(account)?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether this build can encrypt the database
+fn available() -> bool {
+    cfg!(feature = "sqlcipher")
+}
+
+fn active_key() -> Option<&'static str> {
+    ACTIVE_KEY
+        .get_or_init(|| {
+            if !available() {
+                return None;
+            }
+            read_key(KEYCHAIN_ACCOUNT).unwrap_or_else(|e| {
+                eprintln!("[DB] Failed to read the database key from the keychain: {}", e);
+                None
+            })
+        })
+        .as_deref()
+}
+
+/// A key in SQLCipher's raw key form, so it is used as is instead of being derived from a passphrase
+fn raw_key(hex_key: &str) -> String {
+    format!("x'{}'", hex_key)
+}
+
+/// Key a new connection; must run before the connection reads anything
+pub fn apply_key(conn: &Connection) -> Result<(), rusqlite::Error> {
+    if let Some(key) = active_key() {
+        conn.pragma_update(None, "key", raw_key(key))?;
+    }
+    Ok(())
+}
+
+/// Whether the file is an unencrypted SQLite database
+fn is_plaintext(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| &header == SQLITE_HEADER)
+}
+
+/// Encryption state, for the UI
+#[derive(Debug, Serialize)]
+pub struct EncryptionStatus {
+    /// Whether this build supports encryption
+    pub available: bool,
+    pub encrypted: bool,
+    /// Enabled, applied at the next start
+    pub pending: bool,
+}
+
+pub fn status() -> EncryptionStatus {
+    EncryptionStatus {
+        available: available(),
+        encrypted: active_key().is_some(),
+        pending: available() && read_key(PENDING_KEYCHAIN_ACCOUNT).ok().flatten().is_some(),
+    }
+}
+
+/// Generate a key for the database and store it as pending; the database is encrypted with it the
+/// next time the app starts
+pub fn enable() -> Result<EncryptionStatus, String> {
+    use aes_gcm::aead::{KeyInit, OsRng};
+    use aes_gcm::Aes256Gcm;
+
+    if !available() {
+        return Err("This build doesn't include database encryption (SQLCipher)".to_string());
+    }
+    if active_key().is_some() {
+        return Err("The database is already encrypted".to_string());
+    }
+    if read_key(PENDING_KEYCHAIN_ACCOUNT)?.is_none() {
+        let key = hex::encode(Aes256Gcm::generate_key(OsRng));
+        keychain_entry(PENDING_KEYCHAIN_ACCOUNT)?
+            .set_password(&key)
+            .map_err(|e| format!("Failed to store the database key in the keychain: {}", e))?;
+    }
+    Ok(status())
+}
+
+/// Export the plaintext database at `path` into an encrypted copy and put the copy in its place
+fn encrypt_file(path: &Path, hex_key: &str) -> Result<(), String> {
+    let encrypted = path.with_extension("db-encrypting");
+    let _ = fs::remove_file(&encrypted);
+
+    {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        sqlite_zstd::load(&conn).map_err(|e| format!("Failed to load sqlite-zstd: {}", e))?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            rusqlite::params![encrypted.to_string_lossy(), raw_key(hex_key)],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+        conn.execute("DETACH DATABASE encrypted", []).map_err(|e| e.to_string())?;
+    }
+
+    // The plaintext WAL must not be replayed into the encrypted file
+    for suffix in ["-wal", "-shm"] {
+        let mut side_file = path.as_os_str().to_owned();
+        side_file.push(suffix);
+        let _ = fs::remove_file(side_file);
+    }
+    fs::rename(&encrypted, path).map_err(|e| e.to_string())
+}
+
+/// Finish enabling encryption: encrypt the database with the pending key if it is still plaintext,
+/// then make that key the active one. Runs at startup, before the database is first opened.
+pub fn apply_pending(path: &str) {
+    if !available() {
+        return;
+    }
+    let key = match read_key(PENDING_KEYCHAIN_ACCOUNT) {
+        Ok(Some(key)) => key,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("[DB] Failed to read the pending database key: {}", e);
+            return;
+        }
+    };
+
+    let path = Path::new(path);
+    if path.exists() && is_plaintext(path) {
+        println!("[DB] Encrypting the database...");
+        if let Err(e) = encrypt_file(path, &key) {
+            eprintln!("[DB] Failed to encrypt the database, keeping it unencrypted: {}", e);
+            return;
+        }
+    }
+
+    let promoted = keychain_entry(KEYCHAIN_ACCOUNT)
+        .and_then(|entry| entry.set_password(&key).map_err(|e| e.to_string()))
+        .and_then(|_| keychain_entry(PENDING_KEYCHAIN_ACCOUNT))
+        .and_then(|entry| entry.delete_credential().map_err(|e| e.to_string()));
+    match promoted {
+        Ok(()) => println!("[DB] Database encryption enabled"),
+        Err(e) => eprintln!("[DB] Failed to store the database key: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plaintext_detection() {
+        let dir = std::env::temp_dir().join(format!("llmwatcher-db-encryption-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let plain = dir.join("plain.db");
+        Connection::open(&plain).unwrap().execute_batch("CREATE TABLE t (x)").unwrap();
+        assert!(is_plaintext(&plain));
+
+        let scrambled = dir.join("scrambled.db");
+        fs::write(&scrambled, [0x5au8; 64]).unwrap();
+        assert!(!is_plaintext(&scrambled));
+        assert!(!is_plaintext(&dir.join("missing.db")));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(raw_key("00ff"), "x'00ff'");
+    }
+}
@@ -0,0 +1,219 @@
+// Dictionary-Based PII Detectors
+//
+// Names and street addresses have no fixed shape: a regex alone either misses them or
+// flags every capitalized word pair. A dictionary detector finds candidates with a regex
+// built from a bundled wordlist, then scores each candidate by the other wordlists and the
+// surrounding context. Candidates below the detector's minimum score are dropped.
+//
+// Used by patterns with pattern_type "dictionary"; each pattern line names a detector.
+
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+const FIRST_NAMES: &str = include_str!("wordlists/first_names.txt");
+const LAST_NAMES: &str = include_str!("wordlists/last_names.txt");
+const STREET_SUFFIXES: &str = include_str!("wordlists/street_suffixes.txt");
+const CITIES: &str = include_str!("wordlists/cities.txt");
+
+/// How far (in bytes) before a candidate to look for titles and context keywords
+const CONTEXT_BEFORE: usize = 40;
+
+/// How far (in bytes) after a street to look for the city, state and ZIP code
+const CONTEXT_AFTER: usize = 60;
+
+/// Words right before a name that mark it as a person
+const HONORIFICS: &[&str] = &["mr", "mrs", "ms", "miss", "dr", "prof", "sir"];
+
+const NAME_CONTEXT: &[&str] = &[
+    "name", "patient", "customer", "client", "employee", "applicant", "contact", "dear",
+    "signed", "sincerely", "regards", "attn",
+];
+
+const ADDRESS_CONTEXT: &[&str] = &[
+    "address", "ship", "deliver", "mail", "lives", "located", "residence", "billing",
+];
+
+/// Non-empty, non-comment lines of a wordlist
+fn words(list: &'static str) -> impl Iterator<Item = &'static str> {
+    list.lines()
+        .map(str::trim)
+        .filter(|w| !w.is_empty() && !w.starts_with('#'))
+}
+
+/// `\b(?:a|b|...)\b` over a wordlist, longest words first
+fn alternation(list: &'static str) -> String {
+    let mut list: Vec<&str> = words(list).collect();
+    list.sort_by_key(|w| std::cmp::Reverse(w.len()));
+    let escaped: Vec<String> = list.iter().map(|w| regex::escape(w)).collect();
+    format!(r"\b(?:{})\b", escaped.join("|"))
+}
+
+fn last_names() -> &'static HashSet<&'static str> {
+    static SET: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    SET.get_or_init(|| words(LAST_NAMES).collect())
+}
+
+fn city_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(&alternation(CITIES)).expect("city wordlist compiles"))
+}
+
+fn state_zip_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b[A-Z]{2}\s+\d{5}(?:-\d{4})?\b").expect("state/ZIP regex compiles"))
+}
+
+/// Up to `len` bytes of text before `start`, on a char boundary
+fn window_before(text: &str, start: usize, len: usize) -> &str {
+    let mut from = start.saturating_sub(len);
+    while !text.is_char_boundary(from) {
+        from += 1;
+    }
+    &text[from..start]
+}
+
+/// Up to `len` bytes of text after `end`, on a char boundary
+fn window_after(text: &str, end: usize, len: usize) -> &str {
+    let mut to = (end + len).min(text.len());
+    while !text.is_char_boundary(to) {
+        to -= 1;
+    }
+    &text[end..to]
+}
+
+fn contains_any_word(window: &str, keywords: &[&str]) -> bool {
+    window
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|w| keywords.iter().any(|k| w.eq_ignore_ascii_case(k)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DictionaryDetector {
+    /// A known first name followed by a capitalized surname
+    PersonName,
+    /// A house number, street name and known street suffix
+    StreetAddress,
+}
+
+impl DictionaryDetector {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "person_name" => Some(DictionaryDetector::PersonName),
+            "street_address" => Some(DictionaryDetector::StreetAddress),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DictionaryDetector::PersonName => "person_name",
+            DictionaryDetector::StreetAddress => "street_address",
+        }
+    }
+
+    /// Regex that finds candidates for this detector
+    pub fn candidate_regex(&self) -> String {
+        match self {
+            DictionaryDetector::PersonName => format!(
+                r"{}(?:\s+[A-Z]\.)?\s+[A-Z][a-z]+(?:[-'][A-Z][a-z]+)?\b",
+                alternation(FIRST_NAMES)
+            ),
+            DictionaryDetector::StreetAddress => format!(
+                r"\b\d{{1,6}}\s+(?:(?:[A-Z][a-z]+|\d+(?:st|nd|rd|th))\s+){{1,4}}{}\.?",
+                alternation(STREET_SUFFIXES)
+            ),
+        }
+    }
+
+    /// Minimum score for a candidate to be reported
+    pub fn min_score(&self) -> u32 {
+        3
+    }
+
+    /// Proximity score of the candidate at text[start..end]
+    /// Person names: first name 2 (guaranteed by the regex), known surname +2, title right before +1,
+    /// context keyword nearby +1.
+    /// Street addresses: number and suffix 2 (guaranteed), city after +1, state and ZIP after +1,
+    /// context keyword before +1.
+    pub fn score(&self, text: &str, start: usize, end: usize) -> u32 {
+        let before = window_before(text, start, CONTEXT_BEFORE);
+        let mut score = 2;
+
+        match self {
+            DictionaryDetector::PersonName => {
+                let surname = text[start..end].split_whitespace().last().unwrap_or("");
+                if last_names().contains(surname) {
+                    score += 2;
+                }
+                let previous_word = before
+                    .split(|c: char| !c.is_alphanumeric())
+                    .rfind(|w| !w.is_empty())
+                    .unwrap_or("");
+                if HONORIFICS.iter().any(|h| previous_word.eq_ignore_ascii_case(h)) {
+                    score += 1;
+                }
+                if contains_any_word(before, NAME_CONTEXT) {
+                    score += 1;
+                }
+            }
+            DictionaryDetector::StreetAddress => {
+                let after = window_after(text, end, CONTEXT_AFTER);
+                if city_regex().is_match(after) {
+                    score += 1;
+                }
+                if state_zip_regex().is_match(after) {
+                    score += 1;
+                }
+                if contains_any_word(before, ADDRESS_CONTEXT) {
+                    score += 1;
+                }
+            }
+        }
+
+        score
+    }
+
+    /// Whether the candidate scores high enough to be reported
+    pub fn accepts(&self, text: &str, start: usize, end: usize) -> bool {
+        self.score(text, start, end) >= self.min_score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(detector: DictionaryDetector, text: &str) -> Vec<String> {
+        let re = Regex::new(&detector.candidate_regex()).unwrap();
+        re.find_iter(text)
+            .filter(|m| detector.accepts(text, m.start(), m.end()))
+            .map(|m| m.as_str().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_person_name() {
+        let names = DictionaryDetector::PersonName;
+        assert_eq!(detect(names, "Please email John Smith today"), vec!["John Smith"]);
+        assert_eq!(detect(names, "Dear Mr. Karen Okafor,"), vec!["Karen Okafor"]);
+        assert_eq!(detect(names, "Patient: Emily Nakamura"), vec!["Emily Nakamura"]);
+        // Known first name, unknown surname and no context
+        assert!(detect(names, "Mark Down is a format").is_empty());
+    }
+
+    #[test]
+    fn test_street_address() {
+        let addresses = DictionaryDetector::StreetAddress;
+        assert_eq!(
+            detect(addresses, "Ship to 742 Evergreen Terrace, Springfield"),
+            vec!["742 Evergreen Terrace"]
+        );
+        assert_eq!(
+            detect(addresses, "1600 Pennsylvania Ave NW, Washington, DC 20500"),
+            vec!["1600 Pennsylvania Ave"]
+        );
+        // Street shape alone is not enough
+        assert!(detect(addresses, "See 221 Main St for the fixture").is_empty());
+    }
+}
@@ -1,11 +1,139 @@
 // DLP (Data Loss Prevention) Redaction Logic
 
-use crate::database::open_connection;
+use crate::canary::find_canaries;
+use crate::commands::dlp::DlpPattern;
+use crate::database::{
+    get_dlp_action_from_db, get_dlp_min_block_confidence_from_db, get_dlp_ner_settings_from_db,
+    get_dlp_scan_limits_from_db, get_dlp_severity_policy_from_db,
+    DLP_ACTION_ALERTED, DLP_ACTION_BLOCKED, DLP_ACTION_PASSED, DLP_ACTION_REDACTED,
+};
 use crate::pattern_utils::{
-    compile_pattern_set, count_unique_chars, is_match_excluded_by_context,
+    compile_pattern_set, collect_matches_with_negative_context, find_base64_segments, match_confidence,
+    select_non_overlapping_spans, Allowlist, MatchFilters, MatchResult,
 };
-use regex::Regex;
+use crate::dlp_policy::{load_dlp_policies, select_policy, DlpPolicy};
+use crate::dlp_vault::TokenVault;
+use crate::ocr;
+use crate::prompt_injection::find_prompt_injections;
+use crate::storage::{self, DlpStore};
+use crate::validators::Validator;
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// What to do with a request when a pattern matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DlpAction {
+    /// Replace the value with a placeholder (restored in the response)
+    Redact,
+    /// Reject the whole request
+    Block,
+    /// Record a detection but forward the value unchanged
+    Alert,
+    /// Exempt matched values from every other pattern (e.g. documented sample keys)
+    Allow,
+}
+
+impl DlpAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "redact" => Some(DlpAction::Redact),
+            "block" => Some(DlpAction::Block),
+            "alert" => Some(DlpAction::Alert),
+            "allow" => Some(DlpAction::Allow),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DlpAction::Redact => "redact",
+            DlpAction::Block => "block",
+            DlpAction::Alert => "alert",
+            DlpAction::Allow => "allow",
+        }
+    }
+}
+
+/// How serious a pattern's matches are; drives the global severity policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(Severity::Low),
+            "medium" => Some(Severity::Medium),
+            "high" => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Which part of a request a pattern is matched against
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PatternScope {
+    /// Message text, tool outputs and file contents
+    #[default]
+    Content,
+    /// File paths read through Cursor hooks (the file itself and attachments)
+    Path,
+    /// Proxied request headers, one "name: value" line per header
+    Header,
+}
+
+impl PatternScope {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "content" => Some(PatternScope::Content),
+            "path" => Some(PatternScope::Path),
+            "header" => Some(PatternScope::Header),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PatternScope::Content => "content",
+            PatternScope::Path => "path",
+            PatternScope::Header => "header",
+        }
+    }
+}
+
+/// Whether a detection was found in the request sent upstream or in the model's response
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DlpDirection {
+    Request,
+    Response,
+}
+
+impl DlpDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DlpDirection::Request => "request",
+            DlpDirection::Response => "response",
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct DlpDetection {
@@ -14,6 +142,13 @@ pub struct DlpDetection {
     pub original_value: String,
     pub placeholder: String,
     pub message_index: Option<i32>,
+    pub category: String, // "secret", "pii" or "entity"
+    pub metadata: Option<String>, // JSON extracted by the validator (e.g. JWT claims)
+    pub action: DlpAction,
+    pub severity: Severity,
+    pub direction: DlpDirection,
+    /// 0.0-1.0, from the validator, nearby credential keywords and entropy
+    pub confidence: f64,
 }
 
 #[derive(Clone)]
@@ -21,60 +156,347 @@ pub struct DlpRedactionResult {
     pub redacted_body: String,
     pub replacements: HashMap<String, String>, // placeholder -> original
     pub detections: Vec<DlpDetection>,
+    /// Why (part of) the body was forwarded unscanned, if a scan limit was hit
+    pub scan_skipped: Option<String>,
+}
+
+/// Detections of a detection-only scan (see `check_dlp_patterns`)
+#[derive(Default)]
+pub struct DlpCheckResult {
+    pub detections: Vec<DlpDetection>,
+    /// Why (part of) the text was not scanned, if a scan limit was hit
+    pub scan_skipped: Option<String>,
+}
+
+impl DlpCheckResult {
+    /// Add the result of scanning another text; the first skip reason is kept
+    pub fn merge(&mut self, other: DlpCheckResult) {
+        self.detections.extend(other.detections);
+        if self.scan_skipped.is_none() {
+            self.scan_skipped = other.scan_skipped;
+        }
+    }
+
+    fn skip(&mut self, reason: String) {
+        if self.scan_skipped.is_none() {
+            eprintln!("[DLP] Scan skipped: {}", reason);
+            self.scan_skipped = Some(reason);
+        }
+    }
+}
+
+/// Limits that keep a huge body or a slow custom pattern from stalling the proxy (0 = no limit)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DlpScanLimits {
+    /// Request bodies larger than this are forwarded unscanned
+    pub max_body_bytes: usize,
+    /// Single string values larger than this (e.g. base64 images) are not scanned
+    pub max_value_bytes: usize,
+    /// Scanning stops once a request has taken this long; checked between values and patterns
+    pub time_budget_ms: u64,
+}
+
+impl Default for DlpScanLimits {
+    fn default() -> Self {
+        DlpScanLimits {
+            max_body_bytes: 8 * 1024 * 1024,
+            max_value_bytes: 1024 * 1024,
+            time_budget_ms: 2000,
+        }
+    }
+}
+
+/// How many layers of base64 are decoded (e.g. a base64 file holding a base64 config value)
+const MAX_BASE64_DEPTH: usize = 2;
+
+/// Per-request scan state: the token vault, the limits and the first limit that was hit
+struct ScanContext {
+    vault: TokenVault,
+    limits: DlpScanLimits,
+    started: Instant,
+    skipped: Option<String>,
+    /// Base64 layers around the text being scanned
+    base64_depth: usize,
+    /// Read image blocks with OCR (see DlpScanScope::block_image_secrets)
+    scan_images: bool,
+}
+
+impl ScanContext {
+    fn new() -> Self {
+        ScanContext {
+            vault: TokenVault::open(),
+            limits: get_dlp_scan_limits_from_db(),
+            started: Instant::now(),
+            skipped: None,
+            base64_depth: 0,
+            scan_images: false,
+        }
+    }
+
+    /// Whether the scan is still within its time budget; records the skip otherwise
+    fn within_time_budget(&mut self) -> bool {
+        let budget = self.limits.time_budget_ms;
+        if budget > 0 && self.started.elapsed() > Duration::from_millis(budget) {
+            self.skip(format!("scan exceeded the {} ms time budget", budget));
+            return false;
+        }
+        true
+    }
+
+    /// Whether text can be scanned; records why not otherwise
+    fn can_scan(&mut self, text: &str) -> bool {
+        let max = self.limits.max_value_bytes;
+        if max > 0 && text.len() > max {
+            self.skip(format!("value of {} bytes exceeds the {} byte limit", text.len(), max));
+            return false;
+        }
+        self.within_time_budget()
+    }
+
+    fn skip(&mut self, reason: String) {
+        if self.skipped.is_none() {
+            eprintln!("[DLP] Scan skipped: {}", reason);
+            self.skipped = Some(reason);
+        }
+    }
 }
 
 /// Compiled DLP pattern with all validation rules
 #[derive(Clone)]
 pub struct CompiledDlpPattern {
+    pub id: i64,
+    /// Globally enabled; a DLP policy can still turn the pattern on or off per backend and model
+    pub enabled: bool,
     pub name: String,
     pub pattern_type: String,
     pub regexes: Vec<Regex>,
     pub negative_regexes: Vec<Regex>,
     pub min_occurrences: i32,
-    pub min_unique_chars: i32,
+    pub filters: MatchFilters,
+    pub category: String,
+    pub action: DlpAction,
+    pub severity: Severity,
+    pub scope: PatternScope,
+    /// "block" detections below this confidence are downgraded to alerts (global setting)
+    pub min_block_confidence: f64,
 }
 
-/// Get all enabled DLP patterns from database
-pub fn get_enabled_dlp_patterns() -> Vec<CompiledDlpPattern> {
-    let mut patterns: Vec<CompiledDlpPattern> = Vec::new();
+/// Enabled patterns plus a RegexSet over all their positive regexes
+/// The set scans a text once to find which patterns can match at all, so large
+/// prompts only pay for the full match, context and validator pass of candidate patterns
+pub struct DlpPatternSet {
+    pub patterns: Vec<CompiledDlpPattern>,
+    /// None if the combined set fails to compile (e.g. exceeds the size limit); every pattern is then a candidate
+    prefilter: Option<RegexSet>,
+    /// Index into `patterns` for each regex in the prefilter
+    prefilter_owners: Vec<usize>,
+}
 
-    let conn = match open_connection() {
-        Ok(c) => c,
-        Err(_) => return patterns,
-    };
+impl DlpPatternSet {
+    pub fn new(patterns: Vec<CompiledDlpPattern>) -> Self {
+        let mut sources = Vec::new();
+        let mut prefilter_owners = Vec::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            for regex in &pattern.regexes {
+                sources.push(regex.as_str());
+                prefilter_owners.push(idx);
+            }
+        }
 
-    let mut stmt = match conn.prepare(
-        "SELECT name, pattern_type, patterns, negative_pattern_type, negative_patterns,
-                min_occurrences, min_unique_chars
-         FROM dlp_patterns WHERE enabled = 1",
-    ) {
-        Ok(s) => s,
-        Err(_) => return patterns,
-    };
+        let prefilter = match RegexSet::new(&sources) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                eprintln!("[DLP] Pattern pre-filter unavailable, scanning with every pattern: {}", e);
+                None
+            }
+        };
 
-    let db_patterns: Vec<(String, String, String, Option<String>, Option<String>, i32, i32)> = stmt
-        .query_map([], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, Option<String>>(3)?,
-                row.get::<_, Option<String>>(4)?,
-                row.get::<_, i32>(5)?,
-                row.get::<_, i32>(6)?,
-            ))
-        })
-        .ok()
-        .map(|iter| iter.filter_map(|r| r.ok()).collect())
-        .unwrap_or_default();
+        DlpPatternSet {
+            patterns,
+            prefilter,
+            prefilter_owners,
+        }
+    }
 
-    for (name, pattern_type, patterns_json, negative_pattern_type, negative_patterns_json, min_occurrences, min_unique_chars) in db_patterns {
-        let pattern_list: Vec<String> = serde_json::from_str(&patterns_json).unwrap_or_default();
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
 
-        // Parse negative patterns if present
-        let neg_pattern_list: Option<Vec<String>> = negative_patterns_json
+    /// Patterns with at least one regex matching somewhere in text, in pattern order
+    pub fn candidates(&self, text: &str) -> Vec<&CompiledDlpPattern> {
+        let Some(prefilter) = &self.prefilter else {
+            return self.patterns.iter().collect();
+        };
+
+        // "ner" patterns have no regex, and normalized patterns match folded text the prefilter
+        // never sees, so both are always candidates
+        let mut matched: Vec<bool> = self
+            .patterns
+            .iter()
+            .map(|p| !p.filters.entities.is_empty() || p.filters.normalize)
+            .collect();
+        for regex_idx in prefilter.matches(text).iter() {
+            matched[self.prefilter_owners[regex_idx]] = true;
+        }
+
+        self.patterns
+            .iter()
+            .zip(matched)
+            .filter_map(|(pattern, is_candidate)| is_candidate.then_some(pattern))
+            .collect()
+    }
+}
+
+/// Where scanned content is going; selects the DLP policies that apply
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DlpTarget<'a> {
+    /// Backend name ("claude", a custom backend's name, "cursor-hooks"); empty if unknown
+    pub backend: &'a str,
+    pub model: Option<&'a str>,
+}
+
+/// Policies that apply to a target, by index: targets matching the same policies get the same patterns
+type PolicyProfile = Vec<usize>;
+
+/// Compiled patterns and policies, plus the pattern set resolved for each policy profile and scope
+/// seen so far. Sets are keyed by profile rather than by target, so clients sending arbitrary
+/// model names don't grow the cache: there are at most as many entries as policy combinations.
+struct PatternCache {
+    /// Every pattern, enabled or not: a policy can turn a disabled pattern on for one target
+    patterns: Vec<CompiledDlpPattern>,
+    policies: Vec<DlpPolicy>,
+    sets: HashMap<(PolicyProfile, PatternScope), Arc<DlpPatternSet>>,
+}
+
+impl PatternCache {
+    fn load() -> Self {
+        PatternCache {
+            patterns: load_dlp_patterns(),
+            policies: load_dlp_policies(),
+            sets: HashMap::new(),
+        }
+    }
+
+    fn profile(&self, target: DlpTarget) -> PolicyProfile {
+        self.policies
+            .iter()
+            .enumerate()
+            .filter(|(_, policy)| policy.applies_to(target.backend, target.model))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Patterns of a scope for a target: the most specific matching policy sets each pattern's
+    /// action (or turns it off); patterns without one keep their global state
+    fn resolve(&self, target: DlpTarget, scope: PatternScope) -> DlpPatternSet {
+        let patterns = self
+            .patterns
+            .iter()
+            .filter(|pattern| pattern.scope == scope)
+            .filter_map(|pattern| {
+                match select_policy(&self.policies, pattern.id, target.backend, target.model) {
+                    Some(policy) => policy.pattern_action().map(|action| CompiledDlpPattern {
+                        action,
+                        ..pattern.clone()
+                    }),
+                    None => pattern.enabled.then(|| pattern.clone()),
+                }
+            })
+            .collect();
+        DlpPatternSet::new(patterns)
+    }
+}
+
+/// Compiled patterns, shared by all requests until patterns, policies or DLP settings change
+static PATTERN_CACHE: RwLock<Option<PatternCache>> = RwLock::new(None);
+
+/// Bumped on every invalidation, so callers can tell which pattern configuration is live
+static PATTERN_CACHE_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Get the content DLP patterns that apply to a target, compiling them from the database on first use
+pub fn get_dlp_patterns_for(target: DlpTarget) -> Arc<DlpPatternSet> {
+    get_scoped_dlp_patterns_for(target, PatternScope::Content)
+}
+
+/// Get the DLP patterns of a scope that apply to a target
+fn get_scoped_dlp_patterns_for(target: DlpTarget, scope: PatternScope) -> Arc<DlpPatternSet> {
+    let cached = PATTERN_CACHE.read().ok().and_then(|cache| {
+        cache
             .as_ref()
-            .and_then(|json| serde_json::from_str(json).ok());
+            .and_then(|c| c.sets.get(&(c.profile(target), scope)).cloned())
+    });
+    if let Some(patterns) = cached {
+        return patterns;
+    }
+
+    let Ok(mut cache) = PATTERN_CACHE.write() else {
+        return Arc::new(PatternCache::load().resolve(target, scope));
+    };
+    let cache = cache.get_or_insert_with(PatternCache::load);
+    let key = (cache.profile(target), scope);
+    if let Some(patterns) = cache.sets.get(&key) {
+        return patterns.clone();
+    }
+    let patterns = Arc::new(cache.resolve(target, scope));
+    cache.sets.insert(key, patterns.clone());
+    patterns
+}
+
+/// Drop the compiled pattern cache; called after patterns, policies, the DLP action or the severity policy change
+pub fn invalidate_dlp_pattern_cache() {
+    if let Ok(mut cache) = PATTERN_CACHE.write() {
+        *cache = None;
+    }
+    PATTERN_CACHE_VERSION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Version of the pattern cache (invalidations since startup) and whether it is compiled
+pub fn dlp_pattern_cache_status() -> (u64, bool) {
+    let loaded = PATTERN_CACHE.read().is_ok_and(|cache| cache.is_some());
+    (PATTERN_CACHE_VERSION.load(Ordering::Relaxed), loaded)
+}
+
+/// Compile all DLP patterns from the database, enabled or not
+fn load_dlp_patterns() -> Vec<CompiledDlpPattern> {
+    let mut patterns: Vec<CompiledDlpPattern> = Vec::new();
+
+    // Patterns without their own action follow the severity policy, then the global DLP action
+    let default_action = DlpAction::from_name(&get_dlp_action_from_db()).unwrap_or(DlpAction::Block);
+    let severity_policy = get_dlp_severity_policy_from_db();
+    let min_block_confidence = get_dlp_min_block_confidence_from_db();
+    let ner = get_dlp_ner_settings_from_db();
+
+    let db_patterns = match storage::shared().and_then(|store| store.dlp_patterns()) {
+        Ok(p) => p,
+        Err(_) => return patterns,
+    };
+
+    for pattern in db_patterns {
+        let DlpPattern {
+            id,
+            enabled,
+            name,
+            pattern_type,
+            patterns: pattern_list,
+            negative_pattern_type,
+            negative_patterns: neg_pattern_list,
+            min_occurrences,
+            min_unique_chars,
+            min_entropy,
+            validator: validator_name,
+            category,
+            action,
+            severity,
+            allowlist: allowlist_entries,
+            scope,
+            normalize,
+            ..
+        } = pattern;
 
         // Compile patterns using shared utility
         let compiled = match compile_pattern_set(
@@ -90,14 +512,65 @@ pub fn get_enabled_dlp_patterns() -> Vec<CompiledDlpPattern> {
             }
         };
 
-        if !compiled.regexes.is_empty() {
+        // Unknown validator names disable the pattern rather than letting unvalidated matches through
+        let validator = match validator_name.as_deref() {
+            Some(v) if !v.is_empty() => match Validator::from_name(v) {
+                Some(v) => Some(v),
+                None => {
+                    eprintln!("[DLP] Unknown validator '{}' for pattern '{}'", v, name);
+                    continue;
+                }
+            },
+            _ => None,
+        };
+
+        let allowlist = match Allowlist::compile(&allowlist_entries) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("[DLP] Error compiling allowlist for pattern '{}': {}", name, e);
+                continue;
+            }
+        };
+
+        let severity = Severity::from_name(&severity).unwrap_or(Severity::Medium);
+        let scope = PatternScope::from_name(&scope).unwrap_or_default();
+        let action = action
+            .as_deref()
+            .and_then(DlpAction::from_name)
+            .or_else(|| {
+                severity_policy
+                    .get(severity.name())
+                    .and_then(|a| DlpAction::from_name(a))
+            })
+            .unwrap_or(default_action);
+
+        // "ner" patterns only run while NER is turned on
+        let entities = if ner.enabled { compiled.entities } else { Vec::new() };
+
+        if !compiled.regexes.is_empty() || !entities.is_empty() {
             patterns.push(CompiledDlpPattern {
+                id,
+                enabled,
                 name,
                 pattern_type,
                 regexes: compiled.regexes,
                 negative_regexes: compiled.negative_regexes,
                 min_occurrences,
-                min_unique_chars,
+                filters: MatchFilters {
+                    min_unique_chars,
+                    min_entropy,
+                    validator,
+                    allowlist,
+                    dictionaries: compiled.dictionaries,
+                    entities,
+                    min_entity_score: ner.min_score,
+                    normalize,
+                },
+                category,
+                action,
+                severity,
+                scope,
+                min_block_confidence,
             });
         }
     }
@@ -106,11 +579,58 @@ pub fn get_enabled_dlp_patterns() -> Vec<CompiledDlpPattern> {
 }
 
 
-/// Apply DLP redaction to request body (only user messages, not system)
-/// Supports both Claude (messages array) and Codex (input array) formats
-pub fn apply_dlp_redaction(body: &str) -> DlpRedactionResult {
+/// Request parts scanned besides user messages and tool outputs (per-backend settings)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DlpScanScope {
+    /// `system` / `instructions` / `systemInstruction` fields and system or developer messages
+    pub system_prompt: bool,
+    /// The `tools` array; names and types are left intact so tool calls still resolve
+    pub tool_definitions: bool,
+    /// Assistant turns: text and tool call inputs (Claude tool_use, OpenAI tool_calls,
+    /// Codex function_call, Gemini functionCall)
+    pub assistant_turns: bool,
+    /// Read text in attached images (OCR) and block images containing detections
+    pub block_image_secrets: bool,
+}
+
+/// Top-level system prompt fields: Claude `system`, Responses/Assistants `instructions` and
+/// `additional_instructions`, Gemini `systemInstruction`
+const SYSTEM_PROMPT_KEYS: &[&str] = &[
+    "system",
+    "instructions",
+    "additional_instructions",
+    "systemInstruction",
+    "system_instruction",
+];
+
+/// Message roles that carry system prompts in messages / input arrays
+const SYSTEM_ROLES: &[&str] = &["system", "developer"];
+
+/// Keys in tool definitions whose values are identifiers the model must echo back exactly
+const TOOL_IDENTIFIER_KEYS: &[&str] = &["name", "type", "required"];
+
+/// Keys in content blocks whose values are identifiers or signatures checked by the API
+const BLOCK_IDENTIFIER_KEYS: &[&str] = &[
+    "type",
+    "id",
+    "tool_use_id",
+    "call_id",
+    "name",
+    "signature",
+    "thoughtSignature",
+    "thought_signature",
+];
+
+/// Content block types never rewritten: thinking blocks are signed, reasoning is encrypted
+const UNSCANNED_BLOCK_TYPES: &[&str] = &["thinking", "redacted_thinking", "reasoning"];
+
+/// Apply DLP redaction to request body (user messages, plus system prompts and tool
+/// definitions when the backend's scope includes them)
+/// Supports Claude (messages array), Codex (input array), Gemini (contents array) and
+/// Assistants API (thread message / run creation) formats
+pub fn apply_dlp_redaction(body: &str, scope: DlpScanScope, target: DlpTarget) -> DlpRedactionResult {
     println!("[DLP] Starting redaction...");
-    let patterns = get_enabled_dlp_patterns();
+    let patterns = get_dlp_patterns_for(target);
     println!("[DLP] Got {} pattern groups", patterns.len());
 
     if patterns.is_empty() {
@@ -119,6 +639,19 @@ pub fn apply_dlp_redaction(body: &str) -> DlpRedactionResult {
             redacted_body: body.to_string(),
             replacements: HashMap::new(),
             detections: Vec::new(),
+            scan_skipped: None,
+        };
+    }
+
+    let mut scan = ScanContext::new();
+    scan.scan_images = scope.block_image_secrets;
+    if scan.limits.max_body_bytes > 0 && body.len() > scan.limits.max_body_bytes {
+        scan.skip(format!("body of {} bytes exceeds the {} byte limit", body.len(), scan.limits.max_body_bytes));
+        return DlpRedactionResult {
+            redacted_body: body.to_string(),
+            replacements: HashMap::new(),
+            detections: Vec::new(),
+            scan_skipped: scan.skipped,
         };
     }
 
@@ -129,82 +662,114 @@ pub fn apply_dlp_redaction(body: &str) -> DlpRedactionResult {
                 redacted_body: body.to_string(),
                 replacements: HashMap::new(),
                 detections: Vec::new(),
+                scan_skipped: None,
             }
         }
     };
 
     let mut replacements: HashMap<String, String> = HashMap::new();
     let mut detections: Vec<DlpDetection> = Vec::new();
-    let mut counter = 1;
 
     // Process Claude format: messages array
     if let Some(messages) = json.get_mut("messages").and_then(|m| m.as_array_mut()) {
-        for (msg_idx, message) in messages.iter_mut().enumerate() {
-            // Only process user messages (skip assistant, system handled separately)
-            let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("");
-            if role != "user" {
-                continue;
-            }
+        redact_messages(messages, scope, &patterns, &mut replacements, &mut detections, &mut scan);
+    }
 
-            // Recursively process entire content structure
-            if let Some(content) = message.get_mut("content") {
-                redact_value_recursive(
-                    content,
+    // Process Assistants API: create message ({"role": "user", "content": ...}),
+    // create run (additional_messages) and create thread (and run) (messages / thread.messages)
+    if json.get("role").and_then(|r| r.as_str()) == Some("user") {
+        if let Some(content) = json.get_mut("content") {
+            redact_content_blocks(
+                content,
+                &patterns,
+                &mut replacements,
+                &mut detections,
+                &mut scan,
+                Some(0),
+            );
+        }
+    }
+    if let Some(messages) = json.get_mut("additional_messages").and_then(|m| m.as_array_mut()) {
+        redact_messages(messages, scope, &patterns, &mut replacements, &mut detections, &mut scan);
+    }
+    if let Some(messages) = json
+        .get_mut("thread")
+        .and_then(|t| t.get_mut("messages"))
+        .and_then(|m| m.as_array_mut())
+    {
+        redact_messages(messages, scope, &patterns, &mut replacements, &mut detections, &mut scan);
+    }
+
+    // Process Codex format: input array
+    if let Some(input) = json.get_mut("input").and_then(|m| m.as_array_mut()) {
+        for (item_idx, item) in input.iter_mut().enumerate() {
+            let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            let role = item.get("role").and_then(|r| r.as_str()).unwrap_or("");
+
+            // Field holding the scanned text, if this item is scanned
+            let field = match (item_type, role) {
+                // Process content array (contains {type: "input_text", text: "..."} items)
+                ("message", "user") => "content",
+                ("message", "assistant") if scope.assistant_turns => "content",
+                // Tool outputs may contain sensitive data echoed back
+                ("function_call_output" | "custom_tool_call_output", _) => "output",
+                // Tool inputs the model wrote in earlier turns
+                ("function_call", _) if scope.assistant_turns => "arguments",
+                ("custom_tool_call", _) if scope.assistant_turns => "input",
+                // Skip reasoning, system messages (see redact_system_prompts), etc.
+                _ => continue,
+            };
+
+            if let Some(value) = item.get_mut(field) {
+                redact_content_blocks(
+                    value,
                     &patterns,
                     &mut replacements,
                     &mut detections,
-                    &mut counter,
-                    Some(msg_idx as i32),
+                    &mut scan,
+                    Some(item_idx as i32),
                 );
             }
         }
     }
 
-    // Process Codex format: input array
-    if let Some(input) = json.get_mut("input").and_then(|m| m.as_array_mut()) {
-        for (item_idx, item) in input.iter_mut().enumerate() {
-            let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    // Process Gemini format: contents array (role defaults to "user" when omitted)
+    if let Some(contents) = json.get_mut("contents").and_then(|c| c.as_array_mut()) {
+        for (content_idx, content) in contents.iter_mut().enumerate() {
+            // Function responses are sent back with role "function" (or "user");
+            // model turns carry function calls and are only scanned when configured
+            let role = content.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+            let scanned = match role {
+                "user" | "function" => true,
+                "model" => scope.assistant_turns,
+                _ => false,
+            };
+            if !scanned {
+                continue;
+            }
 
-            match item_type {
-                "message" => {
-                    // Only process user messages
-                    let role = item.get("role").and_then(|r| r.as_str()).unwrap_or("");
-                    if role != "user" {
-                        continue;
-                    }
-
-                    // Process content array (contains {type: "input_text", text: "..."} items)
-                    if let Some(content) = item.get_mut("content") {
-                        redact_value_recursive(
-                            content,
-                            &patterns,
-                            &mut replacements,
-                            &mut detections,
-                            &mut counter,
-                            Some(item_idx as i32),
-                        );
-                    }
-                }
-                "function_call_output" => {
-                    // Function call outputs may contain sensitive data echoed back
-                    if let Some(output) = item.get_mut("output") {
-                        redact_value_recursive(
-                            output,
-                            &patterns,
-                            &mut replacements,
-                            &mut detections,
-                            &mut counter,
-                            Some(item_idx as i32),
-                        );
-                    }
-                }
-                _ => {
-                    // Skip reasoning, function_call, etc.
-                }
+            if let Some(parts) = content.get_mut("parts") {
+                redact_content_blocks(
+                    parts,
+                    &patterns,
+                    &mut replacements,
+                    &mut detections,
+                    &mut scan,
+                    Some(content_idx as i32),
+                );
             }
         }
     }
 
+    if scope.system_prompt {
+        redact_system_prompts(&mut json, &patterns, &mut replacements, &mut detections, &mut scan);
+    }
+    if scope.tool_definitions {
+        if let Some(tools) = json.get_mut("tools") {
+            redact_tool_definitions(tools, &patterns, &mut replacements, &mut detections, &mut scan);
+        }
+    }
+
     println!(
         "[DLP] Redaction complete. {} detections, {} replacements",
         detections.len(),
@@ -214,159 +779,510 @@ pub fn apply_dlp_redaction(body: &str) -> DlpRedactionResult {
         redacted_body: serde_json::to_string(&json).unwrap_or_else(|_| body.to_string()),
         replacements,
         detections,
+        scan_skipped: scan.skipped,
+    }
+}
+
+/// Apply DLP redaction to a single Realtime API client event
+/// `conversation.item.create` events carrying user messages or function call outputs are
+/// scanned; assistant messages and function calls, and `session.update` instructions and tools,
+/// when the backend's scope includes them.
+/// Replacements are shared across the whole WebSocket session so server events can be
+/// unredacted; the vault keeps each value's placeholder stable.
+/// Returns None if the event is not scanned or nothing was redacted.
+pub fn apply_dlp_redaction_to_realtime_event(
+    event: &str,
+    replacements: &mut HashMap<String, String>,
+    scope: DlpScanScope,
+    target: DlpTarget,
+) -> Option<(String, Vec<DlpDetection>)> {
+    let mut json: serde_json::Value = serde_json::from_str(event).ok()?;
+    let event_type = json.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    let scanned = match event_type {
+        "conversation.item.create" => true,
+        "session.update" => scope.system_prompt || scope.tool_definitions,
+        _ => false,
+    };
+    if !scanned {
+        return None;
+    }
+
+    let patterns = get_dlp_patterns_for(target);
+    if patterns.is_empty() {
+        return None;
+    }
+
+    // Values already seen earlier in the session are replaced without a new detection,
+    // so compare against the original event to decide whether anything changed
+    let original = json.clone();
+    let mut detections: Vec<DlpDetection> = Vec::new();
+    let mut scan = ScanContext::new();
+    scan.scan_images = scope.block_image_secrets;
+
+    if let Some(item) = json.get_mut("item") {
+        let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let role = item.get("role").and_then(|r| r.as_str()).unwrap_or("");
+
+        let target = match (item_type, role) {
+            ("message", "user") => item.get_mut("content")?,
+            ("message", "assistant") if scope.assistant_turns => item.get_mut("content")?,
+            ("function_call_output", _) => item.get_mut("output")?,
+            ("function_call", _) if scope.assistant_turns => item.get_mut("arguments")?,
+            _ => return None,
+        };
+        redact_content_blocks(target, &patterns, replacements, &mut detections, &mut scan, None);
+    } else if let Some(session) = json.get_mut("session") {
+        if scope.system_prompt {
+            if let Some(instructions) = session.get_mut("instructions") {
+                redact_value_recursive(instructions, &patterns, replacements, &mut detections, &mut scan, None);
+            }
+        }
+        if scope.tool_definitions {
+            if let Some(tools) = session.get_mut("tools") {
+                redact_tool_definitions(tools, &patterns, replacements, &mut detections, &mut scan);
+            }
+        }
+    }
+
+    // Block and alert detections leave the text unchanged but still need to be reported
+    if json == original && detections.is_empty() {
+        return None;
+    }
+
+    let redacted = serde_json::to_string(&json).ok()?;
+    Some((redacted, detections))
+}
+
+/// Redact user and tool messages in an OpenAI/Claude-style messages array, and assistant
+/// messages (text, tool_use inputs and tool_calls arguments) when the scope includes them
+fn redact_messages(
+    messages: &mut [serde_json::Value],
+    scope: DlpScanScope,
+    patterns: &DlpPatternSet,
+    replacements: &mut HashMap<String, String>,
+    detections: &mut Vec<DlpDetection>,
+    scan: &mut ScanContext,
+) {
+    for (msg_idx, message) in messages.iter_mut().enumerate() {
+        // User messages carry tool_result blocks (Claude); "tool" / "function" messages carry
+        // tool outputs (OpenAI). System messages are handled by redact_system_prompts
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("");
+        let scanned = match role {
+            "user" | "tool" | "function" => true,
+            "assistant" => scope.assistant_turns,
+            _ => false,
+        };
+        if !scanned {
+            continue;
+        }
+
+        for field in ["content", "tool_calls"] {
+            if let Some(value) = message.get_mut(field) {
+                redact_content_blocks(
+                    value,
+                    patterns,
+                    replacements,
+                    detections,
+                    scan,
+                    Some(msg_idx as i32),
+                );
+            }
+        }
+    }
+}
+
+/// Redact message content, skipping block identifiers (tool_use ids, tool names, signatures)
+/// so tool calls and results still pair up, and signed thinking blocks entirely
+fn redact_content_blocks(
+    value: &mut serde_json::Value,
+    patterns: &DlpPatternSet,
+    replacements: &mut HashMap<String, String>,
+    detections: &mut Vec<DlpDetection>,
+    scan: &mut ScanContext,
+    message_index: Option<i32>,
+) {
+    match value {
+        serde_json::Value::Array(arr) => {
+            for item in arr.iter_mut() {
+                redact_content_blocks(item, patterns, replacements, detections, scan, message_index);
+            }
+        }
+        serde_json::Value::Object(obj) => {
+            // Gemini marks thought summaries with "thought": true
+            let block_type = obj.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            let is_thought = obj.get("thought").and_then(|t| t.as_bool()).unwrap_or(false);
+            if UNSCANNED_BLOCK_TYPES.contains(&block_type) || is_thought {
+                return;
+            }
+            if let Some(data) = image_block_data(obj) {
+                if scan.scan_images {
+                    scan_image(data, patterns, detections, scan, message_index);
+                }
+                return;
+            }
+            for (key, v) in obj.iter_mut() {
+                if !BLOCK_IDENTIFIER_KEYS.contains(&key.as_str()) {
+                    redact_content_blocks(v, patterns, replacements, detections, scan, message_index);
+                }
+            }
+        }
+        _ => redact_value_recursive(value, patterns, replacements, detections, scan, message_index),
+    }
+}
+
+/// Redact top-level system prompt fields and system/developer messages
+fn redact_system_prompts(
+    json: &mut serde_json::Value,
+    patterns: &DlpPatternSet,
+    replacements: &mut HashMap<String, String>,
+    detections: &mut Vec<DlpDetection>,
+    scan: &mut ScanContext,
+) {
+    for key in SYSTEM_PROMPT_KEYS {
+        if let Some(prompt) = json.get_mut(*key) {
+            redact_value_recursive(prompt, patterns, replacements, detections, scan, None);
+        }
+    }
+
+    for key in ["messages", "input"] {
+        let Some(messages) = json.get_mut(key).and_then(|m| m.as_array_mut()) else {
+            continue;
+        };
+        for (msg_idx, message) in messages.iter_mut().enumerate() {
+            let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("");
+            if !SYSTEM_ROLES.contains(&role) {
+                continue;
+            }
+            if let Some(content) = message.get_mut("content") {
+                redact_value_recursive(content, patterns, replacements, detections, scan, Some(msg_idx as i32));
+            }
+        }
+    }
+}
+
+/// Redact descriptions, enums and defaults in tool definitions, skipping identifier keys
+fn redact_tool_definitions(
+    value: &mut serde_json::Value,
+    patterns: &DlpPatternSet,
+    replacements: &mut HashMap<String, String>,
+    detections: &mut Vec<DlpDetection>,
+    scan: &mut ScanContext,
+) {
+    match value {
+        serde_json::Value::Array(arr) => {
+            for item in arr.iter_mut() {
+                redact_tool_definitions(item, patterns, replacements, detections, scan);
+            }
+        }
+        serde_json::Value::Object(obj) => {
+            for (key, v) in obj.iter_mut() {
+                if !TOOL_IDENTIFIER_KEYS.contains(&key.as_str()) {
+                    redact_tool_definitions(v, patterns, replacements, detections, scan);
+                }
+            }
+        }
+        _ => redact_value_recursive(value, patterns, replacements, detections, scan, None),
+    }
+}
+
+/// Base64 data (or data: URL) of an image content block: Claude `image`, OpenAI `image_url`,
+/// Responses `input_image` and Gemini `inlineData`. Remote image URLs are not fetched.
+fn image_block_data(block: &serde_json::Map<String, serde_json::Value>) -> Option<&str> {
+    let data = match block.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+        "image" => block
+            .get("source")
+            .filter(|s| s.get("type").and_then(|t| t.as_str()) == Some("base64"))?
+            .get("data"),
+        "image_url" => block.get("image_url")?.get("url"),
+        "input_image" => block.get("image_url"),
+        _ => block
+            .get("inlineData")
+            .or_else(|| block.get("inline_data"))
+            .filter(|d| {
+                d.get("mimeType")
+                    .or_else(|| d.get("mime_type"))
+                    .and_then(|m| m.as_str())
+                    .is_some_and(|m| m.starts_with("image/"))
+            })?
+            .get("data"),
+    }?;
+    data.as_str().filter(|d| !d.starts_with("http"))
+}
+
+/// Scan the text OCR reads from an image. Pixels can't be rewritten, so "redact"
+/// detections block the request instead.
+fn scan_image(
+    data: &str,
+    patterns: &DlpPatternSet,
+    detections: &mut Vec<DlpDetection>,
+    scan: &mut ScanContext,
+    message_index: Option<i32>,
+) {
+    if !scan.within_time_budget() {
+        return;
+    }
+    let Some(text) = ocr::extract_image_text(data) else {
+        return;
+    };
+
+    let mut found = DlpCheckResult::default();
+    check_text(&text, patterns, &scan.limits, scan.started, 0, &mut HashSet::new(), &mut found);
+    if scan.skipped.is_none() {
+        scan.skipped = found.scan_skipped;
+    }
+    for mut detection in found.detections {
+        if detection.action == DlpAction::Redact {
+            detection.action = DlpAction::Block;
+        }
+        detection.message_index = message_index;
+        add_detection_metadata(&mut detection, "source", "image");
+        detections.push(detection);
     }
 }
 
 /// Recursively redact all string values in a JSON structure
 fn redact_value_recursive(
     value: &mut serde_json::Value,
-    patterns: &[CompiledDlpPattern],
+    patterns: &DlpPatternSet,
     replacements: &mut HashMap<String, String>,
     detections: &mut Vec<DlpDetection>,
-    counter: &mut u32,
+    scan: &mut ScanContext,
     message_index: Option<i32>,
 ) {
     match value {
         serde_json::Value::String(s) => {
-            let redacted = redact_text(s, patterns, replacements, detections, counter, message_index);
+            let redacted = redact_text(s, patterns, replacements, detections, scan, message_index);
             *s = redacted;
         }
         serde_json::Value::Array(arr) => {
             for item in arr.iter_mut() {
-                redact_value_recursive(item, patterns, replacements, detections, counter, message_index);
+                redact_value_recursive(item, patterns, replacements, detections, scan, message_index);
             }
         }
         serde_json::Value::Object(obj) => {
             for (_key, v) in obj.iter_mut() {
-                redact_value_recursive(v, patterns, replacements, detections, counter, message_index);
+                redact_value_recursive(v, patterns, replacements, detections, scan, message_index);
             }
         }
         _ => {} // Numbers, bools, null - no redaction needed
     }
 }
 
-/// Create a same-length fake key that looks realistic
-fn create_placeholder(id: u32, original: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+/// Value recorded for a detection (validators may truncate tokens before storage)
+fn stored_value(pattern: &CompiledDlpPattern, matched: &str) -> String {
+    match pattern.filters.validator {
+        Some(v) => v.stored_value(matched),
+        None => matched.to_string(),
+    }
+}
 
-    // Create a seeded "random" generator based on id
-    let mut hasher = DefaultHasher::new();
-    id.hash(&mut hasher);
-    let mut seed = hasher.finish();
+/// Build a detection for the match at text[start..end]
+fn new_detection(
+    pattern: &CompiledDlpPattern,
+    text: &str,
+    (start, end): (usize, usize),
+    placeholder: String,
+    message_index: Option<i32>,
+) -> DlpDetection {
+    let matched = &text[start..end];
+    let confidence = match_confidence(text, start, end, pattern.filters.validator.is_some());
 
-    // Helper to get next pseudo-random value
-    let mut next_rand = || -> u64 {
-        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
-        seed
+    // Low-confidence matches of "block" patterns are reported without blocking
+    let action = if pattern.action == DlpAction::Block && confidence < pattern.min_block_confidence {
+        DlpAction::Alert
+    } else {
+        pattern.action
     };
 
-    let chars: Vec<char> = original
-        .chars()
-        .map(|c| {
-            if c.is_ascii_lowercase() {
-                // Replace with random lowercase letter
-                let idx = (next_rand() % 26) as u8;
-                (b'a' + idx) as char
-            } else if c.is_ascii_uppercase() {
-                // Replace with random uppercase letter
-                let idx = (next_rand() % 26) as u8;
-                (b'A' + idx) as char
-            } else if c.is_ascii_digit() {
-                // Replace with random digit
-                let idx = (next_rand() % 10) as u8;
-                (b'0' + idx) as char
-            } else {
-                // Keep special chars like -, _, etc.
-                c
-            }
-        })
-        .collect();
+    DlpDetection {
+        pattern_name: pattern.name.clone(),
+        pattern_type: pattern.pattern_type.clone(),
+        original_value: stored_value(pattern, matched),
+        placeholder,
+        message_index,
+        category: pattern.category.clone(),
+        metadata: pattern.filters.validator.and_then(|v| v.metadata(matched)),
+        action,
+        severity: pattern.severity,
+        direction: DlpDirection::Request,
+        confidence,
+    }
+}
+
+/// Collect a pattern's matches in text, honoring its min_occurrences threshold
+fn pattern_matches(text: &str, pattern: &CompiledDlpPattern) -> MatchResult {
+    let result = collect_matches_with_negative_context(
+        text,
+        &pattern.regexes,
+        &pattern.negative_regexes,
+        &pattern.filters,
+    );
 
-    chars.into_iter().collect()
+    if (result.matches.len() as i32) < pattern.min_occurrences {
+        MatchResult {
+            matches: Vec::new(),
+            spans: Vec::new(),
+        }
+    } else {
+        result
+    }
+}
+
+/// Values matched by "allow" patterns, which every other pattern skips
+fn allowed_values(text: &str, patterns: &[&CompiledDlpPattern]) -> HashSet<String> {
+    patterns
+        .iter()
+        .filter(|p| p.action == DlpAction::Allow)
+        .flat_map(|p| pattern_matches(text, p).matches)
+        .collect()
 }
 
 /// Redact text and track replacements
+/// Only "redact" patterns change the text; "block" and "alert" patterns only record detections.
+/// Values on a pattern's allowlist or matched by an "allow" pattern are left untouched.
+/// All patterns scan the original text; the match spans are then replaced back-to-front,
+/// so a secret that is also a substring of a longer token is only replaced where it matched
 fn redact_text(
     text: &str,
-    patterns: &[CompiledDlpPattern],
+    patterns: &DlpPatternSet,
     replacements: &mut HashMap<String, String>,
     detections: &mut Vec<DlpDetection>,
-    counter: &mut u32,
+    scan: &mut ScanContext,
     message_index: Option<i32>,
 ) -> String {
-    let mut result = text.to_string();
-
-    for pattern in patterns {
-        // Collect all matches with their positions, filtering by context-aware negative patterns
-        let mut valid_matches: Vec<String> = Vec::new();
-        let mut seen: HashSet<String> = HashSet::new();
+    if !scan.can_scan(text) {
+        return text.to_string();
+    }
 
-        for regex in pattern.regexes.iter() {
-            for m in regex.find_iter(&result) {
-                let matched = m.as_str().to_string();
+    let candidates = patterns.candidates(text);
+    let allowed = allowed_values(text, &candidates);
+    let mut spans: Vec<(usize, usize, &CompiledDlpPattern)> = Vec::new();
 
-                // Skip duplicates
-                if seen.contains(&matched) {
-                    continue;
-                }
+    for pattern in candidates.into_iter().filter(|p| p.action != DlpAction::Allow) {
+        if !scan.within_time_budget() {
+            break;
+        }
 
-                // Check if this match should be excluded based on its context
-                // Context = 30 chars before + match + 30 chars after
-                if is_match_excluded_by_context(&result, m.start(), m.end(), &pattern.negative_regexes) {
-                    continue;
-                }
+        // Collect unique matches, filtering by context-aware negative patterns and the validator
+        let found = pattern_matches(text, pattern);
 
-                // Validate min_unique_chars
-                if pattern.min_unique_chars > 0 {
-                    let unique_count = count_unique_chars(&matched);
-                    if (unique_count as i32) < pattern.min_unique_chars {
-                        continue;
-                    }
-                }
+        if pattern.action == DlpAction::Redact {
+            spans.extend(
+                found
+                    .spans
+                    .into_iter()
+                    .filter(|&(start, end)| !allowed.contains(&text[start..end]))
+                    .map(|(start, end)| (start, end, pattern)),
+            );
+            continue;
+        }
 
-                seen.insert(matched.clone());
-                valid_matches.push(matched);
+        // Record once per pattern and value (at its first occurrence); the text is left unchanged
+        for span in found.spans {
+            let matched = &text[span.0..span.1];
+            if allowed.contains(matched) {
+                continue;
+            }
+            let already_detected = detections
+                .iter()
+                .any(|d| d.pattern_name == pattern.name && d.original_value == stored_value(pattern, matched));
+            if !already_detected {
+                detections.push(new_detection(pattern, text, span, String::new(), message_index));
             }
         }
+    }
 
-        // Check min_occurrences threshold
-        if (valid_matches.len() as i32) < pattern.min_occurrences {
-            continue;
+    // Overlapping matches (e.g. a key inside a connection string) keep the earliest, longest span
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for (start, end, pattern) in select_non_overlapping_spans(spans) {
+        let matched = &text[start..end];
+
+        // Check if we already have a placeholder for this exact value
+        let (placeholder, is_new) = replacements
+            .iter()
+            .find(|(_, v)| v.as_str() == matched)
+            .map(|(k, _)| (k.clone(), false))
+            .unwrap_or_else(|| {
+                // Same-length surrogate from the vault, stable across requests
+                let p = scan.vault.tokenize(matched, &pattern.name);
+                replacements.insert(p.clone(), matched.to_string());
+                (p, true)
+            });
+
+        // Track detection (only for new placeholders to avoid duplicates)
+        if is_new {
+            detections.push(new_detection(pattern, text, (start, end), placeholder.clone(), message_index));
         }
 
-        for matched in valid_matches {
-            // Check if we already have a placeholder for this exact value
-            let (placeholder, is_new) = replacements
-                .iter()
-                .find(|(_, v)| *v == &matched)
-                .map(|(k, _)| (k.clone(), false))
-                .unwrap_or_else(|| {
-                    // Create same-length fake key that looks realistic
-                    let p = create_placeholder(*counter, &matched);
-                    replacements.insert(p.clone(), matched.clone());
-                    *counter += 1;
-                    (p, true)
-                });
+        edits.push((start, end, placeholder));
+    }
 
-            // Track detection (only for new placeholders to avoid duplicates)
-            if is_new {
-                detections.push(DlpDetection {
-                    pattern_name: pattern.name.clone(),
-                    pattern_type: pattern.pattern_type.clone(),
-                    original_value: matched.clone(),
-                    placeholder: placeholder.clone(),
-                    message_index,
-                });
+    // Values hidden in base64 (file attachments, data: URLs) are scanned decoded and re-encoded
+    if scan.base64_depth < MAX_BASE64_DEPTH {
+        scan.base64_depth += 1;
+        for segment in find_base64_segments(text) {
+            // Already replaced as a whole (e.g. by High-Entropy Secrets)
+            if edits.iter().any(|&(start, end, _)| start < segment.end && segment.start < end) {
+                continue;
             }
 
-            result = result.replace(&matched, &placeholder);
+            let first_new = detections.len();
+            let redacted = redact_text(&segment.decoded, patterns, replacements, detections, scan, message_index);
+            detections[first_new..].iter_mut().for_each(|d| add_detection_metadata(d, "encoding", "base64"));
+            if redacted != segment.decoded {
+                edits.push((segment.start, segment.end, segment.encode(&redacted)));
+            }
         }
+        scan.base64_depth -= 1;
+        edits.sort_by_key(|&(start, _, _)| start);
+    }
+
+    // Apply back-to-front so the byte offsets of earlier spans stay valid
+    let mut result = text.to_string();
+    for (start, end, placeholder) in edits.into_iter().rev() {
+        result.replace_range(start..end, &placeholder);
     }
 
     result
 }
 
+/// Record where a detection's value was found (e.g. "encoding": "base64") in its metadata
+fn add_detection_metadata(detection: &mut DlpDetection, key: &str, value: &str) {
+    let mut metadata: serde_json::Map<String, serde_json::Value> = detection
+        .metadata
+        .as_deref()
+        .and_then(|m| serde_json::from_str(m).ok())
+        .unwrap_or_default();
+    metadata.insert(key.to_string(), value.into());
+    detection.metadata = serde_json::to_string(&metadata).ok();
+}
+
+/// Whether any detection comes from a "block" pattern
+pub fn should_block(detections: &[DlpDetection]) -> bool {
+    detections.iter().any(|d| d.action == DlpAction::Block)
+}
+
+/// Whether a path that cannot rewrite content (Cursor hooks) must deny the request:
+/// "redact" patterns fall back to blocking, only "alert" detections pass
+pub fn should_block_without_redaction(detections: &[DlpDetection]) -> bool {
+    detections
+        .iter()
+        .any(|d| matches!(d.action, DlpAction::Block | DlpAction::Redact))
+}
+
+/// dlp_action status code for a forwarded or blocked request
+pub fn dlp_action_for(detections: &[DlpDetection]) -> i32 {
+    if detections.is_empty() {
+        DLP_ACTION_PASSED
+    } else if should_block(detections) {
+        DLP_ACTION_BLOCKED
+    } else if detections.iter().any(|d| d.action == DlpAction::Redact) {
+        DLP_ACTION_REDACTED
+    } else {
+        DLP_ACTION_ALERTED
+    }
+}
+
 /// Apply DLP unredaction to response body
 pub fn apply_dlp_unredaction(body: &str, replacements: &HashMap<String, String>) -> String {
     if replacements.is_empty() {
@@ -385,64 +1301,345 @@ pub fn apply_dlp_unredaction(body: &str, replacements: &HashMap<String, String>)
 
 /// Check text for DLP patterns without redaction (detection only)
 /// Used by Cursor hooks to detect and block sensitive data
-pub fn check_dlp_patterns(text: &str) -> Vec<DlpDetection> {
-    let patterns = get_enabled_dlp_patterns();
+pub fn check_dlp_patterns(text: &str, target: DlpTarget) -> DlpCheckResult {
+    // Issued canaries are reported whatever patterns are configured
+    let mut result = DlpCheckResult {
+        detections: find_canaries(text, DlpDirection::Request),
+        scan_skipped: None,
+    };
+
+    let patterns = get_dlp_patterns_for(target);
 
     if patterns.is_empty() {
-        return Vec::new();
+        return result;
+    }
+
+    result.merge(check_text_within_limits(text, &patterns, &get_dlp_scan_limits_from_db(), Instant::now()));
+    result
+}
+
+/// Detection-only scan of a text, within the body size limit and the time budget that started
+/// counting at `started`
+fn check_text_within_limits(
+    text: &str,
+    patterns: &DlpPatternSet,
+    limits: &DlpScanLimits,
+    started: Instant,
+) -> DlpCheckResult {
+    let mut result = DlpCheckResult::default();
+    if limits.max_body_bytes > 0 && text.len() > limits.max_body_bytes {
+        result.skip(format!("text of {} bytes exceeds the {} byte limit", text.len(), limits.max_body_bytes));
+        return result;
     }
 
-    let mut detections: Vec<DlpDetection> = Vec::new();
     let mut seen_values: HashSet<String> = HashSet::new();
+    check_text(text, patterns, limits, started, 0, &mut seen_values, &mut result);
+    result
+}
 
-    for pattern in patterns {
-        // Collect all matches, filtering by context-aware negative patterns
-        let mut valid_matches: Vec<String> = Vec::new();
+/// Detection-only scan of text and of the base64 segments in it
+fn check_text(
+    text: &str,
+    patterns: &DlpPatternSet,
+    limits: &DlpScanLimits,
+    started: Instant,
+    base64_depth: usize,
+    seen_values: &mut HashSet<String>,
+    result: &mut DlpCheckResult,
+) {
+    let over_budget = |result: &mut DlpCheckResult| {
+        let exceeded = limits.time_budget_ms > 0 && started.elapsed() > Duration::from_millis(limits.time_budget_ms);
+        if exceeded {
+            result.skip(format!("scan exceeded the {} ms time budget", limits.time_budget_ms));
+        }
+        exceeded
+    };
 
-        for regex in &pattern.regexes {
-            for m in regex.find_iter(text) {
-                let matched = m.as_str().to_string();
+    // Allowed values are skipped like values already reported by another pattern
+    let candidates = patterns.candidates(text);
+    seen_values.extend(allowed_values(text, &candidates));
 
-                // Skip duplicates (across all patterns)
-                if seen_values.contains(&matched) {
-                    continue;
-                }
+    for pattern in candidates.into_iter().filter(|p| p.action != DlpAction::Allow) {
+        if over_budget(result) {
+            return;
+        }
 
-                // Check if this match should be excluded based on its context
-                // Context = 30 chars before + match + 30 chars after
-                if is_match_excluded_by_context(text, m.start(), m.end(), &pattern.negative_regexes) {
-                    continue;
-                }
+        for span in pattern_matches(text, pattern).spans {
+            // First occurrence of each value not reported yet
+            if !seen_values.insert(text[span.0..span.1].to_string()) {
+                continue;
+            }
+            // Placeholder is not used for detection-only
+            result.detections.push(new_detection(pattern, text, span, String::new(), None));
+        }
+    }
+
+    if base64_depth < MAX_BASE64_DEPTH {
+        for segment in find_base64_segments(text) {
+            if over_budget(result) {
+                return;
+            }
+            let first_new = result.detections.len();
+            check_text(&segment.decoded, patterns, limits, started, base64_depth + 1, seen_values, result);
+            result.detections[first_new..].iter_mut().for_each(|d| add_detection_metadata(d, "encoding", "base64"));
+        }
+    }
+}
+
+/// Check a file path against "path" scope patterns (detection only)
+pub fn check_dlp_path(path: &str, target: DlpTarget) -> Vec<DlpDetection> {
+    check_scoped_text(path, PatternScope::Path, target)
+}
+
+/// Check request headers against "header" scope patterns, as one "name: value" line per header
+pub fn check_dlp_headers(headers: &[(&str, &str)], target: DlpTarget) -> Vec<DlpDetection> {
+    headers
+        .iter()
+        .flat_map(|(name, value)| check_scoped_text(&format!("{}: {}", name, value), PatternScope::Header, target))
+        .collect()
+}
 
-                // Validate min_unique_chars
-                if pattern.min_unique_chars > 0 {
-                    let unique_count = count_unique_chars(&matched);
-                    if (unique_count as i32) < pattern.min_unique_chars {
-                        continue;
-                    }
+/// Detection-only scan with the patterns of a non-content scope. A path or header can't be
+/// rewritten, so "redact" detections block instead.
+fn check_scoped_text(text: &str, scope: PatternScope, target: DlpTarget) -> Vec<DlpDetection> {
+    let patterns = get_scoped_dlp_patterns_for(target, scope);
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+
+    let limits = get_dlp_scan_limits_from_db();
+    let mut result = DlpCheckResult::default();
+    check_text(text, &patterns, &limits, Instant::now(), 0, &mut HashSet::new(), &mut result);
+    let mut detections = result.detections;
+    for detection in &mut detections {
+        if detection.action == DlpAction::Redact {
+            detection.action = DlpAction::Block;
+        }
+        add_detection_metadata(detection, "scope", scope.name());
+    }
+    detections
+}
+
+/// Prompt-injection markers in the tool outputs of a request: Claude `tool_result` blocks, OpenAI
+/// `tool` / `function` messages, Codex function call outputs and Gemini function responses
+pub fn find_injections_in_tool_outputs(body: &str) -> Vec<DlpDetection> {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Vec::new();
+    };
+
+    let mut outputs: Vec<(i32, &serde_json::Value)> = Vec::new();
+    let message_lists = [
+        json.get("messages"),
+        json.get("additional_messages"),
+        json.get("thread").and_then(|t| t.get("messages")),
+    ];
+    for messages in message_lists.into_iter().flatten().filter_map(|m| m.as_array()) {
+        for (msg_idx, message) in messages.iter().enumerate() {
+            let Some(content) = message.get("content") else {
+                continue;
+            };
+            match message.get("role").and_then(|r| r.as_str()).unwrap_or("") {
+                "tool" | "function" => outputs.push((msg_idx as i32, content)),
+                "user" => {
+                    let tool_results = content
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+                        .filter_map(|b| b.get("content"));
+                    outputs.extend(tool_results.map(|c| (msg_idx as i32, c)));
                 }
+                _ => {}
+            }
+        }
+    }
+    if let Some(input) = json.get("input").and_then(|i| i.as_array()) {
+        for (item_idx, item) in input.iter().enumerate() {
+            let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            if matches!(item_type, "function_call_output" | "custom_tool_call_output") {
+                outputs.extend(item.get("output").map(|o| (item_idx as i32, o)));
+            }
+        }
+    }
+    if let Some(contents) = json.get("contents").and_then(|c| c.as_array()) {
+        for (content_idx, content) in contents.iter().enumerate() {
+            let parts = content.get("parts").and_then(|p| p.as_array()).into_iter().flatten();
+            for part in parts {
+                let response = part.get("functionResponse").or_else(|| part.get("function_response"));
+                outputs.extend(response.map(|r| (content_idx as i32, r)));
+            }
+        }
+    }
+
+    let mut detections = Vec::new();
+    for (message_index, output) in outputs {
+        let mut text = String::new();
+        collect_strings(output, &mut text);
+        detections.extend(find_prompt_injections(&text).into_iter().map(|d| DlpDetection {
+            message_index: Some(message_index),
+            ..d
+        }));
+    }
+    detections
+}
+
+/// Append every string in a JSON value to the buffer, one per line
+fn collect_strings(value: &serde_json::Value, buffer: &mut String) {
+    match value {
+        serde_json::Value::String(s) => {
+            buffer.push_str(s);
+            buffer.push('\n');
+        }
+        serde_json::Value::Array(arr) => arr.iter().for_each(|v| collect_strings(v, buffer)),
+        serde_json::Value::Object(obj) => obj.values().for_each(|v| collect_strings(v, buffer)),
+        _ => {}
+    }
+}
+
+/// JSON keys whose string values carry generated text in SSE events
+/// (Claude text/thinking/partial_json deltas, OpenAI chat delta content, Responses API deltas)
+const SSE_TEXT_KEYS: &[&str] = &["text", "thinking", "partial_json", "content", "delta", "arguments"];
 
-                valid_matches.push(matched);
+/// Append every string found under a text-bearing key to the buffer
+fn collect_sse_text(value: &serde_json::Value, key: Option<&str>, buffer: &mut String) {
+    match value {
+        serde_json::Value::String(s) if key.is_some_and(|k| SSE_TEXT_KEYS.contains(&k)) => {
+            buffer.push_str(s);
+        }
+        serde_json::Value::Array(arr) => {
+            for item in arr {
+                collect_sse_text(item, key, buffer);
             }
         }
+        serde_json::Value::Object(obj) => {
+            for (k, v) in obj {
+                collect_sse_text(v, Some(k), buffer);
+            }
+        }
+        _ => {}
+    }
+}
 
-        // Check min_occurrences threshold
-        if (valid_matches.len() as i32) < pattern.min_occurrences {
+/// Concatenate the generated text of an SSE stream so values split across deltas can be matched
+fn accumulate_sse_text(body: &str) -> String {
+    let mut text = String::new();
+
+    for line in body.lines() {
+        let Some(data) = line.strip_prefix("data:") else {
             continue;
+        };
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(data.trim()) {
+            collect_sse_text(&json, None, &mut text);
         }
+    }
 
-        for matched in valid_matches {
-            seen_values.insert(matched.clone());
+    text
+}
 
-            detections.push(DlpDetection {
-                pattern_name: pattern.name.clone(),
-                pattern_type: pattern.pattern_type.clone(),
-                original_value: matched,
-                placeholder: String::new(), // Not used for detection-only
-                message_index: None,
-            });
-        }
+/// Scan a model response for sensitive data it echoed or generated (detection only)
+/// Streaming bodies are scanned as accumulated delta text; detections are recorded as alerts
+/// because the response has already been forwarded
+pub fn scan_response_for_dlp(body: &str, is_streaming: bool, target: DlpTarget) -> Vec<DlpDetection> {
+    check_dlp_patterns(&response_text(body, is_streaming), target)
+        .detections
+        .into_iter()
+        .map(|d| DlpDetection {
+            action: DlpAction::Alert,
+            direction: DlpDirection::Response,
+            ..d
+        })
+        .collect()
+}
+
+/// Scan a model response for issued canary tokens only (when full response scanning is off)
+pub fn scan_response_for_canaries(body: &str, is_streaming: bool) -> Vec<DlpDetection> {
+    find_canaries(&response_text(body, is_streaming), DlpDirection::Response)
+}
+
+/// Text of a response body; streaming bodies are joined from their delta events
+fn response_text(body: &str, is_streaming: bool) -> String {
+    if is_streaming {
+        accumulate_sse_text(body)
+    } else {
+        body.to_string()
     }
+}
 
-    detections
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_patterns() -> DlpPatternSet {
+        DlpPatternSet::new(vec![CompiledDlpPattern {
+            id: 1,
+            enabled: true,
+            name: "API Keys".to_string(),
+            pattern_type: "regex".to_string(),
+            regexes: vec![Regex::new(r"sk-[a-zA-Z0-9]{20,}").unwrap()],
+            negative_regexes: Vec::new(),
+            min_occurrences: 1,
+            filters: MatchFilters::default(),
+            category: "secret".to_string(),
+            action: DlpAction::Block,
+            severity: Severity::High,
+            scope: PatternScope::Content,
+            min_block_confidence: 0.0,
+        }])
+    }
+
+    const TEXT: &str = "deploy with sk-abcdefghijklmnopqrstuvwx please";
+
+    #[test]
+    fn test_check_skips_body_over_size_limit() {
+        let patterns = key_patterns();
+        let limits = DlpScanLimits { max_body_bytes: TEXT.len(), ..DlpScanLimits::default() };
+        let result = check_text_within_limits(TEXT, &patterns, &limits, Instant::now());
+        assert_eq!(result.detections.len(), 1);
+        assert!(result.scan_skipped.is_none());
+
+        let limits = DlpScanLimits { max_body_bytes: TEXT.len() - 1, ..DlpScanLimits::default() };
+        let result = check_text_within_limits(TEXT, &patterns, &limits, Instant::now());
+        assert!(result.detections.is_empty());
+        assert!(result.scan_skipped.unwrap().contains("byte limit"));
+    }
+
+    #[test]
+    fn test_check_stops_at_time_budget() {
+        let patterns = key_patterns();
+        let limits = DlpScanLimits { time_budget_ms: 1, ..DlpScanLimits::default() };
+        let started = Instant::now() - Duration::from_millis(50);
+        let result = check_text_within_limits(TEXT, &patterns, &limits, started);
+        assert!(result.detections.is_empty());
+        assert!(result.scan_skipped.unwrap().contains("time budget"));
+
+        // A skip in one text is kept when another text's result is merged in
+        let mut merged = check_text_within_limits(TEXT, &patterns, &DlpScanLimits::default(), Instant::now());
+        merged.merge(check_text_within_limits(TEXT, &patterns, &limits, started));
+        assert_eq!(merged.detections.len(), 1);
+        assert!(merged.scan_skipped.is_some());
+    }
+
+    #[test]
+    fn test_pattern_sets_keyed_by_policy_profile() {
+        let policy = |id, backend: &str, model_glob: &str| DlpPolicy {
+            id,
+            pattern_id: 1,
+            backend: backend.to_string(),
+            model_glob: model_glob.to_string(),
+            action: "alert".to_string(),
+        };
+        let cache = PatternCache {
+            patterns: Vec::new(),
+            policies: vec![policy(1, "claude", "*"), policy(2, "*", "gpt-4*")],
+            sets: HashMap::new(),
+        };
+        let target = |backend, model| DlpTarget { backend, model: Some(model) };
+
+        // Any number of model names matching no glob share one entry per backend
+        assert_eq!(cache.profile(target("codex", "o3-abc")), Vec::<usize>::new());
+        assert_eq!(cache.profile(target("codex", "random-model-123")), Vec::<usize>::new());
+        assert_eq!(cache.profile(target("claude", "claude-sonnet-4")), vec![0]);
+        assert_eq!(cache.profile(target("claude", "claude-opus-4")), vec![0]);
+        assert_eq!(cache.profile(target("claude", "gpt-4o")), vec![0, 1]);
+    }
 }
@@ -2,25 +2,69 @@
 
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
 pub const DEFAULT_PORT: u16 = 8008;
 
+/// Environment variable giving the database file to use instead of the default location
+pub const DB_PATH_ENV: &str = "LLMWATCHER_DB_PATH";
+
+/// Folder for the app's data under the platform data directory
+const APP_DATA_DIR: &str = "quilr-agent-gateway";
+
+/// Folder in the home directory the app used before moving to the platform data directory
+const LEGACY_DATA_DIR: &str = ".quilrdlpapp";
+
+const DB_FILE: &str = "proxy_requests.db";
+
+/// What moves with the database: its WAL files, the DLP vault key and the NER model
+const DATA_FILES: &[&str] = &[DB_FILE, "proxy_requests.db-wal", "proxy_requests.db-shm", "dlp_vault.key", "ner"];
+
 static DB_PATH: OnceLock<String> = OnceLock::new();
 
-/// Returns the path to the database file at ~/.quilrdlpapp/proxy_requests.db
-/// Creates the directory if it doesn't exist.
+/// Returns the path to the database file: $LLMWATCHER_DB_PATH if set, otherwise proxy_requests.db
+/// in the platform data directory (~/Library/Application Support/quilr-agent-gateway on macOS,
+/// %APPDATA%\quilr-agent-gateway on Windows, ~/.local/share/quilr-agent-gateway on Linux).
+/// The vault key and the NER model live next to it. Creates the directory if it doesn't exist.
 pub fn get_db_path() -> &'static str {
     DB_PATH.get_or_init(|| {
-        let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let dir = PathBuf::from(&home).join(".quilrdlpapp");
+        if let Some(path) = env::var_os(DB_PATH_ENV).filter(|p| !p.is_empty()) {
+            let path = PathBuf::from(path);
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                fs::create_dir_all(dir).expect("Failed to create the database directory");
+            }
+            return path.to_string_lossy().to_string();
+        }
+
+        let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let dir = dirs::data_dir().unwrap_or_else(|| home.clone()).join(APP_DATA_DIR);
+        fs::create_dir_all(&dir).expect("Failed to create the app data directory");
 
-        // Create directory if it doesn't exist
-        if !dir.exists() {
-            fs::create_dir_all(&dir).expect("Failed to create ~/.quilrdlpapp directory");
+        let legacy = home.join(LEGACY_DATA_DIR);
+        if !dir.join(DB_FILE).exists() && legacy.join(DB_FILE).exists() && !move_data_files(&legacy, &dir) {
+            // Keep using the old location rather than starting over with an empty database
+            return legacy.join(DB_FILE).to_string_lossy().to_string();
         }
 
-        dir.join("proxy_requests.db").to_string_lossy().to_string()
+        dir.join(DB_FILE).to_string_lossy().to_string()
     })
 }
+
+/// One-time move of the data files to the new directory. Files are renamed, so a failure (e.g.
+/// the directories are on different volumes) puts back whatever was moved and returns false.
+fn move_data_files(from: &Path, to: &Path) -> bool {
+    let mut moved = Vec::new();
+    for name in DATA_FILES.iter().filter(|name| from.join(name).exists()) {
+        if let Err(e) = fs::rename(from.join(name), to.join(name)) {
+            eprintln!("[DB] Failed to move {} to {}: {}", from.join(name).display(), to.display(), e);
+            for name in moved {
+                let _ = fs::rename(to.join(name), from.join(name));
+            }
+            return false;
+        }
+        moved.push(name);
+    }
+    println!("[DB] Moved app data from {} to {}", from.display(), to.display());
+    true
+}
@@ -0,0 +1,146 @@
+// DLP Policies (per backend and per model)
+//
+// A policy overrides one pattern for requests to a backend and/or model: it sets the action
+// used there, or turns the pattern off. Policies can also turn on a pattern that is disabled
+// globally, so a pattern set can be attached to a single backend.
+//
+// e.g. "Internal Hostnames" -> allow for backend "ollama", block for backend "openai"
+//
+// When several policies match a request, the most specific wins: a named backend beats "*",
+// then a model glob beats "*", then the glob with more literal characters.
+
+use crate::dlp::DlpAction;
+use crate::storage::{self, DlpStore};
+use serde::Serialize;
+
+/// Matches any backend or any model
+pub const ANY: &str = "*";
+
+/// Policy action that removes the pattern for matching requests
+pub const OFF: &str = "off";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DlpPolicy {
+    pub id: i64,
+    pub pattern_id: i64,
+    /// Backend name (as listed under Backends) or "*"
+    pub backend: String,
+    /// Model name glob (`*` and `?` wildcards), "*" for any model
+    pub model_glob: String,
+    /// Action name, or "off"
+    pub action: String,
+}
+
+impl DlpPolicy {
+    /// Whether this policy applies to a request to `backend` for `model`
+    pub fn applies_to(&self, backend: &str, model: Option<&str>) -> bool {
+        let backend_matches = self.backend == ANY || self.backend == backend;
+        let model_matches =
+            self.model_glob == ANY || model.is_some_and(|m| glob_matches(&self.model_glob, m));
+        backend_matches && model_matches
+    }
+
+    /// Resolved action; None when the policy turns the pattern off
+    pub fn pattern_action(&self) -> Option<DlpAction> {
+        DlpAction::from_name(&self.action)
+    }
+
+    fn specificity(&self) -> (bool, bool, usize) {
+        let literal_chars = self.model_glob.chars().filter(|c| !matches!(c, '*' | '?')).count();
+        (self.backend != ANY, self.model_glob != ANY, literal_chars)
+    }
+}
+
+/// The most specific policy for a pattern and request, if any
+pub fn select_policy<'a>(
+    policies: &'a [DlpPolicy],
+    pattern_id: i64,
+    backend: &str,
+    model: Option<&str>,
+) -> Option<&'a DlpPolicy> {
+    policies
+        .iter()
+        .filter(|p| p.pattern_id == pattern_id && p.applies_to(backend, model))
+        // max_by_key keeps the last maximum; reverse so the oldest policy wins ties
+        .rev()
+        .max_by_key(|p| p.specificity())
+}
+
+/// Case-insensitive glob match with `*` (any run) and `?` (one character)
+pub fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Backtracking over the last `*`
+    let (mut g, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if g < glob.len() && (glob[g] == '?' || glob[g] == text[t]) {
+            g += 1;
+            t += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            star = Some((g, t));
+            g += 1;
+        } else if let Some((star_g, star_t)) = star {
+            g = star_g + 1;
+            t = star_t + 1;
+            star = Some((star_g, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// All policies, oldest first
+pub fn load_dlp_policies() -> Vec<DlpPolicy> {
+    storage::shared()
+        .and_then(|store| store.dlp_policies())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(id: i64, backend: &str, model_glob: &str, action: &str) -> DlpPolicy {
+        DlpPolicy {
+            id,
+            pattern_id: 1,
+            backend: backend.to_string(),
+            model_glob: model_glob.to_string(),
+            action: action.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", "gpt-4o"));
+        assert!(glob_matches("gpt-4*", "GPT-4o-mini"));
+        assert!(glob_matches("claude-*-4-?", "claude-opus-4-1"));
+        assert!(glob_matches("*llama*", "llama3.1:8b"));
+        assert!(!glob_matches("gpt-4*", "gpt-3.5-turbo"));
+        assert!(!glob_matches("llama?", "llama"));
+    }
+
+    #[test]
+    fn test_select_policy() {
+        let policies = vec![
+            policy(1, "*", "*", "alert"),
+            policy(2, "openai", "*", "block"),
+            policy(3, "ollama", "*", "allow"),
+            policy(4, "openai", "gpt-4o*", "redact"),
+            policy(5, "*", "llama*", OFF),
+        ];
+
+        let action = |backend, model| select_policy(&policies, 1, backend, model).map(|p| p.id);
+        assert_eq!(action("openai", Some("gpt-4o-mini")), Some(4));
+        assert_eq!(action("openai", Some("o3")), Some(2));
+        assert_eq!(action("openai", None), Some(2));
+        assert_eq!(action("ollama", Some("llama3")), Some(3));
+        assert_eq!(action("custom", Some("llama3")), Some(5));
+        assert_eq!(action("claude", None), Some(1));
+        assert_eq!(select_policy(&policies, 2, "openai", None).map(|p| p.id), None);
+        assert_eq!(policies[4].pattern_action(), None);
+    }
+}
@@ -0,0 +1,324 @@
+// Streaming DLP Unredaction for SSE Responses
+//
+// Placeholders can be split across network chunks and across SSE delta events
+// (e.g. one `content_block_delta` ends with "sk-ab" and the next starts with "cd...").
+// The unredactor buffers incomplete events, and for each text delta holds back the
+// shortest tail that could still be the start of a placeholder, carrying it into the
+// next delta of the same stream. Held text is flushed before any unrelated event.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// JSON keys whose string values are streamed text deltas
+/// (Claude text/thinking/partial_json, OpenAI chat content/arguments, Responses API delta, Gemini text)
+const DELTA_KEYS: &[&str] = &["text", "thinking", "partial_json", "content", "delta", "arguments"];
+
+/// Top-level fields that identify which block/item a delta belongs to
+const STREAM_ID_KEYS: &[&str] = &["index", "item_id", "output_index", "content_index"];
+
+/// Text held back from one delta stream, with the event it came from so it can be re-emitted
+struct HeldTail {
+    /// Non-data lines of the original event (e.g. `event: content_block_delta`)
+    event_lines: Vec<String>,
+    /// Parsed data payload of the last event in this stream
+    template: Value,
+    /// JSON pointer to the delta string inside the template
+    pointer: String,
+    tail: String,
+}
+
+pub struct StreamingUnredactor {
+    /// (placeholder, original) pairs
+    replacements: Vec<(String, String)>,
+    /// Bytes of an SSE event that hasn't been terminated yet
+    pending: Vec<u8>,
+    /// Held-back tails keyed by stream id + JSON pointer, in insertion order
+    held: Vec<(String, HeldTail)>,
+}
+
+impl StreamingUnredactor {
+    pub fn new(replacements: &HashMap<String, String>) -> Self {
+        StreamingUnredactor {
+            replacements: replacements
+                .iter()
+                .map(|(placeholder, original)| (placeholder.clone(), original.clone()))
+                .collect(),
+            pending: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Feed a network chunk; returns the bytes that are safe to forward now
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<u8> {
+        if self.replacements.is_empty() {
+            return chunk.to_vec();
+        }
+
+        self.pending.extend_from_slice(chunk);
+
+        let mut output = String::new();
+        while let Some((event_end, separator_len)) = find_event_end(&self.pending) {
+            let event_bytes: Vec<u8> = self.pending.drain(..event_end + separator_len).collect();
+            let event = String::from_utf8_lossy(&event_bytes[..event_end]).to_string();
+            let separator = String::from_utf8_lossy(&event_bytes[event_end..]).to_string();
+            output.push_str(&self.process_event(&event, &separator));
+        }
+
+        output.into_bytes()
+    }
+
+    /// Flush everything still buffered at the end of the stream
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut output = self.flush_held(&[]);
+        if !self.pending.is_empty() {
+            let rest = String::from_utf8_lossy(&self.pending).to_string();
+            output.push_str(&self.unredact(&rest));
+            self.pending.clear();
+        }
+        output.into_bytes()
+    }
+
+    fn unredact(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for (placeholder, original) in &self.replacements {
+            result = result.replace(placeholder, original);
+        }
+        result
+    }
+
+    /// Length of the longest suffix of text that is a proper prefix of some placeholder
+    fn partial_placeholder_len(&self, text: &str) -> usize {
+        self.replacements
+            .iter()
+            .map(|(placeholder, _)| {
+                let max = placeholder.len().saturating_sub(1).min(text.len());
+                (1..=max)
+                    .rev()
+                    .find(|&k| {
+                        placeholder.is_char_boundary(k)
+                            && text.is_char_boundary(text.len() - k)
+                            && text.ends_with(&placeholder[..k])
+                    })
+                    .unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Unredact one complete SSE event, carrying partial placeholders between deltas
+    fn process_event(&mut self, event: &str, separator: &str) -> String {
+        let (data_lines, event_lines): (Vec<&str>, Vec<&str>) =
+            event.lines().partition(|line| line.starts_with("data:"));
+        let data: String = data_lines
+            .iter()
+            .map(|line| line["data:".len()..].trim_start())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut json: Value = match serde_json::from_str(&data) {
+            Ok(v) => v,
+            Err(_) => {
+                // Not a JSON event (e.g. `data: [DONE]`): flush held text first
+                let mut output = self.flush_held(&[]);
+                output.push_str(&self.unredact(event));
+                output.push_str(separator);
+                return output;
+            }
+        };
+
+        let stream_id = stream_id(&json);
+        let mut deltas: Vec<String> = Vec::new();
+        collect_delta_pointers(&json, "", None, &mut deltas);
+
+        let keys: Vec<String> = deltas.iter().map(|p| format!("{}{}", stream_id, p)).collect();
+        let mut output = self.flush_held(&keys);
+
+        let mut changed = false;
+        for (pointer, key) in deltas.iter().zip(&keys) {
+            let Some(Value::String(text)) = json.pointer_mut(pointer) else {
+                continue;
+            };
+
+            let carried = self.take_held(key).unwrap_or_default();
+            let combined = format!("{}{}", carried, text);
+            let unredacted = self.unredact(&combined);
+            let hold = self.partial_placeholder_len(&unredacted);
+            let (emit, tail) = unredacted.split_at(unredacted.len() - hold);
+
+            if emit != text.as_str() {
+                *text = emit.to_string();
+                changed = true;
+            }
+
+            if !tail.is_empty() {
+                let mut template = json.clone();
+                if let Some(slot) = template.pointer_mut(pointer) {
+                    *slot = Value::String(String::new());
+                }
+                self.held.push((
+                    key.clone(),
+                    HeldTail {
+                        event_lines: event_lines.iter().map(|l| l.to_string()).collect(),
+                        template,
+                        pointer: pointer.clone(),
+                        tail: tail.to_string(),
+                    },
+                ));
+            }
+        }
+
+        if changed {
+            output.push_str(&format_event(&event_lines, &json));
+        } else {
+            output.push_str(&self.unredact(event));
+        }
+        output.push_str(separator);
+        output
+    }
+
+    fn take_held(&mut self, key: &str) -> Option<String> {
+        let pos = self.held.iter().position(|(k, _)| k == key)?;
+        Some(self.held.remove(pos).1.tail)
+    }
+
+    /// Re-emit held tails as synthetic delta events, except for the streams in `keep`
+    fn flush_held(&mut self, keep: &[String]) -> String {
+        let mut output = String::new();
+        let mut kept = Vec::new();
+
+        for (key, held) in std::mem::take(&mut self.held) {
+            if keep.contains(&key) {
+                kept.push((key, held));
+                continue;
+            }
+
+            let mut json = held.template;
+            if let Some(slot) = json.pointer_mut(&held.pointer) {
+                *slot = Value::String(held.tail);
+            }
+            let lines: Vec<&str> = held.event_lines.iter().map(String::as_str).collect();
+            output.push_str(&format_event(&lines, &json));
+            output.push_str("\n\n");
+        }
+
+        self.held = kept;
+        output
+    }
+}
+
+/// Position and separator length of the first complete SSE event in buf
+fn find_event_end(buf: &[u8]) -> Option<(usize, usize)> {
+    let lf = buf.windows(2).position(|w| w == b"\n\n").map(|p| (p, 2));
+    let crlf = buf.windows(4).position(|w| w == b"\r\n\r\n").map(|p| (p, 4));
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Identifier of the block/item an event belongs to, built from its top-level id fields
+fn stream_id(json: &Value) -> String {
+    STREAM_ID_KEYS
+        .iter()
+        .filter_map(|key| json.get(key).map(|v| format!("{}={};", key, v)))
+        .collect()
+}
+
+/// Collect JSON pointers to every string stored under a delta key
+fn collect_delta_pointers(value: &Value, path: &str, key: Option<&str>, out: &mut Vec<String>) {
+    match value {
+        Value::String(_) if key.is_some_and(|k| DELTA_KEYS.contains(&k)) => {
+            out.push(path.to_string());
+        }
+        Value::Array(arr) => {
+            for (i, item) in arr.iter().enumerate() {
+                collect_delta_pointers(item, &format!("{}/{}", path, i), key, out);
+            }
+        }
+        Value::Object(obj) => {
+            for (k, v) in obj {
+                let escaped = k.replace('~', "~0").replace('/', "~1");
+                collect_delta_pointers(v, &format!("{}/{}", path, escaped), Some(k), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn format_event(event_lines: &[&str], json: &Value) -> String {
+    let mut lines: Vec<String> = event_lines.iter().map(|l| l.to_string()).collect();
+    lines.push(format!("data: {}", json));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unredactor() -> StreamingUnredactor {
+        let mut replacements = HashMap::new();
+        replacements.insert("sk-fakeFAKE1234".to_string(), "sk-realREAL5678".to_string());
+        StreamingUnredactor::new(&replacements)
+    }
+
+    fn claude_delta(text: &str) -> String {
+        format!(
+            "event: content_block_delta\ndata: {}\n\n",
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}})
+        )
+    }
+
+    fn streamed_text(output: &str) -> String {
+        output
+            .split("\n\n")
+            .filter_map(|event| event.lines().find_map(|l| l.strip_prefix("data: ")))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .filter_map(|json| json.pointer("/delta/text").and_then(|t| t.as_str()).map(String::from))
+            .collect()
+    }
+
+    #[test]
+    fn test_placeholder_split_across_chunks() {
+        let mut u = unredactor();
+        let event = claude_delta("key sk-fakeFAKE1234 done");
+        let (a, b) = event.split_at(40);
+
+        let mut output = u.push(a.as_bytes());
+        output.extend(u.push(b.as_bytes()));
+        output.extend(u.finish());
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(streamed_text(&output), "key sk-realREAL5678 done");
+    }
+
+    #[test]
+    fn test_placeholder_split_across_delta_events() {
+        let mut u = unredactor();
+        let mut output = u.push(claude_delta("key sk-fake").as_bytes());
+        output.extend(u.push(claude_delta("FAKE1234 done").as_bytes()));
+        output.extend(u.push(b"event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n"));
+        output.extend(u.finish());
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(streamed_text(&output), "key sk-realREAL5678 done");
+        assert!(!output.contains("sk-fake"));
+    }
+
+    #[test]
+    fn test_held_tail_flushed_before_next_event() {
+        let mut u = unredactor();
+        let mut output = u.push(claude_delta("ends with sk-").as_bytes());
+        output.extend(u.push(b"event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n"));
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(streamed_text(&output), "ends with sk-");
+        assert!(output.find("sk-").unwrap() < output.find("content_block_stop\",").unwrap());
+    }
+
+    #[test]
+    fn test_passthrough_without_replacements() {
+        let mut u = StreamingUnredactor::new(&HashMap::new());
+        let chunk = b"data: {\"partial";
+        assert_eq!(u.push(chunk), chunk.to_vec());
+    }
+}
@@ -0,0 +1,194 @@
+// DLP Detection Webhooks
+//
+// Detections at or above a severity threshold are POSTed as JSON, one event per detection, to
+// each configured URL so a SOC pipeline can consume them without polling the database. Values
+// are sent as a masked preview only. Delivery runs in the background after the detections are
+// logged; failed deliveries (network errors, 5xx, 429) are retried with exponential backoff.
+
+use crate::dlp::{DlpDetection, Severity};
+use crate::dlp_vault::masked_preview;
+use crate::upstream_proxy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Attempts per event and URL, including the first
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry; doubled after each failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook settings (`dlp_webhooks` in the settings table)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    /// Detections below this severity are not sent
+    pub min_severity: String,
+    pub urls: Vec<String>,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        WebhookSettings {
+            enabled: false,
+            min_severity: "high".to_string(),
+            urls: Vec::new(),
+        }
+    }
+}
+
+impl WebhookSettings {
+    /// Whether a detection should be sent
+    pub fn wants(&self, detection: &DlpDetection) -> bool {
+        let min_severity = Severity::from_name(&self.min_severity).unwrap_or(Severity::High);
+        self.enabled && !self.urls.is_empty() && detection.severity >= min_severity
+    }
+}
+
+/// Where the request with the detections came from
+pub struct DetectionSource {
+    pub backend: String,
+    pub model: Option<String>,
+    pub user_email: Option<String>,
+}
+
+/// JSON body POSTed for each detection
+#[derive(Serialize)]
+pub struct DetectionEvent {
+    pub event: &'static str,
+    pub detection_id: i64,
+    pub request_id: i64,
+    pub timestamp: String,
+    pub pattern: String,
+    pub category: String,
+    pub severity: &'static str,
+    pub action: &'static str,
+    pub direction: &'static str,
+    pub confidence: f64,
+    pub backend: String,
+    pub model: Option<String>,
+    pub user_email: Option<String>,
+    pub masked_value: String,
+}
+
+impl DetectionEvent {
+    pub fn new(
+        detection: &DlpDetection,
+        detection_id: i64,
+        request_id: i64,
+        timestamp: &str,
+        source: &DetectionSource,
+    ) -> Self {
+        DetectionEvent {
+            event: "dlp.detection",
+            detection_id,
+            request_id,
+            timestamp: timestamp.to_string(),
+            pattern: detection.pattern_name.clone(),
+            category: detection.category.clone(),
+            severity: detection.severity.name(),
+            action: detection.action.name(),
+            direction: detection.direction.name(),
+            confidence: detection.confidence,
+            backend: source.backend.clone(),
+            model: source.model.clone(),
+            user_email: source.user_email.clone(),
+            masked_value: masked_preview(&detection.original_value),
+        }
+    }
+}
+
+fn client() -> Client {
+    upstream_proxy::client(REQUEST_TIMEOUT)
+}
+
+/// One POST; `Err((message, retryable))` on failure
+async fn post(url: &str, event: &DetectionEvent) -> Result<(), (String, bool)> {
+    let body = serde_json::to_vec(event).map_err(|e| (e.to_string(), false))?;
+    let request = client()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        // Client errors other than rate limiting (bad path, auth) won't succeed on a retry
+        Ok(response) => {
+            let status = response.status();
+            let retryable = !status.is_client_error() || status.as_u16() == 429;
+            Err((format!("{} answered {}", url, status), retryable))
+        }
+        Err(e) => Err((format!("{} unreachable: {}", url, e), true)),
+    }
+}
+
+/// POST one event, retrying with backoff; returns the last error if every attempt failed
+pub async fn deliver(url: &str, event: &DetectionEvent) -> Result<(), String> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        match post(url, event).await {
+            Ok(()) => return Ok(()),
+            Err((error, retryable)) if !retryable || attempt == MAX_ATTEMPTS => return Err(error),
+            Err(_) => {}
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// POST a sample event once, so a URL can be checked from the settings page
+pub async fn send_test_event(url: &str) -> Result<(), String> {
+    let event = DetectionEvent {
+        event: "dlp.test",
+        detection_id: 0,
+        request_id: 0,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        pattern: "Webhook Test".to_string(),
+        category: "secret".to_string(),
+        severity: Severity::High.name(),
+        action: "alert",
+        direction: "request",
+        confidence: 1.0,
+        backend: "test".to_string(),
+        model: None,
+        user_email: None,
+        masked_value: masked_preview("sk-test-0000000000000000"),
+    };
+    post(url, &event).await.map_err(|(error, _)| error)
+}
+
+/// Validate a webhook URL (http or https)
+pub fn validate_url(url: &str) -> Result<(), String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => Ok(()),
+        Ok(_) => Err(format!("Webhook URL must use http or https: {}", url)),
+        Err(e) => Err(format!("Invalid webhook URL '{}': {}", url, e)),
+    }
+}
+
+/// Send events to every configured URL in the background
+pub fn spawn_delivery(settings: &WebhookSettings, events: Vec<DetectionEvent>) {
+    if events.is_empty() {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+
+    let urls = settings.urls.clone();
+    runtime.spawn(async move {
+        for url in &urls {
+            for event in &events {
+                if let Err(e) = deliver(url, event).await {
+                    eprintln!("[WEBHOOK] Dropped detection {}: {}", event.detection_id, e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,215 @@
+// Document Text Extraction
+//
+// Cursor attachments are files on disk. Plain text files are read as-is, but PDFs and Office
+// documents are binary: read as UTF-8 they fail, so their content was never scanned.
+// This module extracts their text for DLP scanning:
+// - PDF: text layer via pdf-extract (scanned images without a text layer yield nothing)
+// - .docx / .pptx / .xlsx: the document is a zip of XML parts; text runs (`w:t`, `a:t`, `t`)
+//   and cell values (`v`) are collected from every part under word/, ppt/ or xl/
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Text of an attachment for DLP scanning: PDF and Office documents are extracted, anything
+/// else is read as UTF-8. Extracted text is cut at `max_bytes` (0 = no limit), and PDFs larger
+/// than that aren't parsed at all, as the whole file is loaded to extract it.
+pub fn read_attachment_text(path: &str, max_bytes: usize) -> Result<String, String> {
+    let max_bytes = if max_bytes == 0 { usize::MAX } else { max_bytes };
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("pdf") => pdf_text(path, max_bytes),
+        Some("docx" | "docm") => office_text(path, "word/", max_bytes),
+        Some("pptx" | "pptm") => office_text(path, "ppt/", max_bytes),
+        Some("xlsx" | "xlsm") => office_text(path, "xl/", max_bytes),
+        _ => std::fs::read_to_string(path).map_err(|e| e.to_string()),
+    }
+}
+
+fn pdf_text(path: &str, max_bytes: usize) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > max_bytes as u64 {
+        return Err(format!("PDF of {} bytes exceeds the {} byte scan limit", size, max_bytes));
+    }
+
+    // pdf-extract panics on some malformed files
+    let owned = path.to_string();
+    let text = std::panic::catch_unwind(move || pdf_extract::extract_text(&owned))
+        .map_err(|_| "PDF could not be parsed".to_string())?
+        .map_err(|e| format!("PDF could not be parsed: {}", e))?;
+    Ok(truncate_text(text, max_bytes))
+}
+
+/// `text` cut to at most `max_bytes`, at a character boundary
+fn truncate_text(mut text: String, max_bytes: usize) -> String {
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+/// Text of the XML parts under `part_prefix` in an Office Open XML document
+fn office_text(path: &str, part_prefix: &str, max_bytes: usize) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Not a valid Office document: {}", e))?;
+
+    let mut parts: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.starts_with(part_prefix) && name.ends_with(".xml"))
+        .map(String::from)
+        .collect();
+    parts.sort();
+
+    let mut text = String::new();
+    for part in parts {
+        if text.len() >= max_bytes {
+            break;
+        }
+        let entry = archive.by_name(&part).map_err(|e| e.to_string())?;
+        // Bounded read: a small zip can inflate to gigabytes
+        let mut xml = Vec::new();
+        entry
+            .take(max_bytes as u64)
+            .read_to_end(&mut xml)
+            .map_err(|e| e.to_string())?;
+        text.push_str(&xml_text(&String::from_utf8_lossy(&xml)));
+    }
+
+    Ok(truncate_text(text, max_bytes))
+}
+
+/// Text content of `t` and `v` elements (any namespace), with a newline after each paragraph,
+/// row and shared string and a tab after each spreadsheet cell
+fn xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut in_text = false;
+    let mut rest = xml;
+
+    while let Some(lt) = rest.find('<') {
+        if in_text {
+            text.push_str(&unescape_xml(&rest[..lt]));
+        }
+        let Some(gt) = rest[lt..].find('>') else {
+            break;
+        };
+        let tag = &rest[lt + 1..lt + gt];
+        rest = &rest[lt + gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let self_closing = tag.ends_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+
+        match local_name {
+            "t" | "v" if !self_closing => in_text = !closing,
+            "p" | "row" | "si" if closing => text.push('\n'),
+            "c" if closing => text.push('\t'),
+            "tab" => text.push('\t'),
+            "br" => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Decode the predefined XML entities and character references
+fn unescape_xml(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest.find(';').and_then(|semi| {
+            let entity = &rest[1..semi];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, semi))
+        });
+
+        match decoded {
+            Some((c, semi)) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_text_docx() {
+        let xml = r#"<?xml version="1.0"?><w:document><w:body>
+            <w:p><w:r><w:t>API key:</w:t></w:r><w:r><w:t xml:space="preserve"> sk-abc&amp;123</w:t></w:r></w:p>
+            <w:p><w:r><w:t>Second</w:t><w:tab/><w:t>line</w:t></w:r></w:p>
+            <w:p><w:r><w:delText>deleted</w:delText></w:r></w:p>
+        </w:body></w:document>"#;
+        assert_eq!(xml_text(xml), "API key: sk-abc&123\nSecond\tline\n\n");
+    }
+
+    #[test]
+    fn test_xml_text_xlsx() {
+        let shared = r#"<sst><si><t>password</t></si><si><t>hunter2</t></si></sst>"#;
+        assert_eq!(xml_text(shared), "password\nhunter2\n");
+
+        let sheet = r#"<sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1"><v>4111111111111111</v></c></row></sheetData>"#;
+        assert_eq!(xml_text(sheet), "0\t4111111111111111\t\n");
+    }
+
+    #[test]
+    fn test_pdf_size_and_text_limits() {
+        let dir = std::env::temp_dir().join(format!("llmwatcher-document-text-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("large.pdf");
+        std::fs::write(&pdf, vec![b'%'; 2048]).unwrap();
+        let err = read_attachment_text(pdf.to_str().unwrap(), 1024).unwrap_err();
+        assert!(err.contains("exceeds the 1024 byte scan limit"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(truncate_text("héllo".to_string(), 2), "h");
+        assert_eq!(truncate_text("héllo".to_string(), 3), "hé");
+        assert_eq!(truncate_text("hello".to_string(), usize::MAX), "hello");
+    }
+
+    #[test]
+    fn test_unescape_xml() {
+        assert_eq!(unescape_xml("a &lt;b&gt; &#65;&#x42; &unknown; &"), "a <b> AB &unknown; &");
+    }
+}
@@ -0,0 +1,262 @@
+// Backend Failover
+//
+// A backend can name fallback backends (`fallbacks` in its settings). When its upstream is
+// overloaded (529, Anthropic's "overloaded_error", or 503) or its circuit breaker is open, the
+// request is sent to the fallbacks in order, and the first answer that isn't overloaded is given
+// to the client as if the primary had sent it. Fallbacks are expected to speak the same API as
+// the primary, e.g. a second Anthropic-compatible gateway, optionally under another model name.
+// Anthropic Messages requests can also fall back to Claude on Vertex AI: the model moves into the
+// URL and the body gets Vertex's `anthropic_version`. Credentials come from the fallback's stored
+// keys: the client's are meant for the primary's provider and are never forwarded, so a fallback
+// without stored keys is skipped. The backend that served the request is recorded in the log.
+
+use crate::api_keys::{inject_api_key, strip_client_credentials, KeyLease};
+use crate::backends::registry;
+use crate::upstream::{self, UpstreamError};
+use axum::http::{HeaderMap, Method};
+use serde::{Deserialize, Serialize};
+
+/// Anthropic API version Claude on Vertex AI expects in the request body
+const VERTEX_ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+
+/// Anthropic's status for an overloaded API
+const STATUS_OVERLOADED: u16 = 529;
+
+/// A backend to retry on, as listed in the primary's settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FallbackTarget {
+    /// Name of the fallback backend
+    pub backend: String,
+    /// Model to ask the fallback for (empty = the model the client asked for)
+    pub model: String,
+    /// For Claude on Vertex AI: `projects/{project}/locations/{region}`
+    pub vertex_location: String,
+}
+
+impl FallbackTarget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.backend.trim().is_empty() {
+            return Err("Fallback backend name cannot be empty".to_string());
+        }
+        if !self.vertex_location.is_empty() && !is_vertex_location(&self.vertex_location) {
+            return Err(format!(
+                "Vertex location '{}' must look like projects/<project>/locations/<region>",
+                self.vertex_location
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn is_vertex_location(location: &str) -> bool {
+    let parts: Vec<&str> = location.split('/').collect();
+    matches!(parts.as_slice(), ["projects", project, "locations", region] if !project.is_empty() && !region.is_empty())
+}
+
+/// Whether an upstream status should send the request to the fallbacks
+pub fn is_overloaded(status: u16) -> bool {
+    status == STATUS_OVERLOADED || status == 503
+}
+
+/// Path and body of a request as sent to a fallback, or None if it can't be translated
+fn translate(target: &FallbackTarget, fallback_kind: &str, path: &str, full_path: &str, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    let parsed = serde_json::from_slice::<serde_json::Value>(body).ok();
+
+    // Anthropic Messages -> Claude on Vertex AI
+    if fallback_kind == "vertex" && path.ends_with("/messages") {
+        if !is_vertex_location(&target.vertex_location) {
+            return None;
+        }
+        let mut request = parsed?;
+        let object = request.as_object_mut()?;
+        let requested = object.remove("model").and_then(|m| m.as_str().map(str::to_string));
+        let model = if target.model.is_empty() { requested? } else { target.model.clone() };
+        object
+            .entry("anthropic_version")
+            .or_insert_with(|| VERTEX_ANTHROPIC_VERSION.into());
+        let streaming = object.get("stream").and_then(|s| s.as_bool()).unwrap_or(false);
+
+        let method = if streaming { "streamRawPredict" } else { "rawPredict" };
+        let vertex_path = format!(
+            "/v1/{}/publishers/anthropic/models/{}:{}",
+            target.vertex_location, model, method
+        );
+        return Some((vertex_path, serde_json::to_vec(&request).ok()?));
+    }
+
+    // Same API: forward as is, under the fallback's model if one is set
+    match parsed {
+        Some(mut request) if !target.model.is_empty() && request.get("model").is_some() => {
+            request["model"] = target.model.clone().into();
+            Some((full_path.to_string(), serde_json::to_vec(&request).ok()?))
+        }
+        _ => Some((full_path.to_string(), body.to_vec())),
+    }
+}
+
+/// The client's headers without its credentials, plus the fallback's own key set by `inject`
+/// None when the fallback has no key to set
+fn with_fallback_key<T>(client_headers: &HeaderMap, inject: impl FnOnce(&mut HeaderMap) -> Option<T>) -> Option<(HeaderMap, T)> {
+    let mut headers = client_headers.clone();
+    strip_client_credentials(&mut headers);
+    let lease = inject(&mut headers)?;
+    Some((headers, lease))
+}
+
+/// An answer from a fallback
+pub struct FallbackResponse {
+    /// Name of the backend that served the request
+    pub backend: String,
+    pub response: reqwest::Response,
+    /// Lease on the fallback's stored key
+    pub key_lease: KeyLease,
+}
+
+/// Send a request to the fallbacks in order, until one answers without being overloaded
+/// `headers` are the client's (its credentials are dropped), `body` the redacted request body
+pub async fn send_to_fallbacks(
+    primary: &str,
+    targets: &[FallbackTarget],
+    method: &Method,
+    path: &str,
+    full_path: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Option<FallbackResponse> {
+    let registry = registry::current();
+
+    for target in targets.iter().filter(|t| t.backend != primary) {
+        let Some(entry) = registry.by_name(&target.backend) else {
+            eprintln!("[FAILOVER] Fallback backend '{}' is not enabled, skipping", target.backend);
+            continue;
+        };
+        let Some((fallback_path, fallback_body)) = translate(target, &entry.kind, path, full_path, body) else {
+            eprintln!("[FAILOVER] Request can't be translated for fallback backend '{}', skipping", target.backend);
+            continue;
+        };
+
+        if let Err(e) = entry.backend.validate_path(&fallback_path) {
+            eprintln!("[FAILOVER] {} for fallback backend '{}', skipping", e, target.backend);
+            continue;
+        }
+        let Some((forward_headers, key_lease)) =
+            with_fallback_key(headers, |h| inject_api_key(entry.backend.as_ref(), h))
+        else {
+            eprintln!("[FAILOVER] Fallback backend '{}' has no stored API key, skipping", target.backend);
+            continue;
+        };
+
+        let settings = entry.backend.upstream_settings();
+        let url = entry.backend.upstream_url(&fallback_path);
+        let mut request = upstream::client(&settings).request(method.clone(), &url);
+        for (name, value) in forward_headers.iter() {
+            if name != "host" && name != "content-length" {
+                request = request.header(name, value);
+            }
+        }
+        if !fallback_body.is_empty() {
+            request = request.body(fallback_body);
+        }
+
+        println!("[FAILOVER] Primary '{}' unavailable, sending request to '{}': {}", primary, entry.name, url);
+        match upstream::send(&entry.name, &settings, request, method.is_idempotent()).await {
+            Ok(response) if !is_overloaded(response.status().as_u16()) => {
+                return Some(FallbackResponse {
+                    backend: entry.name.clone(),
+                    response,
+                    key_lease,
+                });
+            }
+            Ok(response) => println!("[FAILOVER] Fallback '{}' is unavailable too ({})", entry.name, response.status()),
+            Err(UpstreamError::CircuitOpen(_)) => println!("[FAILOVER] Circuit open for fallback '{}'", entry.name),
+            Err(UpstreamError::Request(e)) => eprintln!("[FAILOVER] Fallback '{}' failed: {}", entry.name, e),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_to_vertex() {
+        let target = FallbackTarget {
+            backend: "vertex".to_string(),
+            model: "claude-sonnet-4@20250514".to_string(),
+            vertex_location: "projects/acme/locations/us-east5".to_string(),
+        };
+        let body = br#"{"model":"claude-sonnet-4-20250514","max_tokens":64,"stream":true,"messages":[]}"#;
+        let (path, translated) = translate(&target, "vertex", "/v1/messages", "/v1/messages?beta=true", body).unwrap();
+        assert_eq!(
+            path,
+            "/v1/projects/acme/locations/us-east5/publishers/anthropic/models/claude-sonnet-4@20250514:streamRawPredict"
+        );
+        let translated: serde_json::Value = serde_json::from_slice(&translated).unwrap();
+        assert!(translated.get("model").is_none());
+        assert_eq!(translated["anthropic_version"], VERTEX_ANTHROPIC_VERSION);
+        assert_eq!(translated["max_tokens"], 64);
+
+        // Without a location there is nowhere to send it
+        let no_location = FallbackTarget {
+            vertex_location: String::new(),
+            ..target
+        };
+        assert!(translate(&no_location, "vertex", "/v1/messages", "/v1/messages", body).is_none());
+    }
+
+    #[test]
+    fn test_primary_credentials_not_forwarded() {
+        let mut client = HeaderMap::new();
+        client.insert("x-api-key", "sk-ant-primary".parse().unwrap());
+        client.insert("authorization", "Bearer sk-ant-primary".parse().unwrap());
+        client.insert("anthropic-version", "2023-06-01".parse().unwrap());
+
+        let (headers, ()) = with_fallback_key(&client, |h| {
+            h.insert("authorization", "Bearer fallback-key".parse().unwrap());
+            Some(())
+        })
+        .unwrap();
+        assert!(headers.get("x-api-key").is_none());
+        assert_eq!(headers.get("authorization").unwrap(), "Bearer fallback-key");
+        assert_eq!(headers.get("anthropic-version").unwrap(), "2023-06-01");
+        assert!(headers.values().all(|v| !v.to_str().unwrap().contains("sk-ant-primary")));
+
+        // No stored key: the fallback is skipped rather than sent the client's
+        assert!(with_fallback_key(&client, |_| None::<()>).is_none());
+    }
+
+    #[test]
+    fn test_translate_same_api() {
+        let body = br#"{"model":"claude-sonnet-4-20250514","messages":[]}"#;
+        let keep_model = FallbackTarget {
+            backend: "claude-backup".to_string(),
+            ..FallbackTarget::default()
+        };
+        let (path, translated) = translate(&keep_model, "custom", "/v1/messages", "/v1/messages?beta=true", body).unwrap();
+        assert_eq!(path, "/v1/messages?beta=true");
+        assert_eq!(translated, body.to_vec());
+
+        let other_model = FallbackTarget {
+            model: "claude-haiku-4".to_string(),
+            ..keep_model
+        };
+        let (_, translated) = translate(&other_model, "custom", "/v1/messages", "/v1/messages", body).unwrap();
+        let translated: serde_json::Value = serde_json::from_slice(&translated).unwrap();
+        assert_eq!(translated["model"], "claude-haiku-4");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(FallbackTarget::default().validate().is_err());
+        let bad_location = FallbackTarget {
+            backend: "vertex".to_string(),
+            vertex_location: "us-east5".to_string(),
+            ..FallbackTarget::default()
+        };
+        assert!(bad_location.validate().is_err());
+        assert!(is_overloaded(529));
+        assert!(!is_overloaded(500));
+    }
+}
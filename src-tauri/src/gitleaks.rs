@@ -0,0 +1,261 @@
+// Gitleaks Ruleset Conversion
+//
+// Converts a gitleaks TOML config into DLP pattern pack entries, so secret rules
+// maintained for repository scanning can be reused for prompts:
+// - `regex` becomes the pattern; `secretGroup` becomes the named `secret` group
+// - `entropy` becomes min_entropy
+// - allowlist `regexes` and `stopwords` (per rule and global) become negative patterns
+// - `keywords` are dropped: the RegexSet pre-filter already skips rules that can't match
+// - `paths` allowlists and path-only rules don't apply to prompts and are skipped
+
+use crate::commands::PatternPackEntry;
+use crate::pattern_utils::SECRET_GROUP;
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+struct GitleaksAllowlist {
+    #[serde(default)]
+    regexes: Vec<String>,
+    #[serde(default)]
+    stopwords: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitleaksRule {
+    id: String,
+    #[serde(default)]
+    regex: Option<String>,
+    #[serde(default)]
+    secret_group: Option<usize>,
+    #[serde(default)]
+    entropy: Option<f64>,
+    /// Single allowlist (gitleaks < 8.25)
+    #[serde(default)]
+    allowlist: Option<GitleaksAllowlist>,
+    #[serde(default)]
+    allowlists: Vec<GitleaksAllowlist>,
+}
+
+#[derive(Deserialize)]
+struct GitleaksExtend {
+    #[serde(default)]
+    use_default: bool,
+}
+
+#[derive(Deserialize)]
+struct GitleaksConfig {
+    #[serde(default)]
+    rules: Vec<GitleaksRule>,
+    #[serde(default)]
+    allowlist: Option<GitleaksAllowlist>,
+    #[serde(default)]
+    allowlists: Vec<GitleaksAllowlist>,
+    #[serde(default)]
+    extend: Option<GitleaksExtend>,
+}
+
+/// Entries converted from a gitleaks config, plus one message per rule that was skipped
+pub struct GitleaksConversion {
+    pub entries: Vec<PatternPackEntry>,
+    pub skipped: Vec<String>,
+}
+
+/// Convert a gitleaks TOML config into pattern pack entries
+pub fn convert_gitleaks_config(contents: &str) -> Result<GitleaksConversion, String> {
+    let config: GitleaksConfig =
+        toml::from_str(contents).map_err(|e| format!("Invalid gitleaks config: {}", e))?;
+
+    let mut skipped = Vec::new();
+    if config.extend.is_some_and(|e| e.use_default) {
+        skipped.push("[extend] useDefault: the default gitleaks rules are not bundled, import them from their file".to_string());
+    }
+
+    // Global allowlists apply to every rule
+    let global_negatives: Vec<String> = config
+        .allowlist
+        .iter()
+        .chain(&config.allowlists)
+        .flat_map(allowlist_negatives)
+        .collect();
+
+    let mut entries = Vec::new();
+    for rule in config.rules {
+        let Some(regex) = rule.regex.as_deref().filter(|r| !r.is_empty()) else {
+            skipped.push(format!("{}: path-only rules don't apply to prompts", rule.id));
+            continue;
+        };
+
+        let pattern = match rule.secret_group.filter(|&g| g > 0) {
+            Some(group) => match name_secret_group(regex, group) {
+                Some(p) => p,
+                None => {
+                    skipped.push(format!("{}: secretGroup {} not found in regex", rule.id, group));
+                    continue;
+                }
+            },
+            None => regex.to_string(),
+        };
+
+        let mut negatives: Vec<String> = rule
+            .allowlist
+            .iter()
+            .chain(&rule.allowlists)
+            .flat_map(allowlist_negatives)
+            .collect();
+        negatives.extend(global_negatives.iter().cloned());
+
+        entries.push(PatternPackEntry {
+            name: rule.id,
+            pattern_type: "regex".to_string(),
+            patterns: vec![pattern],
+            negative_pattern_type: (!negatives.is_empty()).then(|| "regex".to_string()),
+            negative_patterns: (!negatives.is_empty()).then_some(negatives),
+            enabled: true,
+            min_occurrences: 1,
+            min_unique_chars: 0,
+            min_entropy: rule.entropy.unwrap_or(0.0),
+            validator: None,
+            category: Some("secret".to_string()),
+            action: None,
+            severity: None,
+            allowlist: Vec::new(),
+            scope: None,
+            normalize: false,
+        });
+    }
+
+    Ok(GitleaksConversion { entries, skipped })
+}
+
+/// Negative regexes for an allowlist: its regexes as-is, stopwords as case-insensitive literals
+fn allowlist_negatives(allowlist: &GitleaksAllowlist) -> Vec<String> {
+    allowlist
+        .regexes
+        .iter()
+        .cloned()
+        .chain(
+            allowlist
+                .stopwords
+                .iter()
+                .map(|w| format!("(?i){}", regex::escape(w))),
+        )
+        .collect()
+}
+
+/// Rewrite capture group `group` (1-based, counted like gitleaks' secretGroup) as the
+/// named `secret` group. Returns None if the regex has fewer capture groups.
+fn name_secret_group(regex: &str, group: usize) -> Option<String> {
+    let mut count = 0;
+    let mut escaped = false;
+    let mut class_start: Option<usize> = None;
+    let mut chars = regex.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            '[' if class_start.is_none() => class_start = Some(i),
+            // POSIX classes like [:alpha:] inside a bracket expression
+            '[' if regex[i..].starts_with("[:") => {
+                if let Some(end) = regex[i..].find(":]") {
+                    while chars.peek().is_some_and(|&(j, _)| j < i + end + 2) {
+                        chars.next();
+                    }
+                }
+            }
+            ']' => {
+                // A ']' right after '[' or '[^' is a literal
+                if let Some(start) = class_start {
+                    let body = &regex[start + 1..i];
+                    if !body.is_empty() && body != "^" {
+                        class_start = None;
+                    }
+                }
+            }
+            '(' if class_start.is_none() => {
+                let rest = &regex[i + 1..];
+                let name_len = if let Some(named) = rest.strip_prefix("?P<").or_else(|| {
+                    rest.strip_prefix("?<").filter(|r| !r.starts_with('=') && !r.starts_with('!'))
+                }) {
+                    // Named group: the existing name is replaced
+                    Some(rest.len() - named.len() + named.find('>')? + 1)
+                } else if rest.starts_with('?') {
+                    // Non-capturing group or flags
+                    continue;
+                } else {
+                    Some(0)
+                };
+
+                count += 1;
+                if count == group {
+                    let after = i + 1 + name_len.unwrap_or(0);
+                    return Some(format!("{}(?P<{}>{}", &regex[..i], SECRET_GROUP, &regex[after..]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_secret_group() {
+        assert_eq!(
+            name_secret_group(r"(?i)(?:key|token)\s*=\s*([a-z0-9]{32})", 1).as_deref(),
+            Some(r"(?i)(?:key|token)\s*=\s*(?P<secret>[a-z0-9]{32})")
+        );
+        assert_eq!(
+            name_secret_group(r"(\w+)[(]=(?P<value>\d+)", 2).as_deref(),
+            Some(r"(\w+)[(]=(?P<secret>\d+)")
+        );
+        assert_eq!(
+            name_secret_group(r"[[:alpha:]\]]+\((x)", 1).as_deref(),
+            Some(r"[[:alpha:]\]]+\((?P<secret>x)")
+        );
+        assert_eq!(name_secret_group(r"(?:a)b", 1), None);
+    }
+
+    #[test]
+    fn test_convert_gitleaks_config() {
+        let config = r#"
+            [allowlist]
+            stopwords = ["example"]
+
+            [[rules]]
+            id = "generic-api-key"
+            regex = '''(?i)api[_-]?key\s*[:=]\s*['"]?([a-z0-9]{32})'''
+            secretGroup = 1
+            entropy = 3.5
+            keywords = ["api"]
+            [rules.allowlist]
+            regexes = ['''test[a-z0-9]+''']
+
+            [[rules]]
+            id = "pkcs12-file"
+            path = '''\.p12$'''
+        "#;
+
+        let conversion = convert_gitleaks_config(config).unwrap();
+        assert_eq!(conversion.entries.len(), 1);
+        assert_eq!(conversion.skipped.len(), 1);
+
+        let entry = &conversion.entries[0];
+        assert_eq!(entry.name, "generic-api-key");
+        assert!(entry.patterns[0].contains("(?P<secret>[a-z0-9]{32})"));
+        assert_eq!(entry.min_entropy, 3.5);
+        assert_eq!(
+            entry.negative_patterns.as_deref(),
+            Some(&["test[a-z0-9]+".to_string(), "(?i)example".to_string()][..])
+        );
+    }
+}
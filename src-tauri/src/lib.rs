@@ -3,25 +3,60 @@
 // A Tauri app that proxies LLM API requests with DLP (Data Loss Prevention) capabilities.
 // Currently supports Claude (Anthropic), with plans for OpenAI, Gemini, etc.
 
+mod api_keys;
+mod api_translation;
 mod backends;
+mod budgets;
 mod builtin_patterns;
+mod canary;
+mod client_auth;
 mod commands;
+mod content_encoding;
 mod cursor_hooks;
 mod database;
+mod db_encryption;
+mod dictionaries;
 mod dlp;
 mod dlp_pattern_config;
+mod dlp_policy;
+mod dlp_stream;
+mod dlp_vault;
+mod dlp_webhooks;
+mod document_text;
+mod failover;
+mod gitleaks;
+mod log_export;
+mod log_queue;
+mod log_sanitizer;
+mod mirror;
+mod ner;
+mod ocr;
 mod pattern_utils;
+mod pricing;
+mod prompt_injection;
 mod proxy;
+mod realtime;
 mod requestresponsemetadata;
+mod response_cache;
+mod retention;
+mod secret_verifier;
+mod storage;
+mod ttfb_monitor;
+mod unicode_fold;
+mod upstream;
+mod upstream_proxy;
+mod validators;
 
+use backends::registry::Registry;
 use database::get_port_from_db;
 use dlp_pattern_config::DEFAULT_PORT;
-use std::sync::{Arc, Mutex};
+use proxy::RestartRequest;
+use std::sync::{Arc, Mutex, RwLock};
 use tauri::{
     tray::{TrayIconBuilder, TrayIconEvent, MouseButton, MouseButtonState},
-    AppHandle, Manager, WindowEvent, PhysicalPosition,
+    AppHandle, Manager, RunEvent, WindowEvent, PhysicalPosition,
 };
-use tokio::sync::watch;
+use tokio::sync::mpsc;
 
 #[cfg(target_os = "macos")]
 use tauri::ActivationPolicy;
@@ -68,13 +103,18 @@ pub enum ProxyStatus {
 // Global state for reverse proxy control
 pub static PROXY_PORT: std::sync::LazyLock<Arc<Mutex<u16>>> =
     std::sync::LazyLock::new(|| Arc::new(Mutex::new(DEFAULT_PORT)));
-pub static RESTART_SENDER: std::sync::LazyLock<Arc<Mutex<Option<watch::Sender<bool>>>>> =
+pub static RESTART_SENDER: std::sync::LazyLock<Arc<Mutex<Option<mpsc::UnboundedSender<RestartRequest>>>>> =
     std::sync::LazyLock::new(|| Arc::new(Mutex::new(None)));
 pub static PROXY_STATUS: std::sync::LazyLock<Arc<Mutex<ProxyStatus>>> =
     std::sync::LazyLock::new(|| Arc::new(Mutex::new(ProxyStatus::Starting)));
+pub static BACKEND_REGISTRY: std::sync::LazyLock<Arc<RwLock<Registry>>> =
+    std::sync::LazyLock::new(|| Arc::new(RwLock::new(Registry::default())));
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Encrypt the database if that was enabled, before anything opens it
+    db_encryption::apply_pending(dlp_pattern_config::get_db_path());
+
     // Initialize reverse proxy port from environment variable or database
     {
         let port = std::env::var("QPORT")
@@ -96,12 +136,9 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_notification::init())
         .setup(|app| {
-            // Spawn reverse proxy server with app handle for events
+            // Spawn reverse proxy server with app handle for events, on the app's async runtime
             let app_handle = app.handle().clone();
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(proxy::start_proxy_server(app_handle));
-            });
+            tauri::async_runtime::spawn(proxy::start_proxy_server(app_handle));
 
             // Build tray icon with click handler to toggle popup
             let _tray = TrayIconBuilder::new()
@@ -174,12 +211,38 @@ pub fn run() {
             commands::greet,
             commands::get_dashboard_stats,
             commands::get_backends,
+            commands::get_token_count_stats,
             commands::get_models,
             commands::get_message_logs,
             commands::export_message_logs,
             commands::get_port_setting,
             commands::get_proxy_status,
             commands::save_port_setting,
+            commands::get_proxy_body_limits,
+            commands::save_proxy_body_limits,
+            commands::get_token_budgets,
+            commands::add_token_budget,
+            commands::delete_token_budget,
+            commands::get_model_pricing,
+            commands::save_model_price,
+            commands::delete_model_price,
+            commands::restore_default_model_pricing,
+            commands::get_proxy_bind_addresses,
+            commands::save_proxy_bind_addresses,
+            commands::get_proxy_client_auth,
+            commands::save_proxy_client_auth,
+            commands::regenerate_proxy_client_token,
+            commands::get_response_cache_settings,
+            commands::save_response_cache_settings,
+            commands::clear_response_cache,
+            commands::get_upstream_proxy_settings,
+            commands::save_upstream_proxy_settings,
+            commands::get_retention_settings,
+            commands::save_retention_settings,
+            commands::run_retention_cleanup,
+            commands::check_database_orphans,
+            commands::get_database_encryption_status,
+            commands::enable_database_encryption,
             commands::restart_proxy,
             commands::get_dlp_settings,
             commands::add_dlp_pattern,
@@ -190,9 +253,42 @@ pub fn run() {
             commands::get_dlp_detections_for_request,
             commands::get_dlp_action_setting,
             commands::save_dlp_action_setting,
+            commands::get_dlp_scan_responses_setting,
+            commands::save_dlp_scan_responses_setting,
+            commands::get_dlp_verify_secrets_setting,
+            commands::save_dlp_verify_secrets_setting,
+            commands::get_dlp_hash_values_setting,
+            commands::save_dlp_hash_values_setting,
+            commands::get_dlp_canary_setting,
+            commands::save_dlp_canary_setting,
+            commands::get_dlp_detect_injection_setting,
+            commands::save_dlp_detect_injection_setting,
+            commands::get_dlp_webhook_settings,
+            commands::save_dlp_webhook_settings,
+            commands::test_dlp_webhook,
+            commands::get_dlp_incident_window,
+            commands::save_dlp_incident_window,
+            commands::get_dlp_scan_limits,
+            commands::save_dlp_scan_limits,
+            commands::get_dlp_min_block_confidence,
+            commands::save_dlp_min_block_confidence,
+            commands::get_dlp_ner_settings,
+            commands::save_dlp_ner_settings,
+            commands::get_dlp_severity_policy,
+            commands::save_dlp_severity_policy,
+            commands::export_dlp_patterns,
+            commands::import_dlp_patterns,
+            commands::get_dlp_policies,
+            commands::add_dlp_policy,
+            commands::update_dlp_policy,
+            commands::delete_dlp_policy,
+            commands::import_gitleaks_rules,
             commands::test_dlp_pattern,
             // Tool call commands
             commands::get_tool_calls_for_request,
+            commands::get_request_bodies,
+            commands::get_conversations,
+            commands::get_conversation_timeline,
             commands::get_tool_call_stats,
             commands::get_tool_call_insights,
             commands::set_shell_env,
@@ -215,7 +311,23 @@ pub fn run() {
             commands::get_predefined_backends,
             commands::update_predefined_backend,
             commands::reset_predefined_backend,
+            commands::toggle_predefined_backend,
+            commands::restart_backend_port,
+            commands::get_backend_upstream_settings,
+            commands::save_backend_upstream_settings,
+            commands::get_upstream_circuit_breakers,
+            commands::get_upstream_pool_stats,
+            commands::reset_upstream_circuit_breaker,
+            commands::get_backend_api_keys,
+            commands::add_backend_api_key,
+            commands::delete_backend_api_key,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            // Write requests still queued for the log before the process goes away
+            if let RunEvent::Exit = event {
+                tauri::async_runtime::block_on(log_queue::flush());
+            }
+        });
 }
@@ -0,0 +1,179 @@
+// Log Export
+//
+// Writes the request log, as filtered in the Logs view, to a file for data warehouses and incident
+// reports: JSON Lines (one request per line, bodies parsed as JSON where they are JSON) or CSV (one
+// row per request, bodies as text). Each request carries its DLP detections; detected values
+// themselves are left out, only the pattern, where it was found and what replaced it.
+
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Export file format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(format!("Unsupported export format '{}' (use jsonl or csv)", other)),
+        }
+    }
+}
+
+/// A detection on an exported request
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportDetection {
+    pub pattern_name: String,
+    pub category: String,
+    pub severity: String,
+    pub direction: String,
+    pub action: Option<String>,
+    pub replaced_with: String,
+    pub value_hash: Option<String>,
+}
+
+/// A request as exported
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportLog {
+    pub id: i64,
+    pub timestamp: String,
+    pub backend: String,
+    pub model: String,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub latency_ms: i64,
+    pub request_body: Option<String>,
+    pub response_body: Option<String>,
+    pub dlp_action: i64,
+    pub served_by: Option<String>,
+    pub detections: Vec<ExportDetection>,
+}
+
+/// A body as a JSON value: parsed if it is JSON, a string otherwise
+fn body_value(body: &Option<String>) -> serde_json::Value {
+    match body {
+        Some(body) => serde_json::from_str(body).unwrap_or_else(|_| body.clone().into()),
+        None => serde_json::Value::Null,
+    }
+}
+
+fn write_jsonl(logs: &[ExportLog], out: &mut impl Write) -> io::Result<()> {
+    for log in logs {
+        let line = serde_json::json!({
+            "id": log.id,
+            "timestamp": log.timestamp,
+            "backend": log.backend,
+            "model": log.model,
+            "input_tokens": log.input_tokens,
+            "output_tokens": log.output_tokens,
+            "latency_ms": log.latency_ms,
+            "dlp_action": log.dlp_action,
+            "served_by": log.served_by,
+            "detections": log.detections,
+            "request": body_value(&log.request_body),
+            "response": body_value(&log.response_body),
+        });
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// A CSV field, quoted when it holds a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+const CSV_HEADER: &str = "id,timestamp,backend,model,input_tokens,output_tokens,latency_ms,dlp_action,served_by,detections,request_body,response_body";
+
+fn write_csv(logs: &[ExportLog], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    for log in logs {
+        let detections: Vec<&str> = log.detections.iter().map(|d| d.pattern_name.as_str()).collect();
+        let fields = [
+            log.id.to_string(),
+            csv_field(&log.timestamp),
+            csv_field(&log.backend),
+            csv_field(&log.model),
+            log.input_tokens.to_string(),
+            log.output_tokens.to_string(),
+            log.latency_ms.to_string(),
+            log.dlp_action.to_string(),
+            csv_field(log.served_by.as_deref().unwrap_or_default()),
+            csv_field(&detections.join(";")),
+            csv_field(log.request_body.as_deref().unwrap_or_default()),
+            csv_field(log.response_body.as_deref().unwrap_or_default()),
+        ];
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+pub fn write_logs(logs: &[ExportLog], format: ExportFormat, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Jsonl => write_jsonl(logs, out),
+        ExportFormat::Csv => write_csv(logs, out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ExportLog {
+        ExportLog {
+            id: 7,
+            timestamp: "2026-01-02T03:04:05Z".to_string(),
+            backend: "claude".to_string(),
+            model: "claude-sonnet-4".to_string(),
+            input_tokens: 12,
+            output_tokens: 34,
+            latency_ms: 560,
+            request_body: Some(r#"{"messages":[{"role":"user","content":"hi, \"there\""}]}"#.to_string()),
+            response_body: Some("not json\nat all".to_string()),
+            dlp_action: 1,
+            served_by: None,
+            detections: vec![ExportDetection {
+                pattern_name: "AWS Access Key".to_string(),
+                category: "secret".to_string(),
+                severity: "high".to_string(),
+                direction: "request".to_string(),
+                action: Some("redact".to_string()),
+                replaced_with: "AKIA****".to_string(),
+                value_hash: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_jsonl_parses_bodies() {
+        let mut out = Vec::new();
+        write_logs(&[sample()], ExportFormat::Jsonl, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 1);
+        let line: serde_json::Value = serde_json::from_str(text.trim()).unwrap();
+        assert_eq!(line["request"]["messages"][0]["role"], "user");
+        assert_eq!(line["response"], "not json\nat all");
+        assert_eq!(line["detections"][0]["pattern_name"], "AWS Access Key");
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let mut out = Vec::new();
+        write_logs(&[sample()], ExportFormat::Csv, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with(CSV_HEADER));
+        assert!(text.contains(r#""{""messages"":[{""role"":""user"",""content"":""hi, \""there\""""}]}""#));
+        assert!(text.contains("\"not json\nat all\""));
+        assert!(text.contains(",AWS Access Key,"));
+        assert!(ExportFormat::parse("parquet").is_err());
+    }
+}
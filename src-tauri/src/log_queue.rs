@@ -0,0 +1,200 @@
+// Write-Behind Request Log
+//
+// Logging a proxied request takes several statements (the request, then its detections, tool
+// calls and follow-up columns), which kept responses waiting on the database and, under bursty
+// agent traffic, on each other's locks. The proxy hands finished requests to a bounded queue
+// instead, and a single writer drains it, writing each batch in one transaction on a connection
+// of its own. When the queue is full, or not running yet, a request is written inline as before,
+// so nothing is dropped. Restarting the gateway and quitting the app wait for the queue to be
+// written (`flush`), so requests finished just before aren't lost with it.
+
+use crate::database::Database;
+use crate::dlp::DlpDetection;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata};
+use crate::storage::{RequestLog, StorageResult};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Requests waiting to be written before the proxy falls back to writing inline
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Most requests written in one transaction
+const MAX_BATCH: usize = 64;
+
+/// Longest wait for the writer to catch up on shutdown
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+static QUEUE: OnceLock<mpsc::Sender<Message>> = OnceLock::new();
+
+enum Message {
+    Request(Box<LoggedRequest>),
+    /// Answered once every request queued before it is written
+    Flush(oneshot::Sender<()>),
+}
+
+/// A proxied request and everything logged with it
+#[derive(Default)]
+pub struct LoggedRequest {
+    pub backend: String,
+    pub method: String,
+    pub path: String,
+    pub endpoint_name: String,
+    pub request_body: String,
+    pub response_body: String,
+    pub response_status: u16,
+    pub is_streaming: bool,
+    pub latency_ms: u64,
+    pub req_meta: RequestMetadata,
+    pub resp_meta: ResponseMetadata,
+    pub extra_metadata: Option<String>,
+    pub request_headers: Option<String>,
+    pub response_headers: Option<String>,
+    pub dlp_action: i32,
+    /// Canary planted in the request
+    pub canary_id: Option<i64>,
+    /// Some(hit) when the response was cacheable
+    pub response_cache_hit: Option<bool>,
+    /// Fallback backend that answered
+    pub served_by: Option<String>,
+    /// API the client spoke, when translated
+    pub translated_from: Option<&'static str>,
+    /// Time to first byte, and for streamed responses the time after it
+    pub latency_breakdown: Option<(u64, Option<u64>)>,
+    pub detections: Vec<DlpDetection>,
+}
+
+impl LoggedRequest {
+    fn write(&self, db: &Database) -> StorageResult<i64> {
+        let request_id = db.log_request(
+            &self.backend,
+            &self.method,
+            &self.path,
+            &self.endpoint_name,
+            &self.request_body,
+            &self.response_body,
+            self.response_status,
+            self.is_streaming,
+            self.latency_ms,
+            &self.req_meta,
+            &self.resp_meta,
+            self.extra_metadata.as_deref(),
+            self.request_headers.as_deref(),
+            self.response_headers.as_deref(),
+            self.dlp_action,
+        )?;
+
+        if let Some(canary_id) = self.canary_id {
+            let _ = db.set_canary_request_id(canary_id, request_id);
+        }
+        if let Some(hit) = self.response_cache_hit {
+            let _ = db.set_response_cache_hit(request_id, hit);
+        }
+        if let Some(fallback) = self.served_by.as_deref() {
+            let _ = db.set_served_by(request_id, fallback);
+        }
+        if let Some(client_api) = self.translated_from {
+            let _ = db.set_translated_from(request_id, client_api);
+        }
+        if let Some((ttfb_ms, stream_ms)) = self.latency_breakdown {
+            let _ = db.set_latency_breakdown(request_id, ttfb_ms, stream_ms);
+        }
+        if !self.detections.is_empty() {
+            let _ = db.log_dlp_detections(request_id, &self.detections);
+        }
+        if !self.resp_meta.tool_calls.is_empty() {
+            if let Err(e) = db.log_tool_calls(request_id, &self.resp_meta.tool_calls) {
+                eprintln!("[LOG_QUEUE] Failed to log tool calls for request {}: {}", request_id, e);
+            }
+        }
+        Ok(request_id)
+    }
+}
+
+fn write_batch(writer: &Database, batch: &[LoggedRequest]) {
+    let result = writer.in_transaction(|db| {
+        for request in batch {
+            if let Err(e) = request.write(db) {
+                eprintln!("[LOG_QUEUE] Failed to log request for backend '{}': {}", request.backend, e);
+            }
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("[LOG_QUEUE] Failed to write {} logged requests: {}", batch.len(), e);
+    }
+}
+
+/// Start the writer; requests submitted before this are written inline
+pub fn spawn_writer() {
+    let writer = match Database::dedicated() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("[LOG_QUEUE] Failed to open the log writer, logging inline: {}", e);
+            return;
+        }
+    };
+    let (sender, mut receiver) = mpsc::channel(QUEUE_CAPACITY);
+    if QUEUE.set(sender).is_err() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut messages = Vec::with_capacity(MAX_BATCH);
+        while receiver.recv_many(&mut messages, MAX_BATCH).await > 0 {
+            let mut requests = Vec::with_capacity(messages.len());
+            let mut flushes = Vec::new();
+            for message in messages.drain(..) {
+                match message {
+                    Message::Request(request) => requests.push(*request),
+                    Message::Flush(reply) => flushes.push(reply),
+                }
+            }
+            if !requests.is_empty() {
+                let writer = writer.clone();
+                let _ = tokio::task::spawn_blocking(move || write_batch(&writer, &requests)).await;
+            }
+            for reply in flushes {
+                let _ = reply.send(());
+            }
+        }
+    });
+}
+
+/// Wait until every request queued so far is written, for at most `FLUSH_TIMEOUT`
+pub async fn flush() {
+    let Some(queue) = QUEUE.get() else {
+        return;
+    };
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let flushed = tokio::time::timeout(FLUSH_TIMEOUT, async {
+        if queue.send(Message::Flush(reply_tx)).await.is_ok() {
+            let _ = reply_rx.await;
+        }
+    })
+    .await;
+    if flushed.is_err() {
+        eprintln!(
+            "[LOG_QUEUE] Requests still queued after {}s, not waiting for them",
+            FLUSH_TIMEOUT.as_secs()
+        );
+    }
+}
+
+/// Log a request through the writer, or inline when its queue is full or not running
+pub fn submit(db: &Database, request: LoggedRequest) {
+    let request = match QUEUE.get() {
+        Some(queue) => match queue.try_send(Message::Request(Box::new(request))) {
+            Ok(()) => return,
+            Err(mpsc::error::TrySendError::Full(message) | mpsc::error::TrySendError::Closed(message)) => {
+                match message {
+                    Message::Request(request) => *request,
+                    Message::Flush(_) => return,
+                }
+            }
+        },
+        None => request,
+    };
+    if let Err(e) = request.write(db) {
+        eprintln!("[LOG_QUEUE] Failed to log request for backend '{}': {}", request.backend, e);
+    }
+}
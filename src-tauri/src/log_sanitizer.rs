@@ -0,0 +1,136 @@
+// Log Sanitization
+//
+// Client credentials must not end up in the request log. Before headers are stored, credential
+// headers (Authorization, x-api-key, cookies, ...) are replaced by a keyed hash, so two requests
+// made with the same key can still be told apart from ones made with another key, without the key
+// being recoverable. Credentials passed in the query string (e.g. Gemini's `?key=`) are hashed in
+// the logged path the same way. Backends can override the action per header: hash or remove
+// another header, or keep one of the defaults as sent.
+
+use crate::dlp_vault::value_digest;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Headers hashed unless a backend overrides them
+const DEFAULT_SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "api-key",
+    "cookie",
+    "set-cookie",
+];
+
+/// Query parameters that carry credentials
+const SENSITIVE_QUERY_PARAMS: &[&str] = &["key", "api_key", "apikey", "access_token", "token"];
+
+/// Hex digits of the keyed digest kept in a hashed value
+const HASH_PREFIX_LEN: usize = 16;
+
+/// What to log for a header
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderLogAction {
+    Keep,
+    Hash,
+    Remove,
+}
+
+fn header_action(name: &str, overrides: &HashMap<String, HeaderLogAction>) -> HeaderLogAction {
+    let name = name.to_ascii_lowercase();
+    if let Some(action) = overrides.get(&name) {
+        return *action;
+    }
+    if DEFAULT_SENSITIVE_HEADERS.contains(&name.as_str()) {
+        HeaderLogAction::Hash
+    } else {
+        HeaderLogAction::Keep
+    }
+}
+
+/// Keyed hash of a credential, keeping an auth scheme ("Bearer hashed:…") readable
+fn hashed(value: &str) -> String {
+    let (scheme, secret) = match value.split_once(' ') {
+        Some((scheme, secret)) if !scheme.contains('=') => (Some(scheme), secret),
+        _ => (None, value),
+    };
+    let digest = &value_digest(secret.trim())[..HASH_PREFIX_LEN];
+    match scheme {
+        Some(scheme) => format!("{} hashed:{}", scheme, digest),
+        None => format!("hashed:{}", digest),
+    }
+}
+
+/// Headers as a JSON object for the log, with credentials hashed or removed
+pub fn headers_to_json(headers: &HeaderMap, overrides: &HashMap<String, HeaderLogAction>) -> String {
+    let map: HashMap<String, String> = headers
+        .iter()
+        .filter_map(|(k, v)| {
+            let value = v.to_str().unwrap_or("");
+            let logged = match header_action(k.as_str(), overrides) {
+                HeaderLogAction::Keep => value.to_string(),
+                HeaderLogAction::Hash => hashed(value),
+                HeaderLogAction::Remove => return None,
+            };
+            Some((k.as_str().to_string(), logged))
+        })
+        .collect();
+    serde_json::to_string(&map).unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Path with credential query parameters hashed
+pub fn sanitize_path(path: &str) -> String {
+    let Some((base, query)) = path.split_once('?') else {
+        return path.to_string();
+    };
+
+    let params: Vec<String> = query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, value))
+                if !value.is_empty() && SENSITIVE_QUERY_PARAMS.contains(&name.to_ascii_lowercase().as_str()) =>
+            {
+                format!("{}={}", name, hashed(value))
+            }
+            _ => param.to_string(),
+        })
+        .collect();
+    format!("{}?{}", base, params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_hashed_and_overridden() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer sk-secret".parse().unwrap());
+        headers.insert("cookie", "session=abc".parse().unwrap());
+        headers.insert("x-team-token", "t0k3n".parse().unwrap());
+        headers.insert("content-type", "application/json".parse().unwrap());
+
+        let overrides = HashMap::from([
+            ("cookie".to_string(), HeaderLogAction::Remove),
+            ("x-team-token".to_string(), HeaderLogAction::Hash),
+        ]);
+        let logged: HashMap<String, String> =
+            serde_json::from_str(&headers_to_json(&headers, &overrides)).unwrap();
+
+        assert!(logged["authorization"].starts_with("Bearer hashed:"));
+        assert!(!logged["authorization"].contains("sk-secret"));
+        assert!(!logged.contains_key("cookie"));
+        assert!(logged["x-team-token"].starts_with("hashed:"));
+        assert_eq!(logged["content-type"], "application/json");
+    }
+
+    #[test]
+    fn test_sanitize_path() {
+        let path = sanitize_path("/v1beta/models/gemini:generateContent?alt=sse&key=AIzaSecret");
+        assert!(path.starts_with("/v1beta/models/gemini:generateContent?alt=sse&key=hashed:"));
+        assert!(!path.contains("AIzaSecret"));
+        assert_eq!(sanitize_path("/v1/messages"), "/v1/messages");
+    }
+}
@@ -0,0 +1,132 @@
+// Request Mirroring (shadow traffic)
+//
+// A sampled share of a backend's logged requests can be copied to a secondary endpoint, such as a
+// staging gateway or an evaluation service. The copy is sent after DLP redaction (the mirror sees
+// exactly what the provider sees) and without credentials, so neither the client's key nor a
+// stored provider key leaves for the mirror. Mirroring is fire-and-forget: the mirror's response
+// is read and discarded, failures are only logged, and nothing it does affects the client.
+
+use crate::log_sanitizer::sanitize_path;
+use crate::upstream_proxy;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use axum::http::{HeaderMap, Method};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Header marking a mirrored request, set to the backend name
+const MIRROR_HEADER: &str = "x-quilr-mirror";
+
+/// Headers not copied to the mirror: recalculated, or credentials
+const SKIP_MIRROR_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "api-key",
+    "cookie",
+];
+
+/// Longest a mirrored request may take, so a slow mirror can't pile up tasks
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Mirror settings of a backend (`mirror` in its settings JSON)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MirrorSettings {
+    /// Base URL the request path is appended to (empty = off)
+    pub url: String,
+    /// Share of requests mirrored, 0-100
+    pub sample_percent: u8,
+}
+
+impl MirrorSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = self.url.trim();
+        if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Mirror URL must start with http:// or https://".to_string());
+        }
+        if self.sample_percent > 100 {
+            return Err("Mirror sample must be between 0 and 100 percent".to_string());
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.url.trim().is_empty() && self.sample_percent > 0
+    }
+}
+
+fn sampled(percent: u8) -> bool {
+    OsRng.next_u32() % 100 < percent as u32
+}
+
+/// Send a copy of a (redacted) request to the backend's mirror, if it is picked by the sample
+pub fn mirror_request(
+    backend: &str,
+    settings: &MirrorSettings,
+    method: &Method,
+    full_path: &str,
+    headers: &HeaderMap,
+    body: Vec<u8>,
+) {
+    if !settings.is_enabled() || !sampled(settings.sample_percent) {
+        return;
+    }
+
+    // Query-string credentials (e.g. Gemini's ?key=) are hashed like in the log
+    let url = format!("{}{}", settings.url.trim().trim_end_matches('/'), sanitize_path(full_path));
+    let mut request = upstream_proxy::client(MIRROR_TIMEOUT).request(method.clone(), &url);
+    for (name, value) in headers.iter() {
+        if !SKIP_MIRROR_HEADERS.contains(&name.as_str()) {
+            request = request.header(name, value);
+        }
+    }
+    request = request.header(MIRROR_HEADER, backend);
+    if !body.is_empty() {
+        request = request.body(body);
+    }
+
+    let backend = backend.to_string();
+    tokio::spawn(async move {
+        match request.send().await {
+            // Read the body so the connection can be reused; its content is not needed
+            Ok(response) => {
+                let status = response.status();
+                let _ = response.bytes().await;
+                if !status.is_success() {
+                    println!("[MIRROR] Mirror for backend '{}' answered {}", backend, status);
+                }
+            }
+            Err(e) => eprintln!("[MIRROR] Failed to mirror request for backend '{}': {}", backend, e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_sampling() {
+        assert!(MirrorSettings::default().validate().is_ok());
+        assert!(!MirrorSettings::default().is_enabled());
+
+        let bad_url = MirrorSettings {
+            url: "staging.internal".to_string(),
+            sample_percent: 10,
+        };
+        assert!(bad_url.validate().is_err());
+
+        let too_many = MirrorSettings {
+            url: "https://staging.internal".to_string(),
+            sample_percent: 150,
+        };
+        assert!(too_many.validate().is_err());
+
+        assert!(sampled(100));
+        assert!(!sampled(0));
+    }
+}
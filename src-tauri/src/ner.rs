@@ -0,0 +1,408 @@
+// Named-Entity Recognition (NER)
+//
+// Person, organization and place names have no fixed shape, and the dictionary detectors only
+// know common first names and street addresses. With the `ner` feature, "ner" patterns run a
+// small local token-classification model (a BERT-style NER model exported to ONNX) over the
+// text and flag the entities it finds. Text never leaves the machine.
+//
+// The model lives in ner/ next to the database: model.onnx, tokenizer.json and config.json (for its
+// id2label map). ONNX Runtime is loaded at runtime; set ORT_DYLIB_PATH if it is not on the
+// library path. Without the feature or the model, "ner" patterns match nothing.
+
+use crate::dlp_pattern_config::get_db_path;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+/// Text is fed to the model in pieces of at most this many bytes (about 400 tokens of English,
+/// under the 512-token limit of BERT-style models)
+const MAX_CHUNK_BYTES: usize = 1500;
+
+/// Entity types reported by "ner" patterns (one per pattern line)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityLabel {
+    Person,
+    Organization,
+    Location,
+}
+
+impl EntityLabel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "person" => Some(EntityLabel::Person),
+            "organization" => Some(EntityLabel::Organization),
+            "location" => Some(EntityLabel::Location),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntityLabel::Person => "person",
+            EntityLabel::Organization => "organization",
+            EntityLabel::Location => "location",
+        }
+    }
+
+    /// Label of a model tag (B-PER, I-ORG, LOC, GPE, ...); MISC and O are not reported
+    fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag
+            .strip_prefix("B-")
+            .or_else(|| tag.strip_prefix("I-"))
+            .unwrap_or(tag);
+        match tag.to_ascii_uppercase().as_str() {
+            "PER" | "PERSON" => Some(EntityLabel::Person),
+            "ORG" | "ORGANIZATION" => Some(EntityLabel::Organization),
+            "LOC" | "LOCATION" | "GPE" => Some(EntityLabel::Location),
+            _ => None,
+        }
+    }
+}
+
+/// An entity at text[start..end], with the model's average token probability
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    pub label: EntityLabel,
+    pub start: usize,
+    pub end: usize,
+    pub score: f64,
+}
+
+/// Global NER settings (`dlp_ner` in the settings table)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NerSettings {
+    /// Run "ner" patterns; off by default since inference adds latency to every scan
+    pub enabled: bool,
+    /// Entities the model scores below this (0-1) are dropped
+    pub min_score: f64,
+}
+
+impl Default for NerSettings {
+    fn default() -> Self {
+        NerSettings {
+            enabled: false,
+            min_score: 0.85,
+        }
+    }
+}
+
+/// Whether this build can run a NER model
+pub fn is_available() -> bool {
+    cfg!(feature = "ner")
+}
+
+/// Directory holding model.onnx, tokenizer.json and config.json
+pub fn model_dir() -> PathBuf {
+    Path::new(get_db_path()).with_file_name("ner")
+}
+
+/// Entities in text. Each "ner" pattern asks for the same text in turn, so the last result
+/// is kept and the model runs once per text.
+pub fn recognize(text: &str) -> Vec<Entity> {
+    if !is_available() {
+        static WARN: Once = Once::new();
+        WARN.call_once(|| eprintln!("[NER] NER patterns are enabled but this build has no NER support"));
+        return Vec::new();
+    }
+
+    thread_local! {
+        static LAST: RefCell<Option<(String, Vec<Entity>)>> = const { RefCell::new(None) };
+    }
+    let cached = LAST.with(|last| {
+        last.borrow()
+            .as_ref()
+            .filter(|(t, _)| t == text)
+            .map(|(_, entities)| entities.clone())
+    });
+    if let Some(entities) = cached {
+        return entities;
+    }
+
+    let mut entities = Vec::new();
+    for (offset, chunk) in chunks(text) {
+        entities.extend(run_model(chunk).into_iter().map(|e| Entity {
+            start: e.start + offset,
+            end: e.end + offset,
+            ..e
+        }));
+    }
+
+    LAST.with(|last| *last.borrow_mut() = Some((text.to_string(), entities.clone())));
+    entities
+}
+
+/// Text split at whitespace into pieces of at most MAX_CHUNK_BYTES, with their byte offsets
+fn chunks(text: &str) -> Vec<(usize, &str)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let mut end = (start + MAX_CHUNK_BYTES).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < text.len() {
+            if let Some(space) = text[start..end].rfind(char::is_whitespace).filter(|&i| i > 0) {
+                end = start + space;
+            }
+        }
+        pieces.push((start, &text[start..end]));
+        start = end;
+    }
+    pieces
+}
+
+/// Model output for one token
+struct TokenTag<'a> {
+    start: usize,
+    end: usize,
+    /// Word the token belongs to; later pieces of a split word share it
+    word: Option<u32>,
+    tag: &'a str,
+    score: f32,
+}
+
+/// Group token tags into entities: a B- tag or a change of type starts an entity, I- tags
+/// and the later pieces of a split word extend it
+fn merge_tokens(tokens: &[TokenTag]) -> Vec<Entity> {
+    // Open entity, its summed token scores and token count, and the word of its last token
+    let mut open: Option<(Entity, f64, usize, Option<u32>)> = None;
+    let mut entities = Vec::new();
+
+    for token in tokens {
+        if let Some((entity, sum, count, word)) = open.as_mut() {
+            let same_word = token.word.is_some() && token.word == *word;
+            let continues = EntityLabel::from_tag(token.tag) == Some(entity.label)
+                && !token.tag.starts_with("B-");
+            if same_word || continues {
+                entity.end = token.end;
+                *sum += token.score as f64;
+                *count += 1;
+                *word = token.word;
+                continue;
+            }
+        }
+
+        if let Some((entity, sum, count, _)) = open.take() {
+            entities.push(Entity {
+                score: sum / count as f64,
+                ..entity
+            });
+        }
+        if let Some(label) = EntityLabel::from_tag(token.tag) {
+            let entity = Entity {
+                label,
+                start: token.start,
+                end: token.end,
+                score: 0.0,
+            };
+            open = Some((entity, token.score as f64, 1, token.word));
+        }
+    }
+
+    if let Some((entity, sum, count, _)) = open {
+        entities.push(Entity {
+            score: sum / count as f64,
+            ..entity
+        });
+    }
+    entities
+}
+
+#[cfg(feature = "ner")]
+fn run_model(text: &str) -> Vec<Entity> {
+    model::run(text)
+}
+
+#[cfg(not(feature = "ner"))]
+fn run_model(_text: &str) -> Vec<Entity> {
+    Vec::new()
+}
+
+#[cfg(feature = "ner")]
+mod model {
+    use super::{merge_tokens, model_dir, Entity, TokenTag};
+    use ort::session::Session;
+    use ort::value::Tensor;
+    use std::sync::{Mutex, OnceLock};
+    use tokenizers::{Tokenizer, TruncationParams};
+
+    struct NerModel {
+        session: Session,
+        tokenizer: Tokenizer,
+        /// Tag of each output class, from config.json's id2label
+        labels: Vec<String>,
+    }
+
+    fn load() -> Result<NerModel, String> {
+        let dir = model_dir();
+
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(dir.join("model.onnx")))
+            .map_err(|e| format!("model.onnx: {}", e))?;
+
+        let mut tokenizer =
+            Tokenizer::from_file(dir.join("tokenizer.json")).map_err(|e| format!("tokenizer.json: {}", e))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: 512,
+                ..Default::default()
+            }))
+            .map_err(|e| format!("tokenizer.json: {}", e))?;
+
+        let config: serde_json::Value = std::fs::read_to_string(dir.join("config.json"))
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .map_err(|e| format!("config.json: {}", e))?;
+        let id2label = config
+            .get("id2label")
+            .and_then(|v| v.as_object())
+            .ok_or("config.json: no id2label map")?;
+        let mut labels = vec!["O".to_string(); id2label.len()];
+        for (id, label) in id2label {
+            if let (Ok(id), Some(label)) = (id.parse::<usize>(), label.as_str()) {
+                if id < labels.len() {
+                    labels[id] = label.to_string();
+                }
+            }
+        }
+
+        Ok(NerModel {
+            session,
+            tokenizer,
+            labels,
+        })
+    }
+
+    /// The model, loaded on first use; None if it failed to load
+    fn model() -> Option<&'static Mutex<NerModel>> {
+        static MODEL: OnceLock<Option<Mutex<NerModel>>> = OnceLock::new();
+        MODEL
+            .get_or_init(|| match load() {
+                Ok(model) => {
+                    println!("[NER] Model loaded from {}", model_dir().display());
+                    Some(Mutex::new(model))
+                }
+                Err(e) => {
+                    eprintln!("[NER] Model unavailable ({}): {}", model_dir().display(), e);
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    pub(super) fn run(text: &str) -> Vec<Entity> {
+        let Some(Ok(mut model)) = model().map(|m| m.lock()) else {
+            return Vec::new();
+        };
+        model
+            .infer(text)
+            .map_err(|e| eprintln!("[NER] Inference failed: {}", e))
+            .unwrap_or_default()
+    }
+
+    impl NerModel {
+        fn infer(&mut self, text: &str) -> Result<Vec<Entity>, String> {
+            let encoding = self.tokenizer.encode(text, true).map_err(|e| e.to_string())?;
+            let len = encoding.get_ids().len();
+            let as_i64 = |values: &[u32]| -> Vec<i64> { values.iter().map(|&v| v as i64).collect() };
+
+            let tensor = |values: Vec<i64>| Tensor::from_array(([1, len], values)).map_err(|e| e.to_string());
+            let mut inputs = ort::inputs![
+                "input_ids" => tensor(as_i64(encoding.get_ids()))?,
+                "attention_mask" => tensor(as_i64(encoding.get_attention_mask()))?,
+            ];
+            // BERT exports take segment ids as well; DistilBERT and RoBERTa exports don't
+            if self.session.inputs.iter().any(|input| input.name == "token_type_ids") {
+                inputs.push(("token_type_ids".into(), tensor(vec![0; len])?.into()));
+            }
+
+            let outputs = self.session.run(inputs).map_err(|e| e.to_string())?;
+            let (shape, logits) = outputs[0].try_extract_tensor::<f32>().map_err(|e| e.to_string())?;
+            let classes = shape.last().copied().unwrap_or(0) as usize;
+            if classes == 0 || logits.len() < len * classes {
+                return Err(format!("unexpected output shape {}", shape));
+            }
+
+            let mut tokens = Vec::new();
+            for i in 0..len {
+                if encoding.get_special_tokens_mask()[i] == 1 {
+                    continue;
+                }
+                // Softmax probability of the best class
+                let row = &logits[i * classes..(i + 1) * classes];
+                let (best, max) = row
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .fold((0, f32::MIN), |acc, (idx, v)| if v > acc.1 { (idx, v) } else { acc });
+                let total: f32 = row.iter().map(|v| (v - max).exp()).sum();
+                let (start, end) = encoding.get_offsets()[i];
+                tokens.push(TokenTag {
+                    start,
+                    end,
+                    word: encoding.get_word_ids()[i],
+                    tag: self.labels.get(best).map(String::as_str).unwrap_or("O"),
+                    score: 1.0 / total,
+                });
+            }
+
+            Ok(merge_tokens(&tokens))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(start: usize, end: usize, word: u32, tag: &'static str) -> TokenTag<'static> {
+        TokenTag {
+            start,
+            end,
+            word: Some(word),
+            tag,
+            score: 0.9,
+        }
+    }
+
+    #[test]
+    fn test_merge_tokens() {
+        // "Ada Lovelace joined Acme Corp in Lon ##don"
+        let tokens = [
+            tag(0, 3, 0, "B-PER"),
+            tag(4, 12, 1, "I-PER"),
+            tag(13, 19, 2, "O"),
+            tag(20, 24, 3, "B-ORG"),
+            tag(25, 29, 4, "I-ORG"),
+            tag(30, 32, 5, "O"),
+            tag(33, 36, 6, "B-LOC"),
+            tag(36, 39, 6, "O"),
+        ];
+        let found: Vec<(EntityLabel, usize, usize)> =
+            merge_tokens(&tokens).iter().map(|e| (e.label, e.start, e.end)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (EntityLabel::Person, 0, 12),
+                (EntityLabel::Organization, 20, 29),
+                (EntityLabel::Location, 33, 39),
+            ]
+        );
+
+        // A B- tag splits adjacent entities of the same type
+        let tokens = [tag(0, 3, 0, "B-PER"), tag(4, 7, 1, "B-PER")];
+        assert_eq!(merge_tokens(&tokens).len(), 2);
+        assert!(merge_tokens(&[tag(0, 4, 0, "B-MISC")]).is_empty());
+    }
+
+    #[test]
+    fn test_chunks() {
+        let text = "word ".repeat(1000);
+        let pieces = chunks(&text);
+        assert!(pieces.iter().all(|(_, piece)| piece.len() <= MAX_CHUNK_BYTES));
+        assert_eq!(pieces.iter().map(|(_, piece)| *piece).collect::<String>(), text);
+        assert!(pieces.iter().all(|&(offset, piece)| &text[offset..offset + piece.len()] == piece));
+    }
+}
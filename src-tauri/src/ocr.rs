@@ -0,0 +1,50 @@
+// Image Text Extraction (OCR)
+//
+// Screenshots of terminals, config files and dashboards carry the same secrets as text.
+// With the `ocr` feature, images attached to requests are read with a local Tesseract
+// install and the text is scanned like any other content. Without it, images are skipped.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::sync::Once;
+
+/// Whether this build can read text from images
+pub fn is_available() -> bool {
+    cfg!(feature = "ocr")
+}
+
+/// Text in a base64-encoded image, or a `data:image/...;base64,` URL
+/// Returns None when OCR is unavailable, the data doesn't decode or no text is found
+pub fn extract_image_text(data: &str) -> Option<String> {
+    if !is_available() {
+        static WARN: Once = Once::new();
+        WARN.call_once(|| eprintln!("[OCR] Image scanning is enabled but this build has no OCR support"));
+        return None;
+    }
+
+    let encoded = match data.strip_prefix("data:") {
+        Some(url) => url.split_once(";base64,")?.1,
+        None => data,
+    };
+    let bytes = STANDARD.decode(encoded.trim()).ok()?;
+
+    let text = recognize(&bytes)?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(feature = "ocr")]
+fn recognize(image: &[u8]) -> Option<String> {
+    let read = || -> Result<String, tesseract::TesseractError> {
+        let mut tesseract = tesseract::Tesseract::new(None, Some("eng"))?
+            .set_image_from_mem(image)?
+            .recognize()?;
+        Ok(tesseract.get_text()?)
+    };
+    read().map_err(|e| eprintln!("[OCR] Failed to read image: {}", e)).ok()
+}
+
+#[cfg(not(feature = "ocr"))]
+fn recognize(_image: &[u8]) -> Option<String> {
+    None
+}
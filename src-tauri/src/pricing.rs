@@ -0,0 +1,234 @@
+// Model Pricing and Cost Estimates
+//
+// Each row of `model_pricing` prices a model family in USD per million tokens: input, output,
+// cache reads and cache writes. A request's model is matched to the longest price whose model
+// name it starts with, so "claude-sonnet-4" covers every dated Sonnet 4 release; provider
+// prefixes such as Bedrock's "us.anthropic." or OpenRouter's "openai/" are skipped. The table is
+// seeded with list prices when it is created, and can be edited from Settings or restored.
+//
+// Costs are estimated from the logged token counts when the dashboard is loaded, so edited prices
+// apply to past requests too. Anthropic reports cache reads and writes apart from the input
+// tokens; the other APIs count cache reads as part of the input, so they are taken out of it
+// before it is priced.
+
+use crate::storage::{self, SpendStore, StorageError};
+use serde::{Deserialize, Serialize};
+use std::sync::{LazyLock, RwLock};
+
+/// List prices the table is seeded with: model, then USD per million input, output, cache read
+/// and cache write tokens
+const DEFAULT_PRICES: &[(&str, f64, f64, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0, 0.5, 6.25),
+    ("claude-opus-4", 15.0, 75.0, 1.5, 18.75),
+    ("claude-sonnet-4", 3.0, 15.0, 0.3, 3.75),
+    ("claude-haiku-4-5", 1.0, 5.0, 0.1, 1.25),
+    ("claude-3-7-sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("claude-3-5-sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("claude-3-5-haiku", 0.8, 4.0, 0.08, 1.0),
+    ("claude-3-opus", 15.0, 75.0, 1.5, 18.75),
+    ("claude-3-haiku", 0.25, 1.25, 0.03, 0.3),
+    ("gpt-5", 1.25, 10.0, 0.125, 0.0),
+    ("gpt-5-mini", 0.25, 2.0, 0.025, 0.0),
+    ("gpt-5-nano", 0.05, 0.4, 0.005, 0.0),
+    ("gpt-4.1", 2.0, 8.0, 0.5, 0.0),
+    ("gpt-4.1-mini", 0.4, 1.6, 0.1, 0.0),
+    ("gpt-4.1-nano", 0.1, 0.4, 0.025, 0.0),
+    ("gpt-4o", 2.5, 10.0, 1.25, 0.0),
+    ("gpt-4o-mini", 0.15, 0.6, 0.075, 0.0),
+    ("o1", 15.0, 60.0, 7.5, 0.0),
+    ("o1-mini", 1.1, 4.4, 0.55, 0.0),
+    ("o3", 2.0, 8.0, 0.5, 0.0),
+    ("o3-mini", 1.1, 4.4, 0.55, 0.0),
+    ("o4-mini", 1.1, 4.4, 0.275, 0.0),
+    ("gemini-2.5-pro", 1.25, 10.0, 0.125, 0.0),
+    ("gemini-2.5-flash", 0.3, 2.5, 0.03, 0.0),
+    ("gemini-2.5-flash-lite", 0.1, 0.4, 0.01, 0.0),
+    ("gemini-2.0-flash", 0.1, 0.4, 0.025, 0.0),
+    ("deepseek-chat", 0.28, 0.42, 0.028, 0.0),
+    ("deepseek-reasoner", 0.28, 0.42, 0.028, 0.0),
+    ("sonar", 1.0, 1.0, 0.0, 0.0),
+    ("sonar-pro", 3.0, 15.0, 0.0, 0.0),
+];
+
+/// A model price as stored in `model_pricing`, in USD per million tokens
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub id: i64,
+    /// Model name, or the start of one (e.g. "claude-sonnet-4")
+    pub model: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    #[serde(default)]
+    pub cache_read_per_mtok: f64,
+    #[serde(default)]
+    pub cache_write_per_mtok: f64,
+}
+
+impl ModelPrice {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.model.is_empty() {
+            return Err("Model name is required".to_string());
+        }
+        let prices = [self.input_per_mtok, self.output_per_mtok, self.cache_read_per_mtok, self.cache_write_per_mtok];
+        if prices.iter().any(|p| !p.is_finite() || *p < 0.0) {
+            return Err("Prices must be 0 or more".to_string());
+        }
+        Ok(())
+    }
+
+    /// Estimated cost in USD of a request to `model` that used `usage`
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        let input = if model.to_lowercase().contains("claude") {
+            usage.input
+        } else {
+            (usage.input - usage.cache_read).max(0)
+        };
+        (input as f64 * self.input_per_mtok
+            + usage.output as f64 * self.output_per_mtok
+            + usage.cache_read as f64 * self.cache_read_per_mtok
+            + usage.cache_creation as f64 * self.cache_write_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// Tokens used by a request (or summed over several requests to the same model), as logged
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokenUsage {
+    pub input: i64,
+    pub output: i64,
+    pub cache_read: i64,
+    pub cache_creation: i64,
+}
+
+/// Prices read from the database, once; None until first use
+static PRICES: LazyLock<RwLock<Option<Vec<ModelPrice>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Whether `model` is `pattern` or a release of it, with or without a provider prefix
+fn matches(model: &str, pattern: &str) -> bool {
+    model.starts_with(pattern)
+        || model
+            .match_indices(pattern)
+            .any(|(i, _)| i > 0 && matches!(model.as_bytes()[i - 1], b'.' | b'/'))
+}
+
+/// The most specific price for `model`
+pub fn find_price<'a>(prices: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    let model = model.to_lowercase();
+    prices
+        .iter()
+        .filter(|p| matches(&model, &p.model))
+        .max_by_key(|p| p.model.len())
+}
+
+/// The list prices the table is seeded with
+pub fn default_prices() -> Vec<ModelPrice> {
+    DEFAULT_PRICES
+        .iter()
+        .map(|&(model, input, output, cache_read, cache_write)| ModelPrice {
+            id: 0,
+            model: model.to_string(),
+            input_per_mtok: input,
+            output_per_mtok: output,
+            cache_read_per_mtok: cache_read,
+            cache_write_per_mtok: cache_write,
+        })
+        .collect()
+}
+
+/// Every saved price, by model name
+pub fn load_prices() -> Vec<ModelPrice> {
+    if let Some(prices) = PRICES.read().unwrap().as_ref() {
+        return prices.clone();
+    }
+
+    let prices = storage::shared()
+        .and_then(|store| store.model_prices())
+        .unwrap_or_default();
+
+    *PRICES.write().unwrap() = Some(prices.clone());
+    prices
+}
+
+/// Add a price (id 0) or update the one with its id
+pub fn save_price(price: &ModelPrice) -> Result<i64, String> {
+    let mut price = price.clone();
+    price.model = price.model.trim().to_lowercase();
+    price.validate()?;
+
+    let store = storage::shared().map_err(|e| e.to_string())?;
+    let result = if price.id > 0 {
+        store.update_model_price(&price).map(|_| price.id)
+    } else {
+        store.add_model_price(&price)
+    };
+    let id = result.map_err(|e| match e {
+        StorageError::Conflict(_) => format!("'{}' already has a price", price.model),
+        e => e.to_string(),
+    })?;
+
+    *PRICES.write().unwrap() = None;
+    Ok(id)
+}
+
+pub fn delete_price(id: i64) -> Result<(), String> {
+    storage::shared()
+        .and_then(|store| store.delete_model_price(id))
+        .map_err(|e| e.to_string())?;
+    *PRICES.write().unwrap() = None;
+    Ok(())
+}
+
+/// Put the default prices back, keeping prices added for other models
+pub fn restore_defaults() -> Result<Vec<ModelPrice>, String> {
+    storage::shared()
+        .and_then(|store| store.upsert_model_prices(&default_prices()))
+        .map_err(|e| e.to_string())?;
+    *PRICES.write().unwrap() = None;
+    Ok(load_prices())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_price() {
+        let prices = default_prices();
+        let model = |name: &str| find_price(&prices, name).map(|p| p.model.as_str());
+        assert_eq!(model("claude-sonnet-4-5-20250929"), Some("claude-sonnet-4"));
+        assert_eq!(model("claude-opus-4-5-20251101"), Some("claude-opus-4-5"));
+        assert_eq!(model("claude-opus-4-1-20250805"), Some("claude-opus-4"));
+        assert_eq!(model("gpt-4o-mini-2024-07-18"), Some("gpt-4o-mini"));
+        assert_eq!(model("us.anthropic.claude-3-5-haiku-20241022-v1:0"), Some("claude-3-5-haiku"));
+        assert_eq!(model("openai/GPT-4.1"), Some("gpt-4.1"));
+        assert_eq!(model("claude-sonnet-4@20250514"), Some("claude-sonnet-4"));
+        assert_eq!(model("llama-3-70b"), None);
+        assert!(prices.iter().all(|p| p.validate().is_ok()));
+    }
+
+    #[test]
+    fn test_cost() {
+        let prices = default_prices();
+        let usage = TokenUsage {
+            input: 1_000_000,
+            output: 100_000,
+            cache_read: 400_000,
+            cache_creation: 0,
+        };
+
+        // Anthropic: cache reads come on top of the input
+        let sonnet = find_price(&prices, "claude-sonnet-4").unwrap();
+        assert!((sonnet.cost("claude-sonnet-4", &usage) - (3.0 + 1.5 + 0.12)).abs() < 1e-9);
+
+        // OpenAI: cache reads are part of the input
+        let gpt = find_price(&prices, "gpt-4o").unwrap();
+        assert!((gpt.cost("gpt-4o", &usage) - (1.5 + 1.0 + 0.5)).abs() < 1e-9);
+
+        let negative = ModelPrice {
+            input_per_mtok: -1.0,
+            ..gpt.clone()
+        };
+        assert!(negative.validate().is_err());
+    }
+}
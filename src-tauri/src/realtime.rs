@@ -0,0 +1,517 @@
+// Realtime API (WebSocket) Proxy
+//
+// Proxies WebSocket sessions such as `wss://api.openai.com/v1/realtime` through a backend.
+// Client events are DLP-scanned before being forwarded upstream, server events are
+// unredacted before being sent back, and each response turn is logged to the database.
+
+use crate::api_keys::inject_api_key;
+use crate::backends::Backend;
+use crate::database::{Database, DLP_ACTION_BLOCKED};
+use crate::dlp::{
+    apply_dlp_redaction_to_realtime_event, apply_dlp_unredaction, dlp_action_for, should_block,
+    DlpDetection, DlpTarget,
+};
+use crate::log_queue::{self, LoggedRequest};
+use crate::log_sanitizer::headers_to_json;
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+
+use axum::{
+    body::Body,
+    extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type UpstreamSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Headers that belong to the client's own handshake and must not be forwarded upstream
+const SKIP_HANDSHAKE_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "upgrade",
+    "content-length",
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "sec-websocket-extensions",
+];
+
+/// Convert an http(s) base URL into its ws(s) equivalent
+fn to_websocket_url(base_url: &str, full_path: &str) -> String {
+    let base = if let Some(rest) = base_url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base_url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        base_url.to_string()
+    };
+    format!("{}{}", base, full_path)
+}
+
+/// Create the Realtime API error event sent to the client when an event is blocked
+fn create_realtime_error_event(pattern_names: &str) -> String {
+    serde_json::json!({
+        "type": "error",
+        "error": {
+            "type": "invalid_request_error",
+            "code": "content_policy_violation",
+            "message": format!("Event blocked: sensitive data detected ({})", pattern_names)
+        }
+    })
+    .to_string()
+}
+
+/// Strip bulky audio payloads from an event before it is stored in the log
+fn event_for_log(event: &serde_json::Value) -> serde_json::Value {
+    let mut event = event.clone();
+    if let Some(obj) = event.as_object_mut() {
+        if obj.contains_key("audio") {
+            obj.insert("audio".to_string(), serde_json::Value::String("<omitted>".to_string()));
+        }
+        if obj.contains_key("delta")
+            && obj
+                .get("type")
+                .and_then(|t| t.as_str())
+                .map(|t| t.ends_with("audio.delta"))
+                .unwrap_or(false)
+        {
+            obj.insert("delta".to_string(), serde_json::Value::String("<omitted>".to_string()));
+        }
+    }
+    event
+}
+
+/// Handle a WebSocket upgrade request by connecting to the upstream first,
+/// then upgrading the client connection and relaying frames in both directions
+pub async fn proxy_websocket(
+    ws: WebSocketUpgrade,
+    db: Database,
+    backend: Arc<dyn Backend>,
+    full_path: String,
+    headers: HeaderMap,
+) -> Response {
+    let target_url = to_websocket_url(backend.base_url(), &full_path);
+
+    let mut upstream_req = match target_url.as_str().into_client_request() {
+        Ok(r) => r,
+        Err(e) => {
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(format!("Invalid WebSocket target: {}", e)))
+                .unwrap();
+        }
+    };
+
+    for (name, value) in headers.iter() {
+        let header_lower = name.as_str().to_lowercase();
+        if !SKIP_HANDSHAKE_HEADERS.contains(&header_lower.as_str()) {
+            upstream_req.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    let _key_lease = inject_api_key(backend.as_ref(), upstream_req.headers_mut());
+
+    println!("[REALTIME] Connecting to upstream: {}", target_url);
+    let (upstream, upstream_resp) = match tokio_tungstenite::connect_async(upstream_req).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            println!("[REALTIME] Upstream connection failed: {}", e);
+            let status = match &e {
+                tungstenite::Error::Http(resp) => {
+                    StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY)
+                }
+                _ => StatusCode::BAD_GATEWAY,
+            };
+            return Response::builder()
+                .status(status)
+                .body(Body::from(format!("Proxy error: {}", e)))
+                .unwrap();
+        }
+    };
+
+    // Accept the same subprotocol the upstream selected (browser clients pass auth this way)
+    let ws = match upstream_resp
+        .headers()
+        .get("sec-websocket-protocol")
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(protocol) => ws.protocols([protocol.to_string()]),
+        None => ws,
+    };
+
+    // Pick up the model from the query string (e.g. ?model=gpt-4o-realtime-preview)
+    let model = full_path
+        .split_once('?')
+        .and_then(|(_, query)| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("model="))
+                .map(|m| m.to_string())
+        });
+
+    let request_headers_json = headers_to_json(&headers, &backend.log_header_overrides());
+    let session = RealtimeSession {
+        db,
+        dlp_enabled: backend.is_dlp_enabled(),
+        backend,
+        path: full_path,
+        request_headers_json,
+        model,
+        replacements: HashMap::new(),
+        turn: RealtimeTurn::default(),
+    };
+
+    ws.on_upgrade(move |socket| run_session(socket, upstream, session))
+        .into_response()
+}
+
+/// Client events and detections collected since the last completed response
+#[derive(Default)]
+struct RealtimeTurn {
+    started: Option<Instant>,
+    client_events: Vec<serde_json::Value>,
+    detections: Vec<DlpDetection>,
+    user_message_count: i32,
+}
+
+struct RealtimeSession {
+    db: Database,
+    backend: Arc<dyn Backend>,
+    path: String,
+    request_headers_json: String,
+    model: Option<String>,
+    dlp_enabled: bool,
+    replacements: HashMap<String, String>, // placeholder -> original, shared across the session
+    turn: RealtimeTurn,
+}
+
+/// What to do with a client event after DLP inspection
+enum ClientEventOutcome {
+    Forward(String),
+    Blocked(String), // error event to send back to the client
+}
+
+impl RealtimeSession {
+    /// Inspect a text event sent by the client
+    fn handle_client_event(&mut self, text: String) -> ClientEventOutcome {
+        let event: Option<serde_json::Value> = serde_json::from_str(&text).ok();
+        let event_type = event
+            .as_ref()
+            .and_then(|e| e.get("type"))
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        if self.turn.started.is_none() {
+            self.turn.started = Some(Instant::now());
+        }
+
+        if event_type == "conversation.item.create" {
+            let is_user_message = event
+                .as_ref()
+                .and_then(|e| e.get("item"))
+                .and_then(|i| i.get("role"))
+                .and_then(|r| r.as_str())
+                == Some("user");
+            if is_user_message {
+                self.turn.user_message_count += 1;
+            }
+        }
+
+        let mut outgoing = text;
+        if self.dlp_enabled {
+            // Work on a copy so a blocked event doesn't leave its values in the session map
+            let mut replacements = self.replacements.clone();
+            if let Some((redacted, detections)) =
+                apply_dlp_redaction_to_realtime_event(
+                    &outgoing,
+                    &mut replacements,
+                    self.backend.dlp_scan_scope(),
+                    DlpTarget {
+                        backend: self.backend.name(),
+                        model: self.model.as_deref(),
+                    },
+                )
+            {
+                if should_block(&detections) {
+                    let mut pattern_names: Vec<&str> =
+                        detections.iter().map(|d| d.pattern_name.as_str()).collect();
+                    pattern_names.sort();
+                    pattern_names.dedup();
+                    let error_event = create_realtime_error_event(&pattern_names.join(", "));
+
+                    println!(
+                        "[REALTIME] Blocking {} event due to DLP detections: {} patterns",
+                        event_type,
+                        detections.len()
+                    );
+                    self.log_blocked_event(event.as_ref(), &error_event, &detections);
+                    return ClientEventOutcome::Blocked(error_event);
+                }
+
+                self.replacements = replacements;
+                self.turn.detections.extend(detections);
+                outgoing = redacted;
+            }
+        }
+
+        if let Some(event) = event.as_ref() {
+            self.turn.client_events.push(event_for_log(event));
+        }
+
+        ClientEventOutcome::Forward(outgoing)
+    }
+
+    /// Inspect a text event sent by the upstream; returns the (unredacted) text to forward
+    fn handle_server_event(&mut self, text: String) -> String {
+        let unredacted = apply_dlp_unredaction(&text, &self.replacements);
+
+        let event: serde_json::Value = match serde_json::from_str(&unredacted) {
+            Ok(v) => v,
+            Err(_) => return unredacted,
+        };
+
+        match event.get("type").and_then(|t| t.as_str()).unwrap_or("") {
+            "session.created" | "session.updated" => {
+                if let Some(model) = event
+                    .get("session")
+                    .and_then(|s| s.get("model"))
+                    .and_then(|m| m.as_str())
+                {
+                    self.model = Some(model.to_string());
+                }
+            }
+            "response.done" => {
+                self.log_turn(&event, 200);
+            }
+            "error" => {
+                self.log_turn(&event, 400);
+            }
+            _ => {}
+        }
+
+        unredacted
+    }
+
+    /// Log the client events of the current turn together with the server event that ended it
+    fn log_turn(&mut self, server_event: &serde_json::Value, status: u16) {
+        let turn = std::mem::take(&mut self.turn);
+        let latency_ms = turn
+            .started
+            .map(|s| s.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        let request_body = serde_json::json!({ "events": turn.client_events }).to_string();
+        let response_body = server_event.to_string();
+        let req_meta = RequestMetadata {
+            model: self.model.clone(),
+            user_message_count: turn.user_message_count,
+            ..Default::default()
+        };
+        let resp_meta = parse_response_done(server_event);
+        let dlp_action = dlp_action_for(&turn.detections);
+
+        log_queue::submit(&self.db, LoggedRequest {
+            backend: self.backend.name().to_string(),
+            method: "WS".to_string(),
+            path: self.path.clone(),
+            endpoint_name: "Realtime".to_string(),
+            request_body,
+            response_body,
+            response_status: status,
+            is_streaming: true,
+            latency_ms,
+            req_meta,
+            resp_meta,
+            request_headers: Some(self.request_headers_json.clone()),
+            dlp_action,
+            detections: turn.detections,
+            ..LoggedRequest::default()
+        });
+    }
+
+    /// Log a client event that was blocked by DLP
+    fn log_blocked_event(
+        &self,
+        event: Option<&serde_json::Value>,
+        error_event: &str,
+        detections: &[DlpDetection],
+    ) {
+        let request_body = serde_json::json!({
+            "events": event.map(|e| vec![event_for_log(e)]).unwrap_or_default()
+        })
+        .to_string();
+        let req_meta = RequestMetadata {
+            model: self.model.clone(),
+            user_message_count: 1,
+            ..Default::default()
+        };
+
+        log_queue::submit(&self.db, LoggedRequest {
+            backend: self.backend.name().to_string(),
+            method: "WS".to_string(),
+            path: self.path.clone(),
+            endpoint_name: "Realtime".to_string(),
+            request_body,
+            response_body: error_event.to_string(),
+            response_status: 400,
+            is_streaming: true,
+            req_meta,
+            request_headers: Some(self.request_headers_json.clone()),
+            dlp_action: DLP_ACTION_BLOCKED,
+            detections: detections.to_vec(),
+            ..LoggedRequest::default()
+        });
+    }
+}
+
+/// Extract usage and function calls from a `response.done` event
+fn parse_response_done(event: &serde_json::Value) -> ResponseMetadata {
+    let mut meta = ResponseMetadata::default();
+    let response = match event.get("response") {
+        Some(r) => r,
+        None => return meta,
+    };
+
+    meta.stop_reason = response
+        .get("status")
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string());
+
+    if let Some(usage) = response.get("usage") {
+        meta.input_tokens = usage.get("input_tokens").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+        meta.output_tokens = usage.get("output_tokens").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+        meta.cache_read_tokens = usage
+            .get("input_token_details")
+            .and_then(|d| d.get("cached_tokens"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32;
+    }
+
+    if let Some(output) = response.get("output").and_then(|o| o.as_array()) {
+        for item in output {
+            if item.get("type").and_then(|t| t.as_str()) != Some("function_call") {
+                continue;
+            }
+            let arguments = item.get("arguments").and_then(|a| a.as_str()).unwrap_or("{}");
+            meta.tool_calls.push(ToolCall {
+                id: item
+                    .get("call_id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                name: item
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                input: serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| serde_json::Value::String(arguments.to_string())),
+            });
+        }
+    }
+
+    meta
+}
+
+/// Convert a client frame into an upstream frame
+fn to_upstream_message(msg: Message) -> Option<tungstenite::Message> {
+    match msg {
+        Message::Text(t) => Some(tungstenite::Message::Text(t)),
+        Message::Binary(b) => Some(tungstenite::Message::Binary(b)),
+        Message::Ping(p) => Some(tungstenite::Message::Ping(p)),
+        Message::Pong(p) => Some(tungstenite::Message::Pong(p)),
+        Message::Close(frame) => Some(tungstenite::Message::Close(frame.map(|f| {
+            tungstenite::protocol::CloseFrame {
+                code: f.code.into(),
+                reason: f.reason,
+            }
+        }))),
+    }
+}
+
+/// Convert an upstream frame into a client frame
+fn to_client_message(msg: tungstenite::Message) -> Option<Message> {
+    match msg {
+        tungstenite::Message::Text(t) => Some(Message::Text(t)),
+        tungstenite::Message::Binary(b) => Some(Message::Binary(b)),
+        tungstenite::Message::Ping(p) => Some(Message::Ping(p)),
+        tungstenite::Message::Pong(p) => Some(Message::Pong(p)),
+        tungstenite::Message::Close(frame) => Some(Message::Close(frame.map(|f| CloseFrame {
+            code: f.code.into(),
+            reason: f.reason,
+        }))),
+        tungstenite::Message::Frame(_) => None,
+    }
+}
+
+/// Relay frames between the client and upstream until either side closes
+async fn run_session(client: WebSocket, upstream: UpstreamSocket, mut session: RealtimeSession) {
+    println!("[REALTIME] Session started: {}", session.path);
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+    loop {
+        tokio::select! {
+            msg = client_rx.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    _ => break,
+                };
+                let is_close = matches!(msg, Message::Close(_));
+                let outgoing = match msg {
+                    Message::Text(text) => match session.handle_client_event(text) {
+                        ClientEventOutcome::Forward(text) => Some(tungstenite::Message::Text(text)),
+                        ClientEventOutcome::Blocked(error_event) => {
+                            if client_tx.send(Message::Text(error_event)).await.is_err() {
+                                break;
+                            }
+                            None
+                        }
+                    },
+                    other => to_upstream_message(other),
+                };
+                if let Some(outgoing) = outgoing {
+                    if upstream_tx.send(outgoing).await.is_err() {
+                        break;
+                    }
+                }
+                if is_close {
+                    break;
+                }
+            }
+            msg = upstream_rx.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    _ => break,
+                };
+                let is_close = matches!(msg, tungstenite::Message::Close(_));
+                let outgoing = match msg {
+                    tungstenite::Message::Text(text) => Some(Message::Text(session.handle_server_event(text))),
+                    other => to_client_message(other),
+                };
+                if let Some(outgoing) = outgoing {
+                    if client_tx.send(outgoing).await.is_err() {
+                        break;
+                    }
+                }
+                if is_close {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Log any client events that never got a response before the session ended
+    if !session.turn.client_events.is_empty() {
+        session.log_turn(&serde_json::json!({ "type": "session.closed" }), 200);
+    }
+
+    let _ = upstream_tx.close().await;
+    let _ = client_tx.close().await;
+    println!("[REALTIME] Session closed: {}", session.path);
+}
@@ -0,0 +1,227 @@
+// Response Cache
+//
+// Agent loops often send the same request over and over (a retried tool step, a re-run plan). With
+// the cache on, a non-streaming request identical to one answered within the TTL is served from
+// the `response_cache` table instead of the provider. Requests are keyed on a hash of the backend,
+// the path, the client's credentials and the redacted body with its JSON keys sorted, so formatting
+// and key order don't matter but any change to the model, system prompt, messages, tools or
+// sampling parameters does. A hit is served before any upstream auth, so a caller only ever gets
+// answers to requests sent with the same credentials.
+// Only successful responses are stored, and only as the provider sent them: they hold DLP
+// placeholders, never the original values, which are put back per request as usual. Reads and writes
+// run on blocking threads, and expired responses are dropped by the hourly retention job.
+
+use crate::api_keys::CLIENT_CREDENTIAL_HEADERS;
+use crate::database::{get_response_cache_from_db, save_response_cache_to_db};
+use crate::storage::{self, CacheStore, CachedResponse};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{LazyLock, RwLock};
+
+/// Header added to responses served from the cache
+pub const CACHE_HEADER: &str = "x-quilr-cache";
+
+/// Longest TTL accepted (one week)
+const MAX_TTL_SECS: u64 = 7 * 24 * 3600;
+
+/// Response headers not stored: recalculated when the response is served
+const SKIP_CACHED_HEADERS: &[&str] = &["content-length", "transfer-encoding", "connection", "date", "set-cookie"];
+
+/// Response cache settings (`response_cache` in the settings table)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheSettings {
+    pub enabled: bool,
+    /// How long a stored response is served, in seconds
+    pub ttl_secs: u64,
+}
+
+impl Default for ResponseCacheSettings {
+    fn default() -> Self {
+        ResponseCacheSettings {
+            enabled: false,
+            ttl_secs: 300,
+        }
+    }
+}
+
+impl ResponseCacheSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ttl_secs == 0 || self.ttl_secs > MAX_TTL_SECS {
+            return Err(format!("Cache TTL must be between 1 and {} seconds", MAX_TTL_SECS));
+        }
+        Ok(())
+    }
+}
+
+/// Settings read from the database, once
+static SETTINGS: LazyLock<RwLock<Option<ResponseCacheSettings>>> = LazyLock::new(|| RwLock::new(None));
+
+pub fn current() -> ResponseCacheSettings {
+    if let Some(settings) = SETTINGS.read().unwrap().as_ref() {
+        return settings.clone();
+    }
+    let settings = get_response_cache_from_db();
+    *SETTINGS.write().unwrap() = Some(settings.clone());
+    settings
+}
+
+/// Save the settings and apply them to the next request; turning the cache off empties it
+pub fn save_settings(settings: ResponseCacheSettings) -> Result<(), String> {
+    settings.validate()?;
+    save_response_cache_to_db(&settings)?;
+    if !settings.enabled {
+        clear()?;
+    }
+    *SETTINGS.write().unwrap() = Some(settings);
+    Ok(())
+}
+
+/// Remove every stored response
+pub fn clear() -> Result<(), String> {
+    storage::shared()
+        .and_then(|store| store.clear_cached_responses())
+        .map_err(|e| e.to_string())
+}
+
+/// The credentials a client sent, as one string for the cache key
+pub fn client_credential(headers: &HeaderMap) -> String {
+    CLIENT_CREDENTIAL_HEADERS
+        .iter()
+        .filter_map(|name| Some(format!("{}={}", name, headers.get(*name)?.to_str().ok()?)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cache key of a request sent with `credential`, or None if its body isn't JSON
+pub fn cache_key(backend: &str, path: &str, credential: &str, body: &str) -> Option<String> {
+    // serde_json's maps are sorted, so re-serializing normalizes key order and whitespace
+    let normalized = serde_json::to_string(&serde_json::from_str::<serde_json::Value>(body).ok()?).ok()?;
+
+    let mut hasher = Sha256::new();
+    for part in [backend, path, credential, normalized.as_str()] {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// A stored response still within the TTL, rebuilt as if the provider had just sent it
+pub async fn lookup(key: &str, ttl_secs: u64) -> Option<reqwest::Response> {
+    let key = key.to_string();
+    tokio::task::spawn_blocking(move || lookup_blocking(&key, ttl_secs))
+        .await
+        .ok()
+        .flatten()
+}
+
+fn lookup_blocking(key: &str, ttl_secs: u64) -> Option<reqwest::Response> {
+    let oldest = chrono::Utc::now().timestamp() - ttl_secs as i64;
+    let cached = storage::shared().ok()?.cached_response(key, oldest).ok()??;
+
+    let mut builder = axum::http::Response::builder().status(cached.status);
+    for (name, value) in serde_json::from_str::<Vec<(String, String)>>(&cached.headers).unwrap_or_default() {
+        builder = builder.header(name, value);
+    }
+    builder = builder.header(CACHE_HEADER, "hit");
+    builder.body(cached.body).ok().map(reqwest::Response::from)
+}
+
+/// Store a successful response off the request path; expired ones are dropped by `sweep_expired`
+pub fn store(key: &str, backend: &str, status: u16, headers: &reqwest::header::HeaderMap, body: Vec<u8>) {
+    let headers: Vec<(&str, &str)> = headers
+        .iter()
+        .filter(|(name, _)| !SKIP_CACHED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect();
+    let response = CachedResponse {
+        status,
+        headers: serde_json::to_string(&headers).unwrap_or_else(|_| "[]".to_string()),
+        body,
+    };
+    let key = key.to_string();
+    let backend = backend.to_string();
+    let now = chrono::Utc::now().timestamp();
+
+    tokio::task::spawn_blocking(move || {
+        let result = storage::shared().and_then(|store| store.store_cached_response(&key, &backend, &response, now));
+        if let Err(e) = result {
+            eprintln!("[CACHE] Failed to store response for backend '{}': {}", backend, e);
+        }
+    });
+}
+
+/// Delete stored responses past the current TTL (run by the retention job); returns how many
+pub fn sweep_expired() -> Result<usize, String> {
+    let oldest = chrono::Utc::now().timestamp() - current().ttl_secs as i64;
+    storage::shared()
+        .and_then(|store| store.delete_cached_responses_before(oldest))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_normalizes_json() {
+        let a = cache_key(
+            "claude",
+            "/v1/messages",
+            "x-api-key=key-a",
+            r#"{"model":"claude-sonnet-4","messages":[{"role":"user","content":"hi"}],"max_tokens":64}"#,
+        );
+        let b = cache_key(
+            "claude",
+            "/v1/messages",
+            "x-api-key=key-a",
+            r#"{ "max_tokens": 64, "messages": [{"content": "hi", "role": "user"}], "model": "claude-sonnet-4" }"#,
+        );
+        assert!(a.is_some());
+        assert_eq!(a, b);
+
+        let other_prompt = cache_key(
+            "claude",
+            "/v1/messages",
+            "x-api-key=key-a",
+            r#"{"model":"claude-sonnet-4","messages":[{"role":"user","content":"hello"}],"max_tokens":64}"#,
+        );
+        assert_ne!(a, other_prompt);
+        let other_backend = cache_key(
+            "openai",
+            "/v1/messages",
+            "x-api-key=key-a",
+            r#"{"model":"claude-sonnet-4","messages":[{"role":"user","content":"hi"}],"max_tokens":64}"#,
+        );
+        assert_ne!(a, other_backend);
+
+        assert_eq!(cache_key("claude", "/v1/messages", "x-api-key=key-a", "not json"), None);
+    }
+
+    #[test]
+    fn test_cache_key_per_credential() {
+        let body = r#"{"model":"claude-sonnet-4","messages":[{"role":"user","content":"hi"}]}"#;
+        let mut alice = HeaderMap::new();
+        alice.insert("x-api-key", "sk-ant-alice".parse().unwrap());
+        let mut bob = HeaderMap::new();
+        bob.insert("x-api-key", "sk-ant-bob".parse().unwrap());
+        let mut bearer = HeaderMap::new();
+        bearer.insert("authorization", "Bearer sk-ant-alice".parse().unwrap());
+
+        let key = |headers: &HeaderMap| cache_key("claude", "/v1/messages", &client_credential(headers), body);
+        assert_eq!(key(&alice), key(&alice.clone()));
+        assert_ne!(key(&alice), key(&bob));
+        assert_ne!(key(&alice), key(&bearer));
+        assert_ne!(key(&alice), key(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_validate_ttl() {
+        assert!(ResponseCacheSettings::default().validate().is_ok());
+        let zero = ResponseCacheSettings { enabled: true, ttl_secs: 0 };
+        assert!(zero.validate().is_err());
+        let too_long = ResponseCacheSettings { enabled: true, ttl_secs: MAX_TTL_SECS + 1 };
+        assert!(too_long.validate().is_err());
+    }
+}
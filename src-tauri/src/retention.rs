@@ -0,0 +1,248 @@
+// Log Retention
+//
+// Logged requests are deleted once they are older than the retention period, once there are more
+// than the maximum number of them, or while the database is over its size limit (oldest first, in
+// each case). The policy is enforced at startup and then hourly in the background; it can also be
+// run from Settings, which compacts the database afterwards (VACUUM) and reports the space
+// reclaimed. Detections, tool calls and bodies go with their requests.
+//
+// Deleting a request takes its detections, tool calls, bodies and conversation link with it; rows
+// left behind anyway (by older versions, or deletes outside the app) are swept up with each run,
+// and can be checked for and repaired from Settings.
+//
+// Bodies are most of the database. They are kept zstd-compressed in `request_bodies`, apart from
+// the rows that list and dashboard queries read, and can also be cut at a size limit or not stored
+// at all, for deployments that must not keep prompts; the log then keeps the metadata, token
+// counts, detections and tool calls.
+
+use crate::database::{get_retention_from_db, Database};
+use crate::response_cache;
+use crate::storage::LogMaintenance;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// How often the policy is enforced in the background
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Share of the remaining requests deleted per step while the database is over its size limit
+const SIZE_STEP_FRACTION: f64 = 0.1;
+
+/// Give up on the size limit after this many steps (e.g. when settings rows alone exceed it)
+const MAX_SIZE_STEPS: usize = 20;
+
+/// Appended to a body cut at the storage limit
+const TRUNCATED_MARKER: &str = "\n[truncated]";
+
+/// Retention settings (`retention` in the settings table); 0 turns a limit off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// Delete requests older than this many days
+    pub max_age_days: u32,
+    /// Keep at most this many requests
+    pub max_requests: u64,
+    /// Keep the database under this many megabytes
+    pub max_db_mb: u64,
+    /// Store request and response bodies in the log
+    pub store_bodies: bool,
+    /// Cut stored bodies at this many kilobytes
+    pub max_body_kb: u64,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        RetentionSettings {
+            max_age_days: 7,
+            max_requests: 0,
+            max_db_mb: 0,
+            store_bodies: true,
+            max_body_kb: 0,
+        }
+    }
+}
+
+impl RetentionSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_age_days > 3650 {
+            return Err("Retention period must be at most 3650 days".to_string());
+        }
+        Ok(())
+    }
+
+    fn max_db_bytes(&self) -> Option<u64> {
+        (self.max_db_mb > 0).then(|| self.max_db_mb.saturating_mul(1024 * 1024))
+    }
+
+    /// A body as it is stored: None when bodies aren't kept, otherwise cut at the size limit
+    pub fn stored_body<'a>(&self, body: &'a str) -> Option<Cow<'a, str>> {
+        if !self.store_bodies {
+            return None;
+        }
+        let max_bytes = self.max_body_kb.saturating_mul(1024) as usize;
+        if max_bytes == 0 || body.len() <= max_bytes {
+            return Some(Cow::Borrowed(body));
+        }
+        let mut end = max_bytes;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        Some(Cow::Owned(format!("{}{}", &body[..end], TRUNCATED_MARKER)))
+    }
+}
+
+/// Outcome of enforcing the policy
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionReport {
+    pub deleted_requests: usize,
+    pub db_bytes_before: u64,
+    pub db_bytes_after: u64,
+}
+
+/// Delete what the policy no longer keeps; `vacuum` compacts the database afterwards
+pub fn enforce(db: &dyn LogMaintenance, settings: &RetentionSettings, vacuum: bool) -> Result<RetentionReport, String> {
+    let db_bytes_before = db.database_size_bytes().map_err(|e| e.to_string())?;
+    let mut deleted_requests = 0;
+
+    if settings.max_age_days > 0 {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(settings.max_age_days as i64);
+        deleted_requests += db.cleanup_old_data(&cutoff.to_rfc3339()).map_err(|e| e.to_string())?;
+    }
+    if settings.max_requests > 0 {
+        deleted_requests += db.keep_newest_requests(settings.max_requests).map_err(|e| e.to_string())?;
+    }
+    if let Some(max_bytes) = settings.max_db_bytes() {
+        for _ in 0..MAX_SIZE_STEPS {
+            if db.database_size_bytes().map_err(|e| e.to_string())? <= max_bytes {
+                break;
+            }
+            let remaining = db.request_count().map_err(|e| e.to_string())?;
+            if remaining == 0 {
+                break;
+            }
+            let keep = remaining - ((remaining as f64 * SIZE_STEP_FRACTION).ceil() as u64).min(remaining);
+            deleted_requests += db.keep_newest_requests(keep).map_err(|e| e.to_string())?;
+        }
+    }
+
+    db.orphaned_rows(true).map_err(|e| e.to_string())?;
+
+    if vacuum {
+        db.vacuum().map_err(|e| e.to_string())?;
+    }
+
+    Ok(RetentionReport {
+        deleted_requests,
+        db_bytes_before,
+        db_bytes_after: db.database_size_bytes().map_err(|e| e.to_string())?,
+    })
+}
+
+/// Rows of a table whose request (or owning row) is gone
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanCount {
+    pub table: &'static str,
+    pub rows: usize,
+}
+
+/// Outcome of checking for, or repairing, orphaned rows
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    pub orphans: Vec<OrphanCount>,
+    /// Whether the orphans were deleted (or, for canaries, unlinked)
+    pub repaired: bool,
+    /// Tables whose AUTOINCREMENT counter was lowered to their highest id
+    pub sequences_reset: Vec<&'static str>,
+}
+
+/// Report orphaned rows; with `repair`, delete them and reset inflated AUTOINCREMENT counters
+pub fn check_orphans(db: &dyn LogMaintenance, repair: bool) -> Result<RepairReport, String> {
+    let orphans = db
+        .orphaned_rows(repair)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(table, rows)| OrphanCount { table, rows })
+        .collect();
+    let sequences_reset = if repair {
+        db.reset_sequences().map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    Ok(RepairReport {
+        orphans,
+        repaired: repair,
+        sequences_reset,
+    })
+}
+
+fn enforce_saved(db: &dyn LogMaintenance) {
+    match enforce(db, &get_retention_from_db(), false) {
+        Ok(report) if report.deleted_requests > 0 => {
+            println!("[RETENTION] Deleted {} old requests", report.deleted_requests)
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("[RETENTION] Failed to apply retention policy: {}", e),
+    }
+    if let Err(e) = response_cache::sweep_expired() {
+        eprintln!("[RETENTION] Failed to drop expired cached responses: {}", e);
+    }
+}
+
+/// Enforce the saved policy (and drop expired cached responses) now and then hourly
+pub fn spawn_worker(db: Database) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ENFORCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let db = db.clone();
+            let _ = tokio::task::spawn_blocking(move || enforce_saved(&db)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_validate() {
+        let settings: RetentionSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, RetentionSettings::default());
+        assert_eq!(settings.max_age_days, 7);
+        assert!(settings.max_db_bytes().is_none());
+
+        let capped = RetentionSettings {
+            max_db_mb: 500,
+            ..RetentionSettings::default()
+        };
+        assert_eq!(capped.max_db_bytes(), Some(500 * 1024 * 1024));
+
+        let forever = RetentionSettings {
+            max_age_days: 0,
+            ..RetentionSettings::default()
+        };
+        assert!(forever.validate().is_ok());
+        assert!(RetentionSettings { max_age_days: 5000, ..forever }.validate().is_err());
+    }
+
+    #[test]
+    fn test_stored_body() {
+        let body = "héllo wörld";
+        assert_eq!(RetentionSettings::default().stored_body(body).unwrap(), body);
+
+        let off = RetentionSettings {
+            store_bodies: false,
+            ..RetentionSettings::default()
+        };
+        assert!(off.stored_body(body).is_none());
+
+        let capped = RetentionSettings {
+            max_body_kb: 1,
+            ..RetentionSettings::default()
+        };
+        let long = "é".repeat(1000);
+        let stored = capped.stored_body(&long).unwrap();
+        assert!(stored.ends_with(TRUNCATED_MARKER));
+        assert!(stored.len() <= 1024 + TRUNCATED_MARKER.len());
+    }
+}
@@ -0,0 +1,511 @@
+// Storage
+//
+// Everything the app keeps goes through the traits below, one per area: the request log and the
+// queries behind the dashboard and Logs views, retention, backends and their API key records,
+// settings, DLP patterns and policies, budgets and prices, and the response cache. `Database`
+// (SQLite, with sqlite-zstd compressing bodies and headers) is the only implementation; a
+// PostgreSQL store for central deployments is a follow-up that implements the same traits.
+// Schema migrations, compression maintenance, encryption and batching writes in a transaction
+// are specific to the SQLite file and stay on `Database` itself.
+
+use crate::budgets::TokenBudget;
+use crate::commands::dlp::{DlpDetectionRecord, DlpPattern, DlpStats};
+use crate::commands::dlp_packs::PatternPackEntry;
+use crate::commands::stats::{
+    BackendStats, ConversationSummary, ConversationTimeline, DashboardData, PaginatedLogs, RequestBodies,
+    TokenCountStats, TokenTimelinePoint, ToolCallRecord, ToolCallStats,
+};
+use crate::database::{BackendRecord, Database};
+use crate::dlp::DlpDetection;
+use crate::dlp_policy::DlpPolicy;
+use crate::log_export::ExportLog;
+use crate::pricing::{ModelPrice, TokenUsage};
+use crate::requestresponsemetadata::{RequestMetadata, ResponseMetadata, ToolCall};
+use std::fmt;
+use std::sync::Arc;
+
+/// Error of a storage operation
+#[derive(Debug)]
+pub enum StorageError {
+    /// A uniqueness constraint was violated (e.g. a name that is already taken)
+    Conflict(String),
+    Other(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Conflict(message) | StorageError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Other(e.to_string())
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+/// The Logs view's filters, shared by the list and the export
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Oldest timestamp included (RFC 3339)
+    pub since: String,
+    pub backend: Option<String>,
+    /// Model name, "unknown" for requests without one
+    pub model: Option<String>,
+    /// One of the DLP_ACTION_* codes (no action logged counts as passed)
+    pub dlp_action: Option<i32>,
+    /// Case-insensitive text searched for in the request and response bodies
+    pub search: Option<String>,
+}
+
+/// Tokens that reached a model in one UTC day for one backend and model, for cost estimates
+#[derive(Debug, Clone)]
+pub struct ModelUsage {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub backend: String,
+    pub model: String,
+    pub requests: i64,
+    pub usage: TokenUsage,
+}
+
+/// A provider API key's row; the key itself is in the OS keychain
+#[derive(Debug, Clone)]
+pub struct ApiKeyRecord {
+    pub id: i64,
+    pub backend: String,
+    pub label: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub request_count: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+/// An issued canary from the registry
+#[derive(Debug, Clone)]
+pub struct IssuedCanary {
+    pub request_id: Option<i64>,
+    pub backend: String,
+    pub created_at: String,
+}
+
+/// A stored response, as the provider sent it
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: u16,
+    /// (name, value) pairs as JSON
+    pub headers: String,
+    pub body: Vec<u8>,
+}
+
+/// Field changes to a DLP pattern; None leaves a field as it is, Some(None) clears it
+#[derive(Debug, Clone, Default)]
+pub struct DlpPatternChanges {
+    pub name: Option<String>,
+    pub pattern_type: Option<String>,
+    pub patterns: Option<Vec<String>>,
+    pub negative_pattern_type: Option<Option<String>>,
+    pub negative_patterns: Option<Option<Vec<String>>>,
+    pub enabled: Option<bool>,
+    pub min_occurrences: Option<i32>,
+    pub min_unique_chars: Option<i32>,
+    pub min_entropy: Option<f64>,
+    pub validator: Option<Option<String>>,
+    pub category: Option<String>,
+    pub action: Option<Option<String>>,
+    pub severity: Option<String>,
+    pub allowlist: Option<Option<Vec<String>>>,
+    pub scope: Option<String>,
+    pub normalize: Option<bool>,
+}
+
+impl DlpPatternChanges {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.pattern_type.is_none()
+            && self.patterns.is_none()
+            && self.negative_pattern_type.is_none()
+            && self.negative_patterns.is_none()
+            && self.enabled.is_none()
+            && self.min_occurrences.is_none()
+            && self.min_unique_chars.is_none()
+            && self.min_entropy.is_none()
+            && self.validator.is_none()
+            && self.category.is_none()
+            && self.action.is_none()
+            && self.severity.is_none()
+            && self.allowlist.is_none()
+            && self.scope.is_none()
+            && self.normalize.is_none()
+    }
+}
+
+/// Writes of the proxy and the Cursor hooks for each request they handle
+pub trait RequestLog {
+    /// Log a proxied request with its bodies (as far as retention keeps them); returns its id
+    #[allow(clippy::too_many_arguments)]
+    fn log_request(
+        &self,
+        backend: &str,
+        method: &str,
+        path: &str,
+        endpoint_name: &str,
+        request_body: &str,
+        response_body: &str,
+        response_status: u16,
+        is_streaming: bool,
+        latency_ms: u64,
+        req_meta: &RequestMetadata,
+        resp_meta: &ResponseMetadata,
+        extra_metadata: Option<&str>,
+        request_headers: Option<&str>,
+        response_headers: Option<&str>,
+        dlp_action: i32,
+    ) -> StorageResult<i64>;
+
+    /// Log a request's detections, grouping repeats into incidents, and start the secret
+    /// verifications and webhook deliveries they call for
+    fn log_dlp_detections(&self, request_id: i64, detections: &[DlpDetection]) -> StorageResult<()>;
+
+    /// Record the live verification result of a detected key
+    fn set_detection_verified(&self, detection_id: i64, verified: &str) -> StorageResult<()>;
+
+    /// Record a canary injected into a request to `backend`; returns its id
+    fn register_canary(&self, token: &str, backend: &str, model: Option<&str>) -> StorageResult<i64>;
+
+    /// Link a canary to the logged request that carried it
+    fn set_canary_request_id(&self, canary_id: i64, request_id: i64) -> StorageResult<()>;
+
+    /// Record whether a logged request was served from the response cache
+    fn set_response_cache_hit(&self, request_id: i64, hit: bool) -> StorageResult<()>;
+
+    /// Record the fallback backend that answered a logged request
+    fn set_served_by(&self, request_id: i64, backend: &str) -> StorageResult<()>;
+
+    /// Record how a logged request's latency splits into time to first byte and streaming
+    fn set_latency_breakdown(&self, request_id: i64, ttfb_ms: u64, stream_ms: Option<u64>) -> StorageResult<()>;
+
+    /// Record the API the client spoke for a translated request
+    fn set_translated_from(&self, request_id: i64, client_api: &str) -> StorageResult<()>;
+
+    /// Log a token-counting call (prompt size only, no bodies)
+    #[allow(clippy::too_many_arguments)]
+    fn log_token_count(
+        &self,
+        backend: &str,
+        endpoint: &str,
+        req_meta: &RequestMetadata,
+        request_size: usize,
+        input_tokens: i64,
+        status_code: u16,
+        latency_ms: u64,
+    ) -> StorageResult<i64>;
+
+    fn log_tool_calls(&self, request_id: i64, tool_calls: &[ToolCall]) -> StorageResult<()>;
+
+    /// Log a cursor hook request (creates new entry)
+    #[allow(clippy::too_many_arguments)]
+    fn log_cursor_hook_request(
+        &self,
+        generation_id: &str,
+        endpoint_name: &str,
+        model: &str,
+        input_tokens: i32,
+        output_tokens: i32,
+        request_body: &str,
+        response_body: &str,
+        response_status: u16,
+        extra_metadata: Option<&str>,
+        request_headers: Option<&str>,
+        response_headers: Option<&str>,
+        dlp_action: i32,
+    ) -> StorageResult<i64>;
+
+    /// Update cursor hook output tokens, response body, and latency by generation_id
+    /// Returns true if an entry was found and updated, false otherwise
+    fn update_cursor_hook_output(
+        &self,
+        generation_id: &str,
+        output_token_count: i32,
+        response_text: Option<&str>,
+    ) -> StorageResult<bool>;
+
+    /// Add thinking tokens to cursor hook output by generation_id
+    /// Returns true if an entry was found and updated, false otherwise
+    fn add_cursor_hook_thinking_tokens(&self, generation_id: &str, thinking_word_count: i32) -> StorageResult<bool>;
+}
+
+/// Reads behind the tray, dashboard, Logs and conversation views; `since` is an RFC 3339
+/// timestamp and a `backend` of None means every backend
+pub trait LogQueries {
+    /// Requests and tokens per backend, most requests first
+    fn backend_usage(&self, since: &str) -> StorageResult<Vec<BackendStats>>;
+
+    /// Input tokens of each request that used any, oldest first, with its backend
+    fn input_token_points(&self, since: &str) -> StorageResult<Vec<(String, TokenTimelinePoint)>>;
+
+    /// Dashboard figures; costs are left for the caller to estimate from `ModelUsage`
+    fn dashboard_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<DashboardData>;
+
+    /// Prompt sizes measured by token-counting calls
+    fn token_count_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<TokenCountStats>;
+
+    /// Backends with logged requests
+    fn logged_backends(&self) -> StorageResult<Vec<String>>;
+
+    /// Models of the logged requests ("unknown" for requests without one)
+    fn logged_models(&self) -> StorageResult<Vec<String>>;
+
+    /// One page (of `page_size`) of the logs matching `filter`, newest first, and the total
+    fn message_logs(&self, filter: &LogFilter, page: i64, page_size: i64) -> StorageResult<PaginatedLogs>;
+
+    /// Every log matching `filter` with its bodies and detections, newest first
+    fn export_logs(&self, filter: &LogFilter) -> StorageResult<Vec<ExportLog>>;
+
+    fn tool_calls_for_request(&self, request_id: i64) -> StorageResult<Vec<ToolCallRecord>>;
+
+    /// Bodies of a logged request; both None when they weren't stored
+    fn request_bodies(&self, request_id: i64) -> StorageResult<RequestBodies>;
+
+    /// Conversations active since `since`, most recent first (at most 200)
+    fn conversations(&self, since: &str) -> StorageResult<Vec<ConversationSummary>>;
+
+    /// A conversation's requests in order with their tool calls; None if there's no such conversation
+    fn conversation_timeline(&self, conversation_id: i64) -> StorageResult<Option<ConversationTimeline>>;
+
+    /// The 20 most used tools
+    fn tool_call_counts(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<ToolCallStats>>;
+
+    /// Name and input JSON of every tool call
+    fn tool_call_inputs(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<(String, String)>>;
+
+    /// Detection and incident counts, and the latest detection of each recent incident
+    fn dlp_detection_stats(&self, since: &str, backend: Option<&str>) -> StorageResult<DlpStats>;
+
+    fn detections_for_request(&self, request_id: i64) -> StorageResult<Vec<DlpDetectionRecord>>;
+}
+
+/// Retention: deleting old requests with everything that belongs to them
+pub trait LogMaintenance {
+    /// Delete requests older than the cutoff (RFC 3339) with their detections, tool calls, bodies
+    /// and conversation links, and token-counting calls and incidents older than it
+    fn cleanup_old_data(&self, cutoff_ts: &str) -> StorageResult<usize>;
+
+    /// Delete all but the newest `keep` requests, with their detections (and incidents left
+    /// without any), tool calls, bodies and conversation links
+    fn keep_newest_requests(&self, keep: u64) -> StorageResult<usize>;
+
+    /// Count rows left behind by deleted requests (older versions, or deletes outside the app),
+    /// per table; with `delete`, delete them. Canaries are only unlinked from their request, as
+    /// they are still watched for.
+    fn orphaned_rows(&self, delete: bool) -> StorageResult<Vec<(&'static str, usize)>>;
+
+    /// Lower id counters left above the highest remaining id, so emptied tables count from where
+    /// their rows end. Request and detection ids are left alone: they appear in exports and
+    /// webhook events, so they are never reused. Returns the tables whose counter was reset.
+    fn reset_sequences(&self) -> StorageResult<Vec<&'static str>>;
+
+    fn request_count(&self) -> StorageResult<u64>;
+
+    /// Space the stored data takes up
+    fn database_size_bytes(&self) -> StorageResult<u64>;
+
+    /// Give deleted space back to the file system
+    fn vacuum(&self) -> StorageResult<()>;
+
+    /// Whether the store answers a query, for the health check
+    fn check_connection(&self) -> StorageResult<()>;
+}
+
+/// Backends and the records of their stored API keys
+pub trait BackendStore {
+    /// Get all backends (builtin first, then custom by creation date)
+    fn get_backends(&self) -> StorageResult<Vec<BackendRecord>>;
+
+    /// Get enabled backends only
+    fn get_enabled_backends(&self) -> StorageResult<Vec<BackendRecord>>;
+
+    /// Get a backend by name
+    fn get_backend_by_name(&self, name: &str) -> StorageResult<Option<BackendRecord>>;
+
+    /// Get all custom (non-builtin) backends
+    fn get_custom_backends(&self) -> StorageResult<Vec<BackendRecord>>;
+
+    /// Add a new backend
+    fn add_backend(&self, name: &str, kind: &str, base_url: &str, path_prefix: &str, settings: &str) -> StorageResult<i64>;
+
+    /// Update a custom backend (builtin backends only have their settings updated)
+    fn update_backend(&self, id: i64, name: &str, base_url: &str, path_prefix: &str, settings: &str) -> StorageResult<()>;
+
+    /// Replace the settings JSON of any backend, builtin or custom
+    fn update_backend_settings(&self, name: &str, settings: &str) -> StorageResult<()>;
+
+    /// Enable or disable a backend
+    fn set_backend_enabled(&self, id: i64, enabled: bool) -> StorageResult<()>;
+
+    /// Delete a custom backend (builtin backends can only be disabled)
+    fn delete_backend(&self, id: i64) -> StorageResult<()>;
+
+    /// Check if a backend name or path prefix is already taken
+    fn backend_name_exists(&self, name: &str, path_prefix: &str) -> StorageResult<bool> {
+        self.backend_name_exists_excluding(name, path_prefix, -1)
+    }
+
+    /// Check if a backend name or path prefix is taken by another backend (for updates)
+    fn backend_name_exists_excluding(&self, name: &str, path_prefix: &str, exclude_id: i64) -> StorageResult<bool>;
+
+    /// Get settings for a builtin backend (returns default settings if not set)
+    fn get_predefined_backend_settings(&self, name: &str) -> StorageResult<String> {
+        Ok(self
+            .get_backend_by_name(name)?
+            .map(|b| b.settings)
+            .unwrap_or_else(|| "{}".to_string()))
+    }
+
+    /// Update settings for a builtin backend
+    fn update_predefined_backend_settings(&self, name: &str, settings: &str) -> StorageResult<()>;
+
+    /// Reset builtin backend settings to defaults
+    fn reset_predefined_backend_settings(&self, name: &str) -> StorageResult<()>;
+
+    /// Every stored key's record, by backend then id
+    fn api_keys(&self) -> StorageResult<Vec<ApiKeyRecord>>;
+
+    /// Add a key's record; returns the id its keychain entry is stored under
+    fn add_api_key(&self, backend: &str, label: Option<&str>) -> StorageResult<i64>;
+
+    fn delete_api_key(&self, id: i64) -> StorageResult<()>;
+
+    /// Count one more request sent with a key, with its token usage
+    fn record_api_key_usage(&self, id: i64, input_tokens: i64, output_tokens: i64) -> StorageResult<()>;
+}
+
+/// Key-value settings, values as the `*_from_db` helpers serialize them
+pub trait SettingsStore {
+    fn setting(&self, key: &str) -> StorageResult<Option<String>>;
+
+    fn save_setting(&self, key: &str, value: &str) -> StorageResult<()>;
+}
+
+/// DLP patterns, policies, canaries and the token vault
+pub trait DlpStore {
+    /// Every pattern, builtin or custom, by id
+    fn dlp_patterns(&self) -> StorageResult<Vec<DlpPattern>>;
+
+    /// Every custom pattern as a pack entry
+    fn custom_dlp_patterns(&self) -> StorageResult<Vec<PatternPackEntry>>;
+
+    /// Id and builtin flag of the pattern with this name, if any
+    fn dlp_pattern_by_name(&self, name: &str) -> StorageResult<Option<(i64, bool)>>;
+
+    fn dlp_pattern_exists(&self, id: i64) -> StorageResult<bool>;
+
+    /// Add a custom pattern named `name` from a validated entry; returns its id
+    fn add_dlp_pattern(&self, name: &str, pattern: &PatternPackEntry) -> StorageResult<i64>;
+
+    /// Overwrite everything but the name of a pattern with a validated entry
+    fn replace_dlp_pattern(&self, id: i64, pattern: &PatternPackEntry) -> StorageResult<()>;
+
+    fn update_dlp_pattern(&self, id: i64, changes: &DlpPatternChanges) -> StorageResult<()>;
+
+    fn set_dlp_pattern_enabled(&self, id: i64, enabled: bool) -> StorageResult<()>;
+
+    /// Delete a custom pattern with its policies; false (and nothing deleted) for a builtin one
+    fn delete_dlp_pattern(&self, id: i64) -> StorageResult<bool>;
+
+    /// All policies, oldest first
+    fn dlp_policies(&self) -> StorageResult<Vec<DlpPolicy>>;
+
+    /// Add a policy (its id is ignored); returns the new id
+    fn add_dlp_policy(&self, policy: &DlpPolicy) -> StorageResult<i64>;
+
+    /// Change a policy's backend, model glob and action
+    fn update_dlp_policy(&self, policy: &DlpPolicy) -> StorageResult<()>;
+
+    fn delete_dlp_policy(&self, id: i64) -> StorageResult<()>;
+
+    fn find_canary(&self, token: &str) -> StorageResult<Option<IssuedCanary>>;
+
+    fn mark_canary_triggered(&self, token: &str) -> StorageResult<()>;
+
+    /// Replace plaintext values already stored (detections and the bodies they were found in) with
+    /// their hashed or masked form; returns the number of detections converted
+    fn hash_stored_detection_values(&self) -> StorageResult<usize>;
+
+    /// Surrogate stored for a value's digest, marking it as used now
+    fn vault_surrogate(&self, value_hash: &str) -> StorageResult<Option<String>>;
+
+    /// Whether a surrogate already stands for some value
+    fn vault_surrogate_taken(&self, surrogate: &str) -> StorageResult<bool>;
+
+    /// Store a surrogate; a digest that already has one keeps it
+    fn store_vault_surrogate(&self, value_hash: &str, surrogate: &str, pattern_name: &str) -> StorageResult<()>;
+}
+
+/// Token budgets, model prices and the token usage they apply to
+pub trait SpendStore {
+    /// Every budget, by id
+    fn token_budgets(&self) -> StorageResult<Vec<TokenBudget>>;
+
+    fn add_token_budget(&self, budget: &TokenBudget) -> StorageResult<i64>;
+
+    fn delete_token_budget(&self, id: i64) -> StorageResult<()>;
+
+    /// Input and output tokens a budget counts since `since` (RFC 3339): requests that reached a
+    /// model, i.e. not blocked, rate limited or served from the response cache
+    fn tokens_used(&self, budget: &TokenBudget, since: &str) -> StorageResult<i64>;
+
+    /// Tokens that reached a model since `since`, per day, backend and model
+    fn model_usage(&self, since: &str, backend: Option<&str>) -> StorageResult<Vec<ModelUsage>>;
+
+    /// Every saved price, by model name
+    fn model_prices(&self) -> StorageResult<Vec<ModelPrice>>;
+
+    /// Add a price (its id is ignored); a model that already has one is a `StorageError::Conflict`
+    fn add_model_price(&self, price: &ModelPrice) -> StorageResult<i64>;
+
+    fn update_model_price(&self, price: &ModelPrice) -> StorageResult<()>;
+
+    fn delete_model_price(&self, id: i64) -> StorageResult<()>;
+
+    /// Write prices, replacing the saved prices of the same models
+    fn upsert_model_prices(&self, prices: &[ModelPrice]) -> StorageResult<()>;
+}
+
+/// Responses stored by the response cache; `created_at` is a Unix timestamp
+pub trait CacheStore {
+    /// The response stored under `key`, unless it was stored before `oldest`
+    fn cached_response(&self, key: &str, oldest: i64) -> StorageResult<Option<CachedResponse>>;
+
+    /// Store a response, replacing one stored under the same key
+    fn store_cached_response(&self, key: &str, backend: &str, response: &CachedResponse, created_at: i64) -> StorageResult<()>;
+
+    /// Delete responses stored before `oldest`; returns how many
+    fn delete_cached_responses_before(&self, oldest: i64) -> StorageResult<usize>;
+
+    fn clear_cached_responses(&self) -> StorageResult<()>;
+}
+
+/// Every storage area
+pub trait Storage:
+    RequestLog + LogQueries + LogMaintenance + BackendStore + SettingsStore + DlpStore + SpendStore + CacheStore + Send + Sync
+{
+}
+
+impl<T> Storage for T where
+    T: RequestLog + LogQueries + LogMaintenance + BackendStore + SettingsStore + DlpStore + SpendStore + CacheStore + Send + Sync
+{
+}
+
+/// The app's store, shared by the proxy, hooks and commands (see `Database::shared`)
+pub fn shared() -> StorageResult<Arc<dyn Storage>> {
+    let db = Database::shared()?;
+    Ok(Arc::new(db))
+}
@@ -0,0 +1,137 @@
+// TTFB Regression Alerts
+//
+// Time to first byte is mostly the provider queueing and reading the prompt, so a jump in it means
+// the upstream is degraded, even while long generations keep total latency noisy. Each backend
+// keeps a slow-moving baseline of its TTFB and a window of its latest requests; when the window's
+// median is well above the baseline, the user is notified, at most once an hour per backend. The
+// state lives in memory and starts over with the app.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Requests in the window compared to the baseline
+const WINDOW: usize = 20;
+
+/// Requests the baseline needs before it is trusted
+const MIN_BASELINE_SAMPLES: u32 = 50;
+
+/// Weight of each new request in the baseline, once it is trusted
+const BASELINE_WEIGHT: f64 = 0.02;
+
+/// The window's median must be this many times the baseline...
+const REGRESSION_FACTOR: f64 = 2.0;
+
+/// ...and at least this much slower, so fast backends don't alert on noise
+const MIN_REGRESSION_MS: f64 = 1000.0;
+
+/// Shortest time between two alerts for one backend
+const ALERT_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Default)]
+struct BackendTtfb {
+    baseline_ms: f64,
+    samples: u32,
+    recent: VecDeque<u64>,
+    last_alert: Option<Instant>,
+}
+
+static BACKENDS: LazyLock<Mutex<HashMap<String, BackendTtfb>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// TTFB of a backend's latest requests, well above its usual
+pub struct TtfbRegression {
+    pub backend: String,
+    pub recent_ms: u64,
+    pub baseline_ms: u64,
+}
+
+impl TtfbRegression {
+    pub fn message(&self) -> String {
+        format!(
+            "{} is slow to respond: time to first byte is {} ms, usually {} ms",
+            self.backend, self.recent_ms, self.baseline_ms
+        )
+    }
+}
+
+fn median(values: &VecDeque<u64>) -> u64 {
+    let mut sorted: Vec<u64> = values.iter().copied().collect();
+    sorted.sort_unstable();
+    sorted[sorted.len() / 2]
+}
+
+/// Add a request to the backend's history; Some((recent, baseline)) when it is time to alert
+fn record(state: &mut BackendTtfb, ttfb_ms: u64, now: Instant) -> Option<(u64, u64)> {
+    state.recent.push_back(ttfb_ms);
+    if state.recent.len() > WINDOW {
+        state.recent.pop_front();
+    }
+
+    let mut regression = None;
+    if state.samples >= MIN_BASELINE_SAMPLES && state.recent.len() == WINDOW {
+        let recent = median(&state.recent);
+        let slow = recent as f64 > state.baseline_ms * REGRESSION_FACTOR
+            && recent as f64 - state.baseline_ms >= MIN_REGRESSION_MS;
+        let quiet = state.last_alert.is_none_or(|at| now.duration_since(at) >= ALERT_INTERVAL);
+        if slow && quiet {
+            state.last_alert = Some(now);
+            regression = Some((recent, state.baseline_ms.round() as u64));
+        }
+    }
+
+    // A plain average until the baseline is trusted, then a slow exponential one
+    state.samples = state.samples.saturating_add(1);
+    let weight = (1.0 / state.samples as f64).max(BASELINE_WEIGHT);
+    state.baseline_ms += weight * (ttfb_ms as f64 - state.baseline_ms);
+    regression
+}
+
+/// Record the TTFB of a request answered by the upstream
+pub fn record_ttfb(backend: &str, ttfb_ms: u64) -> Option<TtfbRegression> {
+    let mut backends = BACKENDS.lock().unwrap();
+    let state = backends.entry(backend.to_string()).or_default();
+    record(state, ttfb_ms, Instant::now()).map(|(recent_ms, baseline_ms)| TtfbRegression {
+        backend: backend.to_string(),
+        recent_ms,
+        baseline_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts_once_on_regression() {
+        let mut state = BackendTtfb::default();
+        let now = Instant::now();
+
+        for _ in 0..MIN_BASELINE_SAMPLES {
+            assert!(record(&mut state, 400, now).is_none());
+        }
+        // A few slow requests move the median nowhere
+        for _ in 0..5 {
+            assert!(record(&mut state, 5000, now).is_none());
+        }
+
+        let alerts: Vec<(u64, u64)> = (0..WINDOW).filter_map(|_| record(&mut state, 3000, now)).collect();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].0, 3000);
+        assert!(alerts[0].1 < 1500);
+
+        // Quiet for the rest of the hour
+        assert!(record(&mut state, 3000, now + Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    fn test_fast_backends_need_a_real_slowdown() {
+        let mut state = BackendTtfb::default();
+        let now = Instant::now();
+        for _ in 0..MIN_BASELINE_SAMPLES {
+            record(&mut state, 100, now);
+        }
+        // Three times slower, but only by 200 ms
+        let alerts = (0..WINDOW).filter_map(|_| record(&mut state, 300, now)).count();
+        assert_eq!(alerts, 0);
+    }
+}
@@ -0,0 +1,160 @@
+// Unicode Folding for Pattern Matching
+//
+// A key can be smuggled past a regex by writing it with lookalike characters: Cyrillic "ѕk-аnt",
+// fullwidth "ｓｋ－ａｎｔ", zero-width spaces between letters, or letters typed one at a time
+// ("s k - a n t ..."). Patterns that opt in are matched against a folded copy of the text (NFKC,
+// zero-width characters removed, common homoglyphs mapped to ASCII, spaced-out letters joined).
+// Every folded byte remembers the original range it came from, so matches are mapped back and
+// redaction replaces the original characters, not the folded ones.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Shortest run of single characters separated by single spaces that is joined ("s k - a")
+const MIN_SPACED_RUN: usize = 4;
+
+/// Folded text, with the original byte range of every folded byte
+pub struct FoldedText {
+    pub text: String,
+    origins: Vec<(usize, usize)>,
+}
+
+impl FoldedText {
+    /// Original byte range covering folded text[start..end] (end > start)
+    pub fn original_span(&self, start: usize, end: usize) -> (usize, usize) {
+        (self.origins[start].0, self.origins[end - 1].1)
+    }
+}
+
+/// Invisible characters used to split a value without changing how it looks
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{180E}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+    )
+}
+
+/// ASCII lookalike of common Cyrillic and Greek letters and dashes NFKC leaves alone
+fn fold_homoglyph(c: char) -> char {
+    match c {
+        'а' | 'α' => 'a',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' | 'ι' => 'i',
+        'ј' => 'j',
+        'о' | 'ο' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' => 's',
+        'ν' => 'v',
+        'х' => 'x',
+        'у' => 'y',
+        'А' | 'Α' => 'A',
+        'В' | 'Β' => 'B',
+        'С' => 'C',
+        'Е' | 'Ε' => 'E',
+        'Н' | 'Η' => 'H',
+        'І' | 'Ι' => 'I',
+        'Ј' => 'J',
+        'К' | 'Κ' => 'K',
+        'М' | 'Μ' => 'M',
+        'Ν' => 'N',
+        'О' | 'Ο' => 'O',
+        'Р' | 'Ρ' => 'P',
+        'Ѕ' => 'S',
+        'Т' | 'Τ' => 'T',
+        'Х' | 'Χ' => 'X',
+        'У' | 'Υ' => 'Y',
+        'Ζ' => 'Z',
+        '\u{2010}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}' | '\u{2212}' => '-',
+        _ => c,
+    }
+}
+
+/// Whitespace between single characters in runs of at least MIN_SPACED_RUN ("s k - a n t")
+fn spaced_out_gaps(chars: &[(char, usize, usize)]) -> Vec<bool> {
+    let mut gaps = vec![false; chars.len()];
+    let is_space = |i: usize| chars[i].0.is_whitespace();
+    // A non-whitespace character with whitespace (or the text edge) on both sides
+    let is_single = |i: usize| {
+        !is_space(i) && (i == 0 || is_space(i - 1)) && (i + 1 == chars.len() || is_space(i + 1))
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_single(i) {
+            i += 1;
+            continue;
+        }
+        let mut run_end = i;
+        while run_end + 2 < chars.len() && is_single(run_end + 2) {
+            run_end += 2;
+        }
+        if (run_end - i) / 2 + 1 >= MIN_SPACED_RUN {
+            for gap in (i + 1..run_end).step_by(2) {
+                gaps[gap] = true;
+            }
+        }
+        i = run_end + 1;
+    }
+
+    gaps
+}
+
+/// Fold text for matching; see the module comment
+pub fn fold_for_matching(text: &str) -> FoldedText {
+    let mut chars: Vec<(char, usize, usize)> = Vec::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        if is_zero_width(c) {
+            continue;
+        }
+        let end = start + c.len_utf8();
+        for folded in std::iter::once(c).nfkc() {
+            chars.push((fold_homoglyph(folded), start, end));
+        }
+    }
+
+    let gaps = spaced_out_gaps(&chars);
+    let mut folded = String::with_capacity(text.len());
+    let mut origins = Vec::with_capacity(text.len());
+    for (&(c, start, end), is_gap) in chars.iter().zip(gaps) {
+        if is_gap {
+            continue;
+        }
+        folded.push(c);
+        origins.extend(std::iter::repeat_n((start, end), c.len_utf8()));
+    }
+
+    FoldedText {
+        text: folded,
+        origins,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded_span(text: &str, needle: &str) -> Option<String> {
+        let folded = fold_for_matching(text);
+        let start = folded.text.find(needle)?;
+        let (s, e) = folded.original_span(start, start + needle.len());
+        Some(text[s..e].to_string())
+    }
+
+    #[test]
+    fn test_fold_homoglyphs_and_width() {
+        assert_eq!(fold_for_matching("ѕk‐аnt-api03").text, "sk-ant-api03");
+        assert_eq!(fold_for_matching("ｓｋ－ａｎｔ").text, "sk-ant");
+        assert_eq!(folded_span("key: ѕk‐аnt-x", "sk-ant"), Some("ѕk‐аnt".to_string()));
+    }
+
+    #[test]
+    fn test_fold_zero_width_and_spacing() {
+        assert_eq!(fold_for_matching("sk\u{200B}-ant").text, "sk-ant");
+        assert_eq!(fold_for_matching("key s k - a n t end").text, "key sk-ant end");
+        // Short runs and ordinary words are left alone
+        assert_eq!(fold_for_matching("a b c and more").text, "a b c and more");
+        assert_eq!(folded_span("x s k - a n t y", "sk-ant"), Some("s k - a n t".to_string()));
+    }
+}
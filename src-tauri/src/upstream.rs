@@ -0,0 +1,405 @@
+// Upstream Connection Policy
+//
+// Each backend has its own connect/read timeouts, a retry budget and a circuit breaker. Retries
+// (with exponential backoff and jitter) only apply to idempotent requests that failed to connect,
+// timed out, or were answered with 429/5xx; a POST to a model endpoint is sent once, since
+// repeating it could run (and bill) the prompt twice. After enough consecutive failures (network
+// errors or 5xx) the breaker opens and requests fail fast with 503 until the cooldown passes; the
+// first request after that probes the upstream again, and another failure re-opens the breaker.
+//
+// Clients are shared across requests, so connections to a provider are pooled and kept alive
+// between calls, and negotiate HTTP/2 where the provider offers it (ALPN): an agent issuing dozens
+// of calls a minute then reuses one TLS connection instead of a handshake per call. Requests,
+// HTTP/2 use and in-flight counts are kept per upstream host for the stats.
+
+use crate::upstream_proxy;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Delay before the first retry; doubled after each failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait between attempts, including a Retry-After from the upstream
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How long an idle pooled connection is kept open
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Interval of HTTP/2 and TCP keep-alive probes, so idle pooled connections aren't dropped silently
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upstream settings of a backend (`upstream` in its settings JSON)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamSettings {
+    pub connect_timeout_secs: u64,
+    /// Longest wait for the next bytes of a response, not for the whole response (0 = no limit)
+    pub read_timeout_secs: u64,
+    /// Retries after the first attempt, for idempotent requests only
+    pub max_retries: u32,
+    /// Consecutive failures that open the circuit breaker (0 = off)
+    pub breaker_failure_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for UpstreamSettings {
+    fn default() -> Self {
+        UpstreamSettings {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 300,
+            max_retries: 2,
+            breaker_failure_threshold: 5,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl UpstreamSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=300).contains(&self.connect_timeout_secs) {
+            return Err("Connect timeout must be between 1 and 300 seconds".to_string());
+        }
+        if self.read_timeout_secs > 3600 {
+            return Err("Read timeout must be at most 3600 seconds".to_string());
+        }
+        if self.max_retries > 5 {
+            return Err("Retries must be between 0 and 5".to_string());
+        }
+        if self.breaker_failure_threshold > 100 {
+            return Err("Failure threshold must be between 0 and 100".to_string());
+        }
+        if !(1..=3600).contains(&self.breaker_cooldown_secs) {
+            return Err("Cooldown must be between 1 and 3600 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Why a request could not be forwarded
+pub enum UpstreamError {
+    /// The backend's circuit breaker is open; retry after the given time
+    CircuitOpen(Duration),
+    Request(reqwest::Error),
+}
+
+/// Breaker state of one backend
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+static BREAKERS: LazyLock<Mutex<HashMap<String, Breaker>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Clients by (connect, read) timeout, so connections are pooled across requests
+static CLIENTS: LazyLock<Mutex<HashMap<(u64, u64), Client>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// HTTP client with the backend's timeouts
+pub fn client(settings: &UpstreamSettings) -> Client {
+    let key = (settings.connect_timeout_secs, settings.read_timeout_secs);
+    let mut clients = CLIENTS.lock().unwrap();
+    clients
+        .entry(key)
+        .or_insert_with(|| {
+            let mut builder = Client::builder()
+                .connect_timeout(Duration::from_secs(key.0))
+                .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+                .tcp_keepalive(KEEP_ALIVE_INTERVAL)
+                .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+                .http2_keep_alive_while_idle(true)
+                .http2_adaptive_window(true);
+            if key.1 > 0 {
+                builder = builder.read_timeout(Duration::from_secs(key.1));
+            }
+            if let Some(proxy) = upstream_proxy::proxy() {
+                builder = builder.proxy(proxy);
+            }
+            builder.build().unwrap_or_default()
+        })
+        .clone()
+}
+
+/// Drop the shared clients so the next request builds them with the current proxy settings
+pub fn reset_clients() {
+    CLIENTS.lock().unwrap().clear();
+}
+
+/// Request counters of one upstream host
+#[derive(Default)]
+struct HostCounters {
+    requests: u64,
+    http2_requests: u64,
+    errors: u64,
+    in_flight: u64,
+}
+
+static HOST_COUNTERS: LazyLock<Mutex<HashMap<String, HostCounters>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Counts a request as in flight to a host until dropped (also when the client goes away mid-request)
+struct InFlight<'a> {
+    host: &'a str,
+}
+
+impl<'a> InFlight<'a> {
+    fn start(host: &'a str) -> Self {
+        HOST_COUNTERS.lock().unwrap().entry(host.to_string()).or_default().in_flight += 1;
+        InFlight { host }
+    }
+
+    fn finish(&self, result: &Result<Response, reqwest::Error>) {
+        let mut counters = HOST_COUNTERS.lock().unwrap();
+        let host = counters.entry(self.host.to_string()).or_default();
+        host.requests += 1;
+        match result {
+            Ok(response) if response.version() == reqwest::Version::HTTP_2 => host.http2_requests += 1,
+            Ok(_) => {}
+            Err(_) => host.errors += 1,
+        }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(host) = HOST_COUNTERS.lock().unwrap().get_mut(self.host) {
+            host.in_flight = host.in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// Connection pool stats of one upstream host, for the UI
+#[derive(Serialize)]
+pub struct PoolStats {
+    pub host: String,
+    pub requests: u64,
+    /// Requests answered over HTTP/2 (the rest used HTTP/1.1)
+    pub http2_requests: u64,
+    /// Requests that failed without a response (connect errors, timeouts)
+    pub errors: u64,
+    pub in_flight: u64,
+}
+
+/// Stats of every upstream host used since startup
+pub fn pool_stats() -> Vec<PoolStats> {
+    let counters = HOST_COUNTERS.lock().unwrap();
+    let mut stats: Vec<PoolStats> = counters
+        .iter()
+        .map(|(host, c)| PoolStats {
+            host: host.clone(),
+            requests: c.requests,
+            http2_requests: c.http2_requests,
+            errors: c.errors,
+            in_flight: c.in_flight,
+        })
+        .collect();
+    stats.sort_by(|a, b| a.host.cmp(&b.host));
+    stats
+}
+
+/// Err(time until the breaker closes) if requests to the backend should fail fast
+fn admit(backend: &str, settings: &UpstreamSettings) -> Result<(), Duration> {
+    if settings.breaker_failure_threshold == 0 {
+        return Ok(());
+    }
+    let breakers = BREAKERS.lock().unwrap();
+    match breakers.get(backend).and_then(|b| b.open_until) {
+        Some(until) if until > Instant::now() => Err(until - Instant::now()),
+        _ => Ok(()),
+    }
+}
+
+fn record(backend: &str, settings: &UpstreamSettings, failed: bool) {
+    let mut breakers = BREAKERS.lock().unwrap();
+    let breaker = breakers.entry(backend.to_string()).or_default();
+    if !failed {
+        *breaker = Breaker::default();
+        return;
+    }
+
+    breaker.consecutive_failures += 1;
+    let threshold = settings.breaker_failure_threshold;
+    if threshold > 0 && breaker.consecutive_failures >= threshold {
+        if breaker.open_until.is_none_or(|until| until <= Instant::now()) {
+            println!(
+                "[UPSTREAM] Circuit opened for backend '{}' after {} consecutive failures",
+                backend, breaker.consecutive_failures
+            );
+        }
+        breaker.open_until = Some(Instant::now() + Duration::from_secs(settings.breaker_cooldown_secs));
+    }
+}
+
+/// Backoff plus up to 50% random jitter, so clients retrying together don't stay in step
+fn jittered(backoff: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    backoff + backoff.mul_f64((nanos % 1000) as f64 / 2000.0)
+}
+
+/// Seconds in a Retry-After header, if given in that form
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Send a request to a backend's upstream, applying its breaker and retry policy
+pub async fn send(
+    backend: &str,
+    settings: &UpstreamSettings,
+    request: RequestBuilder,
+    idempotent: bool,
+) -> Result<Response, UpstreamError> {
+    admit(backend, settings).map_err(UpstreamError::CircuitOpen)?;
+
+    let (client, request) = request.build_split();
+    let mut request = request.map_err(UpstreamError::Request)?;
+    let host = request.url().host_str().unwrap_or_default().to_string();
+
+    let max_attempts = if idempotent { settings.max_retries + 1 } else { 1 };
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        // Keep a copy for the next attempt; streaming bodies can't be replayed
+        let retry = if attempt < max_attempts { request.try_clone() } else { None };
+
+        let in_flight = InFlight::start(&host);
+        let result = client.execute(request).await;
+        in_flight.finish(&result);
+        drop(in_flight);
+        let failed = match &result {
+            Ok(response) => response.status().is_server_error(),
+            Err(_) => true,
+        };
+        record(backend, settings, failed);
+
+        let wait = match &result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                retry_after(response).unwrap_or(backoff).max(backoff)
+            }
+            Err(e) if e.is_connect() || e.is_timeout() => backoff,
+            _ => return result.map_err(UpstreamError::Request),
+        };
+        let Some(retry) = retry else {
+            return result.map_err(UpstreamError::Request);
+        };
+        if admit(backend, settings).is_err() {
+            return result.map_err(UpstreamError::Request);
+        }
+
+        println!(
+            "[UPSTREAM] Retrying request to backend '{}' (attempt {} of {})",
+            backend,
+            attempt + 1,
+            max_attempts
+        );
+        tokio::time::sleep(jittered(wait).min(MAX_BACKOFF)).await;
+        backoff *= 2;
+        attempt += 1;
+        request = retry;
+    }
+}
+
+/// Circuit breaker state of a backend, for the UI
+#[derive(Serialize)]
+pub struct BreakerStatus {
+    pub backend: String,
+    pub consecutive_failures: u32,
+    pub open: bool,
+    /// Seconds until requests are let through again (0 when closed)
+    pub retry_in_secs: u64,
+}
+
+/// Breaker state of every backend that has sent a request since startup
+pub fn breaker_statuses() -> Vec<BreakerStatus> {
+    let breakers = BREAKERS.lock().unwrap();
+    let now = Instant::now();
+    let mut statuses: Vec<BreakerStatus> = breakers
+        .iter()
+        .map(|(backend, breaker)| {
+            let remaining = breaker
+                .open_until
+                .filter(|until| *until > now)
+                .map(|until| until - now);
+            BreakerStatus {
+                backend: backend.clone(),
+                consecutive_failures: breaker.consecutive_failures,
+                open: remaining.is_some(),
+                retry_in_secs: remaining.map(|d| d.as_secs().max(1)).unwrap_or(0),
+            }
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.backend.cmp(&b.backend));
+    statuses
+}
+
+/// Close a backend's breaker, e.g. once the user knows the upstream is back
+pub fn reset_breaker(backend: &str) {
+    BREAKERS.lock().unwrap().remove(backend);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_and_resets() {
+        let settings = UpstreamSettings {
+            breaker_failure_threshold: 2,
+            ..UpstreamSettings::default()
+        };
+        let backend = "test-breaker";
+
+        record(backend, &settings, true);
+        assert!(admit(backend, &settings).is_ok());
+        record(backend, &settings, true);
+        assert!(admit(backend, &settings).is_err());
+
+        // A threshold of 0 turns the breaker off
+        let off = UpstreamSettings {
+            breaker_failure_threshold: 0,
+            ..settings
+        };
+        assert!(admit(backend, &off).is_ok());
+
+        record(backend, &settings, false);
+        assert!(admit(backend, &settings).is_ok());
+        reset_breaker(backend);
+    }
+
+    #[test]
+    fn test_in_flight_counted_until_dropped() {
+        let host = "pool-test.invalid";
+        let in_flight = |host: &str| pool_stats().into_iter().find(|s| s.host == host).map(|s| s.in_flight);
+
+        let first = InFlight::start(host);
+        let second = InFlight::start(host);
+        assert_eq!(in_flight(host), Some(2));
+        drop(first);
+        assert_eq!(in_flight(host), Some(1));
+        drop(second);
+        assert_eq!(in_flight(host), Some(0));
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        assert!(UpstreamSettings::default().validate().is_ok());
+        let bad = UpstreamSettings {
+            max_retries: 10,
+            ..UpstreamSettings::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// Upstream (Corporate) Proxy
+//
+// Many enterprise machines can only reach providers through an HTTP(S) proxy. With one set here,
+// requests to upstreams (fallbacks included, as they share the upstream clients) go through it,
+// and so do the gateway's own calls out (mirrors, DLP webhooks, key verification), with basic
+// auth if a user is given, while hosts on its no-proxy list are reached directly.
+// Without one, the HTTPS_PROXY / HTTP_PROXY / NO_PROXY environment variables apply as before. The
+// password is kept in the OS keychain, not in the settings table. Changes apply to the next
+// request: the upstream clients are rebuilt, dropping their pooled connections.
+
+use crate::api_keys::KEYCHAIN_SERVICE;
+use crate::database::{get_upstream_proxy_from_db, save_upstream_proxy_to_db};
+use crate::upstream;
+use reqwest::{Client, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::Duration;
+
+/// Keychain entry holding the proxy password
+const KEYCHAIN_ACCOUNT: &str = "upstream-proxy";
+
+/// Upstream proxy settings (`upstream_proxy` in the settings table)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpstreamProxySettings {
+    pub enabled: bool,
+    /// e.g. http://proxy.corp.example:3128
+    pub url: String,
+    /// Basic auth user (empty = no auth)
+    pub username: String,
+    /// Hosts reached directly, comma-separated as in NO_PROXY (e.g. "localhost, .corp, 10.0.0.0/8")
+    pub no_proxy: String,
+}
+
+impl UpstreamProxySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let url = self.url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Proxy URL must start with http:// or https://".to_string());
+        }
+        Proxy::all(url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Settings and the stored password, if any
+type ProxyConfig = (UpstreamProxySettings, Option<String>);
+
+/// Read once, then updated on save
+static SETTINGS: LazyLock<RwLock<Option<ProxyConfig>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Clients of calls that aren't to an upstream, by total timeout
+static CLIENTS: LazyLock<Mutex<HashMap<Duration, Client>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn keychain_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT).map_err(|e| e.to_string())
+}
+
+fn current_with_password() -> ProxyConfig {
+    if let Some(current) = SETTINGS.read().unwrap().as_ref() {
+        return current.clone();
+    }
+    let settings = get_upstream_proxy_from_db();
+    let password = if settings.username.is_empty() {
+        None
+    } else {
+        keychain_entry().and_then(|e| e.get_password().map_err(|e| e.to_string())).ok()
+    };
+    *SETTINGS.write().unwrap() = Some((settings.clone(), password.clone()));
+    (settings, password)
+}
+
+/// Upstream proxy settings, for the UI
+#[derive(Serialize)]
+pub struct UpstreamProxyStatus {
+    #[serde(flatten)]
+    pub settings: UpstreamProxySettings,
+    /// Whether a password is stored (it is never sent back)
+    pub has_password: bool,
+}
+
+pub fn status() -> UpstreamProxyStatus {
+    let (settings, password) = current_with_password();
+    UpstreamProxyStatus {
+        settings,
+        has_password: password.is_some(),
+    }
+}
+
+/// Save the settings and apply them to the next request. A `password` of None keeps the stored
+/// one, an empty one removes it.
+pub fn save_settings(settings: UpstreamProxySettings, password: Option<String>) -> Result<(), String> {
+    settings.validate()?;
+
+    let stored = current_with_password().1;
+    let password = match password {
+        Some(password) if password.is_empty() => {
+            match keychain_entry()?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => return Err(format!("Failed to remove proxy password from the keychain: {}", e)),
+            }
+            None
+        }
+        Some(password) => {
+            keychain_entry()?
+                .set_password(&password)
+                .map_err(|e| format!("Failed to store proxy password in the keychain: {}", e))?;
+            Some(password)
+        }
+        None => stored,
+    };
+
+    save_upstream_proxy_to_db(&settings)?;
+    *SETTINGS.write().unwrap() = Some((settings, password));
+    upstream::reset_clients();
+    CLIENTS.lock().unwrap().clear();
+    Ok(())
+}
+
+fn build_proxy(settings: &UpstreamProxySettings, password: Option<&str>) -> Option<Proxy> {
+    if !settings.enabled {
+        return None;
+    }
+    let mut proxy = Proxy::all(settings.url.trim()).ok()?;
+    if !settings.username.is_empty() {
+        proxy = proxy.basic_auth(&settings.username, password.unwrap_or_default());
+    }
+    Some(proxy.no_proxy(NoProxy::from_string(&settings.no_proxy)))
+}
+
+/// The proxy upstream clients go through, or None to use the environment's
+pub fn proxy() -> Option<Proxy> {
+    let (settings, password) = current_with_password();
+    build_proxy(&settings, password.as_deref())
+}
+
+/// HTTP client for the gateway's own calls (mirrors, webhooks, key verification), through the
+/// same proxy as the upstream clients
+pub fn client(timeout: Duration) -> Client {
+    CLIENTS
+        .lock()
+        .unwrap()
+        .entry(timeout)
+        .or_insert_with(|| {
+            let mut builder = Client::builder().timeout(timeout);
+            if let Some(proxy) = proxy() {
+                builder = builder.proxy(proxy);
+            }
+            builder.build().unwrap_or_default()
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_build() {
+        let off = UpstreamProxySettings::default();
+        assert!(off.validate().is_ok());
+        assert!(build_proxy(&off, None).is_none());
+
+        let socks = UpstreamProxySettings {
+            enabled: true,
+            url: "socks5://proxy.corp:1080".to_string(),
+            ..UpstreamProxySettings::default()
+        };
+        assert!(socks.validate().is_err());
+
+        let corp = UpstreamProxySettings {
+            enabled: true,
+            url: "http://proxy.corp:3128".to_string(),
+            username: "alice".to_string(),
+            no_proxy: "localhost, .corp".to_string(),
+        };
+        assert!(corp.validate().is_ok());
+        assert!(build_proxy(&corp, Some("secret")).is_some());
+    }
+}
@@ -4,6 +4,21 @@ import { invoke, getCurrentPort, escapeHtml } from './utils.js';
 let customBackends = [];
 let predefinedBackends = [];
 
+// Open circuit breakers by backend name
+let openCircuits = {};
+
+// Keychain-stored API keys (masked, with usage) by backend name
+let storedKeys = {};
+
+// Upstream defaults (must match UpstreamSettings::default in upstream.rs)
+const UPSTREAM_DEFAULTS = {
+  connect_timeout_secs: 10,
+  read_timeout_secs: 300,
+  max_retries: 2,
+  breaker_failure_threshold: 5,
+  breaker_cooldown_secs: 30
+};
+
 // Parse settings JSON with defaults
 function parseSettings(settingsJson) {
   try {
@@ -13,22 +28,265 @@ function parseSettings(settingsJson) {
       rate_limit_requests: settings.rate_limit_requests || 0,
       rate_limit_minutes: settings.rate_limit_minutes || 1,
       max_tokens_in_a_request: settings.max_tokens_in_a_request || 0,
-      action_for_max_tokens_in_a_request: settings.action_for_max_tokens_in_a_request || 'block'
+      action_for_max_tokens_in_a_request: settings.action_for_max_tokens_in_a_request || 'block',
+      listen_port: settings.listen_port || 0,
+      log_token_counts: settings.log_token_counts === true,
+      dlp_scan_system_prompt: settings.dlp_scan_system_prompt === true,
+      dlp_scan_tool_definitions: settings.dlp_scan_tool_definitions === true,
+      dlp_scan_assistant_turns: settings.dlp_scan_assistant_turns === true,
+      dlp_block_image_secrets: settings.dlp_block_image_secrets === true,
+      upstream: { ...UPSTREAM_DEFAULTS, ...(settings.upstream || {}) },
+      api_key_balancing: settings.api_key_balancing || 'round_robin',
+      log_header_overrides: settings.log_header_overrides || {},
+      mirror: { url: '', sample_percent: 0, ...(settings.mirror || {}) },
+      fallbacks: settings.fallbacks || [],
+      translation: { mode: 'off', model: '', ...(settings.translation || {}) }
     };
   } catch {
-    return { dlp_enabled: true, rate_limit_requests: 0, rate_limit_minutes: 1, max_tokens_in_a_request: 0, action_for_max_tokens_in_a_request: 'block' };
+    return { dlp_enabled: true, rate_limit_requests: 0, rate_limit_minutes: 1, max_tokens_in_a_request: 0, action_for_max_tokens_in_a_request: 'block', listen_port: 0, log_token_counts: false, dlp_scan_system_prompt: false, dlp_scan_tool_definitions: false, dlp_scan_assistant_turns: false, dlp_block_image_secrets: false, upstream: { ...UPSTREAM_DEFAULTS }, api_key_balancing: 'round_robin', log_header_overrides: {}, mirror: { url: '', sample_percent: 0 }, fallbacks: [], translation: { mode: 'off', model: '' } };
   }
 }
 
-// Build settings JSON from form values
-function buildSettingsJson(dlpEnabled, rateRequests, rateMinutes, maxTokens, maxTokensAction) {
+// Backends that make token-counting calls
+const TOKEN_COUNT_BACKENDS = ['claude', 'vertex', 'gemini'];
+
+// Build settings JSON from form values, keeping settings that have no form field
+function buildSettingsJson(existingJson, dlpEnabled, rateRequests, rateMinutes, maxTokens, maxTokensAction, listenPort, dlpScope, extra) {
+  let existing = {};
+  try {
+    existing = JSON.parse(existingJson || '{}');
+  } catch {
+    existing = {};
+  }
   return JSON.stringify({
+    ...existing,
     dlp_enabled: dlpEnabled,
     rate_limit_requests: rateRequests,
     rate_limit_minutes: rateMinutes,
     max_tokens_in_a_request: maxTokens,
-    action_for_max_tokens_in_a_request: maxTokensAction
+    action_for_max_tokens_in_a_request: maxTokensAction,
+    listen_port: listenPort,
+    ...dlpScope,
+    ...extra
+  });
+}
+
+// DLP scan scope toggles of a backend modal ('backend' or 'predefined-backend')
+function readDlpScope(prefix) {
+  return {
+    dlp_scan_system_prompt: document.getElementById(`${prefix}-dlp-scan-system-prompt`).checked,
+    dlp_scan_tool_definitions: document.getElementById(`${prefix}-dlp-scan-tool-definitions`).checked,
+    dlp_scan_assistant_turns: document.getElementById(`${prefix}-dlp-scan-assistant-turns`).checked,
+    dlp_block_image_secrets: document.getElementById(`${prefix}-dlp-block-image-secrets`).checked
+  };
+}
+
+function fillDlpScope(prefix, settings) {
+  document.getElementById(`${prefix}-dlp-scan-system-prompt`).checked = settings.dlp_scan_system_prompt;
+  document.getElementById(`${prefix}-dlp-scan-tool-definitions`).checked = settings.dlp_scan_tool_definitions;
+  document.getElementById(`${prefix}-dlp-scan-assistant-turns`).checked = settings.dlp_scan_assistant_turns;
+  document.getElementById(`${prefix}-dlp-block-image-secrets`).checked = settings.dlp_block_image_secrets;
+}
+
+// Upstream timeout, retry and breaker inputs of a backend modal ('backend' or 'predefined-backend')
+function readUpstream(prefix) {
+  const readNumber = (field, fallback) => {
+    const value = parseInt(document.getElementById(`${prefix}-upstream-${field}`).value, 10);
+    return Number.isNaN(value) ? fallback : Math.max(0, value);
+  };
+  return {
+    connect_timeout_secs: readNumber('connect-timeout', UPSTREAM_DEFAULTS.connect_timeout_secs),
+    read_timeout_secs: readNumber('read-timeout', UPSTREAM_DEFAULTS.read_timeout_secs),
+    max_retries: readNumber('retries', UPSTREAM_DEFAULTS.max_retries),
+    breaker_failure_threshold: readNumber('breaker-threshold', UPSTREAM_DEFAULTS.breaker_failure_threshold),
+    breaker_cooldown_secs: readNumber('breaker-cooldown', UPSTREAM_DEFAULTS.breaker_cooldown_secs)
+  };
+}
+
+// Settings from the upstream and API key sections of a backend modal
+function readExtraSettings(prefix) {
+  return {
+    upstream: readUpstream(prefix),
+    api_key_balancing: document.getElementById(`${prefix}-api-key-balancing`).value,
+    log_header_overrides: readHeaderOverrides(prefix),
+    mirror: readMirror(prefix),
+    fallbacks: readFallbacks(prefix),
+    translation: readTranslation(prefix)
+  };
+}
+
+// API translation mode and upstream model of a backend modal
+function readTranslation(prefix) {
+  return {
+    mode: document.getElementById(`${prefix}-translation-mode`).value,
+    model: document.getElementById(`${prefix}-translation-model`).value.trim()
+  };
+}
+
+function fillTranslation(prefix, settings) {
+  document.getElementById(`${prefix}-translation-mode`).value = settings.translation.mode;
+  document.getElementById(`${prefix}-translation-model`).value = settings.translation.model;
+}
+
+// Fallback backends, written as "claude-backup, vertex claude-sonnet-4@20250514 projects/acme/locations/us-east5"
+// (name, then optionally the model, "-" to keep the requested one, and for Claude on Vertex AI the location)
+function readFallbacks(prefix) {
+  const text = document.getElementById(`${prefix}-fallbacks`).value;
+  return text.split(',')
+    .map(entry => entry.trim().split(/\s+/))
+    .filter(([backend]) => backend)
+    .map(([backend, model = '', vertex_location = '']) => ({ backend, model: model === '-' ? '' : model, vertex_location }));
+}
+
+function fillFallbacks(prefix, settings) {
+  document.getElementById(`${prefix}-fallbacks`).value = settings.fallbacks
+    .map(f => [f.backend, f.model || (f.vertex_location ? '-' : ''), f.vertex_location].filter(Boolean).join(' '))
+    .join(', ');
+}
+
+// Mirror URL and sample of a backend modal
+function readMirror(prefix) {
+  const percent = parseInt(document.getElementById(`${prefix}-mirror-sample`).value, 10);
+  return {
+    url: document.getElementById(`${prefix}-mirror-url`).value.trim(),
+    sample_percent: Number.isNaN(percent) ? 0 : Math.min(100, Math.max(0, percent))
+  };
+}
+
+function fillMirror(prefix, settings) {
+  document.getElementById(`${prefix}-mirror-url`).value = settings.mirror.url;
+  document.getElementById(`${prefix}-mirror-sample`).value = settings.mirror.sample_percent;
+}
+
+// Header log overrides, written as "x-team-token=hash, cookie=remove"
+function readHeaderOverrides(prefix) {
+  const text = document.getElementById(`${prefix}-log-header-overrides`).value;
+  return Object.fromEntries(text.split(',')
+    .map(entry => entry.split('=').map(part => part.trim().toLowerCase()))
+    .filter(([name, action]) => name && action));
+}
+
+function fillHeaderOverrides(prefix, settings) {
+  document.getElementById(`${prefix}-log-header-overrides`).value = Object.entries(settings.log_header_overrides)
+    .map(([name, action]) => `${name}=${action}`)
+    .join(', ');
+}
+
+function fillUpstream(prefix, settings) {
+  const upstream = settings.upstream;
+  document.getElementById(`${prefix}-upstream-connect-timeout`).value = upstream.connect_timeout_secs;
+  document.getElementById(`${prefix}-upstream-read-timeout`).value = upstream.read_timeout_secs;
+  document.getElementById(`${prefix}-upstream-retries`).value = upstream.max_retries;
+  document.getElementById(`${prefix}-upstream-breaker-threshold`).value = upstream.breaker_failure_threshold;
+  document.getElementById(`${prefix}-upstream-breaker-cooldown`).value = upstream.breaker_cooldown_secs;
+}
+
+// Refresh which backends have an open circuit breaker
+async function loadCircuitBreakers() {
+  try {
+    const breakers = await invoke('get_upstream_circuit_breakers');
+    openCircuits = Object.fromEntries(breakers.filter(b => b.open).map(b => [b.backend, b]));
+  } catch (error) {
+    console.error('Failed to load circuit breakers:', error);
+    openCircuits = {};
+  }
+}
+
+// Refresh the stored API keys of all backends
+async function loadStoredKeys() {
+  try {
+    const keys = await invoke('get_backend_api_keys');
+    storedKeys = {};
+    keys.forEach(k => {
+      (storedKeys[k.backend] = storedKeys[k.backend] || []).push(k);
+    });
+  } catch (error) {
+    console.error('Failed to load stored API keys:', error);
+    storedKeys = {};
+  }
+}
+
+function storedKeyBadgeHtml(name) {
+  const count = (storedKeys[name] || []).length;
+  if (!count) return '';
+  return `<span class="backend-setting-badge stored-key" title="Sent instead of the client's key">${count} Stored Key${count === 1 ? '' : 's'}</span>`;
+}
+
+// List a backend's stored keys in a backend modal ('backend' or 'predefined-backend')
+function fillApiKeys(prefix, name, settings) {
+  const list = document.getElementById(`${prefix}-api-key-list`);
+  const keys = name ? storedKeys[name] || [] : [];
+  list.innerHTML = keys.map(k => `
+    <div class="api-key-item">
+      <span><code>${escapeHtml(k.preview)}</code> ${k.label ? escapeHtml(k.label) : ''}</span>
+      <span class="api-key-usage">${k.request_count} requests, ${k.input_tokens} in / ${k.output_tokens} out tokens</span>
+      <button type="button" class="btn btn-secondary btn-sm api-key-remove" data-id="${k.id}">Remove</button>
+    </div>
+  `).join('');
+  list.querySelectorAll('.api-key-remove').forEach(btn => {
+    btn.addEventListener('click', () => removeApiKey(prefix, parseInt(btn.dataset.id), name, settings));
   });
+
+  document.getElementById(`${prefix}-api-key`).value = '';
+  document.getElementById(`${prefix}-api-key-label`).value = '';
+  document.getElementById(`${prefix}-api-key-balancing`).value = settings.api_key_balancing;
+}
+
+// Store a key typed into a backend modal, if any
+async function saveApiKey(prefix, name) {
+  const key = document.getElementById(`${prefix}-api-key`).value.trim();
+  const label = document.getElementById(`${prefix}-api-key-label`).value.trim();
+  if (key) {
+    await invoke('add_backend_api_key', { name, key, label: label || null });
+  }
+}
+
+async function removeApiKey(prefix, id, name, settings) {
+  if (!confirm(`Remove this API key from ${name}? Once no keys are left, clients' own keys are used again.`)) {
+    return;
+  }
+  try {
+    await invoke('delete_backend_api_key', { id });
+    await loadStoredKeys();
+    fillApiKeys(prefix, name, settings);
+    showBackendsStatus('API key removed.', 'success');
+    loadPredefinedBackends();
+    loadCustomBackends();
+  } catch (error) {
+    alert(`Failed to remove key: ${error}`);
+  }
+}
+
+// Badge for a backend whose upstream is paused; clicking it resumes requests
+function circuitBadgeHtml(name) {
+  const breaker = openCircuits[name];
+  if (!breaker) return '';
+  return `<button class="backend-setting-badge circuit-open circuit-reset" data-name="${escapeHtml(name)}" title="${breaker.consecutive_failures} failures in a row. Click to resume now.">Upstream Paused (${breaker.retry_in_secs}s)</button>`;
+}
+
+// Resume requests to a backend whose circuit breaker is open
+function initCircuitResetButtons(container, reload) {
+  container.querySelectorAll('.circuit-reset').forEach(btn => {
+    btn.addEventListener('click', async (e) => {
+      e.stopPropagation();
+      try {
+        await invoke('reset_upstream_circuit_breaker', { name: btn.dataset.name });
+        showBackendsStatus('Requests resumed.', 'success');
+        reload();
+      } catch (error) {
+        showBackendsStatus(`Failed to resume: ${error}`, 'error');
+      }
+    });
+  });
+}
+
+// Dedicated port URL line for a backend card (empty if none)
+function listenPortHtml(settings) {
+  if (!settings.listen_port) return '';
+  return `<div class="backend-url">
+            <span class="backend-label">Dedicated Port:</span>
+            <code>http://localhost:${settings.listen_port}</code>
+          </div>`;
 }
 
 // Show status message
@@ -61,6 +319,7 @@ function showBackendsStatus(message, type) {
 export async function loadCustomBackends() {
   try {
     customBackends = await invoke('get_custom_backends');
+    await Promise.all([loadCircuitBreakers(), loadStoredKeys()]);
     renderBackends(customBackends);
   } catch (error) {
     console.error('Failed to load custom backends:', error);
@@ -111,8 +370,9 @@ function renderBackends(backends) {
         <div class="backend-details">
           <div class="backend-url">
             <span class="backend-label">Proxy URL:</span>
-            <code>http://localhost:${port}/${escapeHtml(backend.name)}</code>
+            <code>http://localhost:${port}${escapeHtml(backend.path_prefix)}</code>
           </div>
+          ${listenPortHtml(settings)}
           <div class="backend-url">
             <span class="backend-label">Target:</span>
             <code>${escapeHtml(backend.base_url)}</code>
@@ -122,6 +382,8 @@ function renderBackends(backends) {
           ${dlpBadge}
           ${rateBadge}
           ${tokenBadge}
+          ${storedKeyBadgeHtml(backend.name)}
+          ${circuitBadgeHtml(backend.name)}
         </div>
       </div>
       <div class="backend-actions">
@@ -138,6 +400,7 @@ function renderBackends(backends) {
 
   // Re-initialize Lucide icons
   lucide.createIcons();
+  initCircuitResetButtons(container, loadCustomBackends);
 
   // Add event listeners for toggles
   container.querySelectorAll('.backend-toggle').forEach(checkbox => {
@@ -200,12 +463,13 @@ function showBackendModal(backend = null) {
   const rateMinutesInput = document.getElementById('backend-rate-minutes');
   const maxTokensInput = document.getElementById('backend-max-tokens');
   const maxTokensActionInput = document.getElementById('backend-max-tokens-action');
+  const listenPortInput = document.getElementById('backend-listen-port');
 
   // Set title
   title.textContent = backend ? 'Edit Backend' : 'Add Backend';
 
   // Parse existing settings or use defaults
-  const settings = backend ? parseSettings(backend.settings) : { dlp_enabled: true, rate_limit_requests: 0, rate_limit_minutes: 1, max_tokens_in_a_request: 0, action_for_max_tokens_in_a_request: 'block' };
+  const settings = parseSettings(backend ? backend.settings : '{}');
 
   // Reset/populate form
   document.getElementById('backend-id').value = backend ? backend.id : '';
@@ -216,6 +480,14 @@ function showBackendModal(backend = null) {
   rateMinutesInput.value = settings.rate_limit_minutes;
   maxTokensInput.value = settings.max_tokens_in_a_request;
   maxTokensActionInput.value = settings.action_for_max_tokens_in_a_request;
+  listenPortInput.value = settings.listen_port;
+  fillDlpScope('backend', settings);
+  fillUpstream('backend', settings);
+  fillHeaderOverrides('backend', settings);
+  fillMirror('backend', settings);
+  fillFallbacks('backend', settings);
+  fillTranslation('backend', settings);
+  fillApiKeys('backend', backend?.name, settings);
 
   // If editing, disable name field (changing name not allowed)
   nameInput.disabled = !!backend;
@@ -249,9 +521,11 @@ async function saveBackend() {
   const rateMinutes = parseInt(document.getElementById('backend-rate-minutes').value) || 1;
   const maxTokens = parseInt(document.getElementById('backend-max-tokens').value) || 0;
   const maxTokensAction = document.getElementById('backend-max-tokens-action').value || 'block';
+  const listenPort = parseInt(document.getElementById('backend-listen-port').value) || 0;
 
   // Build settings JSON
-  const settings = buildSettingsJson(dlpEnabled, rateRequests, Math.max(1, rateMinutes), maxTokens, maxTokensAction);
+  const existing = customBackends.find(b => String(b.id) === id);
+  const settings = buildSettingsJson(existing?.settings, dlpEnabled, rateRequests, Math.max(1, rateMinutes), maxTokens, maxTokensAction, listenPort, readDlpScope('backend'), readExtraSettings('backend'));
 
   // Validation
   if (!name) {
@@ -285,6 +559,7 @@ async function saveBackend() {
         settings
       });
     }
+    await saveApiKey('backend', name);
     // Restart proxy to apply changes
     await invoke('restart_proxy');
     showBackendsStatus(id ? 'Backend updated and gateway restarted.' : 'Backend added and gateway restarted.', 'success');
@@ -306,6 +581,7 @@ async function saveBackend() {
 export async function loadPredefinedBackends() {
   try {
     predefinedBackends = await invoke('get_predefined_backends');
+    await Promise.all([loadCircuitBreakers(), loadStoredKeys()]);
     renderPredefinedBackends(predefinedBackends);
   } catch (error) {
     console.error('Failed to load predefined backends:', error);
@@ -340,18 +616,20 @@ function renderPredefinedBackends(backends) {
       ? ''
       : `<div class="backend-url">
           <span class="backend-label">Proxy URL:</span>
-          <code>http://localhost:${port}/${escapeHtml(backend.name)}</code>
+          <code>http://localhost:${port}${escapeHtml(backend.path_prefix)}</code>
         </div>`;
 
     return `
-    <div class="backend-item predefined" data-name="${escapeHtml(backend.name)}">
+    <div class="backend-item predefined ${backend.enabled ? '' : 'disabled'}" data-name="${escapeHtml(backend.name)}">
       <div class="backend-info">
         <div class="backend-header">
+          <input type="checkbox" class="dlp-checkbox predefined-backend-toggle" data-name="${escapeHtml(backend.name)}" ${backend.enabled ? 'checked' : ''} />
           <span class="backend-name">${escapeHtml(backend.name)}</span>
-          <span class="backend-status enabled">Pre-defined</span>
+          <span class="backend-status ${backend.enabled ? 'enabled' : 'disabled'}">${backend.enabled ? 'Pre-defined' : 'Disabled'}</span>
         </div>
         <div class="backend-details">
           ${proxyUrlHtml}
+          ${listenPortHtml(settings)}
           <div class="backend-url">
             <span class="backend-label">Target:</span>
             <code>${escapeHtml(backend.base_url)}</code>
@@ -361,6 +639,8 @@ function renderPredefinedBackends(backends) {
           ${dlpBadge}
           ${rateBadge}
           ${tokenBadge}
+          ${storedKeyBadgeHtml(backend.name)}
+          ${circuitBadgeHtml(backend.name)}
         </div>
       </div>
       <div class="backend-actions">
@@ -374,6 +654,25 @@ function renderPredefinedBackends(backends) {
 
   // Re-initialize Lucide icons
   lucide.createIcons();
+  initCircuitResetButtons(container, loadPredefinedBackends);
+
+  // Add event listeners for toggles
+  container.querySelectorAll('.predefined-backend-toggle').forEach(checkbox => {
+    checkbox.addEventListener('change', async (e) => {
+      e.stopPropagation();
+      const name = checkbox.dataset.name;
+      try {
+        await invoke('toggle_predefined_backend', { name, enabled: checkbox.checked });
+        await invoke('restart_proxy');
+        showBackendsStatus('Backend updated and gateway restarted.', 'success');
+        loadPredefinedBackends();
+      } catch (error) {
+        console.error('Failed to toggle predefined backend:', error);
+        checkbox.checked = !checkbox.checked;
+        showBackendsStatus(`Failed to toggle: ${error}`, 'error');
+      }
+    });
+  });
 
   // Add event listeners for edit buttons
   container.querySelectorAll('.predefined-backend-edit').forEach(btn => {
@@ -399,6 +698,7 @@ function showPredefinedBackendModal(backend) {
   const rateMinutesInput = document.getElementById('predefined-backend-rate-minutes');
   const maxTokensInput = document.getElementById('predefined-backend-max-tokens');
   const maxTokensActionInput = document.getElementById('predefined-backend-max-tokens-action');
+  const listenPortInput = document.getElementById('predefined-backend-listen-port');
 
   const settings = parseSettings(backend.settings);
 
@@ -410,6 +710,17 @@ function showPredefinedBackendModal(backend) {
   rateMinutesInput.value = settings.rate_limit_minutes;
   maxTokensInput.value = settings.max_tokens_in_a_request;
   maxTokensActionInput.value = settings.action_for_max_tokens_in_a_request;
+  listenPortInput.value = settings.listen_port;
+  fillDlpScope('predefined-backend', settings);
+  fillUpstream('predefined-backend', settings);
+  fillHeaderOverrides('predefined-backend', settings);
+  fillMirror('predefined-backend', settings);
+  fillFallbacks('predefined-backend', settings);
+  fillTranslation('predefined-backend', settings);
+  fillApiKeys('predefined-backend', backend.name, settings);
+  document.getElementById('predefined-backend-log-token-counts').checked = settings.log_token_counts;
+  document.getElementById('predefined-backend-token-counts-group').style.display =
+    TOKEN_COUNT_BACKENDS.includes(backend.name) ? '' : 'none';
 
   modal.classList.add('show');
 }
@@ -428,8 +739,13 @@ async function savePredefinedBackend() {
   const rateMinutes = parseInt(document.getElementById('predefined-backend-rate-minutes').value) || 1;
   const maxTokens = parseInt(document.getElementById('predefined-backend-max-tokens').value) || 0;
   const maxTokensAction = document.getElementById('predefined-backend-max-tokens-action').value || 'block';
+  const listenPort = parseInt(document.getElementById('predefined-backend-listen-port').value) || 0;
 
-  const settings = buildSettingsJson(dlpEnabled, rateRequests, Math.max(1, rateMinutes), maxTokens, maxTokensAction);
+  const existing = predefinedBackends.find(b => b.name === name);
+  const settings = JSON.stringify({
+    ...JSON.parse(buildSettingsJson(existing?.settings, dlpEnabled, rateRequests, Math.max(1, rateMinutes), maxTokens, maxTokensAction, listenPort, readDlpScope('predefined-backend'), readExtraSettings('predefined-backend'))),
+    log_token_counts: document.getElementById('predefined-backend-log-token-counts').checked
+  });
 
   const saveBtn = document.getElementById('save-predefined-backend-btn');
   saveBtn.disabled = true;
@@ -437,6 +753,7 @@ async function savePredefinedBackend() {
 
   try {
     await invoke('update_predefined_backend', { name, settings });
+    await saveApiKey('predefined-backend', name);
     // Restart proxy to apply changes
     await invoke('restart_proxy');
     showBackendsStatus('Settings updated and gateway restarted.', 'success');
@@ -140,6 +140,8 @@ export function createLatencyChart(container, latencyPoints) {
   const data = [...latencyPoints].reverse();
   const labels = data.map((_, i) => i + 1);
   const values = data.map(p => p.latency_ms);
+  // Time to first byte, null for requests logged before it was recorded
+  const ttfbValues = data.map(p => p.ttfb_ms);
 
   const newCharts = { ...charts };
   newCharts.latency = new Chart(ctx, {
@@ -155,6 +157,15 @@ export function createLatencyChart(container, latencyPoints) {
         tension: 0.3,
         pointRadius: 2,
         pointHoverRadius: 4,
+      }, {
+        label: 'TTFB (ms)',
+        data: ttfbValues,
+        borderColor: colors.warning,
+        fill: false,
+        tension: 0.3,
+        pointRadius: 0,
+        pointHoverRadius: 4,
+        spanGaps: true,
       }]
     },
     options: {
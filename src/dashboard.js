@@ -7,7 +7,9 @@ import {
   formatNumber,
   colors,
   formatLatency,
-  shortenModel
+  formatCost,
+  shortenModel,
+  escapeHtml
 } from './utils.js';
 import { destroyCharts, createModelsChart, createTokenChart, createLatencyChart, createDlpChart, createToolInsightsChart } from './charts.js';
 
@@ -15,10 +17,31 @@ import { destroyCharts, createModelsChart, createTokenChart, createLatencyChart,
 let chartData = {};
 
 // Render dashboard HTML
-function renderDashboard(data, dlpStats, toolInsights) {
+function renderDashboard(data, dlpStats, toolInsights, poolStats) {
   const { models, features, token_totals, recent_requests, latency_points } = data;
 
+  const categoryCount = (category) =>
+    dlpStats?.detections_by_category?.find(c => c.category === category)?.count || 0;
+  const severityCount = (severity) =>
+    dlpStats?.detections_by_severity?.find(s => s.severity === severity)?.count || 0;
   const pct = (val) => features.total_requests > 0 ? Math.round((val / features.total_requests) * 100) : 0;
+  const cacheable = data.cache_hits + data.cache_misses;
+  const cacheHitPct = cacheable > 0 ? Math.round((data.cache_hits / cacheable) * 100) : 0;
+  const { cost } = data;
+  const costPct = (value) => cost.total_usd > 0 ? Math.round((value / cost.total_usd) * 100) : 0;
+  const maxDailyCost = Math.max(0, ...cost.daily.map(d => d.cost_usd));
+  const costBars = (items, label) => items.map(item => `
+            <div class="feature-bar">
+              <div class="feature-label">
+                <span class="feature-name">${escapeHtml(label(item))}</span>
+                <span class="feature-value">${formatCost(item.cost_usd)} (${formatNumber(item.requests)} requests)</span>
+              </div>
+              <div class="bar-track">
+                <div class="bar-fill tools" style="width: ${costPct(item.cost_usd)}%"></div>
+              </div>
+            </div>
+  `).join('');
+  const http2Pct = (host) => host.requests > 0 ? Math.round((host.http2_requests / host.requests) * 100) : 0;
 
   return `
     <div class="charts-grid">
@@ -93,6 +116,17 @@ function renderDashboard(data, dlpStats, toolInsights) {
                 <div class="bar-fill thinking" style="width: ${pct(features.with_thinking)}%"></div>
               </div>
             </div>
+            ${cacheable > 0 ? `
+            <div class="feature-bar">
+              <div class="feature-label">
+                <span class="feature-name">Response Cache Hits</span>
+                <span class="feature-value">${data.cache_hits} hits / ${data.cache_misses} misses (${cacheHitPct}%)</span>
+              </div>
+              <div class="bar-track">
+                <div class="bar-fill cache" style="width: ${cacheHitPct}%"></div>
+              </div>
+            </div>
+            ` : ''}
           </div>
         </div>
       </div>
@@ -102,6 +136,7 @@ function renderDashboard(data, dlpStats, toolInsights) {
         <div class="card-header">
           <span>Latency Trend</span>
           <div class="card-header-actions">
+            ${data.avg_ttfb_ms > 0 ? `<span class="badge" title="Average time to first byte">TTFB ${formatLatency(Math.round(data.avg_ttfb_ms))}</span>` : ''}
             <span class="badge">${latency_points.length} requests</span>
             <button class="expand-btn" data-chart="latency" title="Expand"><svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><polyline points="15 3 21 3 21 9"></polyline><polyline points="9 21 3 21 3 15"></polyline><line x1="21" y1="3" x2="14" y2="10"></line><line x1="3" y1="21" x2="10" y2="14"></line></svg></button>
           </div>
@@ -110,6 +145,51 @@ function renderDashboard(data, dlpStats, toolInsights) {
       </div>
     </div>
 
+    ${cost.total_usd > 0 || cost.unpriced_requests > 0 ? `
+    <!-- Estimated Cost -->
+    <div class="charts-grid">
+      <div class="card">
+        <div class="card-header">
+          <span>Estimated Cost</span>
+          <div class="card-header-actions">
+            <span class="badge">${formatCost(cost.total_usd)}</span>
+            ${cost.unpriced_requests > 0 ? `<span class="badge" title="Requests to models without a price (Settings > Model Pricing)">${cost.unpriced_requests} unpriced</span>` : ''}
+          </div>
+        </div>
+        <div class="card-body">
+          <div class="feature-bars">
+            ${costBars(cost.by_model.slice(0, 5), item => shortenModel(item.name))}
+            ${costBars(cost.by_backend, item => `Backend: ${item.name}`)}
+          </div>
+        </div>
+      </div>
+
+      <div class="card">
+        <div class="card-header">
+          <span>Cost per Day (UTC)</span>
+          <div class="card-header-actions">
+            <span class="badge">${cost.daily.length} days</span>
+          </div>
+        </div>
+        <div class="card-body">
+          <div class="feature-bars">
+            ${cost.daily.map(day => `
+            <div class="feature-bar">
+              <div class="feature-label">
+                <span class="feature-name">${day.date}</span>
+                <span class="feature-value">${formatCost(day.cost_usd)}</span>
+              </div>
+              <div class="bar-track">
+                <div class="bar-fill system" style="width: ${maxDailyCost > 0 ? Math.round((day.cost_usd / maxDailyCost) * 100) : 0}%"></div>
+              </div>
+            </div>
+            `).join('')}
+          </div>
+        </div>
+      </div>
+    </div>
+    ` : ''}
+
     <!-- Token Usage Per Request -->
     <div class="charts-grid">
       <div class="card full-width">
@@ -146,6 +226,13 @@ function renderDashboard(data, dlpStats, toolInsights) {
         <div class="card-header">
           <span>Detections</span>
           <div class="card-header-actions">
+            <span class="badge">${categoryCount('secret')} secrets</span>
+            <span class="badge">${categoryCount('pii')} PII</span>
+            ${categoryCount('entity') > 0 ? `<span class="badge">${categoryCount('entity')} entities</span>` : ''}
+            ${categoryCount('canary') > 0 ? `<span class="badge">${categoryCount('canary')} canary leaks</span>` : ''}
+            ${categoryCount('injection') > 0 ? `<span class="badge">${categoryCount('injection')} injections</span>` : ''}
+            <span class="badge">${severityCount('critical')} critical</span>
+            ${dlpStats && dlpStats.total_detections > dlpStats.total_incidents ? `<span class="badge" title="Repeats of a value in one conversation count as one incident">${dlpStats.total_incidents} incidents / ${dlpStats.total_detections} detections</span>` : ''}
             <button class="expand-btn" data-chart="dlp" title="Expand"><svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><polyline points="15 3 21 3 21 9"></polyline><polyline points="9 21 3 21 3 15"></polyline><line x1="21" y1="3" x2="14" y2="10"></line><line x1="3" y1="21" x2="10" y2="14"></line></svg></button>
           </div>
         </div>
@@ -157,6 +244,35 @@ function renderDashboard(data, dlpStats, toolInsights) {
       </div>
     </div>
 
+    ${poolStats.length > 0 ? `
+    <!-- Upstream Connections -->
+    <div class="charts-grid">
+      <div class="card full-width">
+        <div class="card-header">
+          <span>Upstream Connections</span>
+          <div class="card-header-actions">
+            <span class="badge" title="Counted since the app started">since start</span>
+          </div>
+        </div>
+        <div class="card-body">
+          <div class="feature-bars">
+            ${poolStats.map(host => `
+            <div class="feature-bar">
+              <div class="feature-label">
+                <span class="feature-name">${escapeHtml(host.host)}</span>
+                <span class="feature-value">${formatNumber(host.requests)} requests, ${http2Pct(host)}% HTTP/2, ${host.in_flight} in flight${host.errors > 0 ? `, ${host.errors} failed` : ''}</span>
+              </div>
+              <div class="bar-track">
+                <div class="bar-fill cache" style="width: ${http2Pct(host)}%"></div>
+              </div>
+            </div>
+            `).join('')}
+          </div>
+        </div>
+      </div>
+    </div>
+    ` : ''}
+
     <!-- Fullscreen Chart Modal -->
     <div class="chart-fullscreen-modal" id="chart-fullscreen-modal">
       <div class="chart-fullscreen-content">
@@ -178,11 +294,12 @@ export async function loadDashboard() {
   destroyCharts();
 
   try {
-    // Load dashboard stats, DLP stats, and tool insights in parallel
-    const [data, dlpStats, toolInsights] = await Promise.all([
+    // Load dashboard stats, DLP stats, tool insights, and upstream pool stats in parallel
+    const [data, dlpStats, toolInsights, poolStats] = await Promise.all([
       invoke('get_dashboard_stats', { timeRange: currentTimeRange, backend: currentBackend }),
       invoke('get_dlp_detection_stats', { timeRange: currentTimeRange, backend: currentBackend }),
-      invoke('get_tool_call_insights', { timeRange: currentTimeRange, backend: currentBackend })
+      invoke('get_tool_call_insights', { timeRange: currentTimeRange, backend: currentBackend }),
+      invoke('get_upstream_pool_stats')
     ]);
 
     if (data.total_requests === 0 && dlpStats.total_detections === 0 && toolInsights.tools.length === 0) {
@@ -198,7 +315,7 @@ export async function loadDashboard() {
       return;
     }
 
-    content.innerHTML = renderDashboard(data, dlpStats, toolInsights);
+    content.innerHTML = renderDashboard(data, dlpStats, toolInsights, poolStats);
 
     // Store chart data for fullscreen recreation
     chartData = {
@@ -436,6 +553,7 @@ function createFullscreenLatencyChart(canvas, latencyPoints) {
   const data = [...latencyPoints].reverse();
   const labels = data.map((_, i) => i + 1);
   const values = data.map(p => p.latency_ms);
+  const ttfbValues = data.map(p => p.ttfb_ms);
 
   return new Chart(canvas, {
     type: 'line',
@@ -450,12 +568,21 @@ function createFullscreenLatencyChart(canvas, latencyPoints) {
         tension: 0.3,
         pointRadius: 3,
         pointHoverRadius: 6,
+      }, {
+        label: 'Time to first byte (ms)',
+        data: ttfbValues,
+        borderColor: colors.warning,
+        fill: false,
+        tension: 0.3,
+        pointRadius: 2,
+        pointHoverRadius: 5,
+        spanGaps: true,
       }]
     },
     options: {
       responsive: true,
       maintainAspectRatio: false,
-      plugins: { legend: { display: false } },
+      plugins: { legend: { display: ttfbValues.some(v => v !== null) } },
       scales: {
         x: { grid: { display: false }, ticks: { font: { size: 12 } } },
         y: { grid: { color: '#f0f0f0' }, ticks: { font: { size: 12 }, callback: v => formatLatency(v) } }
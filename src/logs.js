@@ -43,6 +43,8 @@ function highlightSearchTerms(text, searchTerm) {
 // Get DLP status info
 function getDlpStatus(dlpAction) {
   switch (dlpAction) {
+    case 6: return { label: 'Scan Skipped', class: 'scan-skipped' };
+    case 5: return { label: 'Alerted', class: 'alerted' };
     case 4: return { label: 'Notify-Ratelimit', class: 'notify-ratelimit' };
     case 3: return { label: 'Ratelimited', class: 'ratelimited' };
     case 2: return { label: 'Blocked', class: 'blocked' };
@@ -71,11 +73,14 @@ function renderLogCard(log, index, cardNum, total) {
         <span class="log-number">${cardNum}/${total}</span>
         <span class="log-time">${formatRelativeTime(log.timestamp)}</span>
         <span class="log-pill backend">${log.backend}</span>
+        ${log.served_by ? `<span class="log-pill backend" title="Served by a fallback backend">via ${escapeHtml(log.served_by)}</span>` : ''}
+        ${log.translated_from ? `<span class="log-pill backend" title="Translated from the client's API">from ${log.translated_from === 'openai' ? 'OpenAI' : 'Anthropic'}</span>` : ''}
         <span class="log-pill model">${shortenModel(log.model)}</span>
         <span class="log-pill status ${status.class}">${status.label}</span>
       </div>
       <div class="log-card-stats">
         <span class="stat"><strong>Latency:</strong> ${formatLatency(log.latency_ms)}</span>
+        ${log.ttfb_ms !== null ? `<span class="stat" title="Time to first byte${log.stream_ms !== null ? ', then streaming' : ''}"><strong>TTFB:</strong> ${formatLatency(log.ttfb_ms)}${log.stream_ms !== null ? ` + ${formatLatency(log.stream_ms)} streaming` : ''}</span>` : ''}
         <span class="stat"><strong>In:</strong> ${formatNumber(log.input_tokens)}</span>
         <span class="stat"><strong>Out:</strong> ${formatNumber(log.output_tokens)}</span>
       </div>
@@ -84,6 +89,7 @@ function renderLogCard(log, index, cardNum, total) {
         <button class="log-tab" data-tab="headers" data-index="${index}">Headers</button>
         <button class="log-tab" data-tab="dlp" data-index="${index}">Detections</button>
         <button class="log-tab" data-tab="tools" data-index="${index}">Tool Calls</button>
+        ${log.conversation_id !== null ? `<button class="log-tab" data-tab="conversation" data-index="${index}">Conversation</button>` : ''}
       </div>
       <div class="log-card-subtabs">
         <button class="log-subtab active" data-subtab="request" data-index="${index}">Request</button>
@@ -96,7 +102,7 @@ function renderLogCard(log, index, cardNum, total) {
         </button>
       </div>
       <div class="log-card-content">
-        <pre class="log-json" data-index="${index}">${highlightSearchTerms(formatJson(log.request_body), logsSearch)}</pre>
+        <pre class="log-json" data-index="${index}">Loading...</pre>
       </div>
     </div>
   `;
@@ -137,6 +143,34 @@ function renderLogsCards(logs, total) {
   `;
 }
 
+// Timeline of a request's conversation, across proxied requests and hook events
+async function loadConversationTimeline(conversationId) {
+  const timeline = await invoke('get_conversation_timeline', { conversationId });
+  return {
+    conversation: timeline.conversation.conversation_key,
+    backends: timeline.conversation.backends,
+    events: timeline.events.map(e => ({
+      request_id: e.request_id,
+      timestamp: e.timestamp,
+      source: e.source,
+      backend: e.backend,
+      ...(e.hook_event ? { hook_event: e.hook_event } : {}),
+      model: e.model,
+      tokens: { input: e.input_tokens, output: e.output_tokens },
+      dlp: getDlpStatus(e.dlp_action).label,
+      ...(e.tool_calls.length > 0 ? { tool_calls: e.tool_calls.map(tc => tc.tool_name) } : {})
+    }))
+  };
+}
+
+// Bodies are stored apart from the log list and fetched once per log, when its card is shown
+async function loadBodies(log) {
+  if (!log.bodies) {
+    log.bodies = await invoke('get_request_bodies', { requestId: log.id });
+  }
+  return log.bodies;
+}
+
 // Update card content based on current tab/subtab state
 async function updateCardContent(card, index) {
   const log = currentLogs[index];
@@ -157,9 +191,17 @@ async function updateCardContent(card, index) {
         const formatted = detections.map(d => ({
           pattern: d.pattern_name,
           type: d.pattern_type,
+          severity: d.severity,
+          direction: d.direction,
+          ...(d.action ? { action: d.action } : {}),
+          ...(d.confidence != null ? { confidence: d.confidence } : {}),
+          ...(d.verified ? { verified: d.verified } : {}),
           original: d.original_value,
+          ...(d.value_hash ? { value_hash: d.value_hash } : {}),
+          ...(d.occurrence_count > 1 ? { incident_occurrences: d.occurrence_count } : {}),
           replaced_with: d.placeholder,
-          message_index: d.message_index
+          message_index: d.message_index,
+          ...(d.metadata ? { metadata: JSON.parse(d.metadata) } : {})
         }));
         jsonPre.textContent = JSON.stringify(formatted, null, 2);
       }
@@ -184,11 +226,25 @@ async function updateCardContent(card, index) {
     } catch (err) {
       jsonPre.textContent = 'Error loading tool calls: ' + err;
     }
+  } else if (activeTab === 'conversation') {
+    subtabsContainer.style.display = 'none';
+    try {
+      const timeline = await loadConversationTimeline(log.conversation_id);
+      jsonPre.textContent = JSON.stringify(timeline, null, 2);
+    } catch (err) {
+      jsonPre.textContent = 'Error loading conversation: ' + err;
+    }
   } else {
     subtabsContainer.style.display = '';
     let content;
     if (activeTab === 'data') {
-      content = activeSubtab === 'request' ? log.request_body : log.response_body;
+      try {
+        const bodies = await loadBodies(log);
+        content = activeSubtab === 'request' ? bodies.request_body : bodies.response_body;
+      } catch (err) {
+        jsonPre.textContent = 'Error loading bodies: ' + err;
+        return;
+      }
     } else {
       content = activeSubtab === 'request' ? log.request_headers : log.response_headers;
     }
@@ -204,8 +260,9 @@ async function copyLogData(index, tab) {
 
   if (tab === 'data') {
     let request, response;
-    try { request = JSON.parse(log.request_body || '{}'); } catch { request = log.request_body; }
-    try { response = JSON.parse(log.response_body || '{}'); } catch { response = log.response_body; }
+    const bodies = await loadBodies(log).catch(() => ({}));
+    try { request = JSON.parse(bodies.request_body || '{}'); } catch { request = bodies.request_body; }
+    try { response = JSON.parse(bodies.response_body || '{}'); } catch { response = bodies.response_body; }
     data = { request, response };
   } else if (tab === 'headers') {
     let request, response;
@@ -218,9 +275,17 @@ async function copyLogData(index, tab) {
       data = detections.map(d => ({
         pattern: d.pattern_name,
         type: d.pattern_type,
+        severity: d.severity,
+        direction: d.direction,
+        ...(d.action ? { action: d.action } : {}),
+        ...(d.confidence != null ? { confidence: d.confidence } : {}),
+        ...(d.verified ? { verified: d.verified } : {}),
         original: d.original_value,
+        ...(d.value_hash ? { value_hash: d.value_hash } : {}),
+        ...(d.occurrence_count > 1 ? { incident_occurrences: d.occurrence_count } : {}),
         replaced_with: d.placeholder,
-        message_index: d.message_index
+        message_index: d.message_index,
+        ...(d.metadata ? { metadata: JSON.parse(d.metadata) } : {})
       }));
     } catch {
       data = [];
@@ -236,6 +301,12 @@ async function copyLogData(index, tab) {
     } catch {
       data = [];
     }
+  } else if (tab === 'conversation') {
+    try {
+      data = await loadConversationTimeline(log.conversation_id);
+    } catch {
+      data = {};
+    }
   }
 
   navigator.clipboard.writeText(JSON.stringify(data, null, 2)).then(() => {
@@ -326,6 +397,11 @@ export async function loadMessageLogs() {
     attachCardHandlers(content);
     attachPaginationHandlers();
 
+    // Fill in each card's request body
+    await Promise.all(
+      [...content.querySelectorAll('.log-card')].map(card => updateCardContent(card, parseInt(card.dataset.index)))
+    );
+
     // Scroll to first search match if searching
     scrollToFirstHighlight(content);
   } catch (error) {
@@ -457,68 +533,40 @@ export function initLogsSearch() {
   searchBtn.addEventListener('click', performSearch);
 }
 
-// Export logs to JSONL file
+// Export logs matching the current filters to JSONL or CSV (picked by the file extension)
 export async function exportLogs() {
   const exportBtn = document.getElementById('logs-export-btn');
 
   try {
+    // Use Tauri dialog to pick the file; the backend writes it
+    const { save } = window.__TAURI__.dialog;
+    const filePath = await save({
+      defaultPath: `logs_export_${new Date().toISOString().slice(0, 10)}.jsonl`,
+      filters: [
+        { name: 'JSONL', extensions: ['jsonl'] },
+        { name: 'CSV', extensions: ['csv'] }
+      ]
+    });
+    if (!filePath) return;
+
     // Show loading state
     exportBtn.disabled = true;
     exportBtn.classList.add('loading');
 
-    // Fetch all logs matching current filters
-    const logs = await invoke('export_message_logs', {
+    const count = await invoke('export_message_logs', {
       timeRange: logsTimeRange,
       backend: logsBackend,
       model: logsModel,
       dlpAction: logsDlpAction,
-      search: logsSearch
+      search: logsSearch,
+      format: filePath.toLowerCase().endsWith('.csv') ? 'csv' : 'jsonl',
+      path: filePath
     });
 
-    if (logs.length === 0) {
+    if (count === 0) {
       alert('No logs to export with current filters.');
-      return;
-    }
-
-    // Convert to JSONL format
-    const jsonlContent = logs.map(log => {
-      // Parse request/response bodies if they're JSON strings
-      let requestBody = log.request_body;
-      let responseBody = log.response_body;
-
-      try {
-        if (requestBody) requestBody = JSON.parse(requestBody);
-      } catch {}
-      try {
-        if (responseBody) responseBody = JSON.parse(responseBody);
-      } catch {}
-
-      return JSON.stringify({
-        id: log.id,
-        timestamp: log.timestamp,
-        backend: log.backend,
-        model: log.model,
-        input_tokens: log.input_tokens,
-        output_tokens: log.output_tokens,
-        latency_ms: log.latency_ms,
-        dlp_action: log.dlp_action,
-        request: requestBody,
-        response: responseBody
-      });
-    }).join('\n');
-
-    // Use Tauri dialog to save file
-    const { save } = window.__TAURI__.dialog;
-    const filePath = await save({
-      defaultPath: `logs_export_${new Date().toISOString().slice(0, 10)}.jsonl`,
-      filters: [{ name: 'JSONL', extensions: ['jsonl'] }]
-    });
-
-    if (filePath) {
-      // Write file using Tauri fs
-      const { writeTextFile } = window.__TAURI__.fs;
-      await writeTextFile(filePath, jsonlContent);
-      alert(`Exported ${logs.length} logs to ${filePath}`);
+    } else {
+      alert(`Exported ${count} logs to ${filePath}`);
     }
   } catch (error) {
     console.error('Export failed:', error);
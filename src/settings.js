@@ -122,16 +122,15 @@ async function savePortSetting() {
     await invoke('save_port_setting', { port });
     setCurrentPort(port);
 
-    // Restart the proxy server
+    // Restart the proxy server; resolves once it listens on the new port
     showSettingsStatus('Restarting proxy server...', 'info');
     await invoke('restart_proxy');
 
-    // Wait for server to restart
-    await new Promise(resolve => setTimeout(resolve, 1500));
     updateProxyStatusDisplay(port, false);
     showSettingsStatus(`Proxy server now running on port ${port}`, 'success');
   } catch (error) {
-    updateProxyStatusDisplay(currentPort, false);
+    // A failed bind leaves the proxy down on the new port until it is changed again
+    loadProxyStatus();
     showSettingsStatus(`Failed: ${error}`, 'error');
   } finally {
     saveBtn.disabled = false;
@@ -274,6 +273,927 @@ function initDlpActionToggle() {
   loadDlpActionSetting();
 }
 
+// ============ DLP Severity Policy ============
+
+// Load severity -> action policy into the selects
+async function loadDlpSeverityPolicy() {
+  try {
+    const policy = await invoke('get_dlp_severity_policy');
+    document.querySelectorAll('.severity-policy-select').forEach(select => {
+      select.value = policy[select.dataset.severity] || '';
+    });
+  } catch (error) {
+    console.error('Failed to load DLP severity policy:', error);
+  }
+}
+
+// Save the whole policy whenever one severity changes
+async function saveDlpSeverityPolicy() {
+  const policy = {};
+  document.querySelectorAll('.severity-policy-select').forEach(select => {
+    if (select.value) policy[select.dataset.severity] = select.value;
+  });
+
+  try {
+    await invoke('save_dlp_severity_policy', { policy });
+    showSettingsStatus('Severity policy saved', 'success', 'dlp-severity-policy-status');
+  } catch (error) {
+    console.error('Failed to save DLP severity policy:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-severity-policy-status');
+    loadDlpSeverityPolicy();
+  }
+}
+
+// Initialize severity policy selects
+function initDlpSeverityPolicy() {
+  document.querySelectorAll('.severity-policy-select').forEach(select => {
+    select.addEventListener('change', saveDlpSeverityPolicy);
+  });
+
+  loadDlpSeverityPolicy();
+}
+
+// ============ Detection Incident Grouping ============
+
+// Initialize incident grouping window input
+async function initDlpIncidentWindow() {
+  const input = document.getElementById('dlp-incident-window');
+  if (!input) return;
+
+  input.addEventListener('change', async () => {
+    const minutes = parseInt(input.value) || 0;
+    try {
+      await invoke('save_dlp_incident_window', { minutes });
+      showSettingsStatus(
+        minutes > 0 ? 'Incident grouping window saved' : 'Incident grouping disabled',
+        'success',
+        'dlp-incident-window-status'
+      );
+    } catch (error) {
+      console.error('Failed to save incident grouping window:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-incident-window-status');
+    }
+  });
+
+  try {
+    input.value = await invoke('get_dlp_incident_window');
+  } catch (error) {
+    console.error('Failed to load incident grouping window:', error);
+  }
+}
+
+// ============ DLP Minimum Block Confidence ============
+
+// Initialize minimum block confidence input
+async function initDlpMinBlockConfidence() {
+  const input = document.getElementById('dlp-min-block-confidence');
+  if (!input) return;
+
+  input.addEventListener('change', async () => {
+    const confidence = parseFloat(input.value) || 0;
+    try {
+      await invoke('save_dlp_min_block_confidence', { confidence });
+      showSettingsStatus('Minimum block confidence saved', 'success', 'dlp-min-block-confidence-status');
+    } catch (error) {
+      console.error('Failed to save minimum block confidence:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-min-block-confidence-status');
+    }
+  });
+
+  try {
+    input.value = await invoke('get_dlp_min_block_confidence');
+  } catch (error) {
+    console.error('Failed to load minimum block confidence:', error);
+  }
+}
+
+// ============ Named-Entity Detection ============
+
+// Initialize NER toggle and minimum score input
+async function initDlpNerSettings() {
+  const toggle = document.getElementById('dlp-ner-toggle');
+  const minScore = document.getElementById('dlp-ner-min-score');
+  if (!toggle || !minScore) return;
+
+  const save = async () => {
+    const settings = { enabled: toggle.checked, min_score: parseFloat(minScore.value) || 0 };
+    try {
+      await invoke('save_dlp_ner_settings', { settings });
+      showSettingsStatus(
+        settings.enabled ? 'Named-entity detection enabled' : 'Named-entity detection disabled',
+        'success',
+        'dlp-ner-status'
+      );
+    } catch (error) {
+      console.error('Failed to save NER settings:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-ner-status');
+    }
+  };
+  toggle.addEventListener('change', save);
+  minScore.addEventListener('change', save);
+
+  try {
+    const status = await invoke('get_dlp_ner_settings');
+    toggle.checked = status.enabled;
+    minScore.value = status.min_score;
+    if (!status.available) {
+      document.getElementById('dlp-ner-description').textContent +=
+        ' This build has no NER support (build with the "ner" feature).';
+    } else {
+      minScore.title = `Minimum model score. Model files: ${status.model_dir}`;
+    }
+  } catch (error) {
+    console.error('Failed to load NER settings:', error);
+  }
+}
+
+// ============ DLP Response Scanning ============
+
+// Initialize response scanning toggle
+async function initDlpScanResponsesToggle() {
+  const toggle = document.getElementById('dlp-scan-responses-toggle');
+  if (!toggle) return;
+
+  toggle.addEventListener('change', async () => {
+    try {
+      await invoke('save_dlp_scan_responses_setting', { enabled: toggle.checked });
+      showSettingsStatus(
+        toggle.checked ? 'Model responses will be scanned for sensitive data' : 'Response scanning disabled',
+        'success',
+        'dlp-scan-responses-status'
+      );
+    } catch (error) {
+      console.error('Failed to save response scanning setting:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-scan-responses-status');
+      toggle.checked = !toggle.checked;
+    }
+  });
+
+  try {
+    toggle.checked = await invoke('get_dlp_scan_responses_setting');
+  } catch (error) {
+    console.error('Failed to load response scanning setting:', error);
+  }
+}
+
+// ============ Secret Verification ============
+
+// Initialize live key verification toggle
+async function initDlpVerifySecretsToggle() {
+  const toggle = document.getElementById('dlp-verify-secrets-toggle');
+  if (!toggle) return;
+
+  toggle.addEventListener('change', async () => {
+    try {
+      await invoke('save_dlp_verify_secrets_setting', { enabled: toggle.checked });
+      showSettingsStatus(
+        toggle.checked ? 'Detected keys will be verified with their provider' : 'Key verification disabled',
+        'success',
+        'dlp-verify-secrets-status'
+      );
+    } catch (error) {
+      console.error('Failed to save key verification setting:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-verify-secrets-status');
+      toggle.checked = !toggle.checked;
+    }
+  });
+
+  try {
+    toggle.checked = await invoke('get_dlp_verify_secrets_setting');
+  } catch (error) {
+    console.error('Failed to load key verification setting:', error);
+  }
+}
+
+// ============ Hashed Value Storage ============
+
+// Initialize hashed value storage toggle
+async function initDlpHashValuesToggle() {
+  const toggle = document.getElementById('dlp-hash-values-toggle');
+  if (!toggle) return;
+
+  toggle.addEventListener('change', async () => {
+    try {
+      await invoke('save_dlp_hash_values_setting', { enabled: toggle.checked });
+      showSettingsStatus(
+        toggle.checked ? 'Detected values are now stored hashed' : 'New detections will store plaintext values',
+        'success',
+        'dlp-hash-values-status'
+      );
+    } catch (error) {
+      console.error('Failed to save hashed value setting:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-hash-values-status');
+      toggle.checked = !toggle.checked;
+    }
+  });
+
+  try {
+    toggle.checked = await invoke('get_dlp_hash_values_setting');
+  } catch (error) {
+    console.error('Failed to load hashed value setting:', error);
+  }
+}
+
+// ============ Canary Tokens ============
+
+// Initialize canary token toggle
+async function initDlpCanaryToggle() {
+  const toggle = document.getElementById('dlp-canary-toggle');
+  if (!toggle) return;
+
+  toggle.addEventListener('change', async () => {
+    try {
+      await invoke('save_dlp_canary_setting', { enabled: toggle.checked });
+      showSettingsStatus(
+        toggle.checked ? 'Redacted requests will carry a canary token' : 'Canary tokens disabled',
+        'success',
+        'dlp-canary-status'
+      );
+    } catch (error) {
+      console.error('Failed to save canary setting:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-canary-status');
+      toggle.checked = !toggle.checked;
+    }
+  });
+
+  try {
+    toggle.checked = await invoke('get_dlp_canary_setting');
+  } catch (error) {
+    console.error('Failed to load canary setting:', error);
+  }
+}
+
+// ============ Prompt Injection Detection ============
+
+// Initialize prompt-injection detection toggle
+async function initDlpDetectInjectionToggle() {
+  const toggle = document.getElementById('dlp-detect-injection-toggle');
+  if (!toggle) return;
+
+  toggle.addEventListener('change', async () => {
+    try {
+      await invoke('save_dlp_detect_injection_setting', { enabled: toggle.checked });
+      showSettingsStatus(
+        toggle.checked ? 'Prompt-injection detection enabled' : 'Prompt-injection detection disabled',
+        'success',
+        'dlp-detect-injection-status'
+      );
+    } catch (error) {
+      console.error('Failed to save prompt-injection setting:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-detect-injection-status');
+      toggle.checked = !toggle.checked;
+    }
+  });
+
+  try {
+    toggle.checked = await invoke('get_dlp_detect_injection_setting');
+  } catch (error) {
+    console.error('Failed to load prompt-injection setting:', error);
+  }
+}
+
+// ============ Detection Webhooks ============
+
+// Initialize webhook toggle, severity threshold and URL list
+async function initDlpWebhookSettings() {
+  const toggle = document.getElementById('dlp-webhook-toggle');
+  const minSeverity = document.getElementById('dlp-webhook-min-severity');
+  const urlsInput = document.getElementById('dlp-webhook-urls');
+  const saveBtn = document.getElementById('dlp-webhook-save-btn');
+  const testBtn = document.getElementById('dlp-webhook-test-btn');
+  if (!toggle || !minSeverity || !urlsInput) return;
+
+  const readUrls = () => urlsInput.value.split('\n').map(u => u.trim()).filter(Boolean);
+
+  const save = async () => {
+    const settings = { enabled: toggle.checked, min_severity: minSeverity.value, urls: readUrls() };
+    try {
+      await invoke('save_dlp_webhook_settings', { settings });
+      showSettingsStatus(
+        settings.enabled ? 'Detection webhooks enabled' : 'Detection webhooks disabled',
+        'success',
+        'dlp-webhook-status'
+      );
+      return true;
+    } catch (error) {
+      console.error('Failed to save webhook settings:', error);
+      showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-webhook-status');
+      return false;
+    }
+  };
+
+  toggle.addEventListener('change', async () => {
+    if (!(await save())) toggle.checked = !toggle.checked;
+  });
+  minSeverity.addEventListener('change', save);
+  saveBtn?.addEventListener('click', save);
+
+  testBtn?.addEventListener('click', async () => {
+    const urls = readUrls();
+    if (urls.length === 0) {
+      showSettingsStatus('Add a webhook URL first', 'error', 'dlp-webhook-status');
+      return;
+    }
+    testBtn.disabled = true;
+    const failures = [];
+    for (const url of urls) {
+      try {
+        await invoke('test_dlp_webhook', { url });
+      } catch (error) {
+        failures.push(String(error));
+      }
+    }
+    testBtn.disabled = false;
+    if (failures.length === 0) {
+      showSettingsStatus(`Test event delivered to ${urls.length} URL(s)`, 'success', 'dlp-webhook-status');
+    } else {
+      showSettingsStatus(`Test failed: ${failures.join('; ')}`, 'error', 'dlp-webhook-status');
+    }
+  });
+
+  try {
+    const settings = await invoke('get_dlp_webhook_settings');
+    toggle.checked = settings.enabled;
+    minSeverity.value = settings.min_severity;
+    urlsInput.value = settings.urls.join('\n');
+  } catch (error) {
+    console.error('Failed to load webhook settings:', error);
+  }
+}
+
+// ============ Proxy Body Limits ============
+
+// Sizes are edited in MB and stored in bytes
+async function loadProxyBodyLimits() {
+  try {
+    const limits = await invoke('get_proxy_body_limits');
+    document.getElementById('proxy-max-request-mb').value = Math.round(limits.max_request_bytes / (1024 * 1024));
+    document.getElementById('proxy-max-response-mb').value = Math.round(limits.max_response_bytes / (1024 * 1024));
+  } catch (error) {
+    console.error('Failed to load body size limits:', error);
+  }
+}
+
+async function saveProxyBodyLimits() {
+  const readMb = id => Math.max(0, parseInt(document.getElementById(id).value, 10) || 0) * 1024 * 1024;
+  const limits = {
+    max_request_bytes: readMb('proxy-max-request-mb'),
+    max_response_bytes: readMb('proxy-max-response-mb'),
+  };
+
+  try {
+    await invoke('save_proxy_body_limits', { limits });
+    showSettingsStatus('Body size limits saved', 'success', 'proxy-body-limits-status');
+  } catch (error) {
+    console.error('Failed to save body size limits:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'proxy-body-limits-status');
+    loadProxyBodyLimits();
+  }
+}
+
+// Initialize body size limit inputs
+function initProxyBodyLimits() {
+  document.querySelectorAll('.proxy-body-limit').forEach(input => {
+    input.addEventListener('change', saveProxyBodyLimits);
+  });
+
+  loadProxyBodyLimits();
+}
+
+// ============ Proxy Bind Addresses ============
+
+async function loadProxyBindAddresses() {
+  try {
+    const addresses = await invoke('get_proxy_bind_addresses');
+    document.getElementById('proxy-bind-gateway').value = addresses.gateway;
+    document.getElementById('proxy-bind-backend-listeners').value = addresses.backend_listeners;
+  } catch (error) {
+    console.error('Failed to load bind addresses:', error);
+  }
+}
+
+async function saveProxyBindAddresses() {
+  const addresses = {
+    gateway: document.getElementById('proxy-bind-gateway').value.trim(),
+    backend_listeners: document.getElementById('proxy-bind-backend-listeners').value.trim(),
+  };
+
+  try {
+    await invoke('save_proxy_bind_addresses', { addresses });
+    showSettingsStatus('Listen addresses saved. Proxy restarting...', 'success', 'proxy-bind-status');
+  } catch (error) {
+    console.error('Failed to save bind addresses:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'proxy-bind-status');
+  }
+}
+
+// Initialize bind address inputs
+function initProxyBindAddresses() {
+  document.getElementById('save-bind-addresses-btn').addEventListener('click', saveProxyBindAddresses);
+  loadProxyBindAddresses();
+}
+
+// ============ Token Budgets ============
+
+function describeBudget(budget) {
+  const period = budget.period === 'weekly' ? 'Weekly' : 'Daily';
+  if (budget.scope === 'backend') return `${period}, backend ${escapeHtml(budget.target)}`;
+  if (budget.scope === 'user') return `${period}, ${escapeHtml(budget.target)}`;
+  return `${period}, all requests`;
+}
+
+async function loadBudgets() {
+  const list = document.getElementById('budget-list');
+  try {
+    const budgets = await invoke('get_token_budgets');
+    list.innerHTML = budgets.map(b => {
+      const percent = Math.round((b.used_tokens / b.limit_tokens) * 100);
+      return `
+        <div class="budget-item ${b.used_tokens >= b.limit_tokens ? 'exhausted' : ''}">
+          <span>${describeBudget(b)}</span>
+          <span class="budget-usage">${b.used_tokens.toLocaleString()} / ${b.limit_tokens.toLocaleString()} tokens (${percent}%)</span>
+          <button type="button" class="btn btn-secondary btn-sm budget-remove" data-id="${b.id}">Remove</button>
+        </div>
+      `;
+    }).join('');
+    list.querySelectorAll('.budget-remove').forEach(btn => {
+      btn.addEventListener('click', () => removeBudget(parseInt(btn.dataset.id)));
+    });
+  } catch (error) {
+    console.error('Failed to load token budgets:', error);
+  }
+}
+
+async function addBudget() {
+  const budget = {
+    scope: document.getElementById('budget-scope').value,
+    target: document.getElementById('budget-target').value.trim(),
+    period: document.getElementById('budget-period').value,
+    limit_tokens: parseInt(document.getElementById('budget-limit').value, 10) || 0,
+    warn_percent: Math.min(100, Math.max(0, parseInt(document.getElementById('budget-warn').value, 10) || 0)),
+  };
+
+  try {
+    await invoke('add_token_budget', { budget });
+    document.getElementById('budget-limit').value = '';
+    showSettingsStatus('Budget added', 'success', 'budget-status');
+    loadBudgets();
+  } catch (error) {
+    console.error('Failed to add token budget:', error);
+    showSettingsStatus(`Failed to add: ${error}`, 'error', 'budget-status');
+  }
+}
+
+async function removeBudget(id) {
+  try {
+    await invoke('delete_token_budget', { id });
+    loadBudgets();
+  } catch (error) {
+    console.error('Failed to remove token budget:', error);
+    showSettingsStatus(`Failed to remove: ${error}`, 'error', 'budget-status');
+  }
+}
+
+// Initialize token budget controls
+function initBudgets() {
+  const scope = document.getElementById('budget-scope');
+  const target = document.getElementById('budget-target');
+  scope.addEventListener('change', () => {
+    target.disabled = scope.value === 'global';
+    target.placeholder = scope.value === 'user' ? 'e.g., dev@example.com' : 'e.g., claude';
+    if (target.disabled) target.value = '';
+  });
+  document.getElementById('add-budget-btn').addEventListener('click', addBudget);
+
+  loadBudgets();
+}
+
+// ============ Model Pricing ============
+
+// Id of the price being edited (0 adds a new one)
+let editingPriceId = 0;
+
+const PRICING_FIELDS = {
+  input_per_mtok: 'pricing-input',
+  output_per_mtok: 'pricing-output',
+  cache_read_per_mtok: 'pricing-cache-read',
+  cache_write_per_mtok: 'pricing-cache-write',
+};
+
+function describePrice(price) {
+  const usd = (value) => `$${value.toFixed(2)}`;
+  return `${usd(price.input_per_mtok)} in / ${usd(price.output_per_mtok)} out / ${usd(price.cache_read_per_mtok)} cache read / ${usd(price.cache_write_per_mtok)} cache write`;
+}
+
+function clearPricingForm() {
+  editingPriceId = 0;
+  document.getElementById('pricing-model').value = '';
+  Object.values(PRICING_FIELDS).forEach(id => { document.getElementById(id).value = ''; });
+}
+
+async function loadPricing() {
+  const list = document.getElementById('pricing-list');
+  try {
+    const prices = await invoke('get_model_pricing');
+    list.innerHTML = prices.map(p => `
+      <div class="budget-item">
+        <span>${escapeHtml(p.model)}</span>
+        <span class="budget-usage">${describePrice(p)}</span>
+        <span>
+          <button type="button" class="btn btn-secondary btn-sm pricing-edit" data-id="${p.id}">Edit</button>
+          <button type="button" class="btn btn-secondary btn-sm pricing-remove" data-id="${p.id}">Remove</button>
+        </span>
+      </div>
+    `).join('');
+    list.querySelectorAll('.pricing-edit').forEach(btn => {
+      btn.addEventListener('click', () => {
+        const price = prices.find(p => p.id === parseInt(btn.dataset.id));
+        editingPriceId = price.id;
+        document.getElementById('pricing-model').value = price.model;
+        Object.entries(PRICING_FIELDS).forEach(([key, id]) => { document.getElementById(id).value = price[key]; });
+      });
+    });
+    list.querySelectorAll('.pricing-remove').forEach(btn => {
+      btn.addEventListener('click', () => removePrice(parseInt(btn.dataset.id)));
+    });
+  } catch (error) {
+    console.error('Failed to load model pricing:', error);
+  }
+}
+
+async function savePrice() {
+  const price = { id: editingPriceId, model: document.getElementById('pricing-model').value.trim() };
+  Object.entries(PRICING_FIELDS).forEach(([key, id]) => {
+    price[key] = parseFloat(document.getElementById(id).value) || 0;
+  });
+
+  try {
+    await invoke('save_model_price', { price });
+    clearPricingForm();
+    showSettingsStatus('Price saved', 'success', 'pricing-status');
+    loadPricing();
+  } catch (error) {
+    console.error('Failed to save model price:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'pricing-status');
+  }
+}
+
+async function removePrice(id) {
+  try {
+    await invoke('delete_model_price', { id });
+    if (editingPriceId === id) clearPricingForm();
+    loadPricing();
+  } catch (error) {
+    console.error('Failed to remove model price:', error);
+    showSettingsStatus(`Failed to remove: ${error}`, 'error', 'pricing-status');
+  }
+}
+
+async function restoreDefaultPricing() {
+  try {
+    await invoke('restore_default_model_pricing');
+    clearPricingForm();
+    showSettingsStatus('Default prices restored', 'success', 'pricing-status');
+    loadPricing();
+  } catch (error) {
+    console.error('Failed to restore default pricing:', error);
+    showSettingsStatus(`Failed to restore: ${error}`, 'error', 'pricing-status');
+  }
+}
+
+// Initialize model pricing controls
+function initPricing() {
+  document.getElementById('save-pricing-btn').addEventListener('click', savePrice);
+  document.getElementById('restore-pricing-btn').addEventListener('click', restoreDefaultPricing);
+
+  loadPricing();
+}
+
+// ============ Data Retention ============
+
+function formatMegabytes(bytes) {
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
+async function loadRetentionSettings() {
+  try {
+    const settings = await invoke('get_retention_settings');
+    document.getElementById('retention-days').value = settings.max_age_days;
+    document.getElementById('retention-max-requests').value = settings.max_requests;
+    document.getElementById('retention-max-db-mb').value = settings.max_db_mb;
+    document.getElementById('retention-store-bodies').checked = settings.store_bodies;
+    document.getElementById('retention-max-body-kb').value = settings.max_body_kb;
+  } catch (error) {
+    console.error('Failed to load retention settings:', error);
+  }
+}
+
+async function saveRetentionSettings() {
+  const settings = {
+    max_age_days: parseInt(document.getElementById('retention-days').value, 10) || 0,
+    max_requests: parseInt(document.getElementById('retention-max-requests').value, 10) || 0,
+    max_db_mb: parseInt(document.getElementById('retention-max-db-mb').value, 10) || 0,
+    store_bodies: document.getElementById('retention-store-bodies').checked,
+    max_body_kb: parseInt(document.getElementById('retention-max-body-kb').value, 10) || 0,
+  };
+
+  try {
+    const report = await invoke('save_retention_settings', { settings });
+    const deleted = report.deleted_requests > 0 ? `, ${report.deleted_requests.toLocaleString()} old requests deleted` : '';
+    showSettingsStatus(`Retention settings saved${deleted}`, 'success', 'retention-status');
+  } catch (error) {
+    console.error('Failed to save retention settings:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'retention-status');
+    loadRetentionSettings();
+  }
+}
+
+async function runRetentionCleanup() {
+  const button = document.getElementById('run-retention-btn');
+  button.disabled = true;
+  try {
+    const report = await invoke('run_retention_cleanup');
+    const reclaimed = Math.max(0, report.db_bytes_before - report.db_bytes_after);
+    showSettingsStatus(
+      `Deleted ${report.deleted_requests.toLocaleString()} requests, reclaimed ${formatMegabytes(reclaimed)} (database is ${formatMegabytes(report.db_bytes_after)})`,
+      'success',
+      'retention-status'
+    );
+  } catch (error) {
+    console.error('Failed to clean up old data:', error);
+    showSettingsStatus(`Failed to clean up: ${error}`, 'error', 'retention-status');
+  } finally {
+    button.disabled = false;
+  }
+}
+
+async function repairDatabase() {
+  const button = document.getElementById('repair-db-btn');
+  button.disabled = true;
+  try {
+    const report = await invoke('check_database_orphans', { repair: true });
+    const fixed = report.orphans.filter(o => o.rows > 0);
+    const message = fixed.length === 0
+      ? 'No orphaned rows found'
+      : `Removed orphaned rows: ${fixed.map(o => `${o.table} (${o.rows.toLocaleString()})`).join(', ')}`;
+    showSettingsStatus(message, 'success', 'retention-status');
+  } catch (error) {
+    console.error('Failed to repair the database:', error);
+    showSettingsStatus(`Failed to repair: ${error}`, 'error', 'retention-status');
+  } finally {
+    button.disabled = false;
+  }
+}
+
+// Initialize data retention controls
+function initRetention() {
+  document.getElementById('save-retention-btn').addEventListener('click', saveRetentionSettings);
+  document.getElementById('run-retention-btn').addEventListener('click', runRetentionCleanup);
+  document.getElementById('repair-db-btn').addEventListener('click', repairDatabase);
+  document.getElementById('retention-store-bodies').addEventListener('change', saveRetentionSettings);
+  document.getElementById('retention-max-body-kb').addEventListener('change', saveRetentionSettings);
+
+  loadRetentionSettings();
+}
+
+// ============ Database Encryption ============
+
+function renderDatabaseEncryption(status) {
+  const button = document.getElementById('enable-db-encryption-btn');
+  const description = document.getElementById('db-encryption-description');
+  if (!status.available) {
+    button.disabled = true;
+    description.textContent = 'This build doesn\'t include database encryption (build with the sqlcipher feature).';
+  } else if (status.encrypted) {
+    button.disabled = true;
+    button.textContent = 'Encrypted';
+    description.textContent = 'The request log is encrypted at rest (SQLCipher), with the key kept in the OS keychain.';
+  } else if (status.pending) {
+    button.disabled = true;
+    button.textContent = 'Restart to Encrypt';
+  }
+}
+
+async function loadDatabaseEncryption() {
+  try {
+    renderDatabaseEncryption(await invoke('get_database_encryption_status'));
+  } catch (error) {
+    console.error('Failed to load database encryption status:', error);
+  }
+}
+
+async function enableDatabaseEncryption() {
+  try {
+    renderDatabaseEncryption(await invoke('enable_database_encryption'));
+    showSettingsStatus('Encryption enabled; the database is encrypted the next time the app starts', 'success', 'db-encryption-status');
+  } catch (error) {
+    console.error('Failed to enable database encryption:', error);
+    showSettingsStatus(`Failed to enable encryption: ${error}`, 'error', 'db-encryption-status');
+  }
+}
+
+// Initialize database encryption control
+function initDatabaseEncryption() {
+  document.getElementById('enable-db-encryption-btn').addEventListener('click', enableDatabaseEncryption);
+  loadDatabaseEncryption();
+}
+
+// ============ Proxy Client Token ============
+
+function fillProxyClientAuth(auth) {
+  document.getElementById('proxy-require-token-toggle').checked = auth.require_token;
+  document.getElementById('proxy-allow-loopback-toggle').checked = auth.allow_loopback;
+  document.getElementById('proxy-client-token').value = auth.token;
+}
+
+async function loadProxyClientAuth() {
+  try {
+    fillProxyClientAuth(await invoke('get_proxy_client_auth'));
+  } catch (error) {
+    console.error('Failed to load client token settings:', error);
+  }
+}
+
+async function saveProxyClientAuth() {
+  try {
+    const auth = await invoke('save_proxy_client_auth', {
+      requireToken: document.getElementById('proxy-require-token-toggle').checked,
+      allowLoopback: document.getElementById('proxy-allow-loopback-toggle').checked,
+    });
+    fillProxyClientAuth(auth);
+    showSettingsStatus('Client token settings saved', 'success', 'proxy-client-auth-status');
+  } catch (error) {
+    console.error('Failed to save client token settings:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'proxy-client-auth-status');
+    loadProxyClientAuth();
+  }
+}
+
+async function regenerateProxyClientToken() {
+  if (!confirm('Clients using the current token will be rejected until they get the new one. Continue?')) {
+    return;
+  }
+  try {
+    fillProxyClientAuth(await invoke('regenerate_proxy_client_token'));
+    showSettingsStatus('New token generated', 'success', 'proxy-client-auth-status');
+  } catch (error) {
+    console.error('Failed to generate client token:', error);
+    showSettingsStatus(`Failed to generate: ${error}`, 'error', 'proxy-client-auth-status');
+  }
+}
+
+// Initialize client token controls
+function initProxyClientAuth() {
+  document.getElementById('proxy-require-token-toggle').addEventListener('change', saveProxyClientAuth);
+  document.getElementById('proxy-allow-loopback-toggle').addEventListener('change', saveProxyClientAuth);
+  document.getElementById('proxy-regenerate-token-btn').addEventListener('click', regenerateProxyClientToken);
+  document.getElementById('proxy-copy-token-btn').addEventListener('click', () => {
+    const token = document.getElementById('proxy-client-token').value;
+    if (token) {
+      navigator.clipboard.writeText(token).then(() => {
+        showSettingsStatus('Token copied', 'success', 'proxy-client-auth-status');
+      });
+    }
+  });
+
+  loadProxyClientAuth();
+}
+
+// ============ Response Cache ============
+
+async function loadResponseCacheSettings() {
+  try {
+    const settings = await invoke('get_response_cache_settings');
+    document.getElementById('response-cache-toggle').checked = settings.enabled;
+    document.getElementById('response-cache-ttl').value = settings.ttl_secs;
+  } catch (error) {
+    console.error('Failed to load response cache settings:', error);
+  }
+}
+
+async function saveResponseCacheSettings() {
+  const settings = {
+    enabled: document.getElementById('response-cache-toggle').checked,
+    ttl_secs: parseInt(document.getElementById('response-cache-ttl').value, 10) || 0,
+  };
+
+  try {
+    await invoke('save_response_cache_settings', { settings });
+    showSettingsStatus('Response cache settings saved', 'success', 'response-cache-status');
+  } catch (error) {
+    console.error('Failed to save response cache settings:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'response-cache-status');
+    loadResponseCacheSettings();
+  }
+}
+
+async function clearResponseCache() {
+  try {
+    await invoke('clear_response_cache');
+    showSettingsStatus('Response cache cleared', 'success', 'response-cache-status');
+  } catch (error) {
+    console.error('Failed to clear response cache:', error);
+    showSettingsStatus(`Failed to clear: ${error}`, 'error', 'response-cache-status');
+  }
+}
+
+// Initialize response cache controls
+function initResponseCache() {
+  document.getElementById('response-cache-toggle').addEventListener('change', saveResponseCacheSettings);
+  document.getElementById('response-cache-ttl').addEventListener('change', saveResponseCacheSettings);
+  document.getElementById('clear-response-cache-btn').addEventListener('click', clearResponseCache);
+
+  loadResponseCacheSettings();
+}
+
+// ============ Corporate Proxy ============
+
+async function loadUpstreamProxySettings() {
+  try {
+    const settings = await invoke('get_upstream_proxy_settings');
+    document.getElementById('upstream-proxy-toggle').checked = settings.enabled;
+    document.getElementById('upstream-proxy-url').value = settings.url;
+    document.getElementById('upstream-proxy-username').value = settings.username;
+    document.getElementById('upstream-proxy-no-proxy').value = settings.no_proxy;
+    const passwordInput = document.getElementById('upstream-proxy-password');
+    passwordInput.value = '';
+    passwordInput.placeholder = settings.has_password ? 'Stored in keychain' : 'Optional';
+  } catch (error) {
+    console.error('Failed to load corporate proxy settings:', error);
+  }
+}
+
+// An empty password field keeps the stored password; removePassword clears it
+async function saveUpstreamProxySettings(removePassword = false) {
+  const settings = {
+    enabled: document.getElementById('upstream-proxy-toggle').checked,
+    url: document.getElementById('upstream-proxy-url').value.trim(),
+    username: document.getElementById('upstream-proxy-username').value.trim(),
+    no_proxy: document.getElementById('upstream-proxy-no-proxy').value.trim(),
+  };
+  const typed = document.getElementById('upstream-proxy-password').value;
+  const password = removePassword ? '' : (typed || null);
+
+  try {
+    await invoke('save_upstream_proxy_settings', { settings, password });
+    showSettingsStatus(removePassword ? 'Proxy password removed' : 'Corporate proxy settings saved', 'success', 'upstream-proxy-status');
+    loadUpstreamProxySettings();
+  } catch (error) {
+    console.error('Failed to save corporate proxy settings:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'upstream-proxy-status');
+  }
+}
+
+// Initialize corporate proxy controls
+function initUpstreamProxy() {
+  document.getElementById('upstream-proxy-toggle').addEventListener('change', () => saveUpstreamProxySettings());
+  document.getElementById('save-upstream-proxy-btn').addEventListener('click', () => saveUpstreamProxySettings());
+  document.getElementById('remove-upstream-proxy-password-btn').addEventListener('click', () => saveUpstreamProxySettings(true));
+
+  loadUpstreamProxySettings();
+}
+
+// ============ DLP Scan Limits ============
+
+// Sizes are edited in KB and stored in bytes
+async function loadDlpScanLimits() {
+  try {
+    const limits = await invoke('get_dlp_scan_limits');
+    document.getElementById('dlp-max-body-kb').value = Math.round(limits.max_body_bytes / 1024);
+    document.getElementById('dlp-max-value-kb').value = Math.round(limits.max_value_bytes / 1024);
+    document.getElementById('dlp-time-budget-ms').value = limits.time_budget_ms;
+  } catch (error) {
+    console.error('Failed to load DLP scan limits:', error);
+  }
+}
+
+async function saveDlpScanLimits() {
+  const readNumber = id => Math.max(0, parseInt(document.getElementById(id).value, 10) || 0);
+  const limits = {
+    max_body_bytes: readNumber('dlp-max-body-kb') * 1024,
+    max_value_bytes: readNumber('dlp-max-value-kb') * 1024,
+    time_budget_ms: readNumber('dlp-time-budget-ms'),
+  };
+
+  try {
+    await invoke('save_dlp_scan_limits', { limits });
+    showSettingsStatus('Scan limits saved', 'success', 'dlp-scan-limits-status');
+  } catch (error) {
+    console.error('Failed to save DLP scan limits:', error);
+    showSettingsStatus(`Failed to save: ${error}`, 'error', 'dlp-scan-limits-status');
+    loadDlpScanLimits();
+  }
+}
+
+// Initialize scan limit inputs
+function initDlpScanLimits() {
+  document.querySelectorAll('.dlp-scan-limit').forEach(input => {
+    input.addEventListener('change', saveDlpScanLimits);
+  });
+
+  loadDlpScanLimits();
+}
+
 // ============ DLP Settings ============
 
 // Store patterns for editing
@@ -285,6 +1205,8 @@ async function loadDlpSettings() {
     const settings = await invoke('get_dlp_settings');
     dlpPatterns = settings.patterns || [];
     renderPatterns(dlpPatterns);
+    renderPolicyPatternOptions(dlpPatterns);
+    await loadDlpPolicies();
   } catch (error) {
     console.error('Failed to load DLP settings:', error);
     const container = document.getElementById('dlp-patterns');
@@ -309,8 +1231,17 @@ function renderPatterns(patterns) {
       <input type="checkbox" class="dlp-checkbox dlp-pattern-toggle" data-id="${pattern.id}" ${pattern.enabled ? 'checked' : ''} />
       <span class="dlp-pattern-name">${escapeHtml(pattern.name)}</span>
       <span class="dlp-pattern-badge ${pattern.is_builtin ? 'builtin' : pattern.pattern_type}">${pattern.is_builtin ? 'Built-in' : pattern.pattern_type}</span>
+      ${pattern.category === 'pii' ? '<span class="dlp-pattern-badge pii">PII</span>' : ''}
+      ${pattern.category === 'entity' ? '<span class="dlp-pattern-badge entity">Entity</span>' : ''}
+      ${pattern.scope === 'path' ? '<span class="dlp-pattern-badge scope">Path</span>' : ''}
+      ${pattern.scope === 'header' ? '<span class="dlp-pattern-badge scope">Header</span>' : ''}
+      ${pattern.normalize ? '<span class="dlp-pattern-meta">Unicode folded</span>' : ''}
+      <span class="dlp-pattern-badge severity-${pattern.severity}">${pattern.severity}</span>
       ${pattern.min_unique_chars > 0 ? `<span class="dlp-pattern-meta">Unique chars >= ${pattern.min_unique_chars}</span>` : ''}
+      ${pattern.min_entropy > 0 ? `<span class="dlp-pattern-meta">Entropy >= ${pattern.min_entropy}</span>` : ''}
       <span class="dlp-pattern-meta">Occurrence >= ${pattern.min_occurrences}</span>
+      ${pattern.validator ? `<span class="dlp-pattern-meta">Validator: ${escapeHtml(pattern.validator)}</span>` : ''}
+      ${pattern.action ? `<span class="dlp-pattern-meta">Action: ${escapeHtml(pattern.action)}</span>` : ''}
       <div class="dlp-pattern-actions">
         <button class="dlp-pattern-edit" data-id="${pattern.id}" title="Edit pattern">
           <i data-lucide="pencil"></i>
@@ -368,6 +1299,104 @@ function renderPatterns(patterns) {
   });
 }
 
+// ============ DLP Policies ============
+
+// Fill the policy form's pattern select
+function renderPolicyPatternOptions(patterns) {
+  const select = document.getElementById('dlp-policy-pattern');
+  if (!select) return;
+  const selected = select.value;
+  select.innerHTML = patterns.map(pattern =>
+    `<option value="${pattern.id}">${escapeHtml(pattern.name)}</option>`
+  ).join('');
+  if (selected) select.value = selected;
+}
+
+// Fill the policy form's backend select with predefined and custom backends
+async function loadPolicyBackendOptions() {
+  const select = document.getElementById('dlp-policy-backend');
+  if (!select) return;
+  try {
+    const [predefined, custom] = await Promise.all([
+      invoke('get_predefined_backends'),
+      invoke('get_custom_backends'),
+    ]);
+    const names = [...predefined, ...custom].map(b => b.name);
+    select.innerHTML = '<option value="*">Any</option>' + names.map(name =>
+      `<option value="${escapeHtml(name)}">${escapeHtml(name)}</option>`
+    ).join('');
+  } catch (error) {
+    console.error('Failed to load backends for policies:', error);
+  }
+}
+
+async function loadDlpPolicies() {
+  try {
+    const policies = await invoke('get_dlp_policies');
+    renderPolicies(policies);
+  } catch (error) {
+    console.error('Failed to load DLP policies:', error);
+  }
+}
+
+function renderPolicies(policies) {
+  const container = document.getElementById('dlp-policies');
+  if (!container) return;
+
+  if (policies.length === 0) {
+    container.innerHTML = '<p class="empty-text">No policies configured</p>';
+    return;
+  }
+
+  const patternName = (id) => dlpPatterns.find(p => p.id === id)?.name || `Pattern #${id}`;
+  container.innerHTML = policies.map(policy => `
+    <div class="dlp-pattern-item" data-id="${policy.id}">
+      <span class="dlp-pattern-name">${escapeHtml(patternName(policy.pattern_id))}</span>
+      <span class="dlp-pattern-meta">Backend: ${policy.backend === '*' ? 'Any' : escapeHtml(policy.backend)}</span>
+      <span class="dlp-pattern-meta">Model: ${policy.model_glob === '*' ? 'Any' : escapeHtml(policy.model_glob)}</span>
+      <span class="dlp-pattern-meta">Action: ${escapeHtml(policy.action)}</span>
+      <div class="dlp-pattern-actions">
+        <button class="dlp-policy-delete" data-id="${policy.id}" title="Delete policy">
+          <i data-lucide="trash-2"></i>
+        </button>
+      </div>
+    </div>
+  `).join('');
+
+  lucide.createIcons();
+
+  container.querySelectorAll('.dlp-policy-delete').forEach(btn => {
+    btn.addEventListener('click', async (e) => {
+      e.stopPropagation();
+      try {
+        await invoke('delete_dlp_policy', { id: parseInt(btn.dataset.id) });
+        await loadDlpPolicies();
+      } catch (error) {
+        showSettingsStatus('Failed to delete policy: ' + error, 'error', 'dlp-policies-status');
+      }
+    });
+  });
+}
+
+async function addDlpPolicy() {
+  const patternId = parseInt(document.getElementById('dlp-policy-pattern').value);
+  if (isNaN(patternId)) return;
+
+  try {
+    await invoke('add_dlp_policy', {
+      patternId,
+      backend: document.getElementById('dlp-policy-backend').value,
+      modelGlob: document.getElementById('dlp-policy-model').value,
+      action: document.getElementById('dlp-policy-action').value,
+    });
+    document.getElementById('dlp-policy-model').value = '';
+    await loadDlpPolicies();
+    showSettingsStatus('Policy added', 'success', 'dlp-policies-status');
+  } catch (error) {
+    showSettingsStatus('Failed to add policy: ' + error, 'error', 'dlp-policies-status');
+  }
+}
+
 // Show pattern modal (add or edit)
 function showPatternModal(pattern = null) {
   const modal = document.getElementById('pattern-modal');
@@ -391,12 +1420,20 @@ function showPatternModal(pattern = null) {
 
   // Validation
   document.getElementById('min-unique-chars').value = pattern?.min_unique_chars || 0;
+  document.getElementById('min-entropy').value = pattern?.min_entropy || 0;
   document.getElementById('min-occurrences').value = pattern?.min_occurrences || 1;
+  document.getElementById('pattern-validator').value = pattern?.validator || '';
+  document.getElementById('pattern-category').value = pattern?.category || 'secret';
+  document.getElementById('pattern-scope').value = pattern?.scope || 'content';
+  document.getElementById('pattern-normalize').checked = pattern?.normalize || false;
+  document.getElementById('pattern-action').value = pattern?.action || '';
+  document.getElementById('pattern-severity').value = pattern?.severity || 'medium';
 
   // Negative patterns
   const negType = pattern?.negative_pattern_type || '';
   document.querySelector(`input[name="negative-pattern-type"][value="${negType}"]`).checked = true;
   document.getElementById('negative-pattern-values').value = pattern?.negative_patterns?.join('\n') || '';
+  document.getElementById('pattern-allowlist').value = pattern?.allowlist?.join('\n') || '';
 
   modal.classList.add('show');
 
@@ -443,9 +1480,13 @@ async function testPattern() {
   const patternType = document.querySelector('input[name="pattern-type"]:checked').value;
   const patterns = parseLines(document.getElementById('pattern-values').value);
   const minUniqueChars = parseInt(document.getElementById('min-unique-chars').value) || 0;
+  const minEntropy = parseFloat(document.getElementById('min-entropy').value) || 0;
   const minOccurrences = parseInt(document.getElementById('min-occurrences').value) || 1;
+  const validator = document.getElementById('pattern-validator').value;
   const negativePatternType = document.querySelector('input[name="negative-pattern-type"]:checked').value || null;
   const negativePatterns = parseLines(document.getElementById('negative-pattern-values').value);
+  const allowlist = parseLines(document.getElementById('pattern-allowlist').value);
+  const normalize = document.getElementById('pattern-normalize').checked;
 
   if (patterns.length === 0) {
     testResults.innerHTML = '<span class="test-error">Add at least one pattern first</span>';
@@ -464,10 +1505,16 @@ async function testPattern() {
       negativePatterns: negativePatterns.length > 0 ? negativePatterns : null,
       minOccurrences,
       minUniqueChars,
+      minEntropy,
+      validator: validator || null,
+      allowlist,
+      normalize,
       testText
     });
 
-    if (result.excluded) {
+    if (result.below_min_occurrences) {
+      testResults.innerHTML = `<span class="test-excluded">Fewer than ${minOccurrences} distinct matches</span>`;
+    } else if (result.excluded) {
       testResults.innerHTML = '<span class="test-excluded">Excluded by negative pattern</span>';
     } else if (result.matches.length === 0) {
       testResults.innerHTML = '<span class="test-none">No matches found</span>';
@@ -475,6 +1522,17 @@ async function testPattern() {
       testResults.innerHTML = `<span class="test-success">Matches (${result.matches.length}):</span> ` +
         result.matches.map(m => `<code>${escapeHtml(m)}</code>`).join(', ');
     }
+
+    // Explain every candidate: kept, or the negative pattern / filter that dropped it
+    if (result.candidates.length > 0) {
+      testResults.innerHTML += '<ul class="test-candidates">' + result.candidates.map(c => `
+        <li>
+          <code>${escapeHtml(c.value)}</code>
+          ${c.accepted ? '<span class="test-success">matched</span>' : `<span class="test-excluded">${escapeHtml(c.reason)}</span>`}
+          ${c.pattern ? `<span class="test-none">by ${escapeHtml(c.pattern)}</span>` : ''}
+          <div class="test-candidate-context">…${escapeHtml(c.context)}…</div>
+        </li>`).join('') + '</ul>';
+    }
     testResults.style.display = 'block';
   } catch (error) {
     testResults.innerHTML = `<span class="test-error">Error: ${escapeHtml(error)}</span>`;
@@ -492,10 +1550,18 @@ async function savePattern() {
   const patternType = document.querySelector('input[name="pattern-type"]:checked').value;
   const patterns = parseLines(document.getElementById('pattern-values').value);
   const minUniqueChars = parseInt(document.getElementById('min-unique-chars').value) || 0;
+  const minEntropy = parseFloat(document.getElementById('min-entropy').value) || 0;
   const minOccurrences = parseInt(document.getElementById('min-occurrences').value) || 1;
+  const validator = document.getElementById('pattern-validator').value;
+  const category = document.getElementById('pattern-category').value;
+  const scope = document.getElementById('pattern-scope').value;
+  const normalize = document.getElementById('pattern-normalize').checked;
+  const action = document.getElementById('pattern-action').value;
+  const severity = document.getElementById('pattern-severity').value;
 
   const negativePatternType = document.querySelector('input[name="negative-pattern-type"]:checked').value || null;
   const negativePatterns = parseLines(document.getElementById('negative-pattern-values').value);
+  const allowlist = parseLines(document.getElementById('pattern-allowlist').value);
 
   // Validation
   if (!name) {
@@ -523,7 +1589,15 @@ async function savePattern() {
         negativePatternType: negativePatternType || '',
         negativePatterns: negativePatterns.length > 0 ? negativePatterns : [],
         minOccurrences,
-        minUniqueChars
+        minUniqueChars,
+        minEntropy,
+        validator,
+        category,
+        action,
+        severity,
+        allowlist,
+        scope,
+        normalize
       });
     } else {
       // Add new pattern
@@ -534,7 +1608,15 @@ async function savePattern() {
         negativePatternType,
         negativePatterns: negativePatterns.length > 0 ? negativePatterns : null,
         minOccurrences,
-        minUniqueChars
+        minUniqueChars,
+        minEntropy,
+        validator: validator || null,
+        category,
+        action: action || null,
+        severity,
+        allowlist: allowlist.length > 0 ? allowlist : null,
+        scope,
+        normalize
       });
     }
     hidePatternModal();
@@ -547,6 +1629,46 @@ async function savePattern() {
   }
 }
 
+// ============ Pattern Pack Import/Export ============
+
+// Download all custom patterns as a JSON or YAML pattern pack
+async function exportPatterns() {
+  const format = document.getElementById('pattern-export-format')?.value || 'json';
+  try {
+    const pack = await invoke('export_dlp_patterns', { format });
+    const type = format === 'yaml' ? 'application/yaml' : 'application/json';
+    const url = URL.createObjectURL(new Blob([pack], { type }));
+    const link = document.createElement('a');
+    link.href = url;
+    link.download = `llmwatcher-patterns-${new Date().toISOString().slice(0, 10)}.${format}`;
+    link.click();
+    URL.revokeObjectURL(url);
+  } catch (error) {
+    console.error('Failed to export patterns:', error);
+    showSettingsStatus(`Export failed: ${error}`, 'error', 'dlp-patterns-status');
+  }
+}
+
+// Merge a pattern pack (.json or .yaml) or gitleaks config (.toml) into the custom patterns
+async function importPatterns(file) {
+  const onConflict = document.getElementById('pattern-import-conflict')?.value || 'skip';
+  const command = file.name.toLowerCase().endsWith('.toml') ? 'import_gitleaks_rules' : 'import_dlp_patterns';
+
+  try {
+    const contents = await file.text();
+    const summary = await invoke(command, { contents, onConflict });
+    let message = `Import complete: ${summary.added} added, ${summary.replaced} replaced, ${summary.skipped} skipped`;
+    if (summary.errors.length > 0) {
+      message += `. Failed: ${summary.errors.join('; ')}`;
+    }
+    showSettingsStatus(message, summary.errors.length > 0 ? 'error' : 'success', 'dlp-patterns-status');
+    loadDlpSettings();
+  } catch (error) {
+    console.error('Failed to import patterns:', error);
+    showSettingsStatus(`Import failed: ${error}`, 'error', 'dlp-patterns-status');
+  }
+}
+
 // Initialize DLP settings
 function initDlpSettings() {
   // Add pattern button
@@ -555,6 +1677,23 @@ function initDlpSettings() {
     addPatternBtn.addEventListener('click', () => showPatternModal());
   }
 
+  // Pattern pack import/export
+  const exportPatternsBtn = document.getElementById('export-patterns-btn');
+  if (exportPatternsBtn) {
+    exportPatternsBtn.addEventListener('click', exportPatterns);
+  }
+
+  const importPatternsBtn = document.getElementById('import-patterns-btn');
+  const importPatternsFile = document.getElementById('import-patterns-file');
+  if (importPatternsBtn && importPatternsFile) {
+    importPatternsBtn.addEventListener('click', () => importPatternsFile.click());
+    importPatternsFile.addEventListener('change', () => {
+      const file = importPatternsFile.files[0];
+      importPatternsFile.value = '';
+      if (file) importPatterns(file);
+    });
+  }
+
   // Modal close buttons
   const closeModalBtn = document.getElementById('close-pattern-modal');
   const cancelBtn = document.getElementById('cancel-pattern-btn');
@@ -588,6 +1727,13 @@ function initDlpSettings() {
     }
   });
 
+  // Policies
+  const addPolicyBtn = document.getElementById('add-dlp-policy-btn');
+  if (addPolicyBtn) {
+    addPolicyBtn.addEventListener('click', addDlpPolicy);
+  }
+  loadPolicyBackendOptions();
+
   // Load DLP settings
   loadDlpSettings();
 }
@@ -632,8 +1778,29 @@ export function initSettings() {
     loadProxyStatus();
   }, 500);
 
+  initProxyBindAddresses();
+  initProxyBodyLimits();
+  initProxyClientAuth();
+  initResponseCache();
+  initUpstreamProxy();
+  initBudgets();
+  initPricing();
+  initRetention();
+  initDatabaseEncryption();
+
   // Initialize DLP action toggle
   initDlpActionToggle();
+  initDlpSeverityPolicy();
+  initDlpScanResponsesToggle();
+  initDlpVerifySecretsToggle();
+  initDlpHashValuesToggle();
+  initDlpCanaryToggle();
+  initDlpDetectInjectionToggle();
+  initDlpWebhookSettings();
+  initDlpMinBlockConfidence();
+  initDlpIncidentWindow();
+  initDlpNerSettings();
+  initDlpScanLimits();
 
   // Initialize DLP settings
   initDlpSettings();
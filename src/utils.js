@@ -101,6 +101,11 @@ export function formatNumber(num) {
   return num.toLocaleString();
 }
 
+// Format an amount in US dollars, with more decimals for small amounts
+export function formatCost(usd) {
+  return '$' + usd.toFixed(usd >= 1 ? 2 : 4);
+}
+
 // Format latency
 export function formatLatency(ms) {
   if (ms >= 1000) return (ms / 1000).toFixed(2) + 's';